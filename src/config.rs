@@ -1,7 +1,8 @@
-use crate::CameraConfig;
+use crate::{CameraConfig, Texture, TextureLookup};
 use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(untagged)]
@@ -17,13 +18,126 @@ impl MaterialRef {
         materials: &HashMap<String, crate::MaterialConfig>,
     ) -> Result<crate::MaterialConfig, String> {
         match self {
-            MaterialRef::Inline(material) => Ok(*material),
+            MaterialRef::Inline(material) => Ok(material.clone()),
             MaterialRef::Reference(name) => materials
                 .get(name)
                 .cloned()
                 .ok_or_else(|| format!("Material '{name}' not found in materials section")),
         }
     }
+
+    pub fn build(
+        &self,
+        materials: &HashMap<String, crate::MaterialConfig>,
+        textures: &HashMap<String, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn crate::Material>, String> {
+        self.build_nested(materials, textures, &mut Vec::new())
+    }
+
+    /// `build` for a material inside another, where `stack` holds the names of the materials
+    /// being built around it
+    pub(crate) fn build_nested(
+        &self,
+        materials: &HashMap<String, crate::MaterialConfig>,
+        textures: &HashMap<String, Arc<dyn Texture>>,
+        stack: &mut Vec<String>,
+    ) -> Result<Arc<dyn crate::Material>, String> {
+        match self {
+            MaterialRef::Inline(material) => material.build_nested(materials, textures, stack),
+            MaterialRef::Reference(name) => {
+                if stack.contains(name) {
+                    return Err(format!("Material '{name}' contains itself"));
+                }
+                stack.push(name.clone());
+                let material = self
+                    .resolve(materials)
+                    .and_then(|m| m.build_nested(materials, textures, stack));
+                stack.pop();
+                material
+            }
+        }
+    }
+}
+
+/// A texture given inline, as a plain colour, or by name from the `textures` section
+///
+/// A single number is a grey, for the parameters of materials that are scalars.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TextureRef {
+    Color(crate::Color),
+
+    Value(f32),
+
+    Inline(Box<crate::TextureConfig>),
+
+    Reference(String),
+}
+
+impl TextureRef {
+    pub fn resolve(
+        &self,
+        textures: &HashMap<String, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Texture>, String> {
+        self.build(&mut |name| lookup_texture(textures, name))
+    }
+
+    pub(crate) fn build(&self, lookup: &mut TextureLookup) -> Result<Arc<dyn Texture>, String> {
+        match self {
+            TextureRef::Color(color) => Ok(Arc::new(crate::SolidColor::new(*color))),
+            TextureRef::Value(value) => Ok(Arc::new(crate::SolidColor::new(crate::Color::new(
+                *value, *value, *value,
+            )))),
+            TextureRef::Inline(texture) => texture.build(lookup),
+            TextureRef::Reference(name) => lookup(name),
+        }
+    }
+}
+
+fn lookup_texture(
+    textures: &HashMap<String, Arc<dyn Texture>>,
+    name: &str,
+) -> Result<Arc<dyn Texture>, String> {
+    textures
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("Texture '{name}' not found in textures section"))
+}
+
+/// Builds every named texture once, so that objects referring to the same name share it.
+/// Textures may refer to each other by name, as long as there are no cycles.
+pub fn build_textures(
+    configs: &HashMap<String, crate::TextureConfig>,
+) -> Result<HashMap<String, Arc<dyn Texture>>, String> {
+    fn build_named(
+        name: &str,
+        configs: &HashMap<String, crate::TextureConfig>,
+        built: &mut HashMap<String, Arc<dyn Texture>>,
+        stack: &mut Vec<String>,
+    ) -> Result<Arc<dyn Texture>, String> {
+        if let Some(texture) = built.get(name) {
+            return Ok(texture.clone());
+        }
+        if stack.iter().any(|n| n == name) {
+            return Err(format!("Texture '{name}' refers to itself"));
+        }
+        let config = configs
+            .get(name)
+            .ok_or_else(|| format!("Texture '{name}' not found in textures section"))?;
+
+        stack.push(name.to_string());
+        let texture = config.build(&mut |n| build_named(n, configs, built, stack))?;
+        stack.pop();
+
+        built.insert(name.to_string(), texture.clone());
+        Ok(texture)
+    }
+
+    let mut built = HashMap::new();
+    for name in configs.keys() {
+        build_named(name, configs, &mut built, &mut Vec::new())?;
+    }
+    Ok(built)
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -36,8 +150,13 @@ impl Config {
     pub fn to_scene(
         &self,
     ) -> Result<(crate::Camera, crate::HittableList, crate::HittableList), String> {
-        let camera = crate::Camera::from(self.camera.clone());
-        let (world, lights) = self.scene.process()?;
+        let mut camera = crate::Camera::from(self.camera.clone());
+        let (world, mut lights) = self.scene.process()?;
+        if let Some(environment) = &self.camera.environment {
+            let environment = environment.build()?;
+            lights.add(Arc::new(crate::EnvironmentLight::new(environment.clone())));
+            camera.environment = Some(environment);
+        }
         Ok((camera, world, lights))
     }
 }
@@ -48,19 +167,22 @@ pub struct SceneConfig {
     pub lights: Vec<crate::ObjectConfig>,
     #[serde(default)]
     pub materials: std::collections::HashMap<String, crate::MaterialConfig>,
+    #[serde(default)]
+    pub textures: std::collections::HashMap<String, crate::TextureConfig>,
 }
 impl SceneConfig {
     pub fn process(&self) -> Result<(crate::HittableList, crate::HittableList), String> {
         let mut world = crate::HittableList::new();
         let mut lights = crate::HittableList::new();
+        let textures = build_textures(&self.textures)?;
 
         for object in &self.objects {
-            let object = object.to_hittable(&self.materials)?;
+            let object = object.to_hittable(&self.materials, &textures)?;
             world.add(object);
         }
 
         for light_config in &self.lights {
-            let light = light_config.to_hittable(&self.materials)?;
+            let light = light_config.to_hittable(&self.materials, &textures)?;
             lights.add(light.clone());
             world.add(light);
         }
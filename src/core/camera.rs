@@ -2,21 +2,26 @@ use exr::prelude::*;
 use indicatif::ParallelProgressIterator;
 use rayon::prelude::*;
 
-use crate::utils::{degrees_to_radians, random_double, INFINITY};
+use crate::utils::{degrees_to_radians, random_double};
 use crate::{
-    pdf, vec3, Color, HitRecord, Hittable, Interval, Pdf, Point3, Ray, ScatterRecord, Vec3,
+    sampler, vec3, Color, ConstantEnvironment, Environment, EnvironmentConfig, Film, Hittable,
+    Integrator, IntegratorConfig, Point3, Ray, RayDifferentials, SamplerType, Vec3,
 };
 
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use toml;
 
+#[derive(Clone)]
 pub struct Camera {
     pub aspect_ratio: f32,
     pub image_width: usize,
     pub samples_per_pixel: usize,
     pub max_depth: usize,
     pub background: Color,
+    /// Light from infinitely far away, seen instead of `background` by rays that leave the
+    /// scene. It should also be among the lights, wrapped in an `EnvironmentLight`.
+    pub environment: Option<Arc<dyn Environment>>,
     pub vfov: f32,
     pub look_from: Point3,
     pub look_at: Point3,
@@ -26,12 +31,12 @@ pub struct Camera {
     pub file_path: String,
     pub rr_min_depth: usize,
     pub rr_max_contrib_prob: f32,
+    /// Seed for all random sampling. Identical scenes and seeds render identical images.
+    pub seed: u64,
+    pub sampler: SamplerType,
+    pub integrator: IntegratorConfig,
     image_height: usize,
     pixel_samples_scale: f32,
-    /// Square root of number of samples per pixel
-    sqrt_spp: usize,
-    /// 1 / sqrt_spp
-    recip_sqrt_spp: f32,
     center: Vec3,
     pixel00_loc: Vec3,
     pixel_delta_u: Vec3,
@@ -51,6 +56,7 @@ impl Default for Camera {
             samples_per_pixel: 10,
             max_depth: 10,
             background: Color::new(0.0, 0.0, 0.0),
+            environment: None,
             vfov: 90.0,
             look_from: Point3::new(0.0, 0.0, 0.0),
             look_at: Point3::new(0.0, 0.0, -1.0),
@@ -62,8 +68,9 @@ impl Default for Camera {
             pixel_samples_scale: 1.0 / 10.0,
             rr_min_depth: 3,
             rr_max_contrib_prob: 0.95,
-            sqrt_spp: 0,
-            recip_sqrt_spp: 0.,
+            seed: 0,
+            sampler: SamplerType::default(),
+            integrator: IntegratorConfig::default(),
             center: Vec3::new(0.0, 0.0, 0.0),
             pixel00_loc: Vec3::new(0.0, 0.0, 0.0),
             pixel_delta_u: Vec3::new(0.0, 0.0, 0.0),
@@ -83,60 +90,92 @@ impl Camera {
     }
 
     pub fn render(&mut self, world: &dyn Hittable, lights: Arc<dyn Hittable>) {
+        let image = self.render_image(world, lights);
+        self.write_image(&image);
+    }
+
+    /// Renders the scene and returns the pixels in row-major order, without writing a file
+    pub fn render_image(&mut self, world: &dyn Hittable, lights: Arc<dyn Hittable>) -> Vec<Color> {
         self.initialize();
-        let pixels = self.render_pixels_parallel(world, lights);
-        self.write_image(pixels);
+        let integrator = self.integrator.build(self);
+        let mut image = integrator.render(self, world, lights.as_ref());
+        for col in image.iter_mut() {
+            *col = Color::new(
+                if col.x().is_nan() { 0. } else { col.x() },
+                if col.y().is_nan() { 0. } else { col.y() },
+                if col.z().is_nan() { 0. } else { col.z() },
+            );
+        }
+        image
+    }
+
+    pub fn image_height(&self) -> usize {
+        self.image_height
     }
 
-    pub fn render_pixels_parallel(
+    /// What rays that leave the scene see: the environment, or else the flat background color
+    pub fn background_light(&self) -> Arc<dyn Environment> {
+        match &self.environment {
+            Some(environment) => environment.clone(),
+            None => Arc::new(ConstantEnvironment::new(self.background)),
+        }
+    }
+
+    /// Averages `samples_per_pixel` calls to `Integrator::li` for every pixel, in parallel
+    ///
+    /// Splats made by light paths are added on top, scaled like the pixel samples.
+    pub fn render_samples<I: Integrator + ?Sized>(
         &self,
+        integrator: &I,
         world: &dyn Hittable,
-        lights: Arc<dyn Hittable>,
-    ) -> Vec<(usize, usize, Vec3)> {
-        (0..self.image_height)
+        lights: &dyn Hittable,
+    ) -> Vec<Color> {
+        let film = Film::new(self.image_width, self.image_height);
+        let pixels: Vec<Color> = (0..self.image_height)
             .into_par_iter()
             .progress_count(self.image_height as u64)
             .flat_map(|y| {
-                (0..self.image_width).into_par_iter().map({
-                    let value = lights.clone();
-                    move |x| {
-                        let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                        for s_j in 0..self.sqrt_spp {
-                            for s_i in 0..self.sqrt_spp {
-                                let ray = self.get_ray(x, y, s_i, s_j);
-                                pixel_color += self.ray_color(&ray, self.max_depth, world, &value);
+                // Each pixel borrows a sampler built once per batch of pixels, since
+                // `start_pixel_sample` restarts it completely anyway
+                (0..self.image_width).into_par_iter().map_init(
+                    || self.sampler.build(self.samples_per_pixel, self.seed),
+                    {
+                        let film = &film;
+                        move |pixel_sampler, x| {
+                            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+                            sampler::swap_thread_sampler(pixel_sampler);
+                            for sample in 0..self.samples_per_pixel {
+                                sampler::with_thread_sampler(|s| {
+                                    s.start_pixel_sample(x, y, sample)
+                                });
+                                let ray = self.get_ray(x, y);
+                                pixel_color += integrator.li(&ray, world, lights, film);
                             }
+                            sampler::swap_thread_sampler(pixel_sampler);
+                            self.pixel_samples_scale * pixel_color
                         }
-                        (x, y, self.pixel_samples_scale * pixel_color)
-                    }
-                })
+                    },
+                )
+            })
+            .collect();
+
+        pixels
+            .into_iter()
+            .enumerate()
+            .map(|(i, col)| {
+                let (x, y) = (i % self.image_width, i / self.image_width);
+                col + self.pixel_samples_scale * film.splat(x, y)
             })
             .collect()
     }
 
-    fn write_image(&self, pixels: Vec<(usize, usize, Vec3)>) {
-        let mut image: Vec<f32> = Vec::with_capacity(self.image_height * self.image_width * 3);
-        let mut pixel_map = vec![vec![[0.0f32; 3]; self.image_width]; self.image_height];
-
-        for (x, y, col) in pixels {
-            pixel_map[y][x] = [
-                if col.x().is_nan() { 0. } else { col.x() },
-                if col.y().is_nan() { 0. } else { col.y() },
-                if col.z().is_nan() { 0. } else { col.z() },
-            ];
-        }
-
-        for row in pixel_map {
-            for pixel in row {
-                image.extend(&pixel);
-            }
-        }
-
+    /// Writes a row-major image from `render_image` to `file_path` as OpenEXR
+    pub fn write_image(&self, image: &[Color]) {
         Image::from_channels(
             (self.image_width, self.image_height),
             SpecificChannels::rgb(|Vec2(x, y)| {
-                let idx = (y * self.image_width + x) * 3;
-                (image[idx], image[idx + 1], image[idx + 2])
+                let pixel: Color = image[y * self.image_width + x];
+                (pixel.x(), pixel.y(), pixel.z())
             }),
         )
         .write()
@@ -144,8 +183,10 @@ impl Camera {
         .unwrap();
     }
 
-    fn get_ray(&self, i: usize, j: usize, s_i: usize, s_j: usize) -> Ray {
-        let offset = self.sample_square_stratified(s_i, s_j);
+    /// A ray through a random point of pixel (`i`, `j`), drawn from the thread's sampler
+    pub(crate) fn get_ray(&self, i: usize, j: usize) -> Ray {
+        let (px, py) = sampler::with_thread_sampler(|s| s.get_pixel_2d());
+        let offset = Vec3::new(px - 0.5, py - 0.5, 0.);
         let pixel_sample = self.pixel00_loc
             + (((i as f32) + offset.x()) * self.pixel_delta_u)
             + (((j as f32) + offset.y()) * self.pixel_delta_v);
@@ -157,7 +198,68 @@ impl Camera {
         let ray_direction = pixel_sample - ray_origin;
         let ray_time = random_double();
 
-        Ray::new(ray_origin, ray_direction, ray_time)
+        // The neighbouring pixels, brought closer as more samples cover each pixel since the
+        // pixel filter already averages over it, as in pbrt
+        let spacing = self.pixel_samples_scale.sqrt().max(0.125);
+        let differentials = RayDifferentials {
+            rx_origin: ray_origin,
+            rx_direction: ray_direction + spacing * self.pixel_delta_u,
+            ry_origin: ray_origin,
+            ry_direction: ray_direction + spacing * self.pixel_delta_v,
+        };
+
+        Ray::new(ray_origin, ray_direction, ray_time).with_differentials(Some(differentials))
+    }
+
+    /// Area of the image at unit distance from the lens
+    fn film_area(&self) -> f32 {
+        let width = self.pixel_delta_u.length() * self.image_width as f32;
+        let height = self.pixel_delta_v.length() * self.image_height as f32;
+        width * height / (self.focus_dist * self.focus_dist)
+    }
+
+    /// Density, per solid angle, of `get_ray` choosing `direction` once the lens point is fixed
+    pub(crate) fn direction_pdf(&self, direction: Vec3) -> f32 {
+        let cos_theta = vec3::dot(vec3::unit_vector(direction), -self.w);
+        if cos_theta <= 0. {
+            return 0.;
+        }
+        1. / (self.film_area() * cos_theta * cos_theta * cos_theta)
+    }
+
+    /// Connects the scene point `p` to the camera, for light paths to contribute to the image
+    ///
+    /// Samples a point on the lens and returns it, together with the pixel that sees `p`
+    /// through it and the camera's importance times the cosine at the lens, divided by the
+    /// density of the lens point. The caller still has to divide by the squared distance and
+    /// check visibility. Importance is normalized so that splats, summed over all pixel samples
+    /// and scaled like them, estimate the same pixel values.
+    pub(crate) fn sample_importance(&self, p: Point3) -> Option<(Point3, usize, usize, f32)> {
+        let lens_point = if self.defocus_angle > 0.0 {
+            self.defocus_disk_sample()
+        } else {
+            self.center
+        };
+        let direction = p - lens_point;
+        let forward = vec3::dot(direction, -self.w);
+        if forward <= 0. {
+            return None;
+        }
+
+        // Where the ray from the lens point through `p` crosses the plane in focus
+        let focus_point = lens_point + direction * (self.focus_dist / forward);
+        let corner = self.pixel00_loc - 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
+        let offset = focus_point - corner;
+        let x = vec3::dot(offset, self.pixel_delta_u) / self.pixel_delta_u.length_squared();
+        let y = vec3::dot(offset, self.pixel_delta_v) / self.pixel_delta_v.length_squared();
+        if x < 0. || y < 0. || x >= self.image_width as f32 || y >= self.image_height as f32 {
+            return None;
+        }
+
+        // We = 1 / (A_film A_lens cos^4), and the lens point density is 1 / A_lens
+        let cos_theta = forward / direction.length();
+        let importance = 1. / (self.film_area() * cos_theta * cos_theta * cos_theta);
+        Some((lens_point, x as usize, y as usize, importance))
     }
 
     fn defocus_disk_sample(&self) -> Point3 {
@@ -165,12 +267,6 @@ impl Camera {
         self.center + (self.defocus_disk_u * p.x()) + (self.defocus_disk_v * p.y())
     }
 
-    fn sample_square_stratified(&self, s_i: usize, s_j: usize) -> Vec3 {
-        let px = ((s_i as f32 + random_double()) * self.recip_sqrt_spp) - 0.5;
-        let py = ((s_j as f32 + random_double()) * self.recip_sqrt_spp) - 0.5;
-        Vec3::new(px, py, 0.)
-    }
-
     fn initialize(&mut self) {
         let aspect_ratio = self.aspect_ratio;
         let image_width = self.image_width;
@@ -178,9 +274,8 @@ impl Camera {
         let image_height = (image_width as f32 / aspect_ratio) as usize;
         self.image_height = if image_height == 0 { 1 } else { image_height };
 
-        self.sqrt_spp = (f32::sqrt(self.samples_per_pixel as f32)) as usize;
-        self.pixel_samples_scale = 1.0 / (self.sqrt_spp * self.sqrt_spp) as f32;
-        self.recip_sqrt_spp = 1.0 / self.sqrt_spp as f32;
+        self.samples_per_pixel = self.samples_per_pixel.max(1);
+        self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f32;
 
         self.center = self.look_from;
 
@@ -210,61 +305,6 @@ impl Camera {
         self.defocus_disk_v = defocus_radius * self.v;
     }
 
-    fn ray_color(
-        &self,
-        r: &Ray,
-        depth: usize,
-        world: &dyn Hittable,
-        lights: &Arc<dyn Hittable>,
-    ) -> Color {
-        if depth == 0 {
-            return Color::new(0.0, 0.0, 0.0);
-        }
-
-        let mut rec: HitRecord = Default::default();
-        if !world.hit(r, &mut Interval::new(0.001, INFINITY), &mut rec) {
-            return self.background;
-        }
-
-        let mut srec: ScatterRecord = ScatterRecord::default();
-        let color_from_emission: Color = rec.mat.emitted(r, &rec, rec.u, rec.v, rec.p);
-
-        if !rec.mat.scatter(r, &rec, &mut srec) {
-            return color_from_emission;
-        }
-
-        // Russian Roulette stuff
-        let brightness = (srec.attenuation.x() + srec.attenuation.y() + srec.attenuation.z()) / 3.;
-        if depth > self.rr_min_depth {
-            if brightness < (1. - self.rr_max_contrib_prob) {
-                return color_from_emission;
-            }
-        }
-        let compensation = if depth > self.rr_min_depth {
-            1. / self.rr_max_contrib_prob
-        } else {
-            1.
-        };
-
-        if srec.skip_pdf {
-            return color_from_emission
-                + (srec.attenuation
-                    * self.ray_color(&srec.skip_pdf_ray, depth - 1, world, lights) * compensation);
-        }
-
-        let light = Arc::new(pdf::HittablePdf::new(lights.clone(), rec.p));
-        let p = pdf::MixturePdf::new(light, srec.pdf);
-
-        let scattered = Ray::new(rec.p, p.generate(), r.time());
-        let pdf_value = p.value(scattered.direction());
-
-        let scattering_pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
-        let sample_color = self.ray_color(&scattered, depth - 1, world, lights);
-
-        let color_from_scatter = (srec.attenuation * scattering_pdf * sample_color * compensation) / pdf_value;
-        color_from_emission + color_from_scatter
-    }
-
     pub fn from_toml_file(path: &str) -> std::result::Result<Self, Box<dyn std::error::Error>> {
         let toml_content = std::fs::read_to_string(path)?;
         let config: CameraConfig = toml::from_str(&toml_content)?;
@@ -280,6 +320,8 @@ pub struct CameraConfig {
     pub samples_per_pixel: usize,
     pub max_depth: usize,
     pub background: Color,
+    /// Built by `Config::to_scene`, which also adds it to the lights
+    pub environment: Option<EnvironmentConfig>,
     pub vfov: f32,
     pub look_from: Point3,
     pub look_at: Point3,
@@ -289,6 +331,9 @@ pub struct CameraConfig {
     pub file_path: String,
     pub rr_min_depth: usize,
     pub rr_max_contrib_prob: f32,
+    pub seed: u64,
+    pub sampler: SamplerType,
+    pub integrator: IntegratorConfig,
 }
 
 impl Default for CameraConfig {
@@ -299,6 +344,7 @@ impl Default for CameraConfig {
             samples_per_pixel: 10,
             max_depth: 10,
             background: Color::new(0.0, 0.0, 0.0),
+            environment: None,
             vfov: 90.0,
             look_from: Point3::new(0.0, 0.0, 0.0),
             look_at: Point3::new(0.0, 0.0, -1.0),
@@ -308,6 +354,9 @@ impl Default for CameraConfig {
             file_path: "image.ppm".into(),
             rr_min_depth: 3,
             rr_max_contrib_prob: 0.95,
+            seed: 0,
+            sampler: SamplerType::default(),
+            integrator: IntegratorConfig::default(),
         }
     }
 }
@@ -329,6 +378,9 @@ impl From<CameraConfig> for Camera {
             file_path: config.file_path,
             rr_min_depth: config.rr_min_depth,
             rr_max_contrib_prob: config.rr_max_contrib_prob,
+            seed: config.seed,
+            sampler: config.sampler,
+            integrator: config.integrator,
             ..Default::default()
         }
     }
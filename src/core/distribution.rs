@@ -0,0 +1,122 @@
+/// A piecewise-constant density over `[0, 1)`, sampled by inverting its cumulative distribution
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    func: Vec<f32>,
+    /// `cdf[i]` is the probability of landing before piece `i`, with a final 1
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    /// A density proportional to `func`, which must not be empty. Negative values count as zero,
+    /// and a function that is zero everywhere gives a uniform density.
+    pub fn new(func: &[f32]) -> Self {
+        assert!(!func.is_empty(), "a distribution needs at least one piece");
+        let mut func: Vec<f32> = func.iter().map(|f| f.max(0.)).collect();
+        let n = func.len() as f32;
+        let mut integral: f32 = func.iter().sum::<f32>() / n;
+        if !(integral > 0. && integral.is_finite()) {
+            func.fill(1.);
+            integral = 1.;
+        }
+
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        let mut sum = 0.;
+        cdf.push(0.);
+        for f in &func {
+            sum += f / n;
+            cdf.push(sum / integral);
+        }
+        *cdf.last_mut().unwrap() = 1.;
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    /// The number of pieces
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// The average of the function over `[0, 1)`
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    /// Maps the uniform random number `u` to a point in `[0, 1)`, returning the point, its
+    /// density and the piece it fell in
+    pub fn sample_continuous(&self, u: f32) -> (f32, f32, usize) {
+        let piece = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+        let width = self.cdf[piece + 1] - self.cdf[piece];
+        let offset = if width > 0. {
+            (u - self.cdf[piece]) / width
+        } else {
+            0.
+        };
+        let x = ((piece as f32 + offset) / self.count() as f32).min(1. - f32::EPSILON);
+        (x, self.func[piece] / self.integral, piece)
+    }
+
+    /// The density of `sample_continuous` returning `x`
+    pub fn pdf(&self, x: f32) -> f32 {
+        if !(0. ..=1.).contains(&x) {
+            return 0.;
+        }
+        let piece = ((x * self.count() as f32) as usize).min(self.count() - 1);
+        self.func[piece] / self.integral
+    }
+}
+
+/// A piecewise-constant density over the unit square, for importance sampling images
+///
+/// Samples pick a row from the marginal density of the rows, then a point along the row.
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// A density proportional to `func`, given row by row with `width` values in each
+    pub fn new(func: &[f32], width: usize) -> Self {
+        assert!(
+            width > 0 && !func.is_empty() && func.len().is_multiple_of(width),
+            "a distribution of {} values can't have rows of {width}",
+            func.len()
+        );
+        let rows = func.chunks_exact(width).map(Distribution1D::new).collect();
+        // Taken from `func` itself, since rows that are zero everywhere sample uniformly
+        let row_integrals: Vec<f32> = func
+            .chunks_exact(width)
+            .map(|row| row.iter().map(|f| f.max(0.)).sum::<f32>() / width as f32)
+            .collect();
+        Self {
+            rows,
+            marginal: Distribution1D::new(&row_integrals),
+        }
+    }
+
+    /// Maps the uniform random numbers `(u, v)` to a point in the unit square, with `x` along
+    /// the rows and `y` across them, returning the point and its density
+    pub fn sample_continuous(&self, u: f32, v: f32) -> ((f32, f32), f32) {
+        let (y, pdf_y, row) = self.marginal.sample_continuous(v);
+        let (x, pdf_x, _) = self.rows[row].sample_continuous(u);
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    /// The density of `sample_continuous` returning `(x, y)`
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        if !(0. ..=1.).contains(&y) {
+            return 0.;
+        }
+        let row = ((y * self.rows.len() as f32) as usize).min(self.rows.len() - 1);
+        self.marginal.pdf(y) * self.rows[row].pdf(x)
+    }
+}
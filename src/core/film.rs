@@ -0,0 +1,65 @@
+use crate::Color;
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+/// Fixed point scale of splatted values: 32 fractional bits leave room for sums up to 2^31
+const FIXED_POINT_SCALE: f64 = (1u64 << 32) as f64;
+
+/// Largest magnitude of a single splat, so that converting it to fixed point can't overflow
+const MAX_SPLAT: f64 = (1u64 << 30) as f64;
+
+/// Collects contributions that land on arbitrary pixels rather than the pixel being sampled,
+/// such as light paths that are connected to the camera
+///
+/// Any thread may splat onto any pixel. Splats are summed as fixed point integers, so the total
+/// doesn't depend on the order the threads add them in and renders stay reproducible.
+///
+/// Each channel of a pixel holds sums up to about 2.1e9. Past that it saturates instead of
+/// wrapping around, and the order of the splats may then change the result.
+pub struct Film {
+    width: usize,
+    height: usize,
+    splats: Vec<[AtomicI64; 3]>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            splats: (0..width * height).map(|_| Default::default()).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Adds `color` to pixel (`x`, `y`). Non-finite values are dropped, and each channel is
+    /// clamped to about 1.1e9.
+    pub fn add_splat(&self, x: usize, y: usize, color: Color) {
+        let pixel = &self.splats[y * self.width + x];
+        for (sum, value) in pixel.iter().zip(color.0) {
+            if value.is_finite() {
+                let value = (value as f64).clamp(-MAX_SPLAT, MAX_SPLAT);
+                let fixed = (value * FIXED_POINT_SCALE).round() as i64;
+                // Never fails, since the closure always returns a value
+                let _ = sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                    Some(total.saturating_add(fixed))
+                });
+            }
+        }
+    }
+
+    /// Sum of everything splatted onto pixel (`x`, `y`)
+    pub fn splat(&self, x: usize, y: usize) -> Color {
+        let pixel = &self.splats[y * self.width + x];
+        let channel =
+            |i: usize| (pixel[i].load(Ordering::Relaxed) as f64 / FIXED_POINT_SCALE) as f32;
+        Color::new(channel(0), channel(1), channel(2))
+    }
+}
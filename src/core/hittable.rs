@@ -1,5 +1,6 @@
 use crate::{
-    utils::degrees_to_radians, vec3, Interval, Material, NoneMaterial, Point3, Ray, Vec3, AABB,
+    utils::degrees_to_radians, vec3, Interval, Material, NoneMaterial, Point3, Ray,
+    RayDifferentials, Vec3, AABB, ONB,
 };
 
 use std::sync::Arc;
@@ -12,6 +13,21 @@ pub struct HitRecord {
     pub t: f32,
     pub u: f32,
     pub v: f32,
+    /// Derivatives of the hit point with respect to `u` and `v`, zero where the surface has no
+    /// parameterization. They aren't normalized, nor orthogonal to the normal on meshes.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// Derivatives of the outward normal with respect to `u` and `v`, zero on flat surfaces
+    pub dndu: Vec3,
+    pub dndv: Vec3,
+    /// How far the hit point and its texture coordinates move from one pixel to the next, set
+    /// by `compute_differentials`. They are zero when the ray has no differentials.
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
+    pub dudx: f32,
+    pub dvdx: f32,
+    pub dudy: f32,
+    pub dvdy: f32,
     pub front_face: bool,
 }
 
@@ -24,6 +40,16 @@ impl Default for HitRecord {
             t: 0.0,
             u: 0.0,
             v: 0.0,
+            dpdu: Default::default(),
+            dpdv: Default::default(),
+            dndu: Default::default(),
+            dndv: Default::default(),
+            dpdx: Default::default(),
+            dpdy: Default::default(),
+            dudx: 0.0,
+            dvdx: 0.0,
+            dudy: 0.0,
+            dvdy: 0.0,
             front_face: false,
         }
     }
@@ -38,6 +64,136 @@ impl HitRecord {
             -outward_normal
         };
     }
+
+    /// Sets the footprint of a pixel at the hit point from the differentials of the ray `r` that
+    /// found it, after Igehy, "Tracing Ray Differentials"
+    ///
+    /// The offset rays are intersected with the tangent plane, and the texture coordinates
+    /// follow from `dpdu` and `dpdv` in the least-squares sense.
+    pub fn compute_differentials(&mut self, r: &Ray) {
+        let Some(d) = r.differentials() else {
+            self.clear_differentials();
+            return;
+        };
+        let plane = vec3::dot(self.normal, self.p);
+        let offset = |origin: Point3, direction: Vec3| -> Option<Vec3> {
+            let t = (plane - vec3::dot(self.normal, origin)) / vec3::dot(self.normal, direction);
+            t.is_finite().then(|| origin + t * direction - self.p)
+        };
+        let (Some(dpdx), Some(dpdy)) = (
+            offset(d.rx_origin, d.rx_direction),
+            offset(d.ry_origin, d.ry_direction),
+        ) else {
+            self.clear_differentials();
+            return;
+        };
+        self.dpdx = dpdx;
+        self.dpdy = dpdy;
+
+        // Solves dp = du dpdu + dv dpdv through its normal equations
+        let a00 = vec3::dot(self.dpdu, self.dpdu);
+        let a01 = vec3::dot(self.dpdu, self.dpdv);
+        let a11 = vec3::dot(self.dpdv, self.dpdv);
+        let inv_det = 1. / (a00 * a11 - a01 * a01);
+        let solve = |dp: Vec3| -> (f32, f32) {
+            let (b0, b1) = (vec3::dot(self.dpdu, dp), vec3::dot(self.dpdv, dp));
+            let du = (a11 * b0 - a01 * b1) * inv_det;
+            let dv = (a00 * b1 - a01 * b0) * inv_det;
+            if du.is_finite() && dv.is_finite() {
+                (du.clamp(-1e8, 1e8), dv.clamp(-1e8, 1e8))
+            } else {
+                (0., 0.)
+            }
+        };
+        (self.dudx, self.dvdx) = solve(dpdx);
+        (self.dudy, self.dvdy) = solve(dpdy);
+    }
+
+    fn clear_differentials(&mut self) {
+        self.dpdx = Vec3::default();
+        self.dpdy = Vec3::default();
+        (self.dudx, self.dvdx, self.dudy, self.dvdy) = (0., 0., 0., 0.);
+    }
+
+    /// The ray mirrored off the hit point into `direction`, with differentials if `r_in` had them
+    ///
+    /// The differentials follow the turning of the normal across the footprint, so curved
+    /// mirrors spread or focus them.
+    pub fn reflected_ray(&self, r_in: &Ray, direction: Vec3) -> Ray {
+        let differentials = self.specular_differentials(r_in, direction, |wo, dwo, dn| {
+            // Differentiates wi = -wo + 2 (wo·n) n
+            let cos_i = vec3::dot(wo, self.normal);
+            let dcos_i = vec3::dot(dwo, self.normal) + vec3::dot(wo, dn);
+            -dwo + 2. * (cos_i * dn + dcos_i * self.normal)
+        });
+        Ray::new(self.p, direction, r_in.time()).with_differentials(differentials)
+    }
+
+    /// The ray refracted through the hit point into `direction`, with differentials if `r_in`
+    /// had them, where `eta` is the refractive index of the side `r_in` comes from over the
+    /// other side's
+    ///
+    /// Like `reflected_ray`, this follows the turning of the normal across the footprint.
+    pub fn refracted_ray(&self, r_in: &Ray, direction: Vec3, eta: f32) -> Ray {
+        let wi = vec3::unit_vector(direction);
+        let cos_t = vec3::dot(wi, self.normal).abs();
+        let differentials = self.specular_differentials(r_in, direction, |wo, dwo, dn| {
+            // Differentiates wi = -eta wo + (eta (wo·n) - cos θt) n
+            let cos_i = vec3::dot(wo, self.normal);
+            let dcos_i = vec3::dot(dwo, self.normal) + vec3::dot(wo, dn);
+            -eta * dwo
+                + (eta * cos_i - cos_t) * dn
+                + (eta - eta * eta * cos_i / cos_t) * dcos_i * self.normal
+        });
+        Ray::new(self.p, direction, r_in.time()).with_differentials(differentials)
+    }
+
+    /// Differentials of the ray leaving in `direction`, given the change `dwi(wo, dwo, dn)` in
+    /// the unit outgoing direction for a change `dwo` in the unit direction `wo` back along
+    /// `r_in` and a change `dn` in the normal, after Igehy
+    fn specular_differentials(
+        &self,
+        r_in: &Ray,
+        direction: Vec3,
+        dwi: impl Fn(Vec3, Vec3, Vec3) -> Vec3,
+    ) -> Option<RayDifferentials> {
+        let d = r_in.differentials()?;
+        // Hits found without `compute_differentials` have no footprint to carry on
+        if self.dpdx.length_squared() == 0. && self.dpdy.length_squared() == 0. {
+            return None;
+        }
+        let wo = -vec3::unit_vector(r_in.direction());
+        let wi = vec3::unit_vector(direction);
+        let dwodx = -vec3::unit_vector(d.rx_direction) - wo;
+        let dwody = -vec3::unit_vector(d.ry_direction) - wo;
+        // The normal derivatives are of the outward normal, which `normal` may be flipped from
+        let side = if self.front_face { 1. } else { -1. };
+        let dndx = side * (self.dudx * self.dndu + self.dvdx * self.dndv);
+        let dndy = side * (self.dudy * self.dndu + self.dvdy * self.dndv);
+        Some(RayDifferentials {
+            rx_origin: self.p + self.dpdx,
+            rx_direction: wi + dwi(wo, dwodx, dndx),
+            ry_origin: self.p + self.dpdy,
+            ry_direction: wi + dwi(wo, dwody, dndy),
+        })
+    }
+
+    /// The shading frame at the hit point, with the normal as `w` and `u` along `dpdu` turned
+    /// by `rotation` radians about the normal
+    ///
+    /// Where `dpdu` is zero or parallel to the normal, the tangent is arbitrary.
+    pub fn tangent_frame(&self, rotation: f32) -> ONB {
+        let tangent = self.dpdu - vec3::dot(self.dpdu, self.normal) * self.normal;
+        if tangent.length_squared() < 1e-12 {
+            return ONB::new(self.normal);
+        }
+        let frame = ONB::from_tangent(self.normal, tangent);
+        if rotation == 0. {
+            return frame;
+        }
+        let (sin, cos) = rotation.sin_cos();
+        ONB::from_tangent(self.normal, cos * frame.u() + sin * frame.v())
+    }
 }
 
 pub trait Hittable: Send + Sync {
@@ -45,13 +201,37 @@ pub trait Hittable: Send + Sync {
 
     fn bounding_box(&self) -> &AABB;
 
-    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+    // The rest is for objects in the list of lights. Every shape implements it, so any of them
+    // can emit light.
+
+    /// Density per solid angle of `random` picking `direction` from `origin`
+    ///
+    /// Integrators follow the direction through the scene rather than to the point that was
+    /// picked, so this adds up every point of the object along it.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f32 {
         0.0
     }
 
-    fn random(&self, origin: Point3) -> Vec3 {
+    /// A direction from `origin` towards the object, not necessarily of unit length
+    fn random(&self, _origin: Point3) -> Vec3 {
         Vec3::default()
     }
+
+    /// Samples a point on the surface at `time`, for light paths to start from
+    ///
+    /// Returns the hit record of the point, with the outward normal and `front_face` set, and
+    /// the density of the point with respect to surface area. Objects that can't be used as
+    /// light sources return `None`.
+    fn sample_surface(&self, _time: f32) -> Option<(HitRecord, f32)> {
+        None
+    }
+
+    /// Surface area of the object, which lights made of several objects pick their parts by
+    ///
+    /// Objects that can't be used as light sources have none.
+    fn area(&self) -> f32 {
+        0.0
+    }
 }
 
 pub struct Translate {
@@ -87,12 +267,22 @@ impl Hittable for Translate {
         &self.bbox
     }
 
-    fn random(&self, _origin: Point3) -> Vec3 {
-        Vec3::new(1., 0., 0.)
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.object.random(origin - self.offset)
     }
 
-    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f32 {
-        0.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        self.object.pdf_value(origin - self.offset, direction)
+    }
+
+    fn sample_surface(&self, time: f32) -> Option<(HitRecord, f32)> {
+        let (mut rec, pdf) = self.object.sample_surface(time)?;
+        rec.p += self.offset;
+        Some((rec, pdf))
+    }
+
+    fn area(&self) -> f32 {
+        self.object.area()
     }
 }
 
@@ -146,33 +336,43 @@ impl RotateY {
         }
     }
 }
+impl RotateY {
+    /// Turns a vector from the world into the object's space
+    fn rotate(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x() - self.sin_theta * v.z(),
+            v.y(),
+            self.sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
+
+    /// Turns a vector from the object's space back to the world
+    fn rotate_back(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self.cos_theta * v.x() + self.sin_theta * v.z(),
+            v.y(),
+            -self.sin_theta * v.x() + self.cos_theta * v.z(),
+        )
+    }
+}
 impl Hittable for RotateY {
     fn hit(&self, r: &Ray, ray_t: &mut Interval, rec: &mut HitRecord) -> bool {
-        let mut origin = r.origin();
-        let mut direction = r.direction();
-
-        origin.0[0] = self.cos_theta * r.origin().x() - self.sin_theta * r.origin().z();
-        origin.0[2] = self.sin_theta * r.origin().x() + self.cos_theta * r.origin().z();
-
-        direction.0[0] = self.cos_theta * r.direction().x() - self.sin_theta * r.direction().z();
-        direction.0[2] = self.sin_theta * r.direction().x() + self.cos_theta * r.direction().z();
-
-        let rotated_ray = Ray::new(origin, direction, r.time());
+        let rotated_ray = Ray::new(
+            self.rotate(r.origin()),
+            self.rotate(r.direction()),
+            r.time(),
+        );
 
         if !self.object.hit(&rotated_ray, ray_t, rec) {
             return false;
         }
 
-        let mut p = rec.p;
-        p.0[0] = self.cos_theta * rec.p.x() + self.sin_theta * rec.p.z();
-        p.0[2] = -self.sin_theta * rec.p.x() + self.cos_theta * rec.p.z();
-
-        let mut normal = rec.normal;
-        normal.0[0] = self.cos_theta * rec.normal.x() + self.sin_theta * rec.normal.z();
-        normal.0[2] = -self.sin_theta * rec.normal.x() + self.cos_theta * rec.normal.z();
-
-        rec.p = p;
-        rec.normal = normal;
+        rec.p = self.rotate_back(rec.p);
+        rec.normal = self.rotate_back(rec.normal);
+        rec.dpdu = self.rotate_back(rec.dpdu);
+        rec.dpdv = self.rotate_back(rec.dpdv);
+        rec.dndu = self.rotate_back(rec.dndu);
+        rec.dndv = self.rotate_back(rec.dndv);
 
         true
     }
@@ -181,11 +381,27 @@ impl Hittable for RotateY {
         &self.bbox
     }
 
-    fn random(&self, _origin: Point3) -> Vec3 {
-        Vec3::new(1., 0., 0.)
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.rotate_back(self.object.random(self.rotate(origin)))
     }
 
-    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f32 {
-        0.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        self.object
+            .pdf_value(self.rotate(origin), self.rotate(direction))
+    }
+
+    fn sample_surface(&self, time: f32) -> Option<(HitRecord, f32)> {
+        let (mut rec, pdf) = self.object.sample_surface(time)?;
+        rec.p = self.rotate_back(rec.p);
+        rec.normal = self.rotate_back(rec.normal);
+        rec.dpdu = self.rotate_back(rec.dpdu);
+        rec.dpdv = self.rotate_back(rec.dpdv);
+        rec.dndu = self.rotate_back(rec.dndu);
+        rec.dndv = self.rotate_back(rec.dndv);
+        Some((rec, pdf))
+    }
+
+    fn area(&self) -> f32 {
+        self.object.area()
     }
 }
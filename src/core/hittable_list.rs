@@ -1,5 +1,4 @@
-use crate::{HitRecord, Hittable, Interval, Point3, Ray, Vec3, AABB};
-use rand::Rng;
+use crate::{vec3, HitRecord, Hittable, Interval, Point3, Ray, Vec3, AABB};
 use std::sync::Arc;
 
 pub struct HittableList {
@@ -35,10 +34,17 @@ impl Hittable for HittableList {
     }
 
     fn random(&self, origin: Point3) -> Vec3 {
-        self.objects[rand::thread_rng().gen_range(0..self.objects.len())].random(origin)
+        // Any direction will do, since an empty list has zero density everywhere
+        if self.objects.is_empty() {
+            return vec3::random_unit_vector();
+        }
+        self.objects[crate::utils::random_index(self.objects.len())].random(origin)
     }
 
     fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        if self.objects.is_empty() {
+            return 0.;
+        }
         let weight = 1. / self.objects.len() as f32;
         let mut sum = 0.;
         for obj in &self.objects {
@@ -46,6 +52,19 @@ impl Hittable for HittableList {
         }
         sum
     }
+
+    fn sample_surface(&self, time: f32) -> Option<(HitRecord, f32)> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        let object = &self.objects[crate::utils::random_index(self.objects.len())];
+        let (rec, pdf) = object.sample_surface(time)?;
+        Some((rec, pdf / self.objects.len() as f32))
+    }
+
+    fn area(&self) -> f32 {
+        self.objects.iter().map(|object| object.area()).sum()
+    }
 }
 
 impl HittableList {
@@ -0,0 +1,293 @@
+use crate::{utils::degrees_to_radians, vec3, Point3, Vec3};
+
+use std::ops;
+
+/// A row-major 4x4 matrix for affine transformations of points, vectors and normals
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        Self::new([
+            [1., 0., 0., offset.x()],
+            [0., 1., 0., offset.y()],
+            [0., 0., 1., offset.z()],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn scale(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x(), 0., 0., 0.],
+            [0., factors.y(), 0., 0.],
+            [0., 0., factors.z(), 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Counter-clockwise rotation about the x axis, in degrees
+    pub fn rotate_x(degrees: f32) -> Self {
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        Self::new([
+            [1., 0., 0., 0.],
+            [0., cos, -sin, 0.],
+            [0., sin, cos, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Counter-clockwise rotation about the y axis, in degrees
+    pub fn rotate_y(degrees: f32) -> Self {
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        Self::new([
+            [cos, 0., sin, 0.],
+            [0., 1., 0., 0.],
+            [-sin, 0., cos, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Counter-clockwise rotation about the z axis, in degrees
+    pub fn rotate_z(degrees: f32) -> Self {
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        Self::new([
+            [cos, -sin, 0., 0.],
+            [sin, cos, 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Counter-clockwise rotation about an arbitrary `axis`, in degrees
+    pub fn rotate(axis: Vec3, degrees: f32) -> Self {
+        let a = vec3::unit_vector(axis);
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        let t = 1. - cos;
+        Self::new([
+            [
+                t * a.x() * a.x() + cos,
+                t * a.x() * a.y() - sin * a.z(),
+                t * a.x() * a.z() + sin * a.y(),
+                0.,
+            ],
+            [
+                t * a.x() * a.y() + sin * a.z(),
+                t * a.y() * a.y() + cos,
+                t * a.y() * a.z() - sin * a.x(),
+                0.,
+            ],
+            [
+                t * a.x() * a.z() - sin * a.y(),
+                t * a.y() * a.z() + sin * a.x(),
+                t * a.z() * a.z() + cos,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Rotation that turns the local +Z axis towards `forward`, keeping local +Y as close to `up`
+    /// as possible. Returns `None` if `forward` is zero or parallel to `up`.
+    pub fn look_rotation(forward: Vec3, up: Vec3) -> Option<Self> {
+        if forward.near_zero() {
+            return None;
+        }
+        let z = vec3::unit_vector(forward);
+        let x = vec3::cross(up, z);
+        if x.near_zero() {
+            return None;
+        }
+        let x = vec3::unit_vector(x);
+        let y = vec3::cross(z, x);
+        // The basis vectors are the columns
+        Some(Self::new([
+            [x.x(), y.x(), z.x(), 0.],
+            [x.y(), y.y(), z.y(), 0.],
+            [x.z(), y.z(), z.z(), 0.],
+            [0., 0., 0., 1.],
+        ]))
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = [[0.; 4]; 4];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Self::new(t)
+    }
+
+    /// Determinant of the upper-left 3x3 block, i.e. the linear part of the transform
+    pub fn determinant3(&self) -> f32 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// General inverse by Gauss-Jordan elimination with partial pivoting. Returns `None` for
+    /// singular matrices.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m.map(|row| row.map(f64::from));
+        let mut inv = Self::identity().m.map(|row| row.map(f64::from));
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1. / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+
+        Some(Self::new(inv.map(|row| row.map(|x| x as f32))))
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1. {
+            Point3::new(x, y, z)
+        } else {
+            Point3::new(x, y, z) / w
+        }
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+
+    /// Multiplies `v` by the transpose of this matrix. Called on the inverse of a transform, this
+    /// maps normals correctly even under non-uniform scaling.
+    pub fn transform_vector_transposed(&self, v: Vec3) -> Vec3 {
+        self.transpose().transform_vector(v)
+    }
+}
+
+impl ops::Mul for Matrix4 {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Self::new(m)
+    }
+}
+
+/// A rotation stored as a quaternion `w + xi + yj + zk`
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self {
+            w: 1.,
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        }
+    }
+}
+
+impl Quaternion {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub fn from_axis_angle(axis: Vec3, degrees: f32) -> Self {
+        let a = vec3::unit_vector(axis);
+        let (sin, cos) = (degrees_to_radians(degrees) / 2.).sin_cos();
+        Self::new(cos, a.x() * sin, a.y() * sin, a.z() * sin)
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    /// Rotation matrix of the normalized quaternion, or `None` for a zero quaternion
+    pub fn to_matrix(&self) -> Option<Matrix4> {
+        let len = self.length();
+        if len == 0. {
+            return None;
+        }
+        let (w, x, y, z) = (self.w / len, self.x / len, self.y / len, self.z / len);
+        Some(Matrix4::new([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ]))
+    }
+}
@@ -1,5 +1,5 @@
 pub(crate) mod ray;
-pub use ray::Ray;
+pub use ray::{Ray, RayDifferentials};
 pub mod vec3;
 pub use vec3::{Color, Point3, Vec3};
 pub(crate) mod hittable;
@@ -8,11 +8,21 @@ pub(crate) mod hittable_list;
 pub use hittable_list::HittableList;
 pub mod camera;
 pub use camera::{Camera, CameraConfig};
+pub mod film;
+pub use film::Film;
 pub mod interval;
 pub mod utils;
 pub use interval::Interval;
 pub(crate) mod onb;
 pub use onb::ONB;
+pub(crate) mod distribution;
+pub use distribution::{Distribution1D, Distribution2D};
 pub(crate) mod pdf;
-pub use pdf::{CosinePdf, Pdf, SpherePdf};
+pub use pdf::{power_heuristic, CosinePdf, HittablePdf, MixturePdf, Pdf, SpherePdf};
+pub mod matrix;
 pub mod vecmath;
+pub use matrix::{Matrix4, Quaternion};
+pub mod rng;
+pub use rng::Rng;
+pub mod transform;
+pub use transform::{Instance, Transform};
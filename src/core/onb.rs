@@ -20,6 +20,15 @@ impl ONB {
         Self { axis }
     }
 
+    /// A basis with `w` along `n` and `u` along `tangent`, which must be orthogonal to `n`
+    pub fn from_tangent(n: Vec3, tangent: Vec3) -> Self {
+        let w = vec3::unit_vector(n);
+        let u = vec3::unit_vector(tangent);
+        Self {
+            axis: [u, vec3::cross(w, u), w],
+        }
+    }
+
     pub fn u(&self) -> Vec3 {
         self.axis[0]
     }
@@ -33,4 +42,13 @@ impl ONB {
     pub fn transform(&self, v: Vec3) -> Vec3 {
         (v.x() * self.axis[0]) + (v.y() * self.axis[1]) + (v.z() * self.axis[2])
     }
+
+    /// The inverse of `transform`, giving the coordinates of `v` in this basis
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            vec3::dot(v, self.axis[0]),
+            vec3::dot(v, self.axis[1]),
+            vec3::dot(v, self.axis[2]),
+        )
+    }
 }
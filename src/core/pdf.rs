@@ -90,3 +90,17 @@ impl Pdf for MixturePdf {
         (0.5 * self.pdfs[0].value(direction)) + (0.5 * self.pdfs[1].value(direction))
     }
 }
+
+/// Veach's power heuristic with an exponent of two
+///
+/// The MIS weight for a sample drawn from a strategy with density `pdf_f`, when another strategy
+/// could have produced the same sample with density `pdf_g`.
+pub fn power_heuristic(pdf_f: f32, pdf_g: f32) -> f32 {
+    let f = pdf_f * pdf_f;
+    let g = pdf_g * pdf_g;
+    if f + g == 0. {
+        0.
+    } else {
+        f / (f + g)
+    }
+}
@@ -1,9 +1,21 @@
 use crate::vec3::{Point3, Vec3};
 
+/// Rays offset by one pixel to the right (`x`) and one pixel down (`y`) from a camera ray,
+/// which give the footprint of the pixel wherever the ray lands
+#[derive(Clone, Copy, Debug)]
+pub struct RayDifferentials {
+    pub rx_origin: Point3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Point3,
+    pub ry_direction: Vec3,
+}
+
+#[derive(Clone, Copy)]
 pub struct Ray {
     orig: Point3,
     dir: Vec3,
     tm: f32,
+    differentials: Option<RayDifferentials>,
 }
 
 impl Default for Ray {
@@ -12,6 +24,7 @@ impl Default for Ray {
             orig: Point3::default(),
             dir: Vec3::default(),
             tm: 0.0,
+            differentials: None,
         }
     }
 }
@@ -22,9 +35,15 @@ impl Ray {
             orig: origin,
             dir: direction,
             tm: time,
+            differentials: None,
         }
     }
 
+    pub fn with_differentials(mut self, differentials: Option<RayDifferentials>) -> Self {
+        self.differentials = differentials;
+        self
+    }
+
     pub fn origin(&self) -> Point3 {
         self.orig
     }
@@ -37,6 +56,10 @@ impl Ray {
         self.tm
     }
 
+    pub fn differentials(&self) -> Option<&RayDifferentials> {
+        self.differentials.as_ref()
+    }
+
     pub fn at(&self, t: f32) -> Point3 {
         self.orig + self.dir * t
     }
@@ -0,0 +1,70 @@
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+
+/// A PCG32 pseudorandom number generator (XSH-RR variant)
+///
+/// Small, fast and fully determined by its seed and stream, so renders can be reproduced exactly.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+    inc: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl Rng {
+    /// Creates a generator for the given `seed`. Different `stream`s with the same seed give
+    /// independent sequences.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Generator for one sample of one pixel, independent of the order the samples are taken in
+    pub fn for_sample(seed: u64, pixel: u64, sample: u64) -> Self {
+        Self::new(mix_bits(seed ^ mix_bits(sample)), pixel)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Uniform float in [0, 1)
+    pub fn uniform(&mut self) -> f32 {
+        // The top 24 bits fill the mantissa exactly, so the result never rounds up to 1
+        (self.next_u32() >> 8) as f32 * (1. / (1u32 << 24) as f32)
+    }
+
+    /// Uniform integer in [0, n)
+    pub fn uniform_below(&mut self, n: usize) -> usize {
+        ((self.next_u32() as u64 * n as u64) >> 32) as usize
+    }
+}
+
+/// SplitMix64 finalizer, used to spread nearby seeds over the whole state space
+pub(crate) fn mix_bits(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Hashes several values into one well-mixed 64-bit value
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0, |h, &v| mix_bits(h ^ v.wrapping_mul(0x9e3779b97f4a7c15)))
+}
@@ -0,0 +1,189 @@
+use crate::{vec3, HitRecord, Hittable, Interval, Matrix4, Point3, Ray, Vec3, AABB, ONB};
+
+use std::sync::Arc;
+
+/// An invertible affine transformation, stored together with its inverse
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Transform {
+    m: Matrix4,
+    m_inv: Matrix4,
+}
+
+impl Transform {
+    pub fn new(m: Matrix4) -> Option<Self> {
+        let m_inv = m.inverse()?;
+        Some(Self { m, m_inv })
+    }
+
+    pub fn identity() -> Self {
+        Self::default()
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.m
+    }
+
+    pub fn inverse_matrix(&self) -> &Matrix4 {
+        &self.m_inv
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            m: self.m_inv,
+            m_inv: self.m,
+        }
+    }
+
+    /// Returns the transform that applies `self` first and then `next`
+    pub fn then(&self, next: &Transform) -> Self {
+        Self {
+            m: next.m * self.m,
+            m_inv: self.m_inv * next.m_inv,
+        }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.m.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.m.transform_vector(v)
+    }
+
+    /// Maps a normal with the inverse transpose. The result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.m_inv.transform_vector_transposed(n)
+    }
+
+    pub fn bounding_box(&self, bbox: &AABB) -> AABB {
+        let mut min = Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let x = if i == 1 { bbox.x.max } else { bbox.x.min };
+                    let y = if j == 1 { bbox.y.max } else { bbox.y.min };
+                    let z = if k == 1 { bbox.z.max } else { bbox.z.min };
+                    let corner = self.point(Point3::new(x, y, z));
+
+                    for c in 0..3 {
+                        min.0[c] = min.0[c].min(corner.0[c]);
+                        max.0[c] = max.0[c].max(corner.0[c]);
+                    }
+                }
+            }
+        }
+
+        AABB::around_points(min, max)
+    }
+}
+
+/// A hittable placed in the world through an arbitrary affine `Transform`
+///
+/// Rays are moved into object space for intersection, and the resulting hit point and normal
+/// are moved back out. This replaces stacks of `Translate`/`RotateY` wrappers with a single
+/// matrix.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    bbox: AABB,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let bbox = transform.bounding_box(object.bounding_box());
+        Self {
+            object,
+            transform,
+            bbox,
+        }
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// The change in the world's unit normal for a change `dn` in the object's unit normal `n`
+    ///
+    /// The normal is mapped by the inverse transpose and renormalized, so only the part of the
+    /// mapped change across the new normal remains.
+    fn normal_derivative(&self, n: Vec3, dn: Vec3) -> Vec3 {
+        let mapped = self.transform.normal(n);
+        let unit = vec3::unit_vector(mapped);
+        let dmapped = self.transform.normal(dn);
+        (dmapped - vec3::dot(dmapped, unit) * unit) / mapped.length()
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, ray_t: &mut Interval, rec: &mut HitRecord) -> bool {
+        // The direction is not renormalized, so `t` means the same thing in both spaces
+        let inv = self.transform.inverse();
+        let object_ray = Ray::new(inv.point(r.origin()), inv.vector(r.direction()), r.time());
+
+        if !self.object.hit(&object_ray, ray_t, rec) {
+            return false;
+        }
+
+        rec.p = self.transform.point(rec.p);
+        (rec.dndu, rec.dndv) = (
+            self.normal_derivative(rec.normal, rec.dndu),
+            self.normal_derivative(rec.normal, rec.dndv),
+        );
+        rec.normal = vec3::unit_vector(self.transform.normal(rec.normal));
+        rec.dpdu = self.transform.vector(rec.dpdu);
+        rec.dpdv = self.transform.vector(rec.dpdv);
+
+        true
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let inv = self.transform.inverse();
+        self.transform.vector(self.object.random(inv.point(origin)))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        // Change of variables for the solid angle: a linear map A takes the unit direction w to
+        // A^-1 w / |A^-1 w| with Jacobian |det A^-1| / |A^-1 w|^3
+        let inv = self.transform.inverse();
+        let object_direction = inv.vector(vec3::unit_vector(direction));
+        let stretch = object_direction.length();
+        let jacobian = inv.matrix().determinant3().abs() / (stretch * stretch * stretch);
+
+        self.object.pdf_value(inv.point(origin), object_direction) * jacobian
+    }
+
+    fn sample_surface(&self, time: f32) -> Option<(HitRecord, f32)> {
+        let (mut rec, pdf) = self.object.sample_surface(time)?;
+
+        // The area density shrinks by however much the transform stretches a small patch of
+        // the surface around the point
+        let tangents = ONB::new(rec.normal);
+        let stretch = vec3::cross(
+            self.transform.vector(tangents.u()),
+            self.transform.vector(tangents.v()),
+        )
+        .length();
+
+        rec.p = self.transform.point(rec.p);
+        (rec.dndu, rec.dndv) = (
+            self.normal_derivative(rec.normal, rec.dndu),
+            self.normal_derivative(rec.normal, rec.dndv),
+        );
+        rec.normal = vec3::unit_vector(self.transform.normal(rec.normal));
+        rec.dpdu = self.transform.vector(rec.dpdu);
+        rec.dpdv = self.transform.vector(rec.dpdv);
+        Some((rec, pdf / stretch))
+    }
+
+    fn area(&self) -> f32 {
+        // Exact for uniform scales, and near enough under a stretch to weigh lights by
+        let volume = self.transform.matrix().determinant3().abs();
+        self.object.area() * volume.powf(2. / 3.)
+    }
+}
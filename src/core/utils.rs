@@ -1,5 +1,3 @@
-use rand;
-
 pub const PI: f64 = std::f64::consts::PI;
 pub const INFINITY: f32 = f32::INFINITY;
 
@@ -8,9 +6,15 @@ pub fn degrees_to_radians(degrees: f32) -> f32 {
     degrees * PI as f32 / 180.0
 }
 
-/// Generates a pseudorandom floating point number in the range [0, 1]
+/// Takes the next sample dimension, in the range [0, 1), from the current thread's sampler
 pub fn random_double() -> f32 {
-    rand::random::<f32>()
+    crate::sampler::with_thread_sampler(|sampler| sampler.get_1d())
+}
+
+/// Takes the next two sample dimensions as a well-distributed pair. Use this instead of two calls
+/// to `random_double` when the values are used together, e.g. to pick a direction.
+pub fn random_2d() -> (f32, f32) {
+    crate::sampler::with_thread_sampler(|sampler| sampler.get_2d())
 }
 
 /// Generates a pseudorandom number within the range [min, max]
@@ -18,7 +22,51 @@ pub fn random_double_range(min: f32, max: f32) -> f32 {
     min + (max - min) * random_double()
 }
 
+/// Generates a pseudorandom index in the range [0, len)
+pub fn random_index(len: usize) -> usize {
+    ((random_double() * len as f32) as usize).min(len - 1)
+}
+
 /// Linearly interpolates the input value `x` between `a` and `b`
 pub fn lerp(x: f32, a: f32, b: f32) -> f32 {
     (1. - x) * a + x * b
 }
+
+/// The error function, with Abramowitz and Stegun's approximation 7.1.26
+pub fn erf(x: f32) -> f32 {
+    let t = 1. / (1. + 0.327_591_1 * x.abs());
+    let p = t
+        * (0.254_829_6
+            + t * (-0.284_496_74 + t * (1.421_413_7 + t * (-1.453_152 + t * 1.061_405_4))));
+    (1. - p * (-x * x).exp()).copysign(x)
+}
+
+/// Inverse of the error function, with Giles' single precision approximation
+pub fn erf_inv(x: f32) -> f32 {
+    let x = x.clamp(-0.99999, 0.99999);
+    let w = -((1. - x) * (1. + x)).ln();
+    let p = if w < 5. {
+        let w = w - 2.5;
+        let mut p = 2.810_226_4e-08;
+        p = 3.432_739_4e-07 + p * w;
+        p = -3.523_387_7e-06 + p * w;
+        p = -4.391_506_5e-06 + p * w;
+        p = 0.000_218_580_87 + p * w;
+        p = -0.001_253_725 + p * w;
+        p = -0.004_177_681_6 + p * w;
+        p = 0.246_640_73 + p * w;
+        1.501_409_4 + p * w
+    } else {
+        let w = w.sqrt() - 3.;
+        let mut p = -0.000_200_214_26;
+        p = 0.000_100_950_56 + p * w;
+        p = 0.001_349_343_2 + p * w;
+        p = -0.003_673_428_4 + p * w;
+        p = 0.005_739_507_7 + p * w;
+        p = -0.007_622_461 + p * w;
+        p = 0.009_438_870_5 + p * w;
+        p = 1.001_674 + p * w;
+        2.832_976_8 + p * w
+    };
+    p * x
+}
@@ -1,4 +1,4 @@
-use crate::utils::{random_double, random_double_range};
+use crate::utils::{random_2d, random_double, random_double_range};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::ops;
 
@@ -189,7 +189,11 @@ pub fn random_in_unit_sphere() -> Vec3 {
 }
 
 pub fn random_unit_vector() -> Vec3 {
-    unit_vector(random_in_unit_sphere())
+    let (r1, r2) = random_2d();
+    let z = 1. - 2. * r1;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * std::f32::consts::PI * r2;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
 pub fn random_on_hemisphere(normal: Vec3) -> Vec3 {
@@ -212,22 +216,24 @@ pub fn refract(uv: Vec3, n: Vec3, etai_over_etat: f32) -> Vec3 {
     r_out_perp + r_out_parallel
 }
 
+/// Shirley-Chiu concentric mapping of a sample pair to the unit disk
 pub fn random_in_unit_disk() -> Vec3 {
-    loop {
-        let p = Vec3::new(
-            random_double_range(-1.0, 1.0),
-            random_double_range(-1.0, 1.0),
-            0.0,
-        );
-        if p.length_squared() < 1.0 {
-            return p;
-        }
-    }
+    let (r1, r2) = random_2d();
+    let (a, b) = (2. * r1 - 1., 2. * r2 - 1.);
+    if a == 0. && b == 0. {
+        return Vec3::default();
+    }
+    let quarter_pi = std::f32::consts::FRAC_PI_4;
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, quarter_pi * (b / a))
+    } else {
+        (b, 2. * quarter_pi - quarter_pi * (a / b))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.)
 }
 
 pub fn random_cosine_direction() -> Vec3 {
-    let r1 = random_double();
-    let r2 = random_double();
+    let (r1, r2) = random_2d();
 
     let phi = 2. * std::f32::consts::PI * r1;
     let x = phi.cos() * r2.sqrt();
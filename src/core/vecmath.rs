@@ -6,6 +6,8 @@ pub fn spherical_triangle_area(a: Vec3, b: Vec3, c: Vec3) -> f32 {
     (2. * (numerator / denominator)).tan().abs()
 }
 
+/// The solid angle of the spherical quadrilateral with unit vertices `a`, `b`, `c` and `d`, in
+/// order around it
 pub fn spherical_quad_area(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> f32 {
     let axb = vec3::cross(a, b);
     let bxc = vec3::cross(b, c);
@@ -18,9 +20,24 @@ pub fn spherical_quad_area(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> f32 {
     {
         return 0.;
     }
-    let _axb = vec3::unit_vector(axb);
-    let _bxc = vec3::unit_vector(bxc);
-    let _cxd = vec3::unit_vector(cxd);
-    let _dxa = vec3::unit_vector(dxa);
-    todo!();
+    let axb = vec3::unit_vector(axb);
+    let bxc = vec3::unit_vector(bxc);
+    let cxd = vec3::unit_vector(cxd);
+    let dxa = vec3::unit_vector(dxa);
+
+    // The interior angles at each vertex, between the planes of the edges that meet there
+    let alpha = angle_between(dxa, -axb);
+    let beta = angle_between(axb, -bxc);
+    let gamma = angle_between(bxc, -cxd);
+    let delta = angle_between(cxd, -dxa);
+    (alpha + beta + gamma + delta - 2. * std::f32::consts::PI).abs()
+}
+
+/// The angle between the unit vectors `a` and `b`, accurate even when they are nearly parallel
+pub fn angle_between(a: Vec3, b: Vec3) -> f32 {
+    if vec3::dot(a, b) < 0. {
+        std::f32::consts::PI - 2. * ((a + b).length() / 2.).clamp(-1., 1.).asin()
+    } else {
+        2. * ((b - a).length() / 2.).clamp(-1., 1.).asin()
+    }
 }
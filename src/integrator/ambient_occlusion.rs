@@ -0,0 +1,40 @@
+use super::{intersect, Integrator};
+use crate::{Color, CosinePdf, Film, Hittable, Interval, Pdf, Ray};
+
+/// Ambient occlusion at the first hit
+///
+/// Sends one cosine-distributed ray from the surface per sample and returns white when nothing
+/// is hit within `distance`, so creases and contact areas darken. Rays that miss the scene are
+/// black.
+pub struct AmbientOcclusionIntegrator {
+    distance: f32,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(distance: f32) -> Self {
+        Self { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, _lights: &dyn Hittable, _film: &Film) -> Color {
+        let Some(rec) = intersect(r, world) else {
+            return Color::new(0., 0., 0.);
+        };
+
+        // `rec.normal` always faces the incoming ray, i.e. the visible side of the surface
+        let direction = CosinePdf::new(rec.normal).generate();
+        let occlusion_ray = Ray::new(rec.p, direction, r.time());
+        let max_t = self.distance / direction.length();
+        let mut occluder = Default::default();
+        if world.hit(
+            &occlusion_ray,
+            &mut Interval::new(0.001, max_t),
+            &mut occluder,
+        ) {
+            Color::new(0., 0., 0.)
+        } else {
+            Color::new(1., 1., 1.)
+        }
+    }
+}
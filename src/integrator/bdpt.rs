@@ -0,0 +1,530 @@
+use super::{intersect, Integrator};
+use crate::{
+    vec3, Camera, Color, CosinePdf, Environment, Film, HitRecord, Hittable, Interval, Pdf, Point3,
+    Ray, ScatterRecord, Vec3,
+};
+
+use std::sync::Arc;
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+/// A vertex of a camera or light subpath
+#[derive(Clone)]
+struct Vertex {
+    kind: VertexKind,
+    /// Where the vertex is. Light vertices keep the outward normal of the emitter.
+    rec: HitRecord,
+    /// Direction the subpath arrived along, for evaluating the BSDF at surface vertices
+    incoming: Vec3,
+    /// Throughput of the subpath up to this vertex, divided by the density of sampling it
+    beta: Color,
+    /// Whether the vertex scatters in a single direction, so it can't be connected to
+    delta: bool,
+    /// Whether the vertex lies on one of the scene's lights, so light sampling can create it
+    on_light: bool,
+    /// Whether the vertex is on a light subpath, which carries importance rather than radiance
+    importance: bool,
+    /// Density of sampling this vertex from its predecessor on its own subpath, per area
+    pdf_fwd: f32,
+    /// Density of sampling this vertex from the other direction, per area
+    pdf_rev: f32,
+}
+
+impl Vertex {
+    fn new(kind: VertexKind, rec: HitRecord, beta: Color) -> Self {
+        Self {
+            kind,
+            rec,
+            incoming: Vec3::default(),
+            beta,
+            delta: false,
+            on_light: kind == VertexKind::Light,
+            importance: false,
+            pdf_fwd: 0.,
+            pdf_rev: 0.,
+        }
+    }
+
+    fn p(&self) -> Point3 {
+        self.rec.p
+    }
+
+    /// Light or BSDF towards `next`, times the cosine at this vertex
+    ///
+    /// For surfaces this is the BSDF from the subpath's incoming direction to `next`, for
+    /// importance on light subpaths, since refraction treats it differently from radiance.
+    fn f(&self, next: Point3, time: f32) -> Color {
+        let outgoing = Ray::new(self.p(), next - self.p(), time);
+        match self.kind {
+            VertexKind::Surface => {
+                let r_in = Ray::new(self.p() - self.incoming, self.incoming, time);
+                if self.importance {
+                    self.rec.mat.eval_importance(&r_in, &self.rec, &outgoing)
+                } else {
+                    self.rec.mat.eval(&r_in, &self.rec, &outgoing)
+                }
+            }
+            VertexKind::Light => {
+                // Looking at the light from `next` decides which side of it is seen
+                let mut rec = self.rec.clone();
+                let r_in = Ray::new(next, self.p() - next, time);
+                rec.set_face_normal(&r_in, self.rec.normal);
+                let cos_theta = vec3::dot(self.rec.normal, vec3::unit_vector(outgoing.direction()));
+                rec.mat.emitted(&r_in, &rec, rec.u, rec.v, rec.p) * cos_theta.abs()
+            }
+            VertexKind::Camera => Color::new(0., 0., 0.),
+        }
+    }
+
+    /// Converts a solid angle density at this vertex into an area density at `next`
+    fn convert_density(&self, pdf: f32, next: &Vertex) -> f32 {
+        let w = next.p() - self.p();
+        let distance_squared = w.length_squared();
+        if distance_squared == 0. {
+            return 0.;
+        }
+        let mut pdf = pdf / distance_squared;
+        if next.kind != VertexKind::Camera {
+            pdf *= vec3::dot(next.rec.normal, vec3::unit_vector(w)).abs();
+        }
+        pdf
+    }
+
+    /// Area density of this vertex sampling `next`, having been reached from `prev`
+    fn pdf(&self, camera: &Camera, prev: Option<&Vertex>, next: &Vertex, time: f32) -> f32 {
+        let direction = next.p() - self.p();
+        let pdf = match self.kind {
+            VertexKind::Camera => camera.direction_pdf(direction),
+            VertexKind::Light => return self.emission_pdf(next),
+            VertexKind::Surface => {
+                let Some(prev) = prev else { return 0. };
+                let r_in = Ray::new(prev.p(), self.p() - prev.p(), time);
+                let scattered = Ray::new(self.p(), direction, time);
+                self.rec.mat.scattering_pdf(&r_in, &self.rec, &scattered)
+            }
+        };
+        self.convert_density(pdf, next)
+    }
+
+    /// Area density of a light path leaving this point on an emitter towards `next`
+    fn emission_pdf(&self, next: &Vertex) -> f32 {
+        let pdf = CosinePdf::new(self.rec.normal).value(next.p() - self.p());
+        self.convert_density(pdf, next)
+    }
+
+    /// Area density of light sampling choosing this point when connecting from `neighbor`
+    ///
+    /// Light paths start at points picked by area, but connections from the camera subpath
+    /// pick them with `Hittable::random`. For MIS all strategies use the density of the
+    /// latter, which keeps the weights of every path summing to one.
+    fn light_origin_pdf(&self, neighbor: Point3, lights: &dyn Hittable) -> f32 {
+        if !self.on_light {
+            return 0.;
+        }
+        let w = self.p() - neighbor;
+        let distance_squared = w.length_squared();
+        let cos_theta = vec3::dot(self.rec.normal, vec3::unit_vector(w)).abs();
+        lights.pdf_value(neighbor, w) * cos_theta / distance_squared
+    }
+}
+
+/// The camera and light subpaths traced for one camera sample
+struct Subpaths {
+    camera: Vec<Vertex>,
+    light: Vec<Vertex>,
+    time: f32,
+}
+
+/// Contribution of one way of connecting the subpaths, before MIS
+struct Connection {
+    l: Color,
+    /// A vertex sampled to complete the path, standing in for the end of one subpath
+    sampled: Option<Vertex>,
+    /// The pixel to splat onto, for paths connected to the camera
+    pixel: Option<(usize, usize)>,
+}
+
+impl Connection {
+    fn new(l: Color, sampled: Option<Vertex>, pixel: Option<(usize, usize)>) -> Self {
+        Self { l, sampled, pixel }
+    }
+}
+
+/// Bidirectional path tracing
+///
+/// Every camera sample traces a subpath from the camera and another from a point on a light,
+/// and connects every pair of their vertices with a shadow ray. Camera vertices are also
+/// connected to freshly sampled points on the lights, and light vertices to the camera, which
+/// splats onto whatever pixel sees them. All the ways of building a path of a given length are
+/// combined with MIS using the power heuristic, so paths that are hard to find from the camera,
+/// like light reaching a room around an occluder, are found from the light instead.
+pub struct BdptIntegrator {
+    camera: Camera,
+    max_depth: usize,
+    background: Arc<dyn Environment>,
+}
+
+impl BdptIntegrator {
+    /// `camera` must be initialized, since light paths are projected onto its image
+    pub fn new(camera: Camera, max_depth: usize, background: Arc<dyn Environment>) -> Self {
+        Self {
+            camera,
+            max_depth,
+            background,
+        }
+    }
+
+    /// Extends `path` by sampling the BSDF at each vertex, for at most `max_bounces` hits
+    ///
+    /// Returns the ray that left the scene and its throughput, if the walk ended that way.
+    fn random_walk(
+        &self,
+        world: &dyn Hittable,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf_fwd: f32,
+        max_bounces: usize,
+        path: &mut Vec<Vertex>,
+    ) -> Option<(Ray, Color)> {
+        let time = ray.time();
+        let importance = path[0].kind == VertexKind::Light;
+        for _ in 0..max_bounces {
+            let Some(rec) = intersect(&ray, world) else {
+                return Some((ray, beta));
+            };
+
+            let mut srec = ScatterRecord::default();
+            let scatters = rec.mat.scatter(&ray, &rec, &mut srec);
+
+            let prev = path.len() - 1;
+            let mut vertex = Vertex::new(VertexKind::Surface, rec, beta);
+            vertex.incoming = ray.direction();
+            vertex.importance = importance;
+            vertex.pdf_fwd = path[prev].convert_density(pdf_fwd, &vertex);
+            path.push(vertex);
+            let current = path.len() - 1;
+            if !scatters {
+                break;
+            }
+            let rec = &path[current].rec;
+
+            if srec.skip_pdf {
+                // Specular bounces have no density that other strategies could reproduce
+                beta = beta * srec.attenuation;
+                path[current].delta = true;
+                path[prev].pdf_rev = 0.;
+                pdf_fwd = 0.;
+                ray = srec.skip_pdf_ray;
+                continue;
+            }
+
+            let direction = srec.pdf.generate();
+            let pdf = srec.pdf.value(direction);
+            if pdf <= 0. {
+                break;
+            }
+            let scattered = Ray::new(rec.p, direction, time);
+            beta = beta * path[current].f(rec.p + direction, time) / pdf;
+
+            // Density of scattering the other way, back towards the previous vertex
+            let reversed = Ray::new(rec.p + direction, -direction, time);
+            let towards_prev = Ray::new(rec.p, path[prev].p() - rec.p, time);
+            let pdf_rev = rec.mat.scattering_pdf(&reversed, rec, &towards_prev);
+            path[prev].pdf_rev = path[current].convert_density(pdf_rev, &path[prev]);
+
+            pdf_fwd = pdf;
+            ray = scattered;
+        }
+        None
+    }
+
+    /// The camera subpath for the ray `r`. Light from the background is added to `color`.
+    fn camera_subpath(&self, r: &Ray, world: &dyn Hittable, color: &mut Color) -> Vec<Vertex> {
+        let rec = HitRecord {
+            p: r.origin(),
+            ..Default::default()
+        };
+        let mut path = vec![Vertex::new(VertexKind::Camera, rec, Color::new(1., 1., 1.))];
+        let ray = *r;
+        let pdf_dir = self.camera.direction_pdf(r.direction());
+        let beta = Color::new(1., 1., 1.);
+        if let Some((escaped, beta)) =
+            self.random_walk(world, ray, beta, pdf_dir, self.max_depth, &mut path)
+        {
+            // No light path ends in the background, so camera paths get all of it
+            *color += beta * self.background.radiance(escaped.direction());
+        }
+        path
+    }
+
+    /// A light subpath, starting at a point picked by area on `lights`
+    fn light_subpath(&self, time: f32, world: &dyn Hittable, lights: &dyn Hittable) -> Vec<Vertex> {
+        let Some((rec, pdf_pos)) = lights.sample_surface(time) else {
+            return Vec::new();
+        };
+        if pdf_pos <= 0. {
+            return Vec::new();
+        }
+
+        let emission = CosinePdf::new(rec.normal);
+        let direction = emission.generate();
+        let pdf_dir = emission.value(direction);
+        let mut light = Vertex::new(VertexKind::Light, rec, Color::new(1., 1., 1.) / pdf_pos);
+        light.pdf_fwd = pdf_pos;
+        if pdf_dir <= 0. {
+            return vec![light];
+        }
+
+        let ray = Ray::new(light.p(), direction, time);
+        let beta = light.beta * light.f(light.p() + direction, time) / pdf_dir;
+        let mut path = vec![light];
+        if beta.x() > 0. || beta.y() > 0. || beta.z() > 0. {
+            self.random_walk(world, ray, beta, pdf_dir, self.max_depth - 1, &mut path);
+        }
+
+        // Use the same density for the light point as connections from the camera do
+        if path.len() > 1 {
+            path[0].pdf_fwd = path[0].light_origin_pdf(path[1].p(), lights);
+        }
+        path
+    }
+
+    /// Contribution of the strategy that uses `s` light vertices and `t` camera vertices,
+    /// without its MIS weight, together with the vertex sampled to complete it, if any
+    fn connect(
+        &self,
+        paths: &Subpaths,
+        s: usize,
+        t: usize,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+    ) -> Option<Connection> {
+        let (light_path, camera_path, time) = (&paths.light, &paths.camera, paths.time);
+        if s == 0 {
+            // The camera subpath found a light by itself
+            let pt = &camera_path[t - 1];
+            let r_in = Ray::new(pt.p() - pt.incoming, pt.incoming, time);
+            let emitted = pt
+                .rec
+                .mat
+                .emitted(&r_in, &pt.rec, pt.rec.u, pt.rec.v, pt.p());
+            return Some(Connection::new(pt.beta * emitted, None, None));
+        }
+
+        if t == 1 {
+            // Connect a light vertex to the camera, for a pixel that isn't known in advance
+            let qs = &light_path[s - 1];
+            if qs.delta {
+                return None;
+            }
+            let (lens_point, x, y, importance) = self.camera.sample_importance(qs.p())?;
+            let distance_squared = (lens_point - qs.p()).length_squared();
+            let l = qs.beta * qs.f(lens_point, time) * importance / distance_squared;
+            if is_black(l) || !unoccluded(world, qs.p(), lens_point, time) {
+                return None;
+            }
+            let rec = HitRecord {
+                p: lens_point,
+                ..Default::default()
+            };
+            let sampled = Vertex::new(VertexKind::Camera, rec, Color::new(1., 1., 1.));
+            return Some(Connection::new(l, Some(sampled), Some((x, y))));
+        }
+
+        let pt = &camera_path[t - 1];
+        if pt.delta {
+            return None;
+        }
+
+        if s == 1 {
+            // Connect a camera vertex to a point picked on the lights
+            let direction = lights.random(pt.p());
+            let light_pdf = lights.pdf_value(pt.p(), direction);
+            if light_pdf <= 0. {
+                return None;
+            }
+            let ray = Ray::new(pt.p(), direction, time);
+            let mut rec = HitRecord::default();
+            if !lights.hit(&ray, &mut Interval::new(0.001, f32::INFINITY), &mut rec)
+                || !rec.front_face
+            {
+                return None;
+            }
+            let emitted = rec.mat.emitted(&ray, &rec, rec.u, rec.v, rec.p);
+            let l = pt.beta * pt.f(rec.p, time) * emitted / light_pdf;
+            if is_black(l) || !unoccluded(world, pt.p(), rec.p, time) {
+                return None;
+            }
+            let mut sampled = Vertex::new(VertexKind::Light, rec, Color::new(1., 1., 1.));
+            sampled.pdf_fwd = sampled.light_origin_pdf(pt.p(), lights);
+            return Some(Connection::new(l, Some(sampled), None));
+        }
+
+        // Connect the ends of both subpaths
+        let qs = &light_path[s - 1];
+        if qs.delta {
+            return None;
+        }
+        let distance_squared = (qs.p() - pt.p()).length_squared();
+        let l = qs.beta * qs.f(pt.p(), time) * pt.f(qs.p(), time) * pt.beta / distance_squared;
+        if is_black(l) || !unoccluded(world, pt.p(), qs.p(), time) {
+            return None;
+        }
+        Some(Connection::new(l, None, None))
+    }
+
+    /// Power heuristic weight of the strategy with `s` light and `t` camera vertices, against
+    /// every other strategy that builds the same path
+    fn mis_weight(
+        &self,
+        paths: &Subpaths,
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+        lights: &dyn Hittable,
+    ) -> f32 {
+        let (light_path, camera_path, time) = (&paths.light, &paths.camera, paths.time);
+        if s + t == 2 {
+            return 1.;
+        }
+
+        // The vertices at and next to the connection, with the sampled vertex swapped in
+        let pt = match (t, sampled) {
+            (1, Some(sampled)) => sampled,
+            _ => &camera_path[t - 1],
+        };
+        let qs = match (s, sampled) {
+            (0, _) => None,
+            (1, Some(sampled)) if t > 1 => Some(sampled),
+            _ => Some(&light_path[s - 1]),
+        };
+        let pt_minus = (t > 1).then(|| &camera_path[t - 2]);
+        let qs_minus = (s > 1).then(|| &light_path[s - 2]);
+
+        // (pdf_fwd, pdf_rev, delta) of each vertex, from the end of each subpath inwards
+        let mut camera: Vec<(f32, f32, bool)> = camera_path[..t]
+            .iter()
+            .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+            .collect();
+        let mut light: Vec<(f32, f32, bool)> = light_path[..s]
+            .iter()
+            .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+            .collect();
+        if let (1, Some(qs)) = (s, qs) {
+            // The light point's density depends on the vertex it is connected to
+            light[0].0 = qs.light_origin_pdf(pt.p(), lights);
+        }
+
+        // Densities of sampling the connection vertices the other way around
+        camera[t - 1].1 = match qs {
+            Some(qs) => qs.pdf(&self.camera, qs_minus, pt, time),
+            None => pt.light_origin_pdf(pt_minus.map_or(pt.p(), Vertex::p), lights),
+        };
+        camera[t - 1].2 = false;
+        if let Some(pt_minus) = pt_minus {
+            camera[t - 2].1 = match qs {
+                Some(qs) => pt.pdf(&self.camera, Some(qs), pt_minus, time),
+                None => pt.emission_pdf(pt_minus),
+            };
+        }
+        if let Some(qs) = qs {
+            light[s - 1].1 = pt.pdf(&self.camera, pt_minus, qs, time);
+            light[s - 1].2 = false;
+        }
+        if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) {
+            light[s - 2].1 = qs.pdf(&self.camera, Some(pt), qs_minus, time);
+        }
+
+        let remap = |pdf: f32| if pdf != 0. { pdf } else { 1. };
+        let mut sum = 0.;
+
+        // Strategies that take more vertices from the light subpath
+        let mut ratio = 1.;
+        for i in (1..t).rev() {
+            ratio *= remap(camera[i].1) / remap(camera[i].0);
+            if !camera[i].2 && !camera[i - 1].2 {
+                sum += ratio * ratio;
+            }
+        }
+
+        // Strategies that take more vertices from the camera subpath
+        let mut ratio = 1.;
+        for i in (0..s).rev() {
+            ratio *= remap(light[i].1) / remap(light[i].0);
+            let delta_before = i > 0 && light[i - 1].2;
+            if !light[i].2 && !delta_before {
+                sum += ratio * ratio;
+            }
+        }
+
+        1. / (1. + sum)
+    }
+}
+
+impl Integrator for BdptIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, lights: &dyn Hittable, film: &Film) -> Color {
+        let time = r.time();
+        let mut color = Color::new(0., 0., 0.);
+        let mut camera = self.camera_subpath(r, world, &mut color);
+        let light = self.light_subpath(time, world, lights);
+
+        // Mark camera vertices that light sampling could have created
+        for i in 1..camera.len() {
+            camera[i].on_light = is_on_light(camera[i - 1].p(), camera[i].p(), lights, time);
+        }
+
+        let paths = Subpaths {
+            camera,
+            light,
+            time,
+        };
+        for t in 1..=paths.camera.len() {
+            for s in 0..=paths.light.len() {
+                // Paths have `s + t - 1` segments and need at least one. Lights seen directly
+                // are left to the camera subpath.
+                if (t == 1 && s <= 1) || s + t - 1 > self.max_depth {
+                    continue;
+                }
+                let Some(connection) = self.connect(&paths, s, t, world, lights) else {
+                    continue;
+                };
+                if is_black(connection.l) {
+                    continue;
+                }
+                let weight = self.mis_weight(&paths, connection.sampled.as_ref(), s, t, lights);
+                match connection.pixel {
+                    Some((x, y)) => film.add_splat(x, y, connection.l * weight),
+                    None => color += connection.l * weight,
+                }
+            }
+        }
+
+        color
+    }
+}
+
+fn is_black(c: Color) -> bool {
+    c.x() <= 0. && c.y() <= 0. && c.z() <= 0.
+}
+
+/// Whether nothing blocks the segment between `a` and `b`
+fn unoccluded(world: &dyn Hittable, a: Point3, b: Point3, time: f32) -> bool {
+    let ray = Ray::new(a, b - a, time);
+    let epsilon = 0.001 / (b - a).length();
+    let mut rec = HitRecord::default();
+    !world.hit(&ray, &mut Interval::new(epsilon, 1. - epsilon), &mut rec)
+}
+
+/// Whether the first of `lights` seen from `from` towards `p` is at `p`
+fn is_on_light(from: Point3, p: Point3, lights: &dyn Hittable, time: f32) -> bool {
+    let ray = Ray::new(from, p - from, time);
+    let epsilon = 0.001 / (p - from).length();
+    let mut rec = HitRecord::default();
+    lights.hit(&ray, &mut Interval::new(epsilon, 1. + epsilon), &mut rec)
+        && (rec.t - 1.).abs() <= epsilon
+}
@@ -0,0 +1,69 @@
+//! Integrators that show a property of the first hit instead of light. Rays that miss the scene
+//! are black.
+
+use super::{intersect, Integrator};
+use crate::rng::hash;
+use crate::{Color, Film, Hittable, Ray};
+
+use std::sync::Arc;
+
+/// Outward surface normals, mapped from [-1, 1] to [0, 1]
+pub struct NormalsIntegrator;
+
+impl Integrator for NormalsIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, _lights: &dyn Hittable, _film: &Film) -> Color {
+        let Some(rec) = intersect(r, world) else {
+            return Color::new(0., 0., 0.);
+        };
+        let outward = if rec.front_face {
+            rec.normal
+        } else {
+            -rec.normal
+        };
+        0.5 * (outward + Color::new(1., 1., 1.))
+    }
+}
+
+/// Texture coordinates, u in red and v in green
+pub struct UvIntegrator;
+
+impl Integrator for UvIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, _lights: &dyn Hittable, _film: &Film) -> Color {
+        match intersect(r, world) {
+            Some(rec) => Color::new(rec.u, rec.v, 0.),
+            None => Color::new(0., 0., 0.),
+        }
+    }
+}
+
+/// Distance from the camera to the first hit, in scene units in every channel
+pub struct DepthIntegrator;
+
+impl Integrator for DepthIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, _lights: &dyn Hittable, _film: &Film) -> Color {
+        match intersect(r, world) {
+            Some(rec) => {
+                let depth = rec.t * r.direction().length();
+                Color::new(depth, depth, depth)
+            }
+            None => Color::new(0., 0., 0.),
+        }
+    }
+}
+
+/// A flat color for every material instance
+///
+/// Colors are derived from where the material lives in memory, so they tell materials apart
+/// within an image but can change from one run to the next.
+pub struct MaterialIdIntegrator;
+
+impl Integrator for MaterialIdIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, _lights: &dyn Hittable, _film: &Film) -> Color {
+        let Some(rec) = intersect(r, world) else {
+            return Color::new(0., 0., 0.);
+        };
+        let id = hash(&[Arc::as_ptr(&rec.mat) as *const () as u64]);
+        let channel = |shift: u32| ((id >> shift) & 0xff) as f32 / 255.;
+        Color::new(channel(0), channel(8), channel(16))
+    }
+}
@@ -0,0 +1,56 @@
+use super::{direct_lighting, intersect, Integrator};
+use crate::{Color, Environment, Film, Hittable, Ray, ScatterRecord};
+
+use std::sync::Arc;
+
+/// Direct lighting only: emission, plus light that reaches a surface straight from an emitter or
+/// the background
+///
+/// Light is gathered at the first non-specular surface with the same MIS estimate as the path
+/// tracer, but the path stops there. Specular surfaces in front of it are followed, up to
+/// `max_depth` of them, so mirrors and glass still show what they reflect.
+pub struct DirectLightingIntegrator {
+    max_depth: usize,
+    background: Arc<dyn Environment>,
+}
+
+impl DirectLightingIntegrator {
+    pub fn new(max_depth: usize, background: Arc<dyn Environment>) -> Self {
+        Self {
+            max_depth,
+            background,
+        }
+    }
+}
+
+impl Integrator for DirectLightingIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, lights: &dyn Hittable, _film: &Film) -> Color {
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = *r;
+
+        for _ in 0..self.max_depth {
+            let Some(rec) = intersect(&ray, world) else {
+                color += throughput * self.background.radiance(ray.direction());
+                break;
+            };
+            color += throughput * rec.mat.emitted(&ray, &rec, rec.u, rec.v, rec.p);
+
+            let mut srec: ScatterRecord = ScatterRecord::default();
+            if !rec.mat.scatter(&ray, &rec, &mut srec) {
+                break;
+            }
+            if srec.skip_pdf {
+                throughput = throughput * srec.attenuation;
+                ray = srec.skip_pdf_ray;
+                continue;
+            }
+
+            color += throughput
+                * direct_lighting(&ray, &rec, &srec, world, lights, self.background.as_ref());
+            break;
+        }
+
+        color
+    }
+}
@@ -0,0 +1,195 @@
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
+
+use super::{Integrator, PathIntegrator};
+use crate::utils::random_2d;
+use crate::{sampler, Camera, Color, Film, Hittable, MltSampler, Ray, Rng};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Primary sample space Metropolis light transport, after Kelemen et al.
+///
+/// Every path traced by the path tracer is a function of the random numbers it draws, the
+/// primary sample vector, which also picks the pixel. Markov chains explore these vectors with
+/// mutations from `MltSampler`, visiting each path in proportion to its brightness, so once a
+/// chain finds light that is hard to reach, like light through a crack, it keeps exploring the
+/// paths nearby. Every mutation splats onto the film, which is normalized by the average
+/// brightness estimated from `bootstrap_samples` independent paths.
+///
+/// The camera's `samples_per_pixel` is not used: the image gets `mutations_per_pixel` times as
+/// many mutations as it has pixels, shared out between `chains` chains.
+pub struct MltIntegrator {
+    path: PathIntegrator,
+    seed: u64,
+    mutations_per_pixel: usize,
+    bootstrap_samples: usize,
+    chains: usize,
+    sigma: f32,
+    large_step_probability: f32,
+}
+
+impl MltIntegrator {
+    pub fn new(
+        path: PathIntegrator,
+        seed: u64,
+        mutations_per_pixel: usize,
+        bootstrap_samples: usize,
+        chains: usize,
+        sigma: f32,
+        large_step_probability: f32,
+    ) -> Self {
+        Self {
+            path,
+            seed,
+            mutations_per_pixel,
+            bootstrap_samples,
+            chains,
+            sigma,
+            large_step_probability,
+        }
+    }
+
+    /// Creates the sampler for stream `stream` and installs it on the current thread
+    fn new_sampler(&self, stream: u64) -> Rc<RefCell<MltSampler>> {
+        let sampler = MltSampler::new(self.seed, stream, self.sigma, self.large_step_probability);
+        let sampler = Rc::new(RefCell::new(sampler));
+        sampler::set_thread_sampler(Box::new(sampler.clone()));
+        sampler
+    }
+
+    /// Traces the path given by the current sample vector, returning its pixel and radiance
+    fn l(
+        &self,
+        camera: &Camera,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        film: &Film,
+    ) -> (usize, usize, Color) {
+        let (u, v) = random_2d();
+        let x = ((u * camera.image_width as f32) as usize).min(camera.image_width - 1);
+        let y = ((v * camera.image_height() as f32) as usize).min(camera.image_height() - 1);
+        let ray = camera.get_ray(x, y);
+        (x, y, self.path.li(&ray, world, lights, film))
+    }
+
+    /// Total number of mutations over all chains
+    fn total_mutations(&self, camera: &Camera) -> usize {
+        self.mutations_per_pixel * camera.image_width * camera.image_height()
+    }
+
+    /// Runs chain `chain` for its share of the mutations, splatting onto `film`
+    ///
+    /// `bootstrap` holds the running sums of the bootstrap path brightnesses.
+    fn run_chain(
+        &self,
+        chain: usize,
+        bootstrap: &[f32],
+        camera: &Camera,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        film: &Film,
+    ) {
+        // Start from a bootstrap path picked in proportion to its brightness, which replays
+        // exactly since the sampler is seeded by the path's index
+        let mut rng = Rng::for_sample(!self.seed, 0, chain as u64);
+        let total = bootstrap.last().copied().unwrap_or(0.);
+        let target = rng.uniform() * total;
+        let index = bootstrap
+            .partition_point(|&sum| sum <= target)
+            .min(bootstrap.len() - 1);
+
+        let sampler = self.new_sampler(index as u64);
+        let unused = Film::new(0, 0);
+        let (mut x, mut y, mut current) = self.l(camera, world, lights, &unused);
+        let mut current_y = brightness(current);
+
+        let total_mutations = self.total_mutations(camera);
+        let mutations =
+            (chain + 1) * total_mutations / self.chains - chain * total_mutations / self.chains;
+        for _ in 0..mutations {
+            sampler.borrow_mut().start_iteration();
+            let (proposed_x, proposed_y, proposed) = self.l(camera, world, lights, &unused);
+            let proposed_brightness = brightness(proposed);
+            let accept = if current_y > 0. {
+                (proposed_brightness / current_y).min(1.)
+            } else {
+                1.
+            };
+
+            // Both states contribute according to how likely the chain is to be in them next
+            if proposed_brightness > 0. {
+                film.add_splat(
+                    proposed_x,
+                    proposed_y,
+                    proposed * accept / proposed_brightness,
+                );
+            }
+            if current_y > 0. {
+                film.add_splat(x, y, current * (1. - accept) / current_y);
+            }
+
+            if rng.uniform() < accept {
+                (x, y, current, current_y) =
+                    (proposed_x, proposed_y, proposed, proposed_brightness);
+                sampler.borrow_mut().accept();
+            } else {
+                sampler.borrow_mut().reject();
+            }
+        }
+    }
+}
+
+/// Luminance of `c`, the function the chains sample paths in proportion to
+fn brightness(c: Color) -> f32 {
+    let y = 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+    if y.is_finite() && y > 0. {
+        y
+    } else {
+        0.
+    }
+}
+
+impl Integrator for MltIntegrator {
+    fn render(&self, camera: &Camera, world: &dyn Hittable, lights: &dyn Hittable) -> Vec<Color> {
+        let (width, height) = (camera.image_width, camera.image_height());
+
+        // Paths with independent sample vectors estimate the average brightness of the image
+        let unused = Film::new(0, 0);
+        let weights: Vec<f32> = (0..self.bootstrap_samples)
+            .into_par_iter()
+            .map(|i| {
+                self.new_sampler(i as u64);
+                brightness(self.l(camera, world, lights, &unused).2)
+            })
+            .collect();
+        let bootstrap: Vec<f32> = weights
+            .iter()
+            .scan(0., |sum, w| {
+                *sum += w;
+                Some(*sum)
+            })
+            .collect();
+        let total = bootstrap.last().copied().unwrap_or(0.);
+        if total <= 0. || self.chains == 0 {
+            return vec![Color::default(); width * height];
+        }
+        let b = total / self.bootstrap_samples as f32;
+
+        let film = Film::new(width, height);
+        (0..self.chains)
+            .into_par_iter()
+            .progress_count(self.chains as u64)
+            .for_each(|chain| self.run_chain(chain, &bootstrap, camera, world, lights, &film));
+
+        let total_mutations = self.total_mutations(camera).max(1);
+        let scale = b * (width * height) as f32 / total_mutations as f32;
+        (0..width * height)
+            .map(|i| scale * film.splat(i % width, i / width))
+            .collect()
+    }
+
+    fn li(&self, r: &Ray, world: &dyn Hittable, lights: &dyn Hittable, film: &Film) -> Color {
+        self.path.li(r, world, lights, film)
+    }
+}
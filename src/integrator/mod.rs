@@ -0,0 +1,339 @@
+pub(crate) mod path;
+pub use path::PathIntegrator;
+
+pub(crate) mod direct;
+pub use direct::DirectLightingIntegrator;
+
+pub(crate) mod ambient_occlusion;
+pub use ambient_occlusion::AmbientOcclusionIntegrator;
+
+pub(crate) mod bdpt;
+pub use bdpt::BdptIntegrator;
+
+pub(crate) mod spectral;
+pub use spectral::SpectralIntegrator;
+
+pub(crate) mod mlt;
+pub use mlt::MltIntegrator;
+
+pub(crate) mod sppm;
+pub use sppm::SppmIntegrator;
+
+pub(crate) mod debug;
+pub use debug::{DepthIntegrator, MaterialIdIntegrator, NormalsIntegrator, UvIntegrator};
+
+use crate::utils::INFINITY;
+use crate::{
+    pdf, Camera, Color, Environment, Film, HitRecord, Hittable, Interval, Ray, ScatterRecord,
+};
+
+use std::str::FromStr;
+
+/// Computes the light arriving at the camera along a ray
+///
+/// By default the camera calls `li` once for every sample of every pixel, after starting the
+/// pixel sample on the thread's sampler, so integrators draw their random numbers with
+/// `utils::random_double` like the rest of the renderer. Algorithms that need passes over the
+/// whole image instead override `render`.
+pub trait Integrator: Send + Sync {
+    /// Renders the image seen by `camera`, in row-major order
+    fn render(&self, camera: &Camera, world: &dyn Hittable, lights: &dyn Hittable) -> Vec<Color> {
+        camera.render_samples(self, world, lights)
+    }
+
+    /// Radiance arriving along `r`, reversed, from the scene `world` lit by `lights`
+    ///
+    /// Contributions to other pixels can be splatted onto `film`. The camera scales splats like
+    /// the pixel samples, by one over the number of samples per pixel.
+    fn li(&self, r: &Ray, world: &dyn Hittable, lights: &dyn Hittable, film: &Film) -> Color;
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IntegratorConfig {
+    /// Unidirectional path tracing with next-event estimation
+    #[default]
+    Path,
+    /// Emission plus a single bounce of light
+    Direct,
+    /// Bidirectional path tracing, connecting camera and light subpaths
+    Bdpt,
+    /// Path tracing at sampled wavelengths, with RGB colors uplifted to spectra
+    Spectral,
+    /// Primary sample space Metropolis light transport on top of the path tracer
+    ///
+    /// The image gets `mutations_per_pixel` mutations per pixel, spread over `chains` Markov
+    /// chains that start from paths picked among `bootstrap_samples` independent ones. Small
+    /// steps move every sample by a normal offset with standard deviation `sigma`, and a
+    /// fraction `large_step_probability` of the mutations draws a fresh path instead.
+    Mlt {
+        #[serde(default = "default_mutations_per_pixel")]
+        mutations_per_pixel: usize,
+        #[serde(default = "default_bootstrap_samples")]
+        bootstrap_samples: usize,
+        #[serde(default = "default_chains")]
+        chains: usize,
+        #[serde(default = "default_mlt_sigma")]
+        sigma: f32,
+        #[serde(default = "default_large_step_probability")]
+        large_step_probability: f32,
+    },
+    /// Stochastic progressive photon mapping, for caustics
+    ///
+    /// Each of the `samples_per_pixel` iterations traces `photons_per_iteration` photons. The
+    /// gather radius starts at `initial_radius`, or a hundredth of the extent of the visible
+    /// scene, and shrinks so that each iteration keeps a fraction `alpha` of the new photons.
+    Sppm {
+        #[serde(default = "default_photons_per_iteration")]
+        photons_per_iteration: usize,
+        #[serde(default)]
+        initial_radius: Option<f32>,
+        #[serde(default = "default_sppm_alpha")]
+        alpha: f32,
+    },
+    /// Fraction of the hemisphere above the first hit that is unoccluded within `distance`
+    AmbientOcclusion {
+        #[serde(default = "default_occlusion_distance")]
+        distance: f32,
+    },
+    /// Surface normals, mapped from [-1, 1] to [0, 1]
+    Normals,
+    /// Texture coordinates in the red and green channels
+    Uv,
+    /// Distance from the camera to the first hit
+    Depth,
+    /// A different flat color for every material
+    MaterialId,
+}
+
+fn default_occlusion_distance() -> f32 {
+    INFINITY
+}
+
+fn default_mutations_per_pixel() -> usize {
+    100
+}
+
+fn default_bootstrap_samples() -> usize {
+    100_000
+}
+
+fn default_chains() -> usize {
+    1000
+}
+
+fn default_mlt_sigma() -> f32 {
+    0.01
+}
+
+fn default_large_step_probability() -> f32 {
+    0.3
+}
+
+fn default_photons_per_iteration() -> usize {
+    100_000
+}
+
+fn default_sppm_alpha() -> f32 {
+    2. / 3.
+}
+
+impl IntegratorConfig {
+    /// Builds the integrator, taking the path length and background from `camera`
+    pub fn build(&self, camera: &Camera) -> Box<dyn Integrator> {
+        let background = camera.background_light();
+        let path = || {
+            PathIntegrator::new(
+                camera.max_depth,
+                background.clone(),
+                camera.rr_min_depth,
+                camera.rr_max_contrib_prob,
+            )
+        };
+        match self {
+            IntegratorConfig::Path => Box::new(path()),
+            IntegratorConfig::Direct => Box::new(DirectLightingIntegrator::new(
+                camera.max_depth,
+                background.clone(),
+            )),
+            IntegratorConfig::Bdpt => Box::new(BdptIntegrator::new(
+                camera.clone(),
+                camera.max_depth,
+                background.clone(),
+            )),
+            IntegratorConfig::Spectral => Box::new(SpectralIntegrator::new(
+                camera.max_depth,
+                background.clone(),
+                camera.rr_min_depth,
+                camera.rr_max_contrib_prob,
+            )),
+            IntegratorConfig::Mlt {
+                mutations_per_pixel,
+                bootstrap_samples,
+                chains,
+                sigma,
+                large_step_probability,
+            } => Box::new(MltIntegrator::new(
+                path(),
+                camera.seed,
+                *mutations_per_pixel,
+                *bootstrap_samples,
+                *chains,
+                *sigma,
+                *large_step_probability,
+            )),
+            IntegratorConfig::Sppm {
+                photons_per_iteration,
+                initial_radius,
+                alpha,
+            } => Box::new(SppmIntegrator::new(
+                camera.max_depth,
+                background.clone(),
+                *photons_per_iteration,
+                *initial_radius,
+                *alpha,
+            )),
+            IntegratorConfig::AmbientOcclusion { distance } => {
+                Box::new(AmbientOcclusionIntegrator::new(*distance))
+            }
+            IntegratorConfig::Normals => Box::new(NormalsIntegrator),
+            IntegratorConfig::Uv => Box::new(UvIntegrator),
+            IntegratorConfig::Depth => Box::new(DepthIntegrator),
+            IntegratorConfig::MaterialId => Box::new(MaterialIdIntegrator),
+        }
+    }
+}
+
+/// Parses an integrator name as used on the command line, with default parameters
+impl FromStr for IntegratorConfig {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "path" => Ok(IntegratorConfig::Path),
+            "direct" => Ok(IntegratorConfig::Direct),
+            "bdpt" => Ok(IntegratorConfig::Bdpt),
+            "spectral" => Ok(IntegratorConfig::Spectral),
+            "mlt" => Ok(IntegratorConfig::Mlt {
+                mutations_per_pixel: default_mutations_per_pixel(),
+                bootstrap_samples: default_bootstrap_samples(),
+                chains: default_chains(),
+                sigma: default_mlt_sigma(),
+                large_step_probability: default_large_step_probability(),
+            }),
+            "sppm" => Ok(IntegratorConfig::Sppm {
+                photons_per_iteration: default_photons_per_iteration(),
+                initial_radius: None,
+                alpha: default_sppm_alpha(),
+            }),
+            "ambient_occlusion" => Ok(IntegratorConfig::AmbientOcclusion {
+                distance: default_occlusion_distance(),
+            }),
+            "normals" => Ok(IntegratorConfig::Normals),
+            "uv" => Ok(IntegratorConfig::Uv),
+            "depth" => Ok(IntegratorConfig::Depth),
+            "material_id" => Ok(IntegratorConfig::MaterialId),
+            _ => Err(format!(
+                "unknown integrator '{name}', expected one of path, direct, bdpt, spectral, mlt, sppm, \
+                 ambient_occlusion, normals, uv, depth or material_id"
+            )),
+        }
+    }
+}
+
+/// Finds the closest hit along `r`, ignoring hits right at its origin, with the footprint of
+/// the pixel if `r` carries differentials
+pub(crate) fn intersect(r: &Ray, world: &dyn Hittable) -> Option<HitRecord> {
+    let mut rec: HitRecord = Default::default();
+    if !world.hit(r, &mut Interval::new(0.001, INFINITY), &mut rec) {
+        return None;
+    }
+    rec.compute_differentials(r);
+    Some(rec)
+}
+
+/// Light arriving at `rec` from a point sampled on `lights`, times the BSDF and MIS weight
+///
+/// Whatever a shadow ray hits first is what the light sample sees, so occluders block it and
+/// emitters in front of the sampled light are counted instead, with the same weight that BSDF
+/// sampling gives them. Shadow rays that leave the scene see the `background`.
+pub(crate) fn sample_light(
+    r_in: &Ray,
+    rec: &HitRecord,
+    srec: &ScatterRecord,
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    background: &dyn Environment,
+) -> Color {
+    let shadow_ray = Ray::new(rec.p, lights.random(rec.p), r_in.time());
+    let light_pdf = lights.pdf_value(rec.p, shadow_ray.direction());
+    if light_pdf <= 0. {
+        return Color::new(0., 0., 0.);
+    }
+
+    let f = rec.mat.eval(r_in, rec, &shadow_ray);
+    if f.x() == 0. && f.y() == 0. && f.z() == 0. {
+        return Color::new(0., 0., 0.);
+    }
+
+    let emitted = match intersect(&shadow_ray, world) {
+        Some(light_rec) => light_rec.mat.emitted(
+            &shadow_ray,
+            &light_rec,
+            light_rec.u,
+            light_rec.v,
+            light_rec.p,
+        ),
+        None => background.radiance(shadow_ray.direction()),
+    };
+
+    let bsdf_pdf = srec.pdf.value(shadow_ray.direction());
+    f * emitted * pdf::power_heuristic(light_pdf, bsdf_pdf) / light_pdf
+}
+
+/// Light arriving at `rec` straight from an emitter or the background, times the BSDF
+///
+/// Combines `sample_light` with one BSDF sample, which only counts what it hits directly.
+pub(crate) fn direct_lighting(
+    r_in: &Ray,
+    rec: &HitRecord,
+    srec: &ScatterRecord,
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    background: &dyn Environment,
+) -> Color {
+    let mut color = sample_light(r_in, rec, srec, world, lights, background);
+
+    let scattered = Ray::new(rec.p, srec.pdf.generate(), r_in.time());
+    let pdf_value = srec.pdf.value(scattered.direction());
+    if pdf_value > 0. {
+        let f = rec.mat.eval(r_in, rec, &scattered) / pdf_value;
+        let incoming = match intersect(&scattered, world) {
+            Some(light_rec) => light_rec.mat.emitted(
+                &scattered,
+                &light_rec,
+                light_rec.u,
+                light_rec.v,
+                light_rec.p,
+            ),
+            None => background.radiance(scattered.direction()),
+        };
+        let weight = emission_weight(&scattered, Some(pdf_value), lights);
+        color += f * incoming * weight;
+    }
+    color
+}
+
+/// MIS weight for emission reached by a BSDF sample drawn with density `bsdf_pdf`
+///
+/// `None` marks camera rays and specular bounces, which light sampling can't reproduce, so
+/// their emission counts in full.
+pub(crate) fn emission_weight(r: &Ray, bsdf_pdf: Option<f32>, lights: &dyn Hittable) -> f32 {
+    match bsdf_pdf {
+        Some(bsdf_pdf) => {
+            let light_pdf = lights.pdf_value(r.origin(), r.direction());
+            pdf::power_heuristic(bsdf_pdf, light_pdf)
+        }
+        None => 1.,
+    }
+}
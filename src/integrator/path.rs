@@ -0,0 +1,97 @@
+use super::{emission_weight, intersect, sample_light, Integrator};
+use crate::utils::random_double;
+use crate::{Color, Environment, Film, Hittable, Ray, ScatterRecord};
+
+use std::sync::Arc;
+
+/// Unidirectional path tracing, combining light sampling and BSDF sampling with MIS
+///
+/// At every non-specular vertex a shadow ray is sent towards a point sampled on the lights
+/// (next-event estimation), and the path continues in a direction sampled from the BSDF.
+/// Emission found either way is weighted with the power heuristic, so each strategy covers the
+/// cases it handles well: small lights through light sampling, sharp highlights through BSDF
+/// sampling. Specular bounces can only be followed, so emission they reach is counted in full.
+pub struct PathIntegrator {
+    max_depth: usize,
+    background: Arc<dyn Environment>,
+    rr_min_depth: usize,
+    rr_max_contrib_prob: f32,
+}
+
+impl PathIntegrator {
+    pub fn new(
+        max_depth: usize,
+        background: Arc<dyn Environment>,
+        rr_min_depth: usize,
+        rr_max_contrib_prob: f32,
+    ) -> Self {
+        Self {
+            max_depth,
+            background,
+            rr_min_depth,
+            rr_max_contrib_prob,
+        }
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, lights: &dyn Hittable, _film: &Film) -> Color {
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = *r;
+        // Density of the BSDF sample that produced `ray`, see `emission_weight`
+        let mut bsdf_pdf: Option<f32> = None;
+
+        for depth in 0..self.max_depth {
+            let Some(rec) = intersect(&ray, world) else {
+                let background = self.background.radiance(ray.direction());
+                color += throughput * background * emission_weight(&ray, bsdf_pdf, lights);
+                break;
+            };
+
+            let emitted = rec.mat.emitted(&ray, &rec, rec.u, rec.v, rec.p);
+            color += throughput * emitted * emission_weight(&ray, bsdf_pdf, lights);
+
+            let mut srec: ScatterRecord = ScatterRecord::default();
+            if !rec.mat.scatter(&ray, &rec, &mut srec) {
+                break;
+            }
+
+            if srec.skip_pdf {
+                throughput = throughput * srec.attenuation;
+                ray = srec.skip_pdf_ray;
+                bsdf_pdf = None;
+            } else {
+                // Next-event estimation, unless the shadow ray would exceed the path length
+                if depth + 1 < self.max_depth {
+                    color += throughput
+                        * sample_light(&ray, &rec, &srec, world, lights, self.background.as_ref());
+                }
+
+                let scattered = Ray::new(rec.p, srec.pdf.generate(), ray.time());
+                let pdf_value = srec.pdf.value(scattered.direction());
+                if pdf_value <= 0. {
+                    break;
+                }
+                throughput = throughput * rec.mat.eval(&ray, &rec, &scattered) / pdf_value;
+                ray = scattered;
+                bsdf_pdf = Some(pdf_value);
+            }
+
+            // Russian roulette, with the survivors reweighted so the estimate stays unbiased
+            if depth >= self.rr_min_depth {
+                let survival = throughput
+                    .x()
+                    .max(throughput.y())
+                    .max(throughput.z())
+                    .min(self.rr_max_contrib_prob);
+                if random_double() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+
+        color
+    }
+}
@@ -0,0 +1,162 @@
+use super::{emission_weight, intersect, Integrator};
+use crate::radiometry::rgb::{RgbAlbedoSpectrum, RgbIlluminantSpectrum};
+use crate::radiometry::sampling::{SampledSpectrum, SampledWavelengths, NUM_SPECTRUM_SAMPLES};
+use crate::radiometry::spectrum::Spectrum;
+use crate::utils::random_double;
+use crate::{pdf, Color, Environment, Film, HitRecord, Hittable, Ray, ScatterRecord};
+
+use std::sync::Arc;
+
+/// Path tracing at a few sampled wavelengths, converted to linear sRGB through CIE XYZ
+///
+/// Every camera ray draws its own wavelengths and carries a spectral throughput along the
+/// path, with the same light sampling, MIS and Russian roulette as `PathIntegrator`. Dispersive
+/// dielectrics reduce the path to a single wavelength. Emission
+/// comes from `Material::emitted_spectrum`, and the RGB colors of materials and the background
+/// are uplifted to smooth spectra, so existing scenes render with the same colors, apart from
+/// where spectra multiply differently than RGB triples do.
+pub struct SpectralIntegrator {
+    max_depth: usize,
+    background: Arc<dyn Environment>,
+    rr_min_depth: usize,
+    rr_max_contrib_prob: f32,
+}
+
+impl SpectralIntegrator {
+    pub fn new(
+        max_depth: usize,
+        background: Arc<dyn Environment>,
+        rr_min_depth: usize,
+        rr_max_contrib_prob: f32,
+    ) -> Self {
+        Self {
+            max_depth,
+            background,
+            rr_min_depth,
+            rr_max_contrib_prob,
+        }
+    }
+
+    /// Radiance along `r` at the wavelengths `lambda`
+    fn li_spectrum(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        lambda: &mut SampledWavelengths,
+    ) -> SampledSpectrum {
+        let mut l = SampledSpectrum::default();
+        let mut beta = SampledSpectrum::new(&[1.; NUM_SPECTRUM_SAMPLES]);
+        let mut ray = *r;
+        let mut bsdf_pdf: Option<f32> = None;
+
+        for depth in 0..self.max_depth {
+            let Some(rec) = intersect(&ray, world) else {
+                let background = environment_spectrum(self.background.as_ref(), &ray, lambda);
+                l += beta * background * emission_weight(&ray, bsdf_pdf, lights);
+                break;
+            };
+
+            let emitted = rec
+                .mat
+                .emitted_spectrum(&ray, &rec, rec.u, rec.v, rec.p, lambda);
+            l += beta * emitted * emission_weight(&ray, bsdf_pdf, lights);
+
+            let mut srec: ScatterRecord = ScatterRecord::default();
+            if !rec.mat.scatter_spectral(&ray, &rec, &mut srec, lambda) {
+                break;
+            }
+
+            if srec.skip_pdf {
+                beta *= albedo(srec.attenuation, lambda);
+                ray = srec.skip_pdf_ray;
+                bsdf_pdf = None;
+            } else {
+                if depth + 1 < self.max_depth {
+                    let background = self.background.as_ref();
+                    l += beta * sample_light(&ray, &rec, &srec, world, lights, background, lambda);
+                }
+
+                let scattered = Ray::new(rec.p, srec.pdf.generate(), ray.time());
+                let pdf_value = srec.pdf.value(scattered.direction());
+                if pdf_value <= 0. {
+                    break;
+                }
+                let f = albedo(rec.mat.eval(&ray, &rec, &scattered), lambda);
+                beta = beta * f / pdf_value;
+                ray = scattered;
+                bsdf_pdf = Some(pdf_value);
+            }
+
+            if depth >= self.rr_min_depth {
+                let survival = beta.max_component().min(self.rr_max_contrib_prob);
+                if random_double() >= survival {
+                    break;
+                }
+                beta = beta / survival;
+            }
+        }
+
+        l
+    }
+}
+
+impl Integrator for SpectralIntegrator {
+    fn li(&self, r: &Ray, world: &dyn Hittable, lights: &dyn Hittable, _film: &Film) -> Color {
+        let mut lambda = SampledWavelengths::sample_visible(random_double());
+        let l = self.li_spectrum(r, world, lights, &mut lambda);
+        Color::from(l.to_xyz(&lambda))
+    }
+}
+
+/// A color computed by a material, such as an albedo or a BSDF value, at the wavelengths `lambda`
+fn albedo(rgb: Color, lambda: &SampledWavelengths) -> SampledSpectrum {
+    RgbAlbedoSpectrum::new(rgb).sample(lambda)
+}
+
+/// The RGB radiance of `environment` seen by the escaped ray `r`, at the wavelengths `lambda`
+fn environment_spectrum(
+    environment: &dyn Environment,
+    r: &Ray,
+    lambda: &SampledWavelengths,
+) -> SampledSpectrum {
+    RgbIlluminantSpectrum::new(environment.radiance(r.direction())).sample(lambda)
+}
+
+/// Spectral version of `integrator::sample_light`
+fn sample_light(
+    r_in: &Ray,
+    rec: &HitRecord,
+    srec: &ScatterRecord,
+    world: &dyn Hittable,
+    lights: &dyn Hittable,
+    environment: &dyn Environment,
+    lambda: &SampledWavelengths,
+) -> SampledSpectrum {
+    let shadow_ray = Ray::new(rec.p, lights.random(rec.p), r_in.time());
+    let light_pdf = lights.pdf_value(rec.p, shadow_ray.direction());
+    if light_pdf <= 0. {
+        return SampledSpectrum::default();
+    }
+
+    let f = rec.mat.eval(r_in, rec, &shadow_ray);
+    if f.x() == 0. && f.y() == 0. && f.z() == 0. {
+        return SampledSpectrum::default();
+    }
+
+    let emitted = match intersect(&shadow_ray, world) {
+        Some(light_rec) => light_rec.mat.emitted_spectrum(
+            &shadow_ray,
+            &light_rec,
+            light_rec.u,
+            light_rec.v,
+            light_rec.p,
+            lambda,
+        ),
+        None => environment_spectrum(environment, &shadow_ray, lambda),
+    };
+
+    let bsdf_pdf = srec.pdf.value(shadow_ray.direction());
+    let weight = pdf::power_heuristic(light_pdf, bsdf_pdf) / light_pdf;
+    albedo(f, lambda) * emitted * weight
+}
@@ -0,0 +1,344 @@
+use indicatif::ProgressIterator;
+use rayon::prelude::*;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use super::{direct_lighting, intersect, Integrator};
+use crate::utils::random_double;
+use crate::{
+    sampler, vec3, Camera, Color, CosinePdf, Environment, Film, HaltonSampler, HitRecord, Hittable,
+    KdTree, Pdf, Point3, Ray, ScatterRecord, Vec3, AABB,
+};
+
+/// Stochastic progressive photon mapping, after Hachisuka and Jensen
+///
+/// Every iteration traces one camera path per pixel up to its first diffuse or glossy surface,
+/// the visible point, and then `photons_per_iteration` photons from the lights. Photons that
+/// land within a pixel's radius of its visible point are gathered there, and the radius shrinks
+/// as photons accumulate, so the estimate converges while caustics seen through glass, which
+/// path tracing can't find, come out with little noise. The camera path samples direct light
+/// itself, so photons only carry light that has bounced at least once.
+///
+/// The number of iterations is the camera's `samples_per_pixel`. Light from the background is
+/// only counted where camera paths see it, since photons can't be emitted from it.
+pub struct SppmIntegrator {
+    max_depth: usize,
+    background: Arc<dyn Environment>,
+    photons_per_iteration: usize,
+    initial_radius: Option<f32>,
+    alpha: f32,
+}
+
+/// Running estimate for one pixel
+#[derive(Clone)]
+struct SppmPixel {
+    /// Sum of the direct lighting found by the camera paths
+    ld: Color,
+    radius: f32,
+    /// Photon count, decreased by `alpha` as the radius shrinks
+    n: f32,
+    /// Accumulated reflected flux
+    tau: Color,
+}
+
+/// The end of a camera path, where photons are gathered
+struct VisiblePoint {
+    rec: HitRecord,
+    /// Direction of the ray that hit `rec`
+    incoming: Vec3,
+    /// Throughput from the camera
+    beta: Color,
+}
+
+struct Photon {
+    /// Direction the photon was travelling in
+    direction: Vec3,
+    beta: Color,
+}
+
+impl SppmIntegrator {
+    pub fn new(
+        max_depth: usize,
+        background: Arc<dyn Environment>,
+        photons_per_iteration: usize,
+        initial_radius: Option<f32>,
+        alpha: f32,
+    ) -> Self {
+        Self {
+            max_depth,
+            background,
+            photons_per_iteration,
+            initial_radius,
+            alpha,
+        }
+    }
+
+    /// Follows `r` through specular bounces and media to the first diffuse or glossy surface
+    ///
+    /// Returns the direct lighting gathered on the way, and the visible point if the path ends
+    /// at such a surface.
+    fn camera_path(
+        &self,
+        r: &Ray,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+    ) -> (Color, Option<VisiblePoint>) {
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut beta = Color::new(1.0, 1.0, 1.0);
+        let mut ray = *r;
+        // Emission found by a sampled bounce is already part of `direct_lighting`
+        let mut specular_bounce = true;
+
+        for _ in 0..self.max_depth {
+            let Some(rec) = intersect(&ray, world) else {
+                if specular_bounce {
+                    color += beta * self.background.radiance(ray.direction());
+                }
+                break;
+            };
+            if specular_bounce {
+                color += beta * rec.mat.emitted(&ray, &rec, rec.u, rec.v, rec.p);
+            }
+
+            let mut srec: ScatterRecord = ScatterRecord::default();
+            if !rec.mat.scatter(&ray, &rec, &mut srec) {
+                break;
+            }
+            if srec.skip_pdf {
+                beta = beta * srec.attenuation;
+                ray = srec.skip_pdf_ray;
+                specular_bounce = true;
+                continue;
+            }
+
+            color +=
+                beta * direct_lighting(&ray, &rec, &srec, world, lights, self.background.as_ref());
+            if !rec.mat.is_volumetric() {
+                let visible = VisiblePoint {
+                    incoming: ray.direction(),
+                    rec,
+                    beta,
+                };
+                return (color, Some(visible));
+            }
+
+            // Photons aren't stored in media, so keep going to the next surface
+            let scattered = Ray::new(rec.p, srec.pdf.generate(), ray.time());
+            let pdf_value = srec.pdf.value(scattered.direction());
+            if pdf_value <= 0. {
+                break;
+            }
+            beta = beta * rec.mat.eval(&ray, &rec, &scattered) / pdf_value;
+            ray = scattered;
+            specular_bounce = false;
+        }
+
+        (color, None)
+    }
+
+    /// Traces one photon from a point sampled on `lights`, returning the photons it leaves
+    fn trace_photon(
+        &self,
+        world: &dyn Hittable,
+        lights: &dyn Hittable,
+        photons: &mut Vec<(Point3, Photon)>,
+    ) {
+        let time = random_double();
+        let Some((light_rec, pdf_pos)) = lights.sample_surface(time) else {
+            return;
+        };
+        if pdf_pos <= 0. {
+            return;
+        }
+
+        // Cosine-weighted emission, so Le cos / (pdf_pos pdf_dir) is Le pi / pdf_pos
+        let direction = CosinePdf::new(light_rec.normal).generate();
+        let r_in = Ray::new(light_rec.p + light_rec.normal, -light_rec.normal, time);
+        let emitted =
+            light_rec
+                .mat
+                .emitted(&r_in, &light_rec, light_rec.u, light_rec.v, light_rec.p);
+        let mut beta = emitted * PI / pdf_pos;
+        let mut ray = Ray::new(light_rec.p, direction, time);
+
+        // The light is the first vertex of the path, as it is for the path tracer
+        for depth in 1..self.max_depth {
+            if beta.x() <= 0. && beta.y() <= 0. && beta.z() <= 0. {
+                break;
+            }
+            let Some(rec) = intersect(&ray, world) else {
+                break;
+            };
+
+            let mut srec: ScatterRecord = ScatterRecord::default();
+            if !rec.mat.scatter(&ray, &rec, &mut srec) {
+                break;
+            }
+
+            // Direct light is sampled by the camera paths
+            let new_beta = if srec.skip_pdf {
+                ray = srec.skip_pdf_ray;
+                beta * srec.attenuation
+            } else {
+                if depth > 1 && !rec.mat.is_volumetric() {
+                    let photon = Photon {
+                        direction: ray.direction(),
+                        beta,
+                    };
+                    photons.push((rec.p, photon));
+                }
+
+                let scattered = Ray::new(rec.p, srec.pdf.generate(), ray.time());
+                let pdf_value = srec.pdf.value(scattered.direction());
+                if pdf_value <= 0. {
+                    break;
+                }
+                let f = rec.mat.eval_importance(&ray, &rec, &scattered);
+                ray = scattered;
+                beta * f / pdf_value
+            };
+
+            // Russian roulette on how much of its power the photon kept in this bounce
+            let survival = max_component(new_beta) / max_component(beta);
+            if survival.is_nan() || random_double() >= survival {
+                break;
+            }
+            beta = new_beta / survival.min(1.);
+        }
+    }
+
+    /// Reflected flux of the photons within `radius` of `visible`, and how many there are
+    fn gather(
+        &self,
+        visible: &VisiblePoint,
+        radius: f32,
+        photons: &KdTree<Photon>,
+    ) -> (Color, f32) {
+        let rec = &visible.rec;
+        let r_in = Ray::new(rec.p - visible.incoming, visible.incoming, 0.);
+        let mut phi = Color::new(0., 0., 0.);
+        let mut m = 0.;
+        photons.for_each_within(rec.p, radius, |_, photon| {
+            let wi = -vec3::unit_vector(photon.direction);
+            // Photons can arrive from behind surfaces that transmit light
+            let cos_theta = vec3::dot(rec.normal, wi).abs();
+            if cos_theta == 0. {
+                return;
+            }
+            // Photons already carry the cosine at the surface they landed on
+            let scattered = Ray::new(rec.p, wi, 0.);
+            phi += photon.beta * rec.mat.eval(&r_in, rec, &scattered) / cos_theta;
+            m += 1.;
+        });
+        (phi, m)
+    }
+}
+
+fn max_component(c: Color) -> f32 {
+    c.x().max(c.y()).max(c.z())
+}
+
+impl Integrator for SppmIntegrator {
+    fn render(&self, camera: &Camera, world: &dyn Hittable, lights: &dyn Hittable) -> Vec<Color> {
+        let width = camera.image_width;
+        let iterations = camera.samples_per_pixel;
+        let mut pixels = vec![
+            SppmPixel {
+                ld: Color::new(0., 0., 0.),
+                radius: self.initial_radius.unwrap_or(0.),
+                n: 0.,
+                tau: Color::new(0., 0., 0.),
+            };
+            width * camera.image_height()
+        ];
+
+        for iteration in (0..iterations).progress_count(iterations as u64) {
+            let visible_points: Vec<Option<VisiblePoint>> = pixels
+                .par_iter_mut()
+                .enumerate()
+                .map(|(i, pixel)| {
+                    let (x, y) = (i % width, i / width);
+                    sampler::set_thread_sampler(
+                        camera.sampler.build(camera.samples_per_pixel, camera.seed),
+                    );
+                    sampler::with_thread_sampler(|s| s.start_pixel_sample(x, y, iteration));
+                    let ray = camera.get_ray(x, y);
+                    let (ld, visible) = self.camera_path(&ray, world, lights);
+                    pixel.ld += ld;
+                    visible
+                })
+                .collect();
+
+            // Without a radius from the scene file, start from a hundredth of the extent of
+            // what the camera sees
+            if iteration == 0 && self.initial_radius.is_none() {
+                let bbox = visible_points
+                    .iter()
+                    .flatten()
+                    .fold(AABB::empty(), |acc, v| {
+                        AABB::around_boxes(&acc, &AABB::around_points(v.rec.p, v.rec.p))
+                    });
+                let diagonal = Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size()).length();
+                let radius = if diagonal.is_finite() {
+                    diagonal / 100.
+                } else {
+                    1.
+                };
+                pixels.iter_mut().for_each(|pixel| pixel.radius = radius);
+            }
+
+            // Photons use their own sample indices so they don't correlate with camera paths
+            let first_photon = iteration * self.photons_per_iteration;
+            let photons: Vec<(Point3, Photon)> = (0..self.photons_per_iteration)
+                .into_par_iter()
+                .flat_map_iter(|k| {
+                    sampler::set_thread_sampler(Box::new(HaltonSampler::new(!camera.seed)));
+                    sampler::with_thread_sampler(|s| s.start_pixel_sample(0, 0, first_photon + k));
+                    let mut photons = Vec::new();
+                    self.trace_photon(world, lights, &mut photons);
+                    photons
+                })
+                .collect();
+            let photons = KdTree::new(photons);
+
+            pixels
+                .par_iter_mut()
+                .zip(visible_points.par_iter())
+                .for_each(|(pixel, visible)| {
+                    let Some(visible) = visible else {
+                        return;
+                    };
+                    let (phi, m) = self.gather(visible, pixel.radius, &photons);
+                    if m == 0. {
+                        return;
+                    }
+                    // Keep a fraction `alpha` of the new photons and shrink the radius so that
+                    // the photon density stays the same
+                    let n = pixel.n + self.alpha * m;
+                    let radius = pixel.radius * (n / (pixel.n + m)).sqrt();
+                    let shrink = (radius * radius) / (pixel.radius * pixel.radius);
+                    pixel.tau = (pixel.tau + visible.beta * phi) * shrink;
+                    pixel.n = n;
+                    pixel.radius = radius;
+                });
+        }
+
+        let photon_count = (iterations * self.photons_per_iteration) as f32;
+        pixels
+            .iter()
+            .map(|pixel| {
+                let indirect = if photon_count > 0. {
+                    pixel.tau / (photon_count * PI * pixel.radius * pixel.radius)
+                } else {
+                    Color::new(0., 0., 0.)
+                };
+                pixel.ld / iterations as f32 + indirect
+            })
+            .collect()
+    }
+
+    fn li(&self, r: &Ray, world: &dyn Hittable, lights: &dyn Hittable, _film: &Film) -> Color {
+        // Without photons only the direct lighting at the visible point is left
+        self.camera_path(r, world, lights).0
+    }
+}
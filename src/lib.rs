@@ -9,6 +9,12 @@ pub mod materials;
 pub use materials::*;
 pub mod textures;
 pub use textures::*;
+pub mod lights;
+pub use lights::*;
+pub mod sampler;
+pub use sampler::*;
+pub mod integrator;
+pub use integrator::*;
 pub mod config;
 pub mod radiometry;
 pub use config::*;
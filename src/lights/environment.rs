@@ -0,0 +1,194 @@
+use crate::utils::{degrees_to_radians, random_2d};
+use crate::{
+    vec3, Color, Distribution2D, HitRecord, Hittable, ImageTexture, Interval, Point3, Ray, Texture,
+    Vec3, AABB,
+};
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Light arriving from infinitely far away, which rays see once they leave the scene
+pub trait Environment: Send + Sync {
+    /// Radiance arriving along `-direction`, seen by a ray leaving the scene along `direction`
+    fn radiance(&self, direction: Vec3) -> Color;
+
+    /// A unit direction to look for light in, picked roughly in proportion to its radiance
+    fn sample(&self) -> Vec3;
+
+    /// Density per solid angle of `sample` picking `direction`
+    fn pdf(&self, direction: Vec3) -> f32;
+}
+
+/// The same radiance from every direction, like the flat `Camera::background` color
+pub struct ConstantEnvironment {
+    color: Color,
+}
+
+impl ConstantEnvironment {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for ConstantEnvironment {
+    fn radiance(&self, _direction: Vec3) -> Color {
+        self.color
+    }
+
+    fn sample(&self) -> Vec3 {
+        vec3::random_unit_vector()
+    }
+
+    fn pdf(&self, _direction: Vec3) -> f32 {
+        1. / (4. * PI)
+    }
+}
+
+/// The texture coordinates of `direction` in an equirectangular image, matching those of
+/// spheres: `u` turns around the `y` axis and `v` runs from `-y` at 0 to `+y` at 1
+pub(crate) fn direction_to_uv(direction: Vec3) -> (f32, f32) {
+    let d = vec3::unit_vector(direction);
+    let theta = (-d.y()).clamp(-1., 1.).acos();
+    let phi = (-d.z()).atan2(d.x()) + PI;
+    (phi / (2. * PI), theta / PI)
+}
+
+/// The unit direction with texture coordinates `(u, v)`, see `direction_to_uv`
+pub(crate) fn uv_to_direction(u: f32, v: f32) -> Vec3 {
+    let (theta, phi) = (v * PI, u * 2. * PI);
+    let sin_theta = theta.sin();
+    Vec3::new(-phi.cos() * sin_theta, -theta.cos(), phi.sin() * sin_theta)
+}
+
+/// Turns `direction` around the `y` axis by `angle` radians, like `RotateY`
+pub(crate) fn rotate_y(direction: Vec3, angle: f32) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    Vec3::new(
+        cos * direction.x() + sin * direction.z(),
+        direction.y(),
+        -sin * direction.x() + cos * direction.z(),
+    )
+}
+
+/// An equirectangular image of the surroundings, usually a high dynamic range photograph
+///
+/// Directions are picked in proportion to the luminance of the image, so that small, bright
+/// features like the sun are found by light sampling.
+pub struct ImageEnvironment {
+    image: ImageTexture,
+    /// Over `(u, 1 - v)`, following the rows of the image from the top
+    distribution: Distribution2D,
+    intensity: f32,
+    /// Turn of the image around the `y` axis, in radians
+    rotation: f32,
+}
+
+impl ImageEnvironment {
+    pub fn new(image: ImageTexture) -> Self {
+        let (width, height) = (image.width(), image.height());
+        // Bilinear lookups spread every pixel onto its neighbours, so each pixel weighs its own
+        // luminance with theirs, by how much of its area they reach. That keeps the density
+        // positive wherever there is light.
+        const WEIGHTS: [f32; 3] = [1. / 8., 3. / 4., 1. / 8.];
+        let luminance = |x: usize, y: usize| {
+            let c = image.texel(x as i64, y as i64);
+            0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+        };
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..height {
+            // Rows near the poles cover less of the sphere
+            let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+            for x in 0..width {
+                let mut average = 0.;
+                for (dx, wx) in (-1..=1).zip(WEIGHTS) {
+                    for (dy, wy) in (-1..=1).zip(WEIGHTS) {
+                        let nx = (x as i64 + dx).rem_euclid(width as i64) as usize;
+                        let ny = (y as i64 + dy).clamp(0, height as i64 - 1) as usize;
+                        average += wx * wy * luminance(nx, ny);
+                    }
+                }
+                func.push(average * sin_theta);
+            }
+        }
+        Self {
+            distribution: Distribution2D::new(&func, width),
+            image,
+            intensity: 1.,
+            rotation: 0.,
+        }
+    }
+
+    /// Scales the radiance of the image
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Turns the image around the `y` axis by `degrees`
+    pub fn with_rotation(mut self, degrees: f32) -> Self {
+        self.rotation = degrees_to_radians(degrees);
+        self
+    }
+}
+
+impl Environment for ImageEnvironment {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let (u, v) = direction_to_uv(rotate_y(direction, -self.rotation));
+        // Keeps bilinear lookups from blending the top row into the bottom one
+        let half_pixel = 0.5 / self.image.height() as f32;
+        let v = v.clamp(half_pixel, 1. - half_pixel);
+        self.intensity * self.image.value(u, v, Point3::default())
+    }
+
+    fn sample(&self) -> Vec3 {
+        let (r1, r2) = random_2d();
+        let ((u, t), _) = self.distribution.sample_continuous(r1, r2);
+        rotate_y(uv_to_direction(u, 1. - t), self.rotation)
+    }
+
+    fn pdf(&self, direction: Vec3) -> f32 {
+        let d = vec3::unit_vector(rotate_y(direction, -self.rotation));
+        let (u, v) = direction_to_uv(d);
+        // Rather than `sin(v π)`, which rounds to below zero at the poles
+        let sin_theta = d.x().hypot(d.z());
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        // The image covers 2π by π radians, squeezed by sin θ away from the equator
+        self.distribution.pdf(u, 1. - v) / (2. * PI * PI * sin_theta)
+    }
+}
+
+/// Puts an `Environment` in the list of lights, so that light sampling picks directions towards
+/// it. It is never hit, since shadow rays that reach it are those that leave the scene.
+pub struct EnvironmentLight {
+    environment: Arc<dyn Environment>,
+    bbox: AABB,
+}
+
+impl EnvironmentLight {
+    pub fn new(environment: Arc<dyn Environment>) -> Self {
+        Self {
+            environment,
+            bbox: AABB::empty(),
+        }
+    }
+}
+
+impl Hittable for EnvironmentLight {
+    fn hit(&self, _r: &Ray, _ray_t: &mut Interval, _rec: &mut HitRecord) -> bool {
+        false
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f32 {
+        self.environment.pdf(direction)
+    }
+
+    fn random(&self, _origin: Point3) -> Vec3 {
+        self.environment.sample()
+    }
+}
@@ -0,0 +1,114 @@
+pub(crate) mod environment;
+pub use environment::{ConstantEnvironment, Environment, EnvironmentLight, ImageEnvironment};
+pub(crate) mod sky;
+pub use sky::SkyEnvironment;
+
+use crate::{Color, Vec3};
+
+use std::sync::Arc;
+
+/// Light from infinitely far away, replacing the flat `background` color of the camera
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EnvironmentConfig {
+    /// An equirectangular image, see `ImageEnvironment`, scaled by `intensity` and turned
+    /// around the up axis by `rotation` degrees
+    Image {
+        path: String,
+        #[serde(default = "EnvironmentConfig::default_intensity")]
+        intensity: f32,
+        #[serde(default)]
+        rotation: f32,
+    },
+    /// A clear daylight sky with the sun, see `SkyEnvironment`. The sun is either along
+    /// `sun_direction` or where it stands at `time` hours of local solar time on `day` of the
+    /// year, from `latitude` degrees north. North is along `-z` until turned by `rotation`.
+    Sky {
+        #[serde(default)]
+        sun_direction: Option<Vec3>,
+        #[serde(default)]
+        latitude: Option<f32>,
+        #[serde(default)]
+        day: Option<f32>,
+        #[serde(default)]
+        time: Option<f32>,
+        #[serde(default = "EnvironmentConfig::default_turbidity")]
+        turbidity: f32,
+        #[serde(default = "EnvironmentConfig::default_ground_albedo")]
+        ground_albedo: Color,
+        #[serde(default = "EnvironmentConfig::default_sun")]
+        sun: bool,
+        #[serde(default = "EnvironmentConfig::default_intensity")]
+        intensity: f32,
+        #[serde(default)]
+        rotation: f32,
+    },
+}
+
+impl EnvironmentConfig {
+    fn default_intensity() -> f32 {
+        1.
+    }
+
+    fn default_turbidity() -> f32 {
+        3.
+    }
+
+    fn default_ground_albedo() -> Color {
+        Color::new(0.3, 0.3, 0.3)
+    }
+
+    fn default_sun() -> bool {
+        true
+    }
+
+    pub fn build(&self) -> Result<Arc<dyn Environment>, String> {
+        Ok(match self {
+            EnvironmentConfig::Image {
+                path,
+                intensity,
+                rotation,
+            } => Arc::new(
+                ImageEnvironment::new(crate::ImageTexture::load(path, true)?)
+                    .with_intensity(*intensity)
+                    .with_rotation(*rotation),
+            ),
+            EnvironmentConfig::Sky {
+                sun_direction,
+                latitude,
+                day,
+                time,
+                turbidity,
+                ground_albedo,
+                sun,
+                intensity,
+                rotation,
+            } => {
+                let sun_direction = match (sun_direction, latitude, day, time) {
+                    (Some(direction), None, None, None) => {
+                        if direction.near_zero() {
+                            return Err("the sky's sun_direction can't be zero".to_string());
+                        }
+                        *direction
+                    }
+                    (None, Some(latitude), Some(day), Some(time)) => {
+                        SkyEnvironment::sun_position(*latitude, *day, *time)
+                    }
+                    _ => {
+                        return Err(
+                            "the sky needs either a sun_direction or a latitude, day and time"
+                                .to_string(),
+                        )
+                    }
+                };
+                Arc::new(
+                    SkyEnvironment::new(sun_direction, *turbidity)?
+                        .with_ground_albedo(*ground_albedo)
+                        .with_sun(*sun)
+                        .with_intensity(*intensity)
+                        .with_rotation(*rotation),
+                )
+            }
+        })
+    }
+}
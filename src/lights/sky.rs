@@ -0,0 +1,342 @@
+use crate::lights::environment::{direction_to_uv, rotate_y, uv_to_direction};
+use crate::radiometry::color::XYZ;
+use crate::radiometry::spectrum::{
+    spectrum_to_xyz, BlackbodySpectrum, PiecewiseLinearSpectrum, Spectrum,
+};
+use crate::utils::{degrees_to_radians, random_2d, random_double};
+use crate::{vec3, Color, Distribution2D, Environment, Vec3, ONB};
+
+use std::f32::consts::PI;
+
+/// Angular radius of the sun, in radians
+const SUN_RADIUS: f32 = 0.004_654;
+
+/// Illuminance from the sun outside the atmosphere, in kilolux
+const SOLAR_ILLUMINANCE: f32 = 128.;
+
+/// Luminance of a radiance of 1, in kcd/m²
+const LUMINANCE_UNIT: f32 = 100.;
+
+/// Size of the table that directions in the sky are sampled from, over the upper hemisphere
+const TABLE_WIDTH: usize = 128;
+const TABLE_HEIGHT: usize = 32;
+
+/// Coefficients A to E of the Perez sky model for the luminance `Y` and the chromaticities `x`
+/// and `y`, each as `[slope, intercept]` of a line in the turbidity
+#[rustfmt::skip]
+const PEREZ: [[[f32; 2]; 5]; 3] = [
+    [[0.1787, -1.4630], [-0.3554, 0.4275], [-0.0227, 5.3251], [0.1206, -2.5771], [-0.0670, 0.3703]],
+    [[-0.0193, -0.2592], [-0.0665, 0.0008], [-0.0004, 0.2125], [-0.0641, -0.8989], [-0.0033, 0.0452]],
+    [[-0.0167, -0.2608], [-0.0950, 0.0092], [-0.0079, 0.2102], [-0.0441, -1.6537], [-0.0109, 0.0529]],
+];
+
+/// Chromaticities `x` and `y` at the zenith, as polynomials in the zenith angle of the sun
+/// (cubic to constant) for each power of the turbidity (squared to constant)
+#[rustfmt::skip]
+const ZENITH_CHROMATICITY: [[[f32; 4]; 3]; 2] = [
+    [
+        [0.00166, -0.00375, 0.00209, 0.],
+        [-0.02903, 0.06377, -0.03202, 0.00394],
+        [0.11693, -0.21196, 0.06052, 0.25886],
+    ],
+    [
+        [0.00275, -0.00610, 0.00317, 0.],
+        [-0.04214, 0.08970, -0.04153, 0.00516],
+        [0.15346, -0.26756, 0.06670, 0.26688],
+    ],
+];
+
+/// A clear daylight sky, following the analytic model of Preetham, Shirley and Smits, with the
+/// disk of the sun and the ground below the horizon
+///
+/// The sky depends on the direction of the sun and the turbidity of the air, from 2 for a
+/// very clear day to 10 for haze. Sunlight is a 5778 K blackbody dimmed by air and aerosols
+/// along its way, so it turns orange as the sun sets. The ground reflects both by its albedo.
+/// Radiance comes out in units of 100 kcd/m², in which a white floor under the midday sun is
+/// around 0.3 to 0.4.
+///
+/// The model is meant for the sun above the horizon. Below it there is no sun, and the sky
+/// stays as it is at sunset.
+pub struct SkyEnvironment {
+    /// Unit direction towards the sun, before `rotation`
+    sun_direction: Vec3,
+    /// Perez coefficients A to E of `Y`, `x` and `y`
+    perez: [[f32; 5]; 3],
+    /// `Y`, `x` and `y` at the zenith over the Perez function there, which scales the model
+    zenith: [f32; 3],
+    sun_radiance: Color,
+    sun_solid_angle: f32,
+    sun: bool,
+    /// Irradiance of a horizontal surface from the sky without the sun
+    sky_irradiance: Color,
+    /// Luminance of the sky integrated over the upper hemisphere
+    sky_power: f32,
+    /// Over `(u, 2v - 1)`, from the horizon up to the zenith
+    distribution: Distribution2D,
+    ground_albedo: Color,
+    intensity: f32,
+    /// Turn of the sky around the `y` axis, in radians
+    rotation: f32,
+}
+
+impl SkyEnvironment {
+    /// The sky with the sun along `sun_direction` and air of the given `turbidity`, which has to
+    /// be in the range 2 to 10 the model was fit to
+    pub fn new(sun_direction: Vec3, turbidity: f32) -> Result<Self, String> {
+        if !(2. ..=10.).contains(&turbidity) {
+            return Err(format!(
+                "the sky's turbidity must be between 2 and 10, got {turbidity}"
+            ));
+        }
+        let sun_direction = vec3::unit_vector(sun_direction);
+        let theta_sun = sun_direction.y().clamp(0., 1.).acos();
+
+        let perez =
+            PEREZ.map(|channel| channel.map(|[slope, intercept]| slope * turbidity + intercept));
+
+        let chi = (4. / 9. - turbidity / 120.) * (PI - 2. * theta_sun);
+        let zenith_luminance =
+            (4.0453 * turbidity - 4.9710) * chi.tan() - 0.2155 * turbidity + 2.4192;
+        let [zenith_x, zenith_y] = ZENITH_CHROMATICITY.map(|rows| {
+            let [t2, t1, t0] =
+                rows.map(|[a, b, c, d]| ((a * theta_sun + b) * theta_sun + c) * theta_sun + d);
+            (t2 * turbidity + t1) * turbidity + t0
+        });
+        let mut zenith = [zenith_luminance.max(0.), zenith_x, zenith_y];
+        for (value, coefficients) in zenith.iter_mut().zip(&perez) {
+            *value /= perez_function(coefficients, 1., theta_sun, theta_sun.cos());
+        }
+
+        let sun_solid_angle = 4. * PI * (SUN_RADIUS / 2.).sin().powi(2);
+        let mut sky = Self {
+            sun_direction,
+            perez,
+            zenith,
+            sun_radiance: sunlight(theta_sun, turbidity, sun_solid_angle),
+            sun_solid_angle,
+            sun: true,
+            sky_irradiance: Color::default(),
+            sky_power: 0.,
+            distribution: Distribution2D::new(&[1.], 1),
+            ground_albedo: Color::new(0.3, 0.3, 0.3),
+            intensity: 1.,
+            rotation: 0.,
+        };
+
+        let cell_solid_angle = (2. * PI / TABLE_WIDTH as f32) * (PI / 2. / TABLE_HEIGHT as f32);
+        let mut func = Vec::with_capacity(TABLE_WIDTH * TABLE_HEIGHT);
+        for y in 0..TABLE_HEIGHT {
+            let v = (1. + (y as f32 + 0.5) / TABLE_HEIGHT as f32) / 2.;
+            let sin_theta = (v * PI).sin();
+            for x in 0..TABLE_WIDTH {
+                let direction = uv_to_direction((x as f32 + 0.5) / TABLE_WIDTH as f32, v);
+                let radiance = sky.sky_radiance(direction);
+                sky.sky_irradiance += radiance * direction.y() * sin_theta * cell_solid_angle;
+                sky.sky_power += luminance(radiance) * sin_theta * cell_solid_angle;
+                func.push(luminance(radiance) * sin_theta);
+            }
+        }
+        sky.distribution = Distribution2D::new(&func, TABLE_WIDTH);
+        Ok(sky)
+    }
+
+    /// The direction of the sun at `time` hours of local solar time on `day` of the year, from
+    /// `latitude` degrees north, with north along `-z` and east along `+x`
+    pub fn sun_position(latitude: f32, day: f32, time: f32) -> Vec3 {
+        let latitude = degrees_to_radians(latitude);
+        let declination = 0.4093 * (2. * PI * (day - 81.) / 368.).sin();
+        let hour_angle = PI * time / 12.;
+        let sin_elevation = latitude.sin() * declination.sin()
+            - latitude.cos() * declination.cos() * hour_angle.cos();
+        // Measured from the south towards the west
+        let azimuth = (-declination.cos() * hour_angle.sin()).atan2(
+            latitude.cos() * declination.sin()
+                - latitude.sin() * declination.cos() * hour_angle.cos(),
+        );
+        let cos_elevation = (1. - sin_elevation * sin_elevation).max(0.).sqrt();
+        Vec3::new(
+            -azimuth.sin() * cos_elevation,
+            sin_elevation,
+            azimuth.cos() * cos_elevation,
+        )
+    }
+
+    /// Sets the color of the ground below the horizon, which reflects the sun and the sky
+    pub fn with_ground_albedo(mut self, albedo: Color) -> Self {
+        self.ground_albedo = albedo;
+        self
+    }
+
+    /// Whether to show the disk of the sun, which scenes that light the sun some other way can
+    /// leave out
+    pub fn with_sun(mut self, sun: bool) -> Self {
+        self.sun = sun;
+        self
+    }
+
+    /// Scales the radiance of the sky, the sun and the ground
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Turns the sky around the `y` axis by `degrees`
+    pub fn with_rotation(mut self, degrees: f32) -> Self {
+        self.rotation = degrees_to_radians(degrees);
+        self
+    }
+
+    /// Radiance of the sun's disk, after the atmosphere
+    pub fn sun_radiance(&self) -> Color {
+        if self.sun_visible() {
+            self.intensity * self.sun_radiance
+        } else {
+            Color::default()
+        }
+    }
+
+    fn sun_visible(&self) -> bool {
+        self.sun && self.sun_direction.y() > 0.
+    }
+
+    fn in_sun(&self, direction: Vec3) -> bool {
+        vec3::dot(direction, self.sun_direction) >= SUN_RADIUS.cos()
+    }
+
+    /// Radiance of the sky without the sun, along a unit `direction` above the horizon
+    fn sky_radiance(&self, direction: Vec3) -> Color {
+        let cos_theta = direction.y().max(1e-3);
+        let cos_gamma = vec3::dot(direction, self.sun_direction).clamp(-1., 1.);
+        let gamma = cos_gamma.acos();
+        let [luminance, x, y] = [0, 1, 2]
+            .map(|i| self.zenith[i] * perez_function(&self.perez[i], cos_theta, gamma, cos_gamma));
+        xy_luminance_to_color(x, y, luminance / LUMINANCE_UNIT)
+    }
+
+    fn ground(&self) -> Color {
+        let mut irradiance = self.sky_irradiance;
+        if self.sun_visible() {
+            irradiance += self.sun_radiance * self.sun_solid_angle * self.sun_direction.y();
+        }
+        self.ground_albedo * irradiance / PI
+    }
+
+    /// Chances of sampling the sun, the sky and the ground, in proportion to their power
+    fn probabilities(&self) -> [f32; 3] {
+        let sun = if self.sun_visible() {
+            luminance(self.sun_radiance) * self.sun_solid_angle
+        } else {
+            0.
+        };
+        let ground = luminance(self.ground()) * 2. * PI;
+        let total = sun + self.sky_power + ground;
+        [sun / total, self.sky_power / total, ground / total]
+    }
+}
+
+impl Environment for SkyEnvironment {
+    fn radiance(&self, direction: Vec3) -> Color {
+        let d = vec3::unit_vector(rotate_y(direction, -self.rotation));
+        let radiance = if d.y() < 0. {
+            self.ground()
+        } else if self.sun_visible() && self.in_sun(d) {
+            self.sky_radiance(d) + self.sun_radiance
+        } else {
+            self.sky_radiance(d)
+        };
+        self.intensity * radiance
+    }
+
+    fn sample(&self) -> Vec3 {
+        let [sun, sky, _] = self.probabilities();
+        let choice = random_double();
+        let (r1, r2) = random_2d();
+        let d = if choice < sun {
+            let one_minus_z = r2 * self.sun_solid_angle / (2. * PI);
+            let sin_theta = (one_minus_z * (2. - one_minus_z)).sqrt();
+            let phi = 2. * PI * r1;
+            ONB::new(self.sun_direction).transform(Vec3::new(
+                phi.cos() * sin_theta,
+                phi.sin() * sin_theta,
+                1. - one_minus_z,
+            ))
+        } else if choice < sun + sky {
+            let ((u, t), _) = self.distribution.sample_continuous(r1, r2);
+            uv_to_direction(u, (1. + t) / 2.)
+        } else {
+            let d = vec3::random_unit_vector();
+            Vec3::new(d.x(), -d.y().abs(), d.z())
+        };
+        rotate_y(d, self.rotation)
+    }
+
+    fn pdf(&self, direction: Vec3) -> f32 {
+        let d = vec3::unit_vector(rotate_y(direction, -self.rotation));
+        let [sun, sky, ground] = self.probabilities();
+        let mut pdf = 0.;
+        if d.y() < 0. {
+            pdf += ground / (2. * PI);
+        } else {
+            let (u, v) = direction_to_uv(d);
+            let sin_theta = d.x().hypot(d.z());
+            if sin_theta > 0. {
+                // The table covers 2π by π/2 radians, squeezed by sin θ towards the zenith
+                pdf += sky * self.distribution.pdf(u, 2. * v - 1.) / (PI * PI * sin_theta);
+            }
+        }
+        if self.in_sun(d) {
+            pdf += sun / self.sun_solid_angle;
+        }
+        pdf
+    }
+}
+
+/// The Perez sky function of the zenith angle θ and the angle γ away from the sun
+fn perez_function(coefficients: &[f32; 5], cos_theta: f32, gamma: f32, cos_gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+    (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// Radiance of the sun's disk with the sun at `theta_sun` from the zenith, where the light of
+/// the sun passes through `air_mass` times the air above the zenith and loses some to
+/// Rayleigh scattering by the air and to aerosols
+fn sunlight(theta_sun: f32, turbidity: f32, solid_angle: f32) -> Color {
+    let theta_degrees = theta_sun.to_degrees().min(90.);
+    let air_mass = 1. / (theta_sun.cos() + 0.15 * (93.885 - theta_degrees).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+
+    let solar = BlackbodySpectrum::new(5778.);
+    let lambdas: Vec<f32> = (360..=830).step_by(5).map(|l| l as f32).collect();
+    let values = lambdas
+        .iter()
+        .map(|&lambda| {
+            let micrometers = lambda / 1000.;
+            let rayleigh = 0.008735 * micrometers.powf(-4.08);
+            let aerosols = beta * micrometers.powf(-1.3);
+            solar.call(lambda) * (-(rayleigh + aerosols) * air_mass).exp()
+        })
+        .collect();
+    let transmitted = PiecewiseLinearSpectrum::new(lambdas, values)
+        .expect("the wavelengths of sunlight are in order");
+
+    let outside = spectrum_to_xyz(&solar).y;
+    let luminance = SOLAR_ILLUMINANCE / solid_angle / LUMINANCE_UNIT;
+    let c = Color::from(spectrum_to_xyz(&transmitted)) * (luminance / outside);
+    Color::new(c.x().max(0.), c.y().max(0.), c.z().max(0.))
+}
+
+fn xy_luminance_to_color(x: f32, y: f32, luminance: f32) -> Color {
+    if y <= 0. {
+        return Color::default();
+    }
+    let c = Color::from(XYZ {
+        x: x * luminance / y,
+        y: luminance,
+        z: (1. - x - y) * luminance / y,
+    });
+    Color::new(c.x().max(0.), c.y().max(0.), c.z().max(0.))
+}
+
+fn luminance(c: Color) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
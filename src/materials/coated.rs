@@ -0,0 +1,427 @@
+use super::material::{Material, ScatterRecord, TransportMode};
+use super::microfacet::{fresnel_dielectric, Microfacet};
+use super::rough_dielectric::RoughDielectric;
+use crate::{utils, vec3, Color, HitRecord, Pdf, Ray, Vec3, ONB};
+
+use std::sync::Arc;
+
+/// Longest walk between the base and the coating that `CoatedMaterial` follows
+const MAX_BOUNCES: usize = 16;
+
+/// A clear dielectric layer, like varnish or lacquer, over any other material
+///
+/// Light either reflects off the coating, or refracts into it and bounces between the base and
+/// the underside of the coating until it leaves. That has no closed form, so `eval` follows one
+/// random walk through the layer, after Guo et al., "Position-Free Monte Carlo Simulation for
+/// Arbitrary Layered BSDFs": it is unbiased, but noisy. Directions are sampled by refracting into
+/// the layer, sampling the base and refracting back out, which ignores the bounces but has a
+/// density that is known exactly. A specular base makes the whole layer specular, and then
+/// `scatter` follows the walk itself.
+///
+/// The coating is infinitely thin, but each pass through it filters light by `tint`, more so at
+/// grazing angles. Light that the base transmits is lost.
+#[derive(Clone)]
+pub struct CoatedMaterial {
+    base: Arc<dyn Material>,
+    refraction_index: f32,
+    microfacet: Microfacet,
+    tint: Color,
+}
+
+/// The base of a `CoatedMaterial` at a hit point, queried with local directions that point away
+/// from the surface, like the layer's
+struct Base<'a> {
+    material: &'a dyn Material,
+    rec: &'a HitRecord,
+    frame: &'a ONB,
+    time: f32,
+}
+
+impl Base<'_> {
+    /// A ray arriving from `view`
+    fn ray_from(&self, view: Vec3) -> Ray {
+        let d = self.frame.transform(view);
+        Ray::new(self.rec.p + d, -d, self.time)
+    }
+
+    fn ray_to(&self, light: Vec3) -> Ray {
+        Ray::new(self.rec.p, self.frame.transform(light), self.time)
+    }
+
+    fn eval(&self, view: Vec3, light: Vec3) -> Color {
+        self.material
+            .eval(&self.ray_from(view), self.rec, &self.ray_to(light))
+    }
+
+    fn pdf(&self, view: Vec3, light: Vec3) -> f32 {
+        self.material
+            .scattering_pdf(&self.ray_from(view), self.rec, &self.ray_to(light))
+    }
+
+    /// Samples a direction for light to come from, with the weight f / pdf
+    fn sample(&self, view: Vec3) -> Option<(Vec3, Color)> {
+        let r_in = self.ray_from(view);
+        let mut srec = ScatterRecord::default();
+        if !self.material.scatter(&r_in, self.rec, &mut srec) {
+            return None;
+        }
+        if srec.skip_pdf {
+            let direction = vec3::unit_vector(srec.skip_pdf_ray.direction());
+            return Some((self.frame.to_local(direction), srec.attenuation));
+        }
+        let direction = srec.pdf.generate();
+        let pdf = srec.pdf.value(direction);
+        if pdf <= 0. {
+            return None;
+        }
+        let scattered = Ray::new(self.rec.p, direction, self.time);
+        let f = self.material.eval(&r_in, self.rec, &scattered);
+        Some((self.frame.to_local(vec3::unit_vector(direction)), f / pdf))
+    }
+}
+
+impl CoatedMaterial {
+    /// A coating with the refractive index `refraction_index`, rough or smooth depending on
+    /// `microfacet`
+    pub fn new(
+        base: Arc<dyn Material>,
+        refraction_index: f32,
+        microfacet: Microfacet,
+        tint: Color,
+    ) -> Self {
+        Self {
+            base,
+            refraction_index,
+            microfacet,
+            tint,
+        }
+    }
+
+    fn base<'a>(&'a self, rec: &'a HitRecord, frame: &'a ONB, time: f32) -> Base<'a> {
+        Base {
+            material: self.base.as_ref(),
+            rec,
+            frame,
+            time,
+        }
+    }
+
+    fn smooth(&self) -> bool {
+        self.microfacet.effectively_smooth()
+    }
+
+    fn interface(&self) -> RoughDielectric {
+        RoughDielectric::new(self.refraction_index, self.microfacet)
+    }
+
+    /// The direction inside the layer, pointing up, that a smooth coating refracts into `w`
+    fn inside(&self, w: Vec3) -> Option<Vec3> {
+        refract_up(w, 1. / self.refraction_index)
+    }
+
+    /// The direction above the layer that a smooth coating refracts `u`, from inside, into
+    fn outside(&self, u: Vec3) -> Option<Vec3> {
+        refract_up(u, self.refraction_index)
+    }
+
+    /// Fraction of light that crosses the coating along `w`
+    fn transmittance(&self, w: Vec3) -> Color {
+        let exponent = 1. / w.z().abs().max(1e-4);
+        Color::new(
+            self.tint.x().powf(exponent),
+            self.tint.y().powf(exponent),
+            self.tint.z().powf(exponent),
+        )
+    }
+
+    /// Samples how the coating scatters light seen from `w`, above it or from inside the layer,
+    /// returning the direction the light comes from and the weight f / pdf
+    fn sample_coating(&self, w: Vec3) -> Option<(Vec3, f32)> {
+        let (w, eta, flip) = if w.z() < 0. {
+            (-w, 1. / self.refraction_index, true)
+        } else {
+            (w, self.refraction_index, false)
+        };
+        let (wi, weight) = if self.smooth() {
+            if utils::random_double() < fresnel_dielectric(w.z(), eta) {
+                (Vec3::new(-w.x(), -w.y(), w.z()), 1.)
+            } else {
+                // Radiance is compressed into a smaller solid angle on the denser side
+                (-refract_up(w, 1. / eta)?, 1. / (eta * eta))
+            }
+        } else {
+            let interface = self.interface();
+            let wi = interface.sample(w, eta)?;
+            let pdf = interface.pdf(w, wi, eta);
+            if pdf <= 0. {
+                return None;
+            }
+            (wi, interface.f(w, wi, eta, TransportMode::Radiance) / pdf)
+        };
+        Some((if flip { -wi } else { wi }, weight))
+    }
+
+    /// Enters the layer from `wo` above it, returning the direction inside and its weight
+    fn enter(&self, wo: Vec3) -> Option<(Vec3, f32)> {
+        if self.smooth() {
+            let eta = self.refraction_index;
+            let transmitted = 1. - fresnel_dielectric(wo.z(), eta);
+            return Some((-self.inside(wo)?, transmitted / (eta * eta)));
+        }
+        let (w, weight) = self.sample_coating(wo)?;
+        (w.z() < 0.).then_some((w, weight))
+    }
+
+    /// Samples a direction inside the layer along which light from `wi` above it arrives, with
+    /// the weight of the transmission for light going that way
+    fn exit(&self, wi: Vec3) -> Option<(Vec3, f32)> {
+        let eta = self.refraction_index;
+        if self.smooth() {
+            return Some((-self.inside(wi)?, 1. - fresnel_dielectric(wi.z(), eta)));
+        }
+        let interface = self.interface();
+        let v = interface.sample(wi, eta)?;
+        let pdf = interface.pdf(wi, v, eta);
+        if v.z() >= 0. || pdf <= 0. {
+            return None;
+        }
+        // The walk goes the other way to the light here, so the radiance isn't compressed
+        Some((v, interface.f(wi, v, eta, TransportMode::Importance) / pdf))
+    }
+
+    /// One random walk's estimate of the light from `wi` that leaves towards `wo` after
+    /// entering the layer, times the cosine
+    fn through(&self, base: &Base, wo: Vec3, wi: Vec3) -> Color {
+        let mut f = Color::new(0., 0., 0.);
+        let (Some((w, weight)), Some((v, exit))) = (self.enter(wo), self.exit(wi)) else {
+            return f;
+        };
+        // Light reaches the base from `-v`, and the base's `eval` has its cosine
+        let exit = self.transmittance(v) * (exit * wi.z() / v.z().abs());
+
+        let mut beta = self.transmittance(w) * weight;
+        let mut view = -w;
+        for depth in 0..MAX_BOUNCES {
+            f += beta * base.eval(view, -v) * exit;
+
+            let Some((u, weight)) = base.sample(view) else {
+                break;
+            };
+            if u.z() <= 0. {
+                break;
+            }
+            beta = beta * weight * self.transmittance(u);
+
+            // Light that leaves through the coating here can't go towards `wi`, which the
+            // connection above already accounts for
+            let Some((w, weight)) = self.sample_coating(-u) else {
+                break;
+            };
+            if w.z() >= 0. {
+                break;
+            }
+            beta = beta * self.transmittance(w) * weight;
+            view = -w;
+
+            if depth >= 3 {
+                let survival = beta.x().max(beta.y()).max(beta.z()).min(0.95);
+                if utils::random_double() >= survival {
+                    break;
+                }
+                beta /= survival;
+            }
+        }
+        f
+    }
+
+    /// Follows light through the layer from `wo` until it leaves, returning the direction it
+    /// came from and the weight f / pdf
+    fn walk(&self, base: &Base, wo: Vec3) -> Option<(Vec3, Color)> {
+        let (w, weight) = self.sample_coating(wo)?;
+        let mut beta = Color::new(weight, weight, weight);
+        if w.z() > 0. {
+            return Some((w, beta));
+        }
+        beta = beta * self.transmittance(w);
+        let mut view = -w;
+        for _ in 0..MAX_BOUNCES {
+            let (u, weight) = base.sample(view)?;
+            if u.z() <= 0. {
+                return None;
+            }
+            beta = beta * weight * self.transmittance(u);
+            let (w, weight) = self.sample_coating(-u)?;
+            if w.z() > 0. {
+                return Some((w, beta * weight));
+            }
+            beta = beta * self.transmittance(w) * weight;
+            view = -w;
+        }
+        None
+    }
+
+    /// Probability of sampling a reflection off the coating rather than the base
+    fn reflect_probability(&self, wo: Vec3) -> f32 {
+        if self.smooth() {
+            // Taken by `scatter` before the pdf is used
+            0.
+        } else {
+            fresnel_dielectric(wo.z(), self.refraction_index)
+        }
+    }
+
+    /// Density of sampling `wi` from `wo`, where `base_pdf` is the density of the base's
+    /// sampling of a local direction, seen through the coating from `wo`
+    fn pdf(&self, wo: Vec3, wi: Vec3, base_pdf: impl Fn(Vec3) -> f32) -> f32 {
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return 0.;
+        }
+        let reflect = self.reflect_probability(wo);
+        let mut pdf = 0.;
+        if reflect > 0. {
+            let wm = vec3::unit_vector(wo + wi);
+            pdf += reflect * self.microfacet.d_visible(wo, wm) / (4. * vec3::dot(wo, wm));
+        }
+        if let Some(u) = self.inside(wi) {
+            let eta = self.refraction_index;
+            pdf += (1. - reflect) * base_pdf(u) * wi.z() / (eta * eta * u.z());
+        }
+        pdf
+    }
+}
+
+/// Refracts `w` across a smooth interface, with `eta` the refractive index on the side of `w`
+/// over the other, and returns the refracted direction pointing up, or `None` on total internal
+/// reflection
+fn refract_up(w: Vec3, eta: f32) -> Option<Vec3> {
+    let (x, y) = (w.x() * eta, w.y() * eta);
+    let cos2 = 1. - x * x - y * y;
+    (cos2 > 0.).then(|| Vec3::new(x, y, cos2.sqrt()))
+}
+
+/// Samples a `CoatedMaterial` by reflecting off the coating or refracting through it to the
+/// base's own sampling
+struct CoatedPdf {
+    material: CoatedMaterial,
+    rec: HitRecord,
+    frame: ONB,
+    time: f32,
+    wo: Vec3,
+    /// The direction the base is seen from through a smooth coating
+    view: Vec3,
+}
+
+impl Pdf for CoatedPdf {
+    fn generate(&self) -> Vec3 {
+        let material = &self.material;
+        let wi = if utils::random_double() < material.reflect_probability(self.wo) {
+            let wm = material.microfacet.sample_wm(self.wo, utils::random_2d());
+            Some(vec3::reflect(-self.wo, wm)).filter(|wi| wi.z() > 0.)
+        } else {
+            let base = material.base(&self.rec, &self.frame, self.time);
+            base.sample(self.view)
+                .and_then(|(u, _)| (u.z() > 0.).then(|| material.outside(u)).flatten())
+        };
+        match wi {
+            Some(wi) => self.frame.transform(wi),
+            // Which `value` gives no density
+            None => Vec3::default(),
+        }
+    }
+
+    fn value(&self, direction: Vec3) -> f32 {
+        if direction.length_squared() == 0. {
+            return 0.;
+        }
+        let wi = self.frame.to_local(vec3::unit_vector(direction));
+        let base = self.material.base(&self.rec, &self.frame, self.time);
+        self.material.pdf(self.wo, wi, |u| base.pdf(self.view, u))
+    }
+}
+
+impl Material for CoatedMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let frame = ONB::new(rec.normal);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let Some(view) = self.inside(wo) else {
+            return false;
+        };
+        let base = self.base(rec, &frame, r_in.time());
+
+        let mut base_srec = ScatterRecord::default();
+        let base_scatters = self.base.scatter(&base.ray_from(view), rec, &mut base_srec);
+        if base_scatters && base_srec.skip_pdf {
+            let Some((wi, weight)) = self.walk(&base, wo) else {
+                return false;
+            };
+            srec.attenuation = weight;
+            srec.skip_pdf = true;
+            srec.skip_pdf_ray = base.ray_to(wi);
+            return true;
+        }
+
+        if self.smooth() {
+            let reflectance = fresnel_dielectric(wo.z(), self.refraction_index);
+            if !base_scatters || utils::random_double() < reflectance {
+                let weight = if base_scatters { 1. } else { reflectance };
+                srec.attenuation = Color::new(weight, weight, weight);
+                srec.skip_pdf = true;
+                let mirrored = frame.transform(Vec3::new(-wo.x(), -wo.y(), wo.z()));
+                srec.skip_pdf_ray = rec.reflected_ray(r_in, mirrored);
+                return true;
+            }
+        }
+
+        srec.attenuation = if base_scatters {
+            base_srec.attenuation
+        } else {
+            Color::new(1., 1., 1.)
+        };
+        srec.pdf = Arc::new(CoatedPdf {
+            material: self.clone(),
+            rec: rec.clone(),
+            frame,
+            time: r_in.time(),
+            wo,
+            view,
+        });
+        srec.skip_pdf = false;
+        true
+    }
+
+    /// A random estimate of the BSDF. With a smooth coating, it is that of the light that isn't
+    /// reflected off the coating, which `scatter` samples separately.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let frame = ONB::new(rec.normal);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(vec3::unit_vector(scattered.direction()));
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return Color::new(0., 0., 0.);
+        }
+        let base = self.base(rec, &frame, r_in.time());
+
+        let through = self.through(&base, wo, wi);
+        if self.smooth() {
+            let transmitted = 1. - fresnel_dielectric(wo.z(), self.refraction_index);
+            if transmitted <= 0. {
+                return Color::new(0., 0., 0.);
+            }
+            return through / transmitted;
+        }
+        let reflected = self
+            .interface()
+            .f(wo, wi, self.refraction_index, TransportMode::Radiance);
+        through + Color::new(reflected, reflected, reflected)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        let frame = ONB::new(rec.normal);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(vec3::unit_vector(scattered.direction()));
+        let Some(view) = self.inside(wo) else {
+            return 0.;
+        };
+        let base = self.base(rec, &frame, r_in.time());
+        self.pdf(wo, wi, |u| base.pdf(view, u))
+    }
+}
@@ -0,0 +1,162 @@
+use super::material::{Material, ScatterRecord};
+use super::microfacet::{fresnel_conductor, shading_frame, Microfacet};
+use crate::{utils, vec3, Color, HitRecord, Pdf, Ray, Texture, Vec3, ONB};
+
+use std::sync::Arc;
+
+/// A metal, reflecting light off microfacets with the Fresnel reflectance of its complex
+/// refractive index `eta + i k`, given per RGB channel
+#[derive(Clone)]
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    microfacet: Microfacet,
+    rotation: Option<Arc<dyn Texture>>,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, microfacet: Microfacet) -> Self {
+        Self {
+            eta,
+            k,
+            microfacet,
+            rotation: None,
+        }
+    }
+
+    /// Turns the tangent of an anisotropic `microfacet` about the normal by `rotation`, in
+    /// fractions of a full turn
+    pub fn with_rotation(mut self, rotation: Arc<dyn Texture>) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    fn frame(&self, rec: &HitRecord) -> ONB {
+        shading_frame(rec, self.rotation.as_deref())
+    }
+
+    fn fresnel(&self, cos_theta: f32) -> Color {
+        Color::new(
+            fresnel_conductor(cos_theta, self.eta.x(), self.k.x()),
+            fresnel_conductor(cos_theta, self.eta.y(), self.k.y()),
+            fresnel_conductor(cos_theta, self.eta.z(), self.k.z()),
+        )
+    }
+
+    /// The BRDF times the cosine, between local directions
+    fn f(&self, wo: Vec3, wi: Vec3) -> Color {
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return Color::new(0., 0., 0.);
+        }
+        let wm = vec3::unit_vector(wo + wi);
+        let f = self.fresnel(vec3::dot(wo, wm).abs());
+        f * (self.microfacet.d(wm) * self.microfacet.g(wo, wi) / (4. * wo.z()))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return 0.;
+        }
+        let wm = vec3::unit_vector(wo + wi);
+        self.microfacet.d_visible(wo, wm) / (4. * vec3::dot(wo, wm).abs())
+    }
+}
+
+/// Names accepted by `named_conductor`
+pub const NAMED_CONDUCTORS: [&str; 4] = ["gold", "copper", "aluminium", "silver"];
+
+/// Complex refractive index `(eta, k)` of a common metal, averaged over the red, green and blue
+/// parts of the spectrum of measured data
+pub fn named_conductor(name: &str) -> Option<(Color, Color)> {
+    match name {
+        "gold" => Some((
+            Color::new(0.143_119, 0.374_957, 1.442_479),
+            Color::new(3.983_16, 2.385_721, 1.603_215),
+        )),
+        "copper" => Some((
+            Color::new(0.200_438, 0.924_033, 1.102_212),
+            Color::new(3.912_949, 2.452_848, 2.142_188),
+        )),
+        "aluminium" | "aluminum" => Some((
+            Color::new(1.657_46, 0.880_369, 0.521_229),
+            Color::new(9.223_869, 6.269_523, 4.837_001),
+        )),
+        "silver" => Some((
+            Color::new(0.155_265, 0.116_723, 0.138_381),
+            Color::new(4.828_343, 3.122_246, 2.146_95),
+        )),
+        _ => None,
+    }
+}
+
+/// Samples the facets of a `Conductor` visible from the outgoing direction
+struct ConductorPdf {
+    material: Conductor,
+    frame: ONB,
+    wo: Vec3,
+}
+
+impl Pdf for ConductorPdf {
+    fn generate(&self) -> Vec3 {
+        let wm = self
+            .material
+            .microfacet
+            .sample_wm(self.wo, utils::random_2d());
+        let wi = vec3::reflect(-self.wo, wm);
+        if wi.z() <= 0. {
+            // Reflected into the surface, which `value` gives no density
+            return Vec3::default();
+        }
+        self.frame.transform(wi)
+    }
+
+    fn value(&self, direction: Vec3) -> f32 {
+        if direction.length_squared() == 0. {
+            return 0.;
+        }
+        let wi = self.frame.to_local(vec3::unit_vector(direction));
+        self.material.pdf(self.wo, wi)
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let v = -vec3::unit_vector(r_in.direction());
+        if self.microfacet.effectively_smooth() {
+            srec.attenuation = self.fresnel(vec3::dot(v, rec.normal));
+            srec.skip_pdf = true;
+            srec.skip_pdf_ray = rec.reflected_ray(r_in, vec3::reflect(-v, rec.normal));
+            return true;
+        }
+
+        let frame = self.frame(rec);
+        srec.attenuation = self.fresnel(1.);
+        srec.pdf = Arc::new(ConductorPdf {
+            material: self.clone(),
+            wo: frame.to_local(v),
+            frame,
+        });
+        srec.skip_pdf = false;
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        if self.microfacet.effectively_smooth() {
+            return Color::new(0., 0., 0.);
+        }
+        let frame = self.frame(rec);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(vec3::unit_vector(scattered.direction()));
+        self.f(wo, wi)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        if self.microfacet.effectively_smooth() {
+            return 0.;
+        }
+        let frame = self.frame(rec);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(vec3::unit_vector(scattered.direction()));
+        self.pdf(wo, wi)
+    }
+}
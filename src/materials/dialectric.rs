@@ -1,16 +1,40 @@
 use super::material::{Material, ScatterRecord};
 
+use crate::radiometry::ior::REFERENCE_WAVELENGTH;
+use crate::radiometry::sampling::SampledWavelengths;
+use crate::radiometry::spectrum::{ConstantSpectrum, Spectrum};
 use crate::{utils, vec3, Color, HitRecord, Ray};
 
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Dielectric {
+    /// Refractive index as a function of wavelength
+    ior: Arc<dyn Spectrum>,
+    /// Whether `ior` depends on the wavelength, which splits light into its colors
+    dispersive: bool,
+    /// The index used when rendering without wavelengths
     refraction_index: f32,
 }
 impl Dielectric {
     pub fn new(refraction_index: f32) -> Self {
-        Self { refraction_index }
+        Self {
+            ior: Arc::new(ConstantSpectrum::new(refraction_index)),
+            dispersive: false,
+            refraction_index,
+        }
+    }
+
+    /// A dielectric whose refractive index varies with the wavelength, as given by `ior`
+    ///
+    /// Only the spectral integrator disperses light. Other integrators use the index at
+    /// `REFERENCE_WAVELENGTH`.
+    pub fn with_ior(ior: Arc<dyn Spectrum>) -> Self {
+        Self {
+            refraction_index: ior.call(REFERENCE_WAVELENGTH),
+            ior,
+            dispersive: true,
+        }
     }
 
     fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
@@ -18,17 +42,20 @@ impl Dielectric {
         let r0 = r0 * r0;
         r0 + (1.0 - r0) * f32::powi(1.0 - cosine, 5)
     }
-}
 
-impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+    fn scatter_with_index(
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut ScatterRecord,
+        refraction_index: f32,
+    ) -> bool {
         srec.attenuation = Color::new(1.0, 1.0, 1.0);
         srec.skip_pdf = true;
         srec.pdf = Arc::new(crate::SpherePdf::new(rec.normal));
         let ri = if rec.front_face {
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         } else {
-            self.refraction_index
+            refraction_index
         };
 
         let unit_direction = vec3::unit_vector(r_in.direction());
@@ -36,14 +63,34 @@ impl Material for Dielectric {
         let sin_theta = f32::sqrt(1.0 - cos_theta * cos_theta);
 
         let cannot_refract = (ri * sin_theta) > 1.0;
-        let direction: vec3::Vec3 =
+        srec.skip_pdf_ray =
             if cannot_refract || Dielectric::reflectance(cos_theta, ri) > utils::random_double() {
-                vec3::reflect(unit_direction, rec.normal)
+                rec.reflected_ray(r_in, vec3::reflect(unit_direction, rec.normal))
             } else {
-                vec3::refract(unit_direction, rec.normal, ri)
+                rec.refracted_ray(r_in, vec3::refract(unit_direction, rec.normal, ri), ri)
             };
-
-        srec.skip_pdf_ray = Ray::new(rec.p, direction, r_in.time());
         true
     }
 }
+
+impl Material for Dielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        Self::scatter_with_index(r_in, rec, srec, self.refraction_index)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut ScatterRecord,
+        lambda: &mut SampledWavelengths,
+    ) -> bool {
+        if !self.dispersive {
+            return self.scatter(r_in, rec, srec);
+        }
+        // Each wavelength would refract in its own direction, so only the first one follows
+        let refraction_index = self.ior.call(lambda[0]);
+        lambda.terminate_secondary();
+        Self::scatter_with_index(r_in, rec, srec, refraction_index)
+    }
+}
@@ -1,6 +1,6 @@
 // src/materials/cook_torrance.rs
 use super::material::{Material, ScatterRecord};
-use crate::{utils, vec3, Color, CosinePdf, HitRecord, Ray, Vec3};
+use crate::{utils, vec3, Color, CosinePdf, HitRecord, Pdf, Ray, Vec3};
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -61,16 +61,17 @@ impl Glossy {
 
     // Fresnel-Schlick approximation
     fn fresnel_schlick(&self, cos_theta: f32, f0: Color) -> Color {
-        let cos_theta = cos_theta.max(0.0).min(1.0);
+        let cos_theta = cos_theta.clamp(0.0, 1.0);
         f0 + (Color::new(1.0, 1.0, 1.0) - f0) * (1.0 - cos_theta).powf(5.0)
     }
 
     // Sample the GGX distribution to get a half vector
     fn sample_ggx(&self, n: Vec3) -> Vec3 {
-        let r1 = utils::random_double();
-        let r2 = utils::random_double();
+        let (r1, r2) = utils::random_2d();
 
-        let a2 = self.roughness * self.roughness;
+        // Same alpha as `distribution_ggx`, so that `ggx_pdf` is the density of these samples
+        let alpha = self.roughness * self.roughness;
+        let a2 = alpha * alpha;
 
         // Sample theta (polar angle)
         let cos_theta = ((1.0 - r1) / (1.0 + (a2 - 1.0) * r1)).sqrt();
@@ -134,69 +135,79 @@ impl Glossy {
             (d * n_dot_h) / (4.0 * h_dot_v)
         }
     }
-}
 
-impl Material for Glossy {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
-        let v = -vec3::unit_vector(r_in.direction());
-        let n = rec.normal;
-
-        // For path tracing, we need to choose between diffuse and specular sampling
-        // We'll use a simple heuristic based on Fresnel reflectance
+    /// Probability of sampling the specular lobe rather than the diffuse one
+    fn specular_probability(&self, n: Vec3, v: Vec3) -> f32 {
         let f0_avg = (self.f0.x() + self.f0.y() + self.f0.z()) / 3.0;
         let fresnel_avg = self
             .fresnel_schlick(vec3::dot(n, v), Color::new(f0_avg, f0_avg, f0_avg))
             .x();
+        0.25 + 0.75 * fresnel_avg
+    }
+}
 
-        // Probability of sampling specular vs diffuse
-        let spec_prob = 0.25 + 0.75 * fresnel_avg;
-
-        if utils::random_double() < spec_prob {
-            // Sample the specular lobe using GGX distribution
-            let h = self.sample_ggx(n);
-            let l = vec3::reflect(-v, h);
-
-            // Check if the reflected ray is above the surface
-            if vec3::dot(l, n) <= 0.0 {
-                return false;
-            }
+/// Samples a `Glossy` surface with a mixture of GGX half vectors and a cosine lobe
+struct GlossyPdf {
+    material: Glossy,
+    n: Vec3,
+    v: Vec3,
+    spec_prob: f32,
+    diffuse: CosinePdf,
+}
 
-            srec.attenuation =
-                self.evaluate_brdf(n, v, l) * vec3::dot(l, n) / (self.ggx_pdf(n, h, v) * spec_prob);
-            srec.skip_pdf = true;
-            srec.skip_pdf_ray = Ray::new(rec.p, l, r_in.time());
+impl Pdf for GlossyPdf {
+    fn generate(&self) -> Vec3 {
+        if utils::random_double() < self.spec_prob {
+            let h = self.material.sample_ggx(self.n);
+            vec3::reflect(-self.v, h)
         } else {
-            // Sample the diffuse lobe using cosine-weighted hemisphere sampling
-            srec.attenuation = self.evaluate_brdf(n, v, vec3::random_cosine_direction())
-                / ((1.0 - spec_prob) / std::f32::consts::PI);
-            srec.pdf = Arc::new(CosinePdf::new(n));
-            srec.skip_pdf = false;
+            self.diffuse.generate()
         }
+    }
 
-        true
+    fn value(&self, direction: Vec3) -> f32 {
+        let l = vec3::unit_vector(direction);
+        if vec3::dot(self.n, l) <= 0.0 {
+            return 0.0;
+        }
+        let h = vec3::unit_vector(self.v + l);
+        let specular_pdf = self.material.ggx_pdf(self.n, h, self.v);
+        (1.0 - self.spec_prob) * self.diffuse.value(l) + self.spec_prob * specular_pdf
     }
+}
 
-    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+impl Material for Glossy {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
         let v = -vec3::unit_vector(r_in.direction());
-        let l = vec3::unit_vector(scattered.direction());
         let n = rec.normal;
-        let h = vec3::unit_vector(v + l);
 
-        let cos_theta = vec3::dot(n, l);
-        if cos_theta <= 0.0 {
-            return 0.0;
-        }
-
-        // Combined PDF: weighted mixture of diffuse and specular
-        let f0_avg = (self.f0.x() + self.f0.y() + self.f0.z()) / 3.0;
-        let fresnel_avg = self
-            .fresnel_schlick(vec3::dot(n, v), Color::new(f0_avg, f0_avg, f0_avg))
-            .x();
-        let spec_prob = 0.25 + 0.75 * fresnel_avg;
+        srec.attenuation = self.albedo;
+        srec.pdf = Arc::new(GlossyPdf {
+            material: self.clone(),
+            n,
+            v,
+            spec_prob: self.specular_probability(n, v),
+            diffuse: CosinePdf::new(n),
+        });
+        srec.skip_pdf = false;
+        true
+    }
 
-        let diffuse_pdf = cos_theta / std::f32::consts::PI;
-        let specular_pdf = self.ggx_pdf(n, h, v);
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        let v = -vec3::unit_vector(r_in.direction());
+        let l = vec3::unit_vector(scattered.direction());
+        self.evaluate_brdf(rec.normal, v, l) * vec3::dot(rec.normal, l).max(0.0)
+    }
 
-        (1.0 - spec_prob) * diffuse_pdf + spec_prob * specular_pdf
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        let v = -vec3::unit_vector(r_in.direction());
+        GlossyPdf {
+            material: self.clone(),
+            n: rec.normal,
+            v,
+            spec_prob: self.specular_probability(rec.normal, v),
+            diffuse: CosinePdf::new(rec.normal),
+        }
+        .value(scattered.direction())
     }
 }
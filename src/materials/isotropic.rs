@@ -27,7 +27,15 @@ impl Material for Isotropic {
         true
     }
 
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.tex.value(rec.u, rec.v, rec.p) * self.scattering_pdf(r_in, rec, scattered)
+    }
+
     fn scattering_pdf(&self, _: &Ray, _: &HitRecord, _: &Ray) -> f32 {
         1. / (4. * std::f32::consts::PI)
     }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
 }
@@ -21,12 +21,16 @@ impl Lambertian {
 
 impl Material for Lambertian {
     fn scatter(&self, _r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
-        srec.attenuation = self.tex.value(rec.u, rec.v, rec.p);
+        srec.attenuation = self.tex.filtered_value(rec);
         srec.pdf = Arc::new(CosinePdf::new(rec.normal));
         srec.skip_pdf = false;
         true
     }
 
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.tex.filtered_value(rec) * self.scattering_pdf(r_in, rec, scattered)
+    }
+
     fn scattering_pdf(&self, _: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
         let cos_theta = vec3::dot(rec.normal, vec3::unit_vector(scattered.direction()));
         if cos_theta < 0. {
@@ -1,7 +1,6 @@
-use crate::radiometry::{
-    sampling,
-    spectrum::{self, BlackbodySpectrum},
-};
+use super::material::uplift_emission;
+use crate::radiometry::sampling::{SampledSpectrum, SampledWavelengths};
+use crate::radiometry::spectrum::{spectrum_to_xyz, Spectrum};
 use crate::{Color, Material, Point3, Texture};
 use crate::{HitRecord, Ray};
 
@@ -10,20 +9,37 @@ use std::sync::Arc;
 #[derive(Clone)]
 pub struct DiffuseLight {
     emit: Arc<dyn Texture>,
-    emit_spectrum: Arc<dyn spectrum::Spectrum>,
+    /// Emission spectrum for the spectral integrator, whose color is `emit`
+    spectrum: Option<Arc<dyn Spectrum>>,
+    /// Factor on `spectrum`
+    scale: f32,
 }
 impl DiffuseLight {
     pub fn new(emit: Arc<dyn Texture>) -> Self {
         Self {
             emit,
-            emit_spectrum: Arc::new(BlackbodySpectrum::new(1000.)),
+            spectrum: None,
+            scale: 1.,
         }
     }
 
     pub fn from_color(color: Color) -> Self {
+        Self::new(Arc::new(crate::SolidColor::new(color)))
+    }
+
+    /// A light emitting `spectrum`, scaled to the luminance `luminance`
+    ///
+    /// Integrators that work in RGB see the color of the spectrum instead.
+    pub fn from_spectrum(spectrum: Arc<dyn Spectrum>, luminance: f32) -> Self {
+        let xyz = spectrum_to_xyz(spectrum.as_ref());
+        let scale = if xyz.y > 0. { luminance / xyz.y } else { 0. };
+        let color = Color::from(xyz) * scale;
+        // Light outside of the sRGB gamut, like a deep red blackbody, can't be emitted in RGB
+        let color = Color::new(color.x().max(0.), color.y().max(0.), color.z().max(0.));
         Self {
             emit: Arc::new(crate::SolidColor::new(color)),
-            emit_spectrum: Arc::new(BlackbodySpectrum::new(1000.)),
+            spectrum: Some(spectrum),
+            scale,
         }
     }
 }
@@ -39,13 +55,17 @@ impl Material for DiffuseLight {
 
     fn emitted_spectrum(
         &self,
-        _: &Ray,
-        _: &HitRecord,
-        _: f32,
-        _: f32,
-        _: Point3,
-        lambda: &sampling::SampledWavelengths,
-    ) -> sampling::SampledSpectrum {
-        self.emit_spectrum.sample(lambda)
+        r_in: &Ray,
+        rec: &HitRecord,
+        u: f32,
+        v: f32,
+        p: Point3,
+        lambda: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        match &self.spectrum {
+            Some(_) if !rec.front_face => SampledSpectrum::default(),
+            Some(spectrum) => spectrum.sample(lambda) * self.scale,
+            None => uplift_emission(self.emitted(r_in, rec, u, v, p), lambda),
+        }
     }
 }
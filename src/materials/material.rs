@@ -1,4 +1,6 @@
+use crate::radiometry::rgb::RgbIlluminantSpectrum;
 use crate::radiometry::sampling;
+use crate::radiometry::spectrum::Spectrum;
 use crate::{vec3, Color, HitRecord, Ray};
 
 use crate::{Pdf, SpherePdf};
@@ -23,20 +25,68 @@ impl Default for ScatterRecord {
     }
 }
 
+/// What a path carries, which matters for BSDFs that aren't reciprocal
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum TransportMode {
+    /// Radiance, traced from the camera
+    Radiance,
+    /// Importance, traced from the lights
+    Importance,
+}
+
 #[allow(unused_variables)]
 pub trait Material: DynClone + Send + Sync {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
         false
     }
 
+    /// `scatter` for light made of the wavelengths `lambda`
+    ///
+    /// Materials that scatter each wavelength in a different direction follow the first one and
+    /// terminate the others, see `SampledWavelengths::terminate_secondary`.
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut ScatterRecord,
+        lambda: &mut sampling::SampledWavelengths,
+    ) -> bool {
+        self.scatter(r_in, rec, srec)
+    }
+
     fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f32, v: f32, p: vec3::Point3) -> Color {
         Color::new(0., 0., 0.)
     }
 
+    /// Density of `scatter` choosing the direction of `scattered`, as a solid angle pdf
     fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
         0.
     }
 
+    /// The BSDF for light leaving along `scattered`, times the cosine with the normal
+    ///
+    /// Materials that only scatter in a single direction (`skip_pdf`) can't be evaluated for an
+    /// arbitrary direction, and return black.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        Color::new(0., 0., 0.)
+    }
+
+    /// `eval` for importance, as light subpaths and photons carry it, with `r_in` arriving from
+    /// the light and `scattered` leaving towards the camera
+    ///
+    /// Refraction compresses radiance into a smaller solid angle on the denser side but leaves
+    /// importance alone, so materials that refract override this. For the rest it is `eval`.
+    fn eval_importance(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.eval(r_in, rec, scattered)
+    }
+
+    /// Whether the material scatters light inside a volume rather than at a surface, in which
+    /// case `eval` has no cosine term
+    fn is_volumetric(&self) -> bool {
+        false
+    }
+
+    /// `emitted` at the wavelengths `lambda`, by default uplifted from its RGB color
     fn emitted_spectrum(
         &self,
         r_in: &Ray,
@@ -46,8 +96,19 @@ pub trait Material: DynClone + Send + Sync {
         p: vec3::Point3,
         lambda: &sampling::SampledWavelengths,
     ) -> sampling::SampledSpectrum {
-        todo!()
+        uplift_emission(self.emitted(r_in, rec, u, v, p), lambda)
+    }
+}
+
+/// The emitted radiance `emitted` at the wavelengths `lambda`
+pub(crate) fn uplift_emission(
+    emitted: Color,
+    lambda: &sampling::SampledWavelengths,
+) -> sampling::SampledSpectrum {
+    if emitted.x() == 0. && emitted.y() == 0. && emitted.z() == 0. {
+        return sampling::SampledSpectrum::default();
     }
+    RgbIlluminantSpectrum::new(emitted).sample(lambda)
 }
 
 dyn_clone::clone_trait_object!(Material);
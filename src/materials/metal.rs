@@ -1,6 +1,8 @@
 use crate::material::{Material, ScatterRecord};
 
-use crate::{vec3, Color, HitRecord, Ray};
+use crate::{vec3, Color, HitRecord, Pdf, Ray, Vec3};
+
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Metal {
@@ -14,14 +16,71 @@ impl Metal {
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
         }
     }
+
+    fn fuzz_pdf(&self, r_in: &Ray, rec: &HitRecord) -> FuzzPdf {
+        let reflected = vec3::reflect(vec3::unit_vector(r_in.direction()), rec.normal);
+        FuzzPdf {
+            reflected: vec3::unit_vector(reflected),
+            fuzz: self.fuzz,
+        }
+    }
 }
+
+/// Directions of the mirror reflection `reflected` moved by a random offset of length `fuzz`
+///
+/// The offset ends on a sphere of radius `fuzz` around the tip of `reflected`, so directions
+/// spread over the cone that the sphere covers, densest towards its edge.
+struct FuzzPdf {
+    reflected: Vec3,
+    fuzz: f32,
+}
+
+impl Pdf for FuzzPdf {
+    fn generate(&self) -> Vec3 {
+        self.reflected + self.fuzz * vec3::random_unit_vector()
+    }
+
+    fn value(&self, direction: Vec3) -> f32 {
+        // A direction at cosine `c` to `reflected` meets the sphere at the two roots `t` of
+        // t² - 2ct + 1 - fuzz² = 0. The density is the sum over both of the area density
+        // 1 / (4π fuzz²) times t² over the cosine there, sqrt(c² - 1 + fuzz²) / fuzz.
+        let c = vec3::dot(vec3::unit_vector(direction), self.reflected);
+        let a = 1. - self.fuzz * self.fuzz;
+        let discriminant = c * c - a;
+        if c <= 0. || discriminant <= 0. {
+            return 0.;
+        }
+        (4. * c * c - 2. * a) / (4. * std::f32::consts::PI * self.fuzz * discriminant.sqrt())
+    }
+}
+
 impl Material for Metal {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
-        let reflected = crate::vec3::reflect(r_in.direction(), rec.normal);
-        let reflected = vec3::unit_vector(reflected) + (self.fuzz * vec3::random_unit_vector());
         srec.attenuation = self.albedo;
-        srec.skip_pdf = true;
-        srec.skip_pdf_ray = Ray::new(rec.p, reflected, r_in.time());
+        if self.fuzz <= 0. {
+            let reflected = vec3::unit_vector(vec3::reflect(r_in.direction(), rec.normal));
+            srec.skip_pdf = true;
+            srec.skip_pdf_ray = rec.reflected_ray(r_in, reflected);
+        } else {
+            srec.pdf = Arc::new(self.fuzz_pdf(r_in, rec));
+            srec.skip_pdf = false;
+        }
         true
     }
+
+    /// The albedo times the density of the fuzzed reflection, so that sampling it weighs every
+    /// direction above the surface by the albedo. Directions below the surface are absorbed.
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        if vec3::dot(scattered.direction(), rec.normal) <= 0. {
+            return Color::new(0., 0., 0.);
+        }
+        self.albedo * self.scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        if self.fuzz <= 0. {
+            return 0.;
+        }
+        self.fuzz_pdf(r_in, rec).value(scattered.direction())
+    }
 }
@@ -0,0 +1,282 @@
+use crate::utils::{erf, erf_inv, lerp};
+use crate::{vec3, HitRecord, Texture, Vec3, ONB};
+
+use std::f32::consts::PI;
+
+/// Distribution of the normals of the microscopic facets of a rough surface
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MicrofacetDistribution {
+    /// Trowbridge-Reitz, with long tails that give highlights a soft glow
+    #[default]
+    Ggx,
+    /// A Gaussian distribution of slopes, with sharper highlights than GGX
+    Beckmann,
+}
+
+/// A rough surface, made of mirror-like facets whose normals follow `distribution`
+///
+/// All directions are in the local shading frame, where the surface normal is the z axis. The
+/// distribution can be wider along the x axis than along the y axis, or the other way around.
+#[derive(Clone, Copy, Debug)]
+pub struct Microfacet {
+    distribution: MicrofacetDistribution,
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl Microfacet {
+    /// `roughness` is squared to get the width of the distribution, like `Glossy` does, which
+    /// makes it change more evenly
+    pub fn new(distribution: MicrofacetDistribution, roughness: f32) -> Self {
+        Self::anisotropic(distribution, roughness, roughness)
+    }
+
+    /// A distribution with the roughness `roughness_x` along the x axis of the shading frame, the
+    /// tangent, and `roughness_y` along the y axis, each squared like in `new`
+    pub fn anisotropic(
+        distribution: MicrofacetDistribution,
+        roughness_x: f32,
+        roughness_y: f32,
+    ) -> Self {
+        Self {
+            distribution,
+            alpha_x: roughness_x * roughness_x,
+            alpha_y: roughness_y * roughness_y,
+        }
+    }
+
+    /// Whether the surface is so smooth that it should be treated as a perfect mirror, as its
+    /// distribution can't be sampled or evaluated accurately
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    /// Density of facet normals `wm`, per unit of projected area
+    pub fn d(&self, wm: Vec3) -> f32 {
+        let cos2_theta = wm.z() * wm.z();
+        if cos2_theta == 0. {
+            return 0.;
+        }
+        // tan²θ (cos²φ / αx² + sin²φ / αy²)
+        let e = ((wm.x() / self.alpha_x).powi(2) + (wm.y() / self.alpha_y).powi(2)) / cos2_theta;
+        if !e.is_finite() {
+            return 0.;
+        }
+        let norm = PI * self.alpha_x * self.alpha_y * cos2_theta * cos2_theta;
+        match self.distribution {
+            MicrofacetDistribution::Ggx => 1. / (norm * (1. + e) * (1. + e)),
+            MicrofacetDistribution::Beckmann => (-e).exp() / norm,
+        }
+    }
+
+    /// Smith's auxiliary function: the area of facets hidden from `w` per visible area
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2_theta = w.z() * w.z();
+        if cos2_theta == 0. {
+            return 0.;
+        }
+        // α² tan²θ, with the width of the distribution in the azimuth of `w`
+        let alpha2_tan2_theta =
+            ((self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2)) / cos2_theta;
+        if !alpha2_tan2_theta.is_finite() {
+            return 0.;
+        }
+        match self.distribution {
+            MicrofacetDistribution::Ggx => ((1. + alpha2_tan2_theta).sqrt() - 1.) / 2.,
+            MicrofacetDistribution::Beckmann => {
+                let a = 1. / alpha2_tan2_theta.sqrt();
+                if a >= 1.6 {
+                    0.
+                } else {
+                    (1. - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+                }
+            }
+        }
+    }
+
+    /// Fraction of the facets that are visible from `w`
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Fraction of the facets that are visible from both `wo` and `wi`
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of the facet normals seen from `w`, per unit of solid angle around `wm`
+    ///
+    /// Like `sample_wm`, this treats `w` as if it were above the surface.
+    pub fn d_visible(&self, w: Vec3, wm: Vec3) -> f32 {
+        let w = if w.z() < 0. { -w } else { w };
+        self.g1(w) / w.z() * self.d(wm) * vec3::dot(w, wm).max(0.)
+    }
+
+    /// Samples a facet normal seen from `w` with the density `d_visible`, always facing up
+    pub fn sample_wm(&self, w: Vec3, u: (f32, f32)) -> Vec3 {
+        let w = if w.z() < 0. { -w } else { w };
+        match self.distribution {
+            MicrofacetDistribution::Ggx => self.sample_ggx(w, u),
+            MicrofacetDistribution::Beckmann => self.sample_beckmann(w, u),
+        }
+    }
+
+    /// Samples the projection of the stretched hemisphere of facets, after Heitz, "Sampling
+    /// the GGX Distribution of Visible Normals"
+    fn sample_ggx(&self, w: Vec3, u: (f32, f32)) -> Vec3 {
+        let wh = vec3::unit_vector(Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()));
+        let t1 = if wh.z() < 0.99999 {
+            vec3::unit_vector(vec3::cross(Vec3::new(0., 0., 1.), wh))
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = vec3::cross(wh, t1);
+
+        // A point on the unit disk, squashed onto the part of it that projects to visible facets
+        let (r, phi) = (u.0.sqrt(), 2. * PI * u.1);
+        let px = r * phi.cos();
+        let h = (1. - px * px).sqrt();
+        let py = lerp((1. + wh.z()) / 2., h, r * phi.sin());
+        let pz = (1. - px * px - py * py).max(0.).sqrt();
+
+        let nh = px * t1 + py * t2 + pz * wh;
+        vec3::unit_vector(Vec3::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+        ))
+    }
+
+    /// Samples the slopes of the stretched facets, after Jakob's implementation of Heitz and
+    /// d'Eon, "Importance Sampling Microfacet-Based BSDFs using the Distribution of Visible
+    /// Normals"
+    fn sample_beckmann(&self, w: Vec3, u: (f32, f32)) -> Vec3 {
+        let ws = vec3::unit_vector(Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()));
+        let (slope_x, slope_y) = beckmann_sample_slopes(ws.z(), u);
+
+        // Rotate the slopes from the plane of `ws` back to the local frame
+        let sin_theta = (1. - ws.z() * ws.z()).max(0.).sqrt();
+        let (cos_phi, sin_phi) = if sin_theta == 0. {
+            (1., 0.)
+        } else {
+            (
+                (ws.x() / sin_theta).clamp(-1., 1.),
+                (ws.y() / sin_theta).clamp(-1., 1.),
+            )
+        };
+        let (slope_x, slope_y) = (
+            cos_phi * slope_x - sin_phi * slope_y,
+            sin_phi * slope_x + cos_phi * slope_y,
+        );
+
+        vec3::unit_vector(Vec3::new(
+            -self.alpha_x * slope_x,
+            -self.alpha_y * slope_y,
+            1.,
+        ))
+    }
+}
+
+/// The shading frame of a microfacet material at `rec`, with its x axis along the tangent turned
+/// about the normal by `rotation`, in fractions of a full turn
+pub(crate) fn shading_frame(rec: &HitRecord, rotation: Option<&dyn Texture>) -> ONB {
+    let turns = rotation.map_or(0., |texture| {
+        let c = texture.value(rec.u, rec.v, rec.p);
+        (c.x() + c.y() + c.z()) / 3.
+    });
+    rec.tangent_frame(2. * PI * turns)
+}
+
+/// Samples the slopes of visible facets for a Beckmann distribution with unit width, seen from
+/// the direction at `cos_theta` to the normal and with no azimuth
+fn beckmann_sample_slopes(cos_theta: f32, u: (f32, f32)) -> (f32, f32) {
+    if cos_theta > 0.9999 {
+        // Seen from straight above, every facet is visible
+        let r = (-(1. - u.0).ln()).sqrt();
+        let phi = 2. * PI * u.1;
+        return (r * phi.cos(), r * phi.sin());
+    }
+
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let tan_theta = sin_theta / cos_theta;
+    let cot_theta = 1. / tan_theta;
+
+    // Inverts the cumulative distribution of the x slope with Newton's method, safeguarded by
+    // bisection on [a, c]
+    let mut a = -1.;
+    let mut c = erf(cot_theta);
+    let sample_x = u.0.max(1e-6);
+
+    // An approximation of the inverse that is a good starting point
+    let theta = cos_theta.acos();
+    let fit = 1. + theta * (-0.876 + theta * (0.4265 - 0.0594 * theta));
+    let mut b = c - (1. + c) * (1. - sample_x).powf(fit);
+
+    let inv_sqrt_pi = 1. / PI.sqrt();
+    let normalization = 1. / (1. + c + inv_sqrt_pi * tan_theta * (-cot_theta * cot_theta).exp());
+    for _ in 0..10 {
+        if !(a..=c).contains(&b) {
+            b = 0.5 * (a + c);
+        }
+        let inv_erf = erf_inv(b);
+        let value = normalization * (1. + b + inv_sqrt_pi * tan_theta * (-inv_erf * inv_erf).exp())
+            - sample_x;
+        let derivative = normalization * (1. - inv_erf * tan_theta);
+        if value.abs() < 1e-5 {
+            break;
+        }
+        if value > 0. {
+            c = b;
+        } else {
+            a = b;
+        }
+        b -= value / derivative;
+    }
+
+    (erf_inv(b), erf_inv(2. * u.1.max(1e-6) - 1.))
+}
+
+/// Fraction of light reflected by a smooth dielectric interface, at the cosine `cos_theta_i`
+/// between the incident direction and the normal
+///
+/// `eta` is the refractive index of the side the normal points away from, over the one it
+/// points into.
+pub fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let (cos_theta_i, eta) = if cos_theta_i < 0. {
+        (-cos_theta_i.max(-1.), 1. / eta)
+    } else {
+        (cos_theta_i.min(1.), eta)
+    };
+
+    let sin2_theta_t = (1. - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1. {
+        // Total internal reflection
+        return 1.;
+    }
+    let cos_theta_t = (1. - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.
+}
+
+/// Fraction of light reflected by a conductor with the complex refractive index `eta + i k`
+pub fn fresnel_conductor(cos_theta_i: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cos_theta_i.clamp(0., 1.).powi(2);
+    let sin2 = 1. - cos2;
+    let (eta2, k2) = (eta * eta, k * k);
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4. * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+    let t2 = 2. * cos_theta_i.clamp(0., 1.) * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    (r_p + r_s) / 2.
+}
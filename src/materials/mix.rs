@@ -0,0 +1,104 @@
+use super::material::{Material, ScatterRecord};
+use crate::radiometry::sampling;
+use crate::{vec3, Color, HitRecord, Ray, Texture};
+
+use std::sync::Arc;
+
+/// Either of two materials, picked at each point with the probability `amount` of the second
+///
+/// The choice is a hash of the hit point rather than a random number, so that every query about
+/// the same point, from any integrator, sees the same material. Over the many points a pixel
+/// covers this averages to a blend of the two, and with a texture it can follow a mask.
+#[derive(Clone)]
+pub struct MixMaterial {
+    materials: [Arc<dyn Material>; 2],
+    amount: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(
+        first: Arc<dyn Material>,
+        second: Arc<dyn Material>,
+        amount: Arc<dyn Texture>,
+    ) -> Self {
+        Self {
+            materials: [first, second],
+            amount,
+        }
+    }
+
+    fn choose(&self, rec: &HitRecord) -> &dyn Material {
+        let c = self.amount.filtered_value(rec);
+        let amount = (c.x() + c.y() + c.z()) / 3.;
+        if hash_point(rec.p) < amount {
+            self.materials[1].as_ref()
+        } else {
+            self.materials[0].as_ref()
+        }
+    }
+}
+
+/// Hashes a point to a number in [0, 1)
+fn hash_point(p: vec3::Point3) -> f32 {
+    // FNV-1a over the coordinates, then the finalizer of MurmurHash3 to spread the bits
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for c in [p.x(), p.y(), p.z()] {
+        h = (h ^ c.to_bits() as u64).wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        self.choose(rec).scatter(r_in, rec, srec)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        srec: &mut ScatterRecord,
+        lambda: &mut sampling::SampledWavelengths,
+    ) -> bool {
+        self.choose(rec).scatter_spectral(r_in, rec, srec, lambda)
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord, u: f32, v: f32, p: vec3::Point3) -> Color {
+        self.choose(rec).emitted(r_in, rec, u, v, p)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        self.choose(rec).scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.choose(rec).eval(r_in, rec, scattered)
+    }
+
+    fn eval_importance(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.choose(rec).eval_importance(r_in, rec, scattered)
+    }
+
+    /// Mixing a volume with a surface doesn't make sense, so this is only a volume if both are
+    fn is_volumetric(&self) -> bool {
+        self.materials.iter().all(|m| m.is_volumetric())
+    }
+
+    fn emitted_spectrum(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        u: f32,
+        v: f32,
+        p: vec3::Point3,
+        lambda: &sampling::SampledWavelengths,
+    ) -> sampling::SampledSpectrum {
+        self.choose(rec)
+            .emitted_spectrum(r_in, rec, u, v, p, lambda)
+    }
+}
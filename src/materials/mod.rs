@@ -1,40 +1,431 @@
+pub(crate) mod coated;
+pub(crate) mod conductor;
 pub(crate) mod dialectric;
+pub(crate) mod glossy;
 pub(crate) mod isotropic;
 pub(crate) mod lambertian;
 pub(crate) mod lights;
 pub(crate) mod material;
 pub(crate) mod metal;
-pub(crate) mod glossy;
+pub(crate) mod microfacet;
+pub(crate) mod mix;
+pub(crate) mod principled;
+pub(crate) mod rough_dielectric;
 
+pub use coated::CoatedMaterial;
+pub use conductor::{named_conductor, Conductor, NAMED_CONDUCTORS};
 pub use dialectric::Dielectric;
+pub use glossy::Glossy;
 pub use isotropic::Isotropic;
 pub use lambertian::Lambertian;
 pub use lights::DiffuseLight;
 pub use material::{Material, NoneMaterial, ScatterRecord};
 pub use metal::Metal;
-pub use glossy::Glossy;
+pub use microfacet::{Microfacet, MicrofacetDistribution};
+pub use mix::MixMaterial;
+pub use principled::{Principled, PrincipledParameters};
+pub use rough_dielectric::RoughDielectric;
 
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+use crate::radiometry::illuminant;
+use crate::radiometry::ior::{self, CauchySpectrum, SellmeierSpectrum};
+use crate::radiometry::spectrum::{BlackbodySpectrum, PiecewiseLinearSpectrum, Spectrum};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum MaterialConfig {
     None,
-    Lambertian { color: crate::Color },
-    Metal { color: crate::Color, fuzz: f32 },
-    Dieletric { refraction_index: f32 },
-    DiffuseLight { color: crate::Color },
+    Lambertian {
+        #[serde(alias = "color")]
+        texture: crate::TextureRef,
+    },
+    Metal {
+        color: crate::Color,
+        fuzz: f32,
+    },
+    #[serde(alias = "dieletric")]
+    Dielectric {
+        refraction_index: IorConfig,
+    },
+    /// Emits `texture`, or one of the spectra `temperature`, `illuminant` or `spectrum` scaled to
+    /// the luminance `luminance`. Integrators that work in RGB use the color of the spectrum.
+    ///
+    /// A texture already gives the radiance itself, so it can't be given a `luminance` as well.
+    DiffuseLight {
+        #[serde(alias = "color")]
+        texture: Option<crate::TextureRef>,
+        /// Blackbody temperature in Kelvin
+        temperature: Option<f32>,
+        /// One of `radiometry::illuminant::NAMED_ILLUMINANTS`
+        illuminant: Option<String>,
+        /// CSV file of wavelengths and values, see `PiecewiseLinearSpectrum::parse_csv`
+        spectrum: Option<String>,
+        /// Luminance of the spectrum, 1 by default
+        luminance: Option<f32>,
+    },
+    Glossy {
+        color: crate::Color,
+        roughness: f32,
+        #[serde(default)]
+        metallic: f32,
+    },
+    Isotropic {
+        #[serde(alias = "color")]
+        texture: crate::TextureRef,
+    },
+    /// A rough metal, either one of `NAMED_CONDUCTORS` or given by its complex refractive index
+    /// `eta + i k`
+    Conductor {
+        metal: Option<String>,
+        eta: Option<crate::Color>,
+        k: Option<crate::Color>,
+        #[serde(default)]
+        roughness: RoughnessConfig,
+        #[serde(default)]
+        distribution: MicrofacetDistribution,
+        /// Turns the tangent of an anisotropic `roughness`, in fractions of a full turn
+        rotation: Option<crate::TextureRef>,
+    },
+    RoughDielectric {
+        refraction_index: f32,
+        roughness: RoughnessConfig,
+        #[serde(default)]
+        distribution: MicrofacetDistribution,
+        /// Turns the tangent of an anisotropic `roughness`, in fractions of a full turn
+        rotation: Option<crate::TextureRef>,
+    },
+    Principled(Box<PrincipledConfig>),
+    /// Picks the second of `materials` with the probability `amount` at each point, and the
+    /// first otherwise
+    Mix {
+        materials: Box<[crate::MaterialRef; 2]>,
+        #[serde(default = "MaterialConfig::default_amount")]
+        amount: crate::TextureRef,
+    },
+    /// A clear coating with the refractive index `refraction_index` over `base`
+    Coated {
+        base: Box<crate::MaterialRef>,
+        #[serde(default = "MaterialConfig::default_coating_index")]
+        refraction_index: f32,
+        #[serde(default)]
+        roughness: f32,
+        #[serde(default)]
+        distribution: MicrofacetDistribution,
+        /// Fraction of light that crosses the coating straight through
+        #[serde(default = "MaterialConfig::default_tint")]
+        tint: crate::Color,
+    },
 }
 
-use std::sync::Arc;
-impl From<MaterialConfig> for Arc<dyn Material> {
-    fn from(value: MaterialConfig) -> Self {
-        match value {
+/// Roughness of a microfacet material: one number, or a pair for the roughness along the tangent
+/// and along the bitangent of the surface
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+#[serde(untagged)]
+pub enum RoughnessConfig {
+    Isotropic(f32),
+    Anisotropic([f32; 2]),
+}
+
+impl Default for RoughnessConfig {
+    fn default() -> Self {
+        RoughnessConfig::Isotropic(0.)
+    }
+}
+
+impl RoughnessConfig {
+    pub fn build(&self, distribution: MicrofacetDistribution) -> Microfacet {
+        match *self {
+            RoughnessConfig::Isotropic(roughness) => Microfacet::new(distribution, roughness),
+            RoughnessConfig::Anisotropic([x, y]) => Microfacet::anisotropic(distribution, x, y),
+        }
+    }
+}
+
+/// Parameters of a `Principled` material, each a number, a color or a texture. Any that are
+/// left out take the values of a grey plastic.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct PrincipledConfig {
+    #[serde(alias = "color")]
+    pub base_color: crate::TextureRef,
+    pub metallic: crate::TextureRef,
+    pub roughness: crate::TextureRef,
+    pub specular: crate::TextureRef,
+    pub specular_tint: crate::TextureRef,
+    pub sheen: crate::TextureRef,
+    pub sheen_tint: crate::TextureRef,
+    pub clearcoat: crate::TextureRef,
+    pub clearcoat_gloss: crate::TextureRef,
+    pub transmission: crate::TextureRef,
+    pub subsurface: crate::TextureRef,
+    pub anisotropic: crate::TextureRef,
+    pub anisotropic_rotation: crate::TextureRef,
+    #[serde(alias = "refraction_index")]
+    pub ior: crate::TextureRef,
+    pub distribution: MicrofacetDistribution,
+}
+
+impl Default for PrincipledConfig {
+    /// The defaults of `PrincipledParameters`
+    fn default() -> Self {
+        use crate::TextureRef::Value;
+        Self {
+            base_color: Value(0.8),
+            metallic: Value(0.),
+            roughness: Value(0.5),
+            specular: Value(0.5),
+            specular_tint: Value(0.),
+            sheen: Value(0.),
+            sheen_tint: Value(0.5),
+            clearcoat: Value(0.),
+            clearcoat_gloss: Value(1.),
+            transmission: Value(0.),
+            subsurface: Value(0.),
+            anisotropic: Value(0.),
+            anisotropic_rotation: Value(0.),
+            ior: Value(1.5),
+            distribution: MicrofacetDistribution::default(),
+        }
+    }
+}
+
+impl PrincipledConfig {
+    pub fn build(
+        &self,
+        textures: &HashMap<String, Arc<dyn crate::Texture>>,
+    ) -> Result<Principled, String> {
+        Ok(Principled::new(PrincipledParameters {
+            base_color: self.base_color.resolve(textures)?,
+            metallic: self.metallic.resolve(textures)?,
+            roughness: self.roughness.resolve(textures)?,
+            specular: self.specular.resolve(textures)?,
+            specular_tint: self.specular_tint.resolve(textures)?,
+            sheen: self.sheen.resolve(textures)?,
+            sheen_tint: self.sheen_tint.resolve(textures)?,
+            clearcoat: self.clearcoat.resolve(textures)?,
+            clearcoat_gloss: self.clearcoat_gloss.resolve(textures)?,
+            transmission: self.transmission.resolve(textures)?,
+            subsurface: self.subsurface.resolve(textures)?,
+            anisotropic: self.anisotropic.resolve(textures)?,
+            anisotropic_rotation: self.anisotropic_rotation.resolve(textures)?,
+            ior: self.ior.resolve(textures)?,
+            distribution: self.distribution,
+        }))
+    }
+}
+
+/// A refractive index: a constant, a dispersion model, or the name of a common material
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IorConfig {
+    Constant(f32),
+
+    Model(IorModel),
+
+    /// One of `radiometry::ior::NAMED_IORS`
+    Named(String),
+}
+
+/// Refractive index that varies with the wavelength λ, in micrometers
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IorModel {
+    /// n = a + b / λ² + c / λ⁴
+    Cauchy {
+        a: f32,
+        b: f32,
+        #[serde(default)]
+        c: f32,
+    },
+    /// n² = 1 + Σ b_i λ² / (λ² - c_i)
+    Sellmeier { b: Vec<f32>, c: Vec<f32> },
+}
+
+impl IorConfig {
+    pub fn build(&self) -> Result<Dielectric, String> {
+        let ior: Arc<dyn Spectrum> = match self {
+            IorConfig::Constant(refraction_index) => return Ok(Dielectric::new(*refraction_index)),
+            IorConfig::Model(IorModel::Cauchy { a, b, c }) => {
+                Arc::new(CauchySpectrum::new(*a, *b, *c))
+            }
+            IorConfig::Model(IorModel::Sellmeier { b, c }) => {
+                Arc::new(SellmeierSpectrum::new(b.clone(), c.clone())?)
+            }
+            IorConfig::Named(name) => ior::named_ior(name).ok_or_else(|| {
+                format!(
+                    "unknown refractive index '{name}', expected a number, a model or one of {}",
+                    ior::NAMED_IORS.join(", ")
+                )
+            })?,
+        };
+        Ok(Dielectric::with_ior(ior))
+    }
+}
+
+impl MaterialConfig {
+    fn default_amount() -> crate::TextureRef {
+        crate::TextureRef::Value(0.5)
+    }
+
+    fn default_coating_index() -> f32 {
+        1.5
+    }
+
+    fn default_tint() -> crate::Color {
+        crate::Color::new(1., 1., 1.)
+    }
+
+    /// Builds the material, looking up texture references in the scene's named `textures`
+    pub fn build(
+        &self,
+        textures: &HashMap<String, Arc<dyn crate::Texture>>,
+    ) -> Result<Arc<dyn Material>, String> {
+        self.build_with_materials(&HashMap::new(), textures)
+    }
+
+    /// `build`, also looking up the materials that mixes and coatings refer to by name in the
+    /// scene's named `materials`
+    pub fn build_with_materials(
+        &self,
+        materials: &HashMap<String, MaterialConfig>,
+        textures: &HashMap<String, Arc<dyn crate::Texture>>,
+    ) -> Result<Arc<dyn Material>, String> {
+        self.build_nested(materials, textures, &mut Vec::new())
+    }
+
+    pub(crate) fn build_nested(
+        &self,
+        materials: &HashMap<String, MaterialConfig>,
+        textures: &HashMap<String, Arc<dyn crate::Texture>>,
+        stack: &mut Vec<String>,
+    ) -> Result<Arc<dyn Material>, String> {
+        Ok(match self {
             MaterialConfig::None => Arc::new(NoneMaterial {}),
-            MaterialConfig::Lambertian { color } => Arc::new(Lambertian::new(color)),
-            MaterialConfig::Metal { color, fuzz } => Arc::new(Metal::new(color, fuzz)),
-            MaterialConfig::Dieletric { refraction_index } => {
-                Arc::new(Dielectric::new(refraction_index))
+            MaterialConfig::Lambertian { texture } => {
+                Arc::new(Lambertian::with_texture(texture.resolve(textures)?))
             }
-            MaterialConfig::DiffuseLight { color } => Arc::new(DiffuseLight::from_color(color)),
-        }
+            MaterialConfig::Metal { color, fuzz } => Arc::new(Metal::new(*color, *fuzz)),
+            MaterialConfig::Dielectric { refraction_index } => Arc::new(refraction_index.build()?),
+            MaterialConfig::DiffuseLight {
+                texture,
+                temperature,
+                illuminant,
+                spectrum,
+                luminance,
+            } => {
+                let spectrum: Arc<dyn Spectrum> = match (texture, temperature, illuminant, spectrum)
+                {
+                    (Some(_), None, None, None) if luminance.is_some() => {
+                        return Err(
+                            "a diffuse light's luminance only applies to a temperature, \
+                                    illuminant or spectrum, so scale its color instead"
+                                .to_string(),
+                        )
+                    }
+                    (Some(texture), None, None, None) => {
+                        return Ok(Arc::new(DiffuseLight::new(texture.resolve(textures)?)));
+                    }
+                    (None, Some(temperature), None, None) => {
+                        Arc::new(BlackbodySpectrum::new(*temperature))
+                    }
+                    (None, None, Some(name), None) => illuminant::named_illuminant(name)
+                        .ok_or_else(|| {
+                            format!(
+                                "unknown illuminant '{name}', expected one of {}",
+                                illuminant::NAMED_ILLUMINANTS.join(", ")
+                            )
+                        })?,
+                    (None, None, None, Some(path)) => {
+                        Arc::new(PiecewiseLinearSpectrum::load_csv(path)?)
+                    }
+                    _ => {
+                        return Err("a diffuse light needs exactly one of color, temperature, \
+                                    illuminant or spectrum"
+                            .to_string())
+                    }
+                };
+                Arc::new(DiffuseLight::from_spectrum(
+                    spectrum,
+                    luminance.unwrap_or(1.),
+                ))
+            }
+            MaterialConfig::Glossy {
+                color,
+                roughness,
+                metallic,
+            } => Arc::new(Glossy::new(*color, *roughness, *metallic)),
+            MaterialConfig::Isotropic { texture } => {
+                Arc::new(Isotropic::new(texture.resolve(textures)?))
+            }
+            MaterialConfig::Conductor {
+                metal,
+                eta,
+                k,
+                roughness,
+                distribution,
+                rotation,
+            } => {
+                let (eta, k) = match (metal, eta, k) {
+                    (Some(name), None, None) => named_conductor(name).ok_or_else(|| {
+                        format!(
+                            "unknown metal '{name}', expected one of {}",
+                            NAMED_CONDUCTORS.join(", ")
+                        )
+                    })?,
+                    (None, Some(eta), Some(k)) => (*eta, *k),
+                    _ => {
+                        return Err("a conductor needs either a metal or both eta and k".to_string())
+                    }
+                };
+                let conductor = Conductor::new(eta, k, roughness.build(*distribution));
+                match rotation {
+                    Some(rotation) => {
+                        Arc::new(conductor.with_rotation(rotation.resolve(textures)?))
+                    }
+                    None => Arc::new(conductor),
+                }
+            }
+            MaterialConfig::RoughDielectric {
+                refraction_index,
+                roughness,
+                distribution,
+                rotation,
+            } => {
+                let dielectric =
+                    RoughDielectric::new(*refraction_index, roughness.build(*distribution));
+                match rotation {
+                    Some(rotation) => {
+                        Arc::new(dielectric.with_rotation(rotation.resolve(textures)?))
+                    }
+                    None => Arc::new(dielectric),
+                }
+            }
+            MaterialConfig::Principled(config) => Arc::new(config.build(textures)?),
+            MaterialConfig::Mix {
+                materials: pair,
+                amount,
+            } => {
+                let [first, second] = pair.as_ref();
+                Arc::new(MixMaterial::new(
+                    first.build_nested(materials, textures, stack)?,
+                    second.build_nested(materials, textures, stack)?,
+                    amount.resolve(textures)?,
+                ))
+            }
+            MaterialConfig::Coated {
+                base,
+                refraction_index,
+                roughness,
+                distribution,
+                tint,
+            } => Arc::new(CoatedMaterial::new(
+                base.build_nested(materials, textures, stack)?,
+                *refraction_index,
+                Microfacet::new(*distribution, *roughness),
+                *tint,
+            )),
+        })
     }
 }
@@ -0,0 +1,384 @@
+use super::material::{Material, ScatterRecord, TransportMode};
+use super::microfacet::{shading_frame, Microfacet, MicrofacetDistribution};
+use super::rough_dielectric::RoughDielectric;
+use crate::utils::{self, lerp};
+use crate::{vec3, Color, HitRecord, Pdf, Ray, SolidColor, Texture, Vec3, ONB};
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Parameters of a `Principled` material, each a texture so that it can vary over the surface
+///
+/// Scalar parameters are read from the average of the texture's channels, and go from 0 to 1
+/// unless stated otherwise.
+#[derive(Clone)]
+pub struct PrincipledParameters {
+    pub base_color: Arc<dyn Texture>,
+    /// Blends from a dielectric to a metal that reflects `base_color`
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    /// Reflectance of the dielectric at normal incidence, where 0.5 is the 4% of most plastics
+    /// and glasses
+    pub specular: Arc<dyn Texture>,
+    /// Tints the reflections of the dielectric towards the hue of `base_color`
+    pub specular_tint: Arc<dyn Texture>,
+    /// A soft glow at grazing angles, like the fibres of cloth give
+    pub sheen: Arc<dyn Texture>,
+    /// Tints the sheen towards the hue of `base_color`
+    pub sheen_tint: Arc<dyn Texture>,
+    /// A clear layer of varnish over the surface, with a refractive index of 1.5
+    pub clearcoat: Arc<dyn Texture>,
+    /// From a satin clearcoat at 0 to a glossy one at 1
+    pub clearcoat_gloss: Arc<dyn Texture>,
+    /// Blends the dielectric from opaque to a rough glass tinted by `base_color`
+    pub transmission: Arc<dyn Texture>,
+    /// Flattens the diffuse lobe, approximating light that scatters under the surface
+    pub subsurface: Arc<dyn Texture>,
+    /// Stretches highlights along the tangent of the surface
+    pub anisotropic: Arc<dyn Texture>,
+    /// Turns the direction highlights are stretched in about the normal, by a fraction of a
+    /// full turn
+    pub anisotropic_rotation: Arc<dyn Texture>,
+    /// Refractive index of the transmitting lobe, usually above 1
+    pub ior: Arc<dyn Texture>,
+    pub distribution: MicrofacetDistribution,
+}
+
+impl Default for PrincipledParameters {
+    /// A grey plastic
+    fn default() -> Self {
+        let constant = |value: f32| -> Arc<dyn Texture> {
+            Arc::new(SolidColor::new(Color::new(value, value, value)))
+        };
+        Self {
+            base_color: constant(0.8),
+            metallic: constant(0.),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.),
+            sheen: constant(0.),
+            sheen_tint: constant(0.5),
+            clearcoat: constant(0.),
+            clearcoat_gloss: constant(1.),
+            transmission: constant(0.),
+            subsurface: constant(0.),
+            anisotropic: constant(0.),
+            anisotropic_rotation: constant(0.),
+            ior: constant(1.5),
+            distribution: MicrofacetDistribution::default(),
+        }
+    }
+}
+
+/// An artist-friendly material that covers most real ones, after Burley, "Physically Based
+/// Shading at Disney", with the transmission of the 2015 extension
+///
+/// It layers a diffuse lobe with sheen and a subsurface approximation, a Cook-Torrance
+/// reflection like `Glossy`'s, a rough glass like `RoughDielectric` and a clearcoat. Anisotropic
+/// highlights are stretched along the tangent `dpdu` of the hit record.
+#[derive(Clone)]
+pub struct Principled {
+    parameters: PrincipledParameters,
+}
+
+impl Principled {
+    pub fn new(parameters: PrincipledParameters) -> Self {
+        Self { parameters }
+    }
+
+    fn frame(&self, rec: &HitRecord) -> ONB {
+        shading_frame(rec, Some(self.parameters.anisotropic_rotation.as_ref()))
+    }
+
+    /// Evaluates every parameter at the hit point
+    fn bsdf(&self, rec: &HitRecord) -> PrincipledBsdf {
+        let color = |texture: &Arc<dyn Texture>| texture.filtered_value(rec);
+        let scalar = |texture: &Arc<dyn Texture>| {
+            let c = color(texture);
+            (c.x() + c.y() + c.z()) / 3.
+        };
+        let p = &self.parameters;
+
+        let base_color = color(&p.base_color).clamp(0., 1.);
+        let metallic = scalar(&p.metallic).clamp(0., 1.);
+        // Every lobe is sampled as a rough one, so keep them away from perfect mirrors
+        let roughness = scalar(&p.roughness).clamp(0.05, 1.);
+        let transmission = scalar(&p.transmission).clamp(0., 1.);
+
+        let white = Color::new(1., 1., 1.);
+        let base_luminance = luminance(base_color);
+        let tint = if base_luminance > 0. {
+            base_color / base_luminance
+        } else {
+            white
+        };
+        let dielectric_specular = scalar(&p.specular).max(0.)
+            * 0.08
+            * mix(scalar(&p.specular_tint).clamp(0., 1.), white, tint);
+
+        // The mapping of the Disney BRDF, from no stretch at 0 to a ratio of 10 between the
+        // widths of the distribution at 1
+        let aspect = (1. - 0.9 * scalar(&p.anisotropic).clamp(0., 1.)).sqrt();
+        let microfacet = Microfacet::anisotropic(
+            p.distribution,
+            roughness / aspect.sqrt(),
+            roughness * aspect.sqrt(),
+        );
+        let dielectric = RoughDielectric::new(scalar(&p.ior).max(1e-3), microfacet);
+        PrincipledBsdf {
+            base_color,
+            specular_color: mix(metallic, dielectric_specular, base_color),
+            sheen_color: mix(scalar(&p.sheen_tint).clamp(0., 1.), white, tint)
+                * scalar(&p.sheen).max(0.),
+            subsurface: scalar(&p.subsurface).clamp(0., 1.),
+            roughness,
+            diffuse_weight: (1. - metallic) * (1. - transmission),
+            transmission_weight: (1. - metallic) * transmission,
+            clearcoat: scalar(&p.clearcoat).max(0.),
+            clearcoat_alpha: lerp(scalar(&p.clearcoat_gloss).clamp(0., 1.), 0.1, 0.001),
+            eta: dielectric.eta(rec),
+            microfacet,
+            dielectric,
+        }
+    }
+
+    fn eval_mode(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered: &Ray,
+        mode: TransportMode,
+    ) -> Color {
+        let frame = self.frame(rec);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(vec3::unit_vector(scattered.direction()));
+        self.bsdf(rec).f(wo, wi, mode)
+    }
+}
+
+fn luminance(c: Color) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+fn mix(x: f32, a: Color, b: Color) -> Color {
+    a * (1. - x) + b * x
+}
+
+/// Schlick's approximation of the Fresnel factor, without the reflectance at normal incidence
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1. - cos_theta.clamp(0., 1.)).powi(5)
+}
+
+/// The "generalized Trowbridge-Reitz" distribution with exponent 1 of the clearcoat, whose long
+/// tails give it a haze
+fn gtr1(cos_theta: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    (a2 - 1.) / (PI * a2.ln() * (1. + (a2 - 1.) * cos_theta * cos_theta))
+}
+
+/// The parameters of a `Principled` material at one point, and its lobes between local directions
+#[derive(Clone)]
+struct PrincipledBsdf {
+    base_color: Color,
+    specular_color: Color,
+    sheen_color: Color,
+    subsurface: f32,
+    roughness: f32,
+    diffuse_weight: f32,
+    transmission_weight: f32,
+    clearcoat: f32,
+    clearcoat_alpha: f32,
+    eta: f32,
+    microfacet: Microfacet,
+    dielectric: RoughDielectric,
+}
+
+impl PrincipledBsdf {
+    /// The clearcoat's shadowing is that of a fixed GGX distribution
+    fn clearcoat_microfacet() -> Microfacet {
+        Microfacet::new(MicrofacetDistribution::Ggx, 0.5)
+    }
+
+    /// The BSDF times the cosine, for what `mode` carries
+    fn f(&self, wo: Vec3, wi: Vec3, mode: TransportMode) -> Color {
+        let (wo, wi, eta) = RoughDielectric::orient(wo, wi, self.eta);
+        let mut f = Color::new(0., 0., 0.);
+        if wo.z() == 0. || wi.z() == 0. {
+            return f;
+        }
+
+        if self.transmission_weight > 0. {
+            // Light crosses two interfaces to get through an object, so tint each by the root
+            let tint = if wi.z() < 0. {
+                let c = self.base_color;
+                Color::new(c.x().sqrt(), c.y().sqrt(), c.z().sqrt())
+            } else {
+                Color::new(1., 1., 1.)
+            };
+            f += tint * (self.transmission_weight * self.dielectric.f(wo, wi, eta, mode));
+        }
+        if wi.z() < 0. {
+            return f;
+        }
+
+        let wm = vec3::unit_vector(wo + wi);
+        let cos_theta_d = vec3::dot(wi, wm);
+        let (fl, fv, fh) = (
+            schlick_weight(wi.z()),
+            schlick_weight(wo.z()),
+            schlick_weight(cos_theta_d),
+        );
+
+        if self.diffuse_weight > 0. {
+            // Retro-reflection at grazing angles, which grows with roughness
+            let fd90 = 0.5 + 2. * self.roughness * cos_theta_d * cos_theta_d;
+            let fd = lerp(fl, 1., fd90) * lerp(fv, 1., fd90);
+            // Hanrahan-Krueger-like flattening for light scattered under the surface
+            let fss90 = self.roughness * cos_theta_d * cos_theta_d;
+            let fss = lerp(fl, 1., fss90) * lerp(fv, 1., fss90);
+            let ss = 1.25 * (fss * (1. / (wi.z() + wo.z()) - 0.5) + 0.5);
+
+            let diffuse =
+                self.base_color * (lerp(self.subsurface, fd, ss) / PI) + self.sheen_color * fh;
+            f += diffuse * (self.diffuse_weight * wi.z());
+        }
+
+        let fresnel = mix(fh, self.specular_color, Color::new(1., 1., 1.));
+        let dg = self.microfacet.d(wm) * self.microfacet.g(wo, wi);
+        f += fresnel * ((1. - self.transmission_weight) * dg / (4. * wo.z()));
+
+        if self.clearcoat > 0. {
+            let coat = Self::clearcoat_microfacet();
+            let d = gtr1(wm.z(), self.clearcoat_alpha);
+            let g = coat.g1(wo) * coat.g1(wi);
+            f += Color::new(1., 1., 1.)
+                * (0.25 * self.clearcoat * d * lerp(fh, 0.04, 1.) * g / (4. * wo.z()));
+        }
+        f
+    }
+
+    /// Probabilities of sampling the diffuse, specular, transmitting and clearcoat lobes, roughly
+    /// in proportion to how much light they scatter from `wo`
+    fn lobe_probabilities(&self, wo: Vec3) -> [f32; 4] {
+        let fresnel = schlick_weight(wo.z());
+        let specular = mix(fresnel, self.specular_color, Color::new(1., 1., 1.));
+        let weights = [
+            self.diffuse_weight * (luminance(self.base_color) + luminance(self.sheen_color)),
+            (1. - self.transmission_weight) * luminance(specular),
+            self.transmission_weight,
+            0.25 * self.clearcoat * lerp(fresnel, 0.04, 1.),
+        ];
+        let total: f32 = weights.iter().sum();
+        if total <= 0. {
+            return [1., 0., 0., 0.];
+        }
+        weights.map(|w| w / total)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3) -> f32 {
+        let (wo, wi, eta) = RoughDielectric::orient(wo, wi, self.eta);
+        if wo.z() == 0. || wi.z() == 0. {
+            return 0.;
+        }
+        let [diffuse, specular, transmission, clearcoat] = self.lobe_probabilities(wo);
+        let mut pdf = 0.;
+        if transmission > 0. {
+            pdf += transmission * self.dielectric.pdf(wo, wi, eta);
+        }
+        if wi.z() > 0. {
+            let wm = vec3::unit_vector(wo + wi);
+            let jacobian = 1. / (4. * vec3::dot(wo, wm));
+            pdf += diffuse * wi.z() / PI;
+            pdf += specular * self.microfacet.d_visible(wo, wm) * jacobian;
+            pdf += clearcoat * gtr1(wm.z(), self.clearcoat_alpha) * wm.z() * jacobian;
+        }
+        pdf
+    }
+
+    /// Samples one of the lobes, or returns `None` if it sends `wo` to the wrong side
+    fn sample(&self, wo: Vec3) -> Option<Vec3> {
+        let flip = wo.z() < 0.;
+        let (wo, _, eta) = RoughDielectric::orient(wo, wo, self.eta);
+        let [diffuse, specular, transmission, _] = self.lobe_probabilities(wo);
+
+        let u = utils::random_double();
+        if (diffuse + specular..diffuse + specular + transmission).contains(&u) {
+            let wi = self.dielectric.sample(wo, eta)?;
+            return Some(if flip { -wi } else { wi });
+        }
+        let wi = if u < diffuse {
+            vec3::random_cosine_direction()
+        } else if u < diffuse + specular {
+            let wm = self.microfacet.sample_wm(wo, utils::random_2d());
+            vec3::reflect(-wo, wm)
+        } else {
+            let (u1, u2) = utils::random_2d();
+            let a2 = self.clearcoat_alpha * self.clearcoat_alpha;
+            let cos_theta = ((1. - a2.powf(1. - u1)) / (1. - a2)).max(0.).sqrt();
+            let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+            let phi = 2. * PI * u2;
+            let wm = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+            vec3::reflect(-wo, wm)
+        };
+
+        if wi.z() <= 0. {
+            return None;
+        }
+        Some(if flip { -wi } else { wi })
+    }
+}
+
+/// Samples the lobes of a `Principled` material as a mixture
+struct PrincipledPdf {
+    bsdf: PrincipledBsdf,
+    frame: ONB,
+    wo: Vec3,
+}
+
+impl Pdf for PrincipledPdf {
+    fn generate(&self) -> Vec3 {
+        match self.bsdf.sample(self.wo) {
+            Some(wi) => self.frame.transform(wi),
+            // Which `value` gives no density
+            None => Vec3::default(),
+        }
+    }
+
+    fn value(&self, direction: Vec3) -> f32 {
+        if direction.length_squared() == 0. {
+            return 0.;
+        }
+        let wi = self.frame.to_local(vec3::unit_vector(direction));
+        self.bsdf.pdf(self.wo, wi)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let bsdf = self.bsdf(rec);
+        let frame = self.frame(rec);
+        srec.attenuation = bsdf.base_color;
+        srec.pdf = Arc::new(PrincipledPdf {
+            bsdf,
+            wo: frame.to_local(-vec3::unit_vector(r_in.direction())),
+            frame,
+        });
+        srec.skip_pdf = false;
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.eval_mode(r_in, rec, scattered, TransportMode::Radiance)
+    }
+
+    fn eval_importance(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.eval_mode(r_in, rec, scattered, TransportMode::Importance)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        let frame = self.frame(rec);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(vec3::unit_vector(scattered.direction()));
+        self.bsdf(rec).pdf(wo, wi)
+    }
+}
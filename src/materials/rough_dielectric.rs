@@ -0,0 +1,228 @@
+use super::material::{Material, ScatterRecord, TransportMode};
+use super::microfacet::{fresnel_dielectric, shading_frame, Microfacet};
+use crate::{utils, vec3, Color, HitRecord, Pdf, Ray, Texture, Vec3, ONB};
+
+use std::sync::Arc;
+
+/// Frosted glass: a dielectric whose microfacets both reflect and refract light, after Walter et
+/// al., "Microfacet Models for Refraction through Rough Surfaces"
+///
+/// Radiance is scaled by the change in solid angle across the interface but importance isn't, so
+/// light subpaths and photons evaluate it with `eval_importance`. When it is smooth enough to act as a perfect mirror it behaves exactly like `Dielectric`.
+#[derive(Clone)]
+pub struct RoughDielectric {
+    refraction_index: f32,
+    microfacet: Microfacet,
+    rotation: Option<Arc<dyn Texture>>,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f32, microfacet: Microfacet) -> Self {
+        Self {
+            refraction_index,
+            microfacet,
+            rotation: None,
+        }
+    }
+
+    /// Turns the tangent of an anisotropic `microfacet` about the normal by `rotation`, in
+    /// fractions of a full turn
+    pub fn with_rotation(mut self, rotation: Arc<dyn Texture>) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    fn frame(&self, rec: &HitRecord) -> ONB {
+        shading_frame(rec, self.rotation.as_deref())
+    }
+
+    /// Refractive index of the far side of the surface over the side `rec` is seen from
+    pub(crate) fn eta(&self, rec: &HitRecord) -> f32 {
+        if rec.front_face {
+            self.refraction_index
+        } else {
+            1. / self.refraction_index
+        }
+    }
+
+    /// `wo`, `wi` and `eta` seen from the side of the surface `wo` is on
+    ///
+    /// Bidirectional methods ask for the density of scattering back the way a path came, from
+    /// the other side of the surface. Turning both directions around leaves the BSDF unchanged.
+    pub(crate) fn orient(wo: Vec3, wi: Vec3, eta: f32) -> (Vec3, Vec3, f32) {
+        if wo.z() < 0. {
+            (-wo, -wi, 1. / eta)
+        } else {
+            (wo, wi, eta)
+        }
+    }
+
+    /// The facet normal that scatters `wo` into `wi`, facing up, or `None` if there isn't one
+    /// that faces both of them
+    fn half_vector(wo: Vec3, wi: Vec3, eta: f32) -> Option<Vec3> {
+        let reflect = wi.z() > 0.;
+        let etap = if reflect { 1. } else { eta };
+        let wm = wi * etap + wo;
+        if wm.z() == 0. || wm.length_squared() == 0. {
+            return None;
+        }
+        let wm = vec3::unit_vector(if wm.z() < 0. { -wm } else { wm });
+        if vec3::dot(wm, wi) * wi.z() < 0. || vec3::dot(wm, wo) * wo.z() < 0. {
+            return None;
+        }
+        Some(wm)
+    }
+
+    /// The BSDF times the cosine, between local directions, for what `mode` carries
+    pub(crate) fn f(&self, wo: Vec3, wi: Vec3, eta: f32, mode: TransportMode) -> f32 {
+        let (wo, wi, eta) = Self::orient(wo, wi, eta);
+        if wo.z() == 0. || wi.z() == 0. {
+            return 0.;
+        }
+        let Some(wm) = Self::half_vector(wo, wi, eta) else {
+            return 0.;
+        };
+        let fresnel = fresnel_dielectric(vec3::dot(wo, wm), eta);
+        let dg = self.microfacet.d(wm) * self.microfacet.g(wo, wi);
+        if wi.z() > 0. {
+            dg * fresnel / (4. * wo.z())
+        } else {
+            let denom = vec3::dot(wi, wm) + vec3::dot(wo, wm) / eta;
+            let ft = dg
+                * (1. - fresnel)
+                * (vec3::dot(wi, wm) * vec3::dot(wo, wm) / (denom * denom * wo.z())).abs();
+            match mode {
+                TransportMode::Radiance => ft / (eta * eta),
+                TransportMode::Importance => ft,
+            }
+        }
+    }
+
+    pub(crate) fn pdf(&self, wo: Vec3, wi: Vec3, eta: f32) -> f32 {
+        let (wo, wi, eta) = Self::orient(wo, wi, eta);
+        if wo.z() == 0. || wi.z() == 0. {
+            return 0.;
+        }
+        let Some(wm) = Self::half_vector(wo, wi, eta) else {
+            return 0.;
+        };
+        let reflectance = fresnel_dielectric(vec3::dot(wo, wm), eta);
+        let d = self.microfacet.d_visible(wo, wm);
+        if wi.z() > 0. {
+            d / (4. * vec3::dot(wo, wm).abs()) * reflectance
+        } else {
+            let denom = vec3::dot(wi, wm) + vec3::dot(wo, wm) / eta;
+            d * vec3::dot(wi, wm).abs() / (denom * denom) * (1. - reflectance)
+        }
+    }
+
+    /// Reflects or refracts `wo` off a sampled facet, or returns `None` if the facet sends it
+    /// to the wrong side of the surface
+    pub(crate) fn sample(&self, wo: Vec3, eta: f32) -> Option<Vec3> {
+        let wm = self.microfacet.sample_wm(wo, utils::random_2d());
+        let cos_theta_o = vec3::dot(wo, wm);
+        if utils::random_double() < fresnel_dielectric(cos_theta_o, eta) {
+            let wi = vec3::reflect(-wo, wm);
+            return (wi.z() > 0.).then_some(wi);
+        }
+
+        let sin2_theta_t = (1. - cos_theta_o * cos_theta_o).max(0.) / (eta * eta);
+        if sin2_theta_t >= 1. {
+            return None;
+        }
+        let cos_theta_t = (1. - sin2_theta_t).sqrt();
+        let wi = -wo / eta + (cos_theta_o / eta - cos_theta_t) * wm;
+        (wi.z() < 0.).then_some(wi)
+    }
+
+    fn eval_mode(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        scattered: &Ray,
+        mode: TransportMode,
+    ) -> Color {
+        if self.microfacet.effectively_smooth() {
+            return Color::new(0., 0., 0.);
+        }
+        let frame = self.frame(rec);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(vec3::unit_vector(scattered.direction()));
+        let f = self.f(wo, wi, self.eta(rec), mode);
+        Color::new(f, f, f)
+    }
+}
+
+/// Samples the facets of a `RoughDielectric` visible from the outgoing direction, choosing
+/// between reflection and refraction by their Fresnel weights
+struct RoughDielectricPdf {
+    material: RoughDielectric,
+    frame: ONB,
+    wo: Vec3,
+    eta: f32,
+}
+
+impl Pdf for RoughDielectricPdf {
+    fn generate(&self) -> Vec3 {
+        match self.material.sample(self.wo, self.eta) {
+            Some(wi) => self.frame.transform(wi),
+            // Which `value` gives no density
+            None => Vec3::default(),
+        }
+    }
+
+    fn value(&self, direction: Vec3) -> f32 {
+        if direction.length_squared() == 0. {
+            return 0.;
+        }
+        let wi = self.frame.to_local(vec3::unit_vector(direction));
+        self.material.pdf(self.wo, wi, self.eta)
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        let v = -vec3::unit_vector(r_in.direction());
+        let eta = self.eta(rec);
+        srec.attenuation = Color::new(1., 1., 1.);
+
+        if self.microfacet.effectively_smooth() {
+            let cos_theta = vec3::dot(v, rec.normal);
+            srec.skip_pdf = true;
+            srec.skip_pdf_ray = if utils::random_double() < fresnel_dielectric(cos_theta, eta) {
+                rec.reflected_ray(r_in, vec3::reflect(-v, rec.normal))
+            } else {
+                rec.refracted_ray(r_in, vec3::refract(-v, rec.normal, 1. / eta), 1. / eta)
+            };
+            return true;
+        }
+
+        let frame = self.frame(rec);
+        srec.pdf = Arc::new(RoughDielectricPdf {
+            material: self.clone(),
+            wo: frame.to_local(v),
+            frame,
+            eta,
+        });
+        srec.skip_pdf = false;
+        true
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.eval_mode(r_in, rec, scattered, TransportMode::Radiance)
+    }
+
+    fn eval_importance(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Color {
+        self.eval_mode(r_in, rec, scattered, TransportMode::Importance)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        if self.microfacet.effectively_smooth() {
+            return 0.;
+        }
+        let frame = self.frame(rec);
+        let wo = frame.to_local(-vec3::unit_vector(r_in.direction()));
+        let wi = frame.to_local(vec3::unit_vector(scattered.direction()));
+        self.pdf(wo, wi, self.eta(rec))
+    }
+}
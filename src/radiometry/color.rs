@@ -5,6 +5,7 @@ pub const CIE_Y_INT: f32 = 106.856895;
 
 struct Point2(f32, f32);
 
+#[derive(Clone, Copy, Debug)]
 pub struct XYZ {
     pub x: f32,
     pub y: f32,
@@ -36,7 +37,7 @@ impl XYZ {
             Self {
                 x: (xy.0 * y) / xy.1,
                 y,
-                z: (1. - xy.0 - xy.1) / (y * xy.1),
+                z: ((1. - xy.0 - xy.1) * y) / xy.1,
             }
         }
     }
@@ -0,0 +1,149 @@
+use crate::radiometry::spectrum::{BlackbodySpectrum, PiecewiseLinearSpectrum, Spectrum};
+
+use std::sync::Arc;
+
+/// Wavelengths of the CIE daylight components, every 10 nm from 300 nm to 830 nm
+const DAYLIGHT_LAMBDA_MIN: f32 = 300.;
+const DAYLIGHT_LAMBDA_STEP: f32 = 10.;
+
+/// Mean daylight spectrum
+#[rustfmt::skip]
+const DAYLIGHT_S0: [f32; 54] = [
+    0.04, 6.0, 29.6, 55.3, 57.3, 61.8, 61.5, 68.8, 63.4, 65.8,
+    94.8, 104.8, 105.9, 96.8, 113.9, 125.6, 125.5, 121.3, 121.3, 113.5,
+    113.1, 110.8, 106.5, 108.8, 105.3, 104.4, 100.0, 96.0, 95.1, 89.1,
+    90.5, 90.3, 88.4, 84.0, 85.1, 81.9, 82.6, 84.9, 81.3, 71.9,
+    74.3, 76.4, 63.3, 71.7, 77.0, 65.2, 47.7, 68.6, 65.0, 66.0,
+    61.0, 53.3, 58.9, 61.9,
+];
+
+/// First characteristic vector of daylight, a yellow-blue variation
+#[rustfmt::skip]
+const DAYLIGHT_S1: [f32; 54] = [
+    0.02, 4.5, 22.4, 42.0, 40.6, 41.6, 38.0, 42.4, 38.5, 35.0,
+    43.4, 46.3, 43.9, 37.1, 36.7, 35.9, 32.6, 27.9, 24.3, 20.1,
+    16.2, 13.2, 8.6, 6.1, 4.2, 1.9, 0.0, -1.6, -3.5, -3.5,
+    -5.8, -7.2, -8.6, -9.5, -10.9, -10.7, -12.0, -14.0, -13.6, -12.0,
+    -13.3, -12.9, -10.6, -11.6, -12.2, -10.2, -7.8, -11.2, -10.4, -10.6,
+    -9.7, -8.3, -9.3, -9.8,
+];
+
+/// Second characteristic vector of daylight, a pink-green variation
+#[rustfmt::skip]
+const DAYLIGHT_S2: [f32; 54] = [
+    0.0, 2.0, 4.0, 8.5, 7.8, 6.7, 5.3, 6.1, 3.0, 1.2,
+    -1.1, -0.5, -0.7, -1.2, -2.6, -2.9, -2.8, -2.6, -2.6, -1.8,
+    -1.5, -1.3, -1.2, -1.0, -0.5, -0.3, 0.0, 0.2, 0.5, 2.1,
+    3.2, 4.1, 4.7, 5.1, 6.7, 7.3, 8.6, 9.8, 10.2, 8.3,
+    9.6, 8.5, 7.0, 7.6, 8.0, 6.7, 5.2, 7.4, 6.8, 7.0,
+    6.4, 5.5, 6.1, 6.5,
+];
+
+/// The CIE daylight illuminant with correlated color temperature `cct`, in Kelvin
+///
+/// The model is defined from 4000 K to 25000 K, and `cct` is clamped to that range. The
+/// spectrum is normalized to 100 at 560 nm.
+pub fn daylight(cct: f32) -> PiecewiseLinearSpectrum {
+    let t = cct.clamp(4000., 25000.);
+    let x = if t <= 7000. {
+        0.244_063 + 0.099_11e3 / t + 2.967_8e6 / (t * t) - 4.607e9 / (t * t * t)
+    } else {
+        0.237_04 + 0.247_48e3 / t + 1.901_8e6 / (t * t) - 2.006_4e9 / (t * t * t)
+    };
+    let y = -3. * x * x + 2.87 * x - 0.275;
+
+    let m = 0.0241 + 0.2562 * x - 0.7341 * y;
+    let m1 = (-1.3515 - 1.7703 * x + 5.9114 * y) / m;
+    let m2 = (0.03 - 31.4424 * x + 30.0717 * y) / m;
+
+    let lambdas = (0..DAYLIGHT_S0.len())
+        .map(|i| DAYLIGHT_LAMBDA_MIN + i as f32 * DAYLIGHT_LAMBDA_STEP)
+        .collect();
+    let values = (0..DAYLIGHT_S0.len())
+        .map(|i| DAYLIGHT_S0[i] + m1 * DAYLIGHT_S1[i] + m2 * DAYLIGHT_S2[i])
+        .collect();
+    PiecewiseLinearSpectrum::new(lambdas, values).expect("the daylight tables are well formed")
+}
+
+/// Wavelengths of the CIE fluorescent illuminants, every 5 nm from 380 nm to 780 nm
+const FLUORESCENT_LAMBDA_MIN: f32 = 380.;
+const FLUORESCENT_LAMBDA_STEP: f32 = 5.;
+
+/// F2, a cool white standard halophosphate lamp at about 4200 K
+#[rustfmt::skip]
+const FLUORESCENT_F2: [f32; 81] = [
+    1.18, 1.48, 1.84, 2.15, 3.44, 15.69, 3.85, 3.74, 4.19, 4.62,
+    5.06, 34.98, 11.81, 6.27, 6.63, 6.93, 7.19, 7.40, 7.54, 7.62,
+    7.65, 7.62, 7.62, 7.45, 7.28, 7.15, 7.05, 7.04, 7.16, 7.47,
+    8.04, 8.88, 10.01, 24.88, 16.64, 14.59, 16.16, 17.56, 18.62, 21.47,
+    22.79, 19.29, 18.66, 17.73, 16.54, 15.21, 13.80, 12.36, 10.95, 9.65,
+    8.40, 7.32, 6.31, 5.43, 4.68, 4.02, 3.45, 2.96, 2.55, 2.19,
+    1.89, 1.64, 1.53, 1.27, 1.10, 0.99, 0.88, 0.76, 0.68, 0.61,
+    0.56, 0.54, 0.51, 0.47, 0.47, 0.43, 0.46, 0.47, 0.40, 0.33,
+    0.27,
+];
+
+/// F7, a broadband daylight simulator at about 6500 K
+#[rustfmt::skip]
+const FLUORESCENT_F7: [f32; 81] = [
+    2.56, 3.18, 3.84, 4.53, 6.15, 19.37, 7.37, 7.05, 7.71, 8.41,
+    9.15, 44.14, 17.52, 11.35, 12.00, 12.58, 13.08, 13.45, 13.71, 13.88,
+    13.95, 13.93, 13.82, 13.64, 13.43, 13.25, 13.08, 12.93, 12.78, 12.60,
+    12.44, 12.33, 12.26, 29.52, 17.05, 12.44, 12.58, 12.72, 12.83, 15.46,
+    16.75, 12.83, 12.67, 12.45, 12.19, 11.89, 11.60, 11.35, 11.12, 10.95,
+    10.76, 10.42, 10.11, 10.04, 10.02, 10.11, 9.87, 8.65, 7.27, 6.44,
+    5.83, 5.41, 5.04, 4.57, 4.12, 3.77, 3.46, 3.08, 2.73, 2.47,
+    2.25, 2.06, 1.90, 1.75, 1.62, 1.54, 1.45, 1.32, 1.17, 0.99,
+    0.81,
+];
+
+/// F11, a narrow tri-band lamp at about 4000 K
+#[rustfmt::skip]
+const FLUORESCENT_F11: [f32; 81] = [
+    0.91, 0.63, 0.46, 0.37, 1.29, 12.68, 1.59, 1.79, 2.46, 3.33,
+    4.49, 33.94, 12.13, 6.95, 7.19, 7.12, 6.72, 6.13, 5.46, 4.79,
+    5.66, 14.29, 14.96, 8.97, 4.72, 2.33, 1.47, 1.10, 0.89, 0.83,
+    1.18, 4.90, 39.59, 72.84, 32.61, 7.52, 2.83, 1.96, 1.67, 4.43,
+    11.28, 14.76, 12.73, 9.74, 7.33, 9.72, 55.27, 42.58, 13.18, 13.16,
+    12.26, 5.11, 2.07, 2.34, 3.58, 3.01, 2.48, 2.14, 1.54, 1.33,
+    1.46, 1.94, 2.00, 1.20, 1.35, 4.10, 5.58, 2.51, 0.57, 0.27,
+    0.23, 0.21, 0.24, 0.24, 0.20, 0.24, 0.32, 0.26, 0.16, 0.12,
+    0.09,
+];
+
+/// One of the tabulated CIE fluorescent illuminants
+fn fluorescent(values: &[f32]) -> PiecewiseLinearSpectrum {
+    let lambdas = (0..values.len())
+        .map(|i| FLUORESCENT_LAMBDA_MIN + i as f32 * FLUORESCENT_LAMBDA_STEP)
+        .collect();
+    PiecewiseLinearSpectrum::new(lambdas, values.to_vec())
+        .expect("the fluorescent tables are well formed")
+}
+
+/// Names accepted by `named_illuminant`
+pub const NAMED_ILLUMINANTS: [&str; 8] = ["a", "d50", "d55", "d65", "d75", "f2", "f7", "f11"];
+
+/// One of the CIE standard illuminants
+///
+/// - `a`: incandescent tungsten light, a blackbody at 2856 K
+/// - `d50`, `d55`, `d65` and `d75`: daylight at about 5000 K, 5500 K, 6500 K and 7500 K. D65 is
+///   the white point of sRGB.
+/// - `f2`, `f7` and `f11`: the most used of the fluorescent F-series, a cool white lamp, a
+///   daylight simulator and a tri-band lamp. The others can be loaded from their published
+///   tables with `PiecewiseLinearSpectrum::load_csv`.
+pub fn named_illuminant(name: &str) -> Option<Arc<dyn Spectrum>> {
+    // The D-series temperatures predate a revision of Planck's second radiation constant, which
+    // moved them from round numbers by a factor of 1.4388 / 1.438
+    let daylight = |nominal: f32| -> Arc<dyn Spectrum> { Arc::new(daylight(nominal * 1.000_556)) };
+    match name {
+        "a" => Some(Arc::new(BlackbodySpectrum::new(2856.))),
+        "d50" => Some(daylight(5000.)),
+        "d55" => Some(daylight(5500.)),
+        "d65" => Some(daylight(6500.)),
+        "d75" => Some(daylight(7500.)),
+        "f2" => Some(Arc::new(fluorescent(&FLUORESCENT_F2))),
+        "f7" => Some(Arc::new(fluorescent(&FLUORESCENT_F7))),
+        "f11" => Some(Arc::new(fluorescent(&FLUORESCENT_F11))),
+        _ => None,
+    }
+}
@@ -0,0 +1,128 @@
+use crate::radiometry::sampling::{SampledSpectrum, SampledWavelengths, NUM_SPECTRUM_SAMPLES};
+use crate::radiometry::spectrum::Spectrum;
+use crate::radiometry::utils::{LAMBDA_MAX, LAMBDA_MIN};
+
+use std::sync::Arc;
+
+/// The helium d-line, where glass catalogs quote the refractive index `n_d`. Renders that don't
+/// track wavelengths use the index there.
+pub const REFERENCE_WAVELENGTH: f32 = 587.56;
+
+/// Largest value of `f` on the visible range, at every nanometer
+fn max_visible(f: impl Fn(f32) -> f32) -> f32 {
+    ((LAMBDA_MIN as usize)..=(LAMBDA_MAX as usize))
+        .map(|lambda| f(lambda as f32))
+        .fold(f32::MIN, f32::max)
+}
+
+/// Refractive index following Cauchy's equation, n = a + b / λ² + c / λ⁴ with λ in micrometers
+///
+/// A good fit for most transparent materials across the visible range.
+pub struct CauchySpectrum {
+    a: f32,
+    b: f32,
+    c: f32,
+}
+impl CauchySpectrum {
+    pub fn new(a: f32, b: f32, c: f32) -> Self {
+        Self { a, b, c }
+    }
+}
+
+impl Spectrum for CauchySpectrum {
+    fn call(&self, wavelength: f32) -> f32 {
+        let l2 = (wavelength * 1e-3) * (wavelength * 1e-3);
+        self.a + self.b / l2 + self.c / (l2 * l2)
+    }
+
+    fn max_value(&self) -> f32 {
+        max_visible(|lambda| self.call(lambda))
+    }
+
+    fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        let mut s: SampledSpectrum = Default::default();
+        for i in 0..NUM_SPECTRUM_SAMPLES {
+            s.values[i] = self.call(wavelengths.lambdas[i]);
+        }
+        s
+    }
+}
+
+/// Refractive index following the Sellmeier equation, n² = 1 + Σ b_i λ² / (λ² - c_i) with λ in
+/// micrometers
+///
+/// Glass manufacturers publish their data as Sellmeier coefficients, usually three pairs.
+pub struct SellmeierSpectrum {
+    b: Vec<f32>,
+    c: Vec<f32>,
+}
+impl SellmeierSpectrum {
+    /// Returns an error unless there are as many `b` as `c` coefficients
+    pub fn new(b: Vec<f32>, c: Vec<f32>) -> Result<Self, String> {
+        if b.len() != c.len() {
+            return Err(format!(
+                "Sellmeier equation needs as many b as c coefficients, got {} and {}",
+                b.len(),
+                c.len()
+            ));
+        }
+        Ok(Self { b, c })
+    }
+}
+
+impl Spectrum for SellmeierSpectrum {
+    fn call(&self, wavelength: f32) -> f32 {
+        let l2 = (wavelength * 1e-3) * (wavelength * 1e-3);
+        let n2 = 1.
+            + self
+                .b
+                .iter()
+                .zip(&self.c)
+                .map(|(b, c)| b * l2 / (l2 - c))
+                .sum::<f32>();
+        n2.max(0.).sqrt()
+    }
+
+    fn max_value(&self) -> f32 {
+        max_visible(|lambda| self.call(lambda))
+    }
+
+    fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        let mut s: SampledSpectrum = Default::default();
+        for i in 0..NUM_SPECTRUM_SAMPLES {
+            s.values[i] = self.call(wavelengths.lambdas[i]);
+        }
+        s
+    }
+}
+
+/// Names accepted by `named_ior`
+pub const NAMED_IORS: [&str; 4] = ["bk7", "fused_silica", "diamond", "water"];
+
+/// Refractive index of a common optical material
+///
+/// - `bk7`: Schott N-BK7 crown glass, the most common glass for lenses and prisms
+/// - `fused_silica`: fused quartz, after Malitson
+/// - `diamond`: after Peter
+/// - `water`: a Cauchy fit to water at 20 °C
+pub fn named_ior(name: &str) -> Option<Arc<dyn Spectrum>> {
+    let sellmeier = |b: [f32; 3], c: [f32; 3]| -> Arc<dyn Spectrum> {
+        Arc::new(SellmeierSpectrum {
+            b: b.to_vec(),
+            c: c.to_vec(),
+        })
+    };
+    match name {
+        "bk7" => Some(sellmeier(
+            [1.039_612, 0.231_792_3, 1.010_469_5],
+            [0.006_000_699, 0.020_017_914, 103.560_65],
+        )),
+        "fused_silica" => Some(sellmeier(
+            [0.696_166_3, 0.407_942_6, 0.897_479_4],
+            [0.004_679_148, 0.013_512_063, 97.934],
+        )),
+        "diamond" => Some(sellmeier([0.3306, 4.3356, 0.], [0.030_625, 0.011_236, 0.])),
+        "water" => Some(Arc::new(CauchySpectrum::new(1.3234, 0.003_29, 0.))),
+        _ => None,
+    }
+}
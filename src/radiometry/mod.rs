@@ -1,4 +1,7 @@
 pub mod color;
+pub mod illuminant;
+pub mod ior;
+pub mod rgb;
 pub mod sampling;
 pub mod spectrum;
 pub mod utils;
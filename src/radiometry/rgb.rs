@@ -0,0 +1,148 @@
+use crate::radiometry::color::{CIE_Y_INT, XYZ};
+use crate::radiometry::sampling::{SampledSpectrum, SampledWavelengths, NUM_SPECTRUM_SAMPLES};
+use crate::radiometry::spectrum::{DenselySampledSpectrum, Spectrum};
+use crate::radiometry::utils::{LAMBDA_MAX, LAMBDA_MIN};
+use crate::{Color, Matrix4, Vec3};
+
+use std::sync::OnceLock;
+
+/// Where the blue part of the spectrum ends and the red part begins, and how soft the edges are
+const BLUE_EDGE: f32 = 490.;
+const RED_EDGE: f32 = 590.;
+const EDGE_WIDTH: f32 = 10.;
+
+/// Smooth, non-negative spectra for the red, green and blue parts of the visible range
+///
+/// They sum to one at every wavelength, so equal coefficients give a flat spectrum.
+fn basis(lambda: f32) -> [f32; 3] {
+    let sigmoid = |t: f32| 1. / (1. + (-t).exp());
+    let blue = 1. - sigmoid((lambda - BLUE_EDGE) / EDGE_WIDTH);
+    let red = sigmoid((lambda - RED_EDGE) / EDGE_WIDTH);
+    [red, 1. - blue - red, blue]
+}
+
+/// Maps from RGB to the coefficients of the red, green and blue basis spectra
+struct Uplift {
+    albedo: Matrix4,
+    illuminant: Matrix4,
+}
+
+impl Uplift {
+    fn get() -> &'static Uplift {
+        static UPLIFT: OnceLock<Uplift> = OnceLock::new();
+        UPLIFT.get_or_init(|| {
+            // Emission is matched on its own, and reflectance under the spectrum of white light,
+            // so that white light on a white surface stays exactly white
+            let illuminant = Self::inverse_response(|_| 1.);
+            let white = illuminant.transform_vector(Vec3::new(1., 1., 1.));
+            let albedo = Self::inverse_response(|lambda| combine(white, lambda));
+            Uplift { albedo, illuminant }
+        })
+    }
+
+    /// Inverts the matrix whose columns are the linear sRGB colors of the red, green and blue
+    /// basis spectra under `light`
+    fn inverse_response(light: impl Fn(f32) -> f32) -> Matrix4 {
+        let mut columns = [XYZ::default(), XYZ::default(), XYZ::default()];
+        for lambda in (LAMBDA_MIN as usize)..=(LAMBDA_MAX as usize) {
+            let lambda = lambda as f32;
+            let (x, y, z) = (
+                DenselySampledSpectrum::x().call(lambda),
+                DenselySampledSpectrum::y().call(lambda),
+                DenselySampledSpectrum::z().call(lambda),
+            );
+            for (column, b) in columns.iter_mut().zip(basis(lambda)) {
+                let weight = b * light(lambda) / CIE_Y_INT;
+                column.x += weight * x;
+                column.y += weight * y;
+                column.z += weight * z;
+            }
+        }
+
+        let [r, g, b] = columns.map(Color::from);
+        Matrix4::new([
+            [r.x(), g.x(), b.x(), 0.],
+            [r.y(), g.y(), b.y(), 0.],
+            [r.z(), g.z(), b.z(), 0.],
+            [0., 0., 0., 1.],
+        ])
+        .inverse()
+        .expect("the basis spectra have linearly independent colors")
+    }
+}
+
+/// The spectrum with the given basis coefficients at `lambda`, clamped to be non-negative
+fn combine(coefficients: Vec3, lambda: f32) -> f32 {
+    let [r, g, b] = basis(lambda);
+    (r * coefficients.x() + g * coefficients.y() + b * coefficients.z()).max(0.)
+}
+
+fn sample_coefficients(coefficients: Vec3, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+    let mut s: SampledSpectrum = Default::default();
+    for i in 0..NUM_SPECTRUM_SAMPLES {
+        s.values[i] = combine(coefficients, wavelengths.lambdas[i]);
+    }
+    s
+}
+
+/// A smooth reflectance spectrum with the given linear sRGB color
+///
+/// The uplift is linear, so it works for any RGB value, not only albedos in [0, 1], and it
+/// renders back to exactly the same color under white light. Colors outside of what the basis
+/// can reach, like the saturated primaries, are clamped to a non-negative spectrum.
+pub struct RgbAlbedoSpectrum {
+    coefficients: Vec3,
+}
+
+impl RgbAlbedoSpectrum {
+    pub fn new(rgb: Color) -> Self {
+        Self {
+            coefficients: Uplift::get().albedo.transform_vector(rgb),
+        }
+    }
+}
+
+impl Spectrum for RgbAlbedoSpectrum {
+    fn call(&self, wavelength: f32) -> f32 {
+        combine(self.coefficients, wavelength)
+    }
+
+    fn max_value(&self) -> f32 {
+        let c = self.coefficients;
+        c.x().max(c.y()).max(c.z()).max(0.)
+    }
+
+    fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        sample_coefficients(self.coefficients, wavelengths)
+    }
+}
+
+/// An emission spectrum with the given linear sRGB color
+///
+/// Seen directly, it has exactly the color and luminance that an RGB render would give it.
+pub struct RgbIlluminantSpectrum {
+    coefficients: Vec3,
+}
+
+impl RgbIlluminantSpectrum {
+    pub fn new(rgb: Color) -> Self {
+        Self {
+            coefficients: Uplift::get().illuminant.transform_vector(rgb),
+        }
+    }
+}
+
+impl Spectrum for RgbIlluminantSpectrum {
+    fn call(&self, wavelength: f32) -> f32 {
+        combine(self.coefficients, wavelength)
+    }
+
+    fn max_value(&self) -> f32 {
+        let c = self.coefficients;
+        c.x().max(c.y()).max(c.z()).max(0.)
+    }
+
+    fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        sample_coefficients(self.coefficients, wavelengths)
+    }
+}
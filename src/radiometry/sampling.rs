@@ -1,6 +1,7 @@
 use crate::core::utils::lerp;
 use crate::radiometry::color::{CIE_Y_INT, XYZ};
 use crate::radiometry::spectrum::{DenselySampledSpectrum, Spectrum};
+use crate::radiometry::utils::{LAMBDA_MAX, LAMBDA_MIN};
 
 pub const NUM_SPECTRUM_SAMPLES: usize = 4;
 
@@ -34,12 +35,38 @@ impl SampledWavelengths {
         swl
     }
 
+    /// Samples wavelengths in proportion to how visible they are, which follows the luminous
+    /// efficiency curve more closely than uniform sampling
+    ///
+    /// The wavelengths are stratified: the first comes from `u` and the others are spread evenly
+    /// after it, wrapping around.
+    pub fn sample_visible(u: f32) -> Self {
+        let mut swl: SampledWavelengths = Default::default();
+        for i in 0..NUM_SPECTRUM_SAMPLES {
+            let mut up = u + i as f32 / NUM_SPECTRUM_SAMPLES as f32;
+            if up > 1. {
+                up -= 1.;
+            }
+            swl.lambdas[i] = 538. - 138.888_89 * (0.856_910_6 - 1.827_502 * up).atanh();
+            swl.pdf[i] = Self::visible_pdf(swl.lambdas[i]);
+        }
+        swl
+    }
+
+    fn visible_pdf(lambda: f32) -> f32 {
+        if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) {
+            return 0.;
+        }
+        let c = (0.0072 * (lambda - 538.)).cosh();
+        0.003_939_804 / (c * c)
+    }
+
     pub fn pdf(&self) -> SampledSpectrum {
         SampledSpectrum::new(&self.pdf)
     }
 
-    pub fn secondary_terminated(&mut self) -> bool {
-        self.pdf.iter().any(|&x| x != 0.)
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&x| x == 0.)
     }
 
     pub fn terminate_secondary(&mut self) {
@@ -103,6 +130,10 @@ impl SampledSpectrum {
         self.values.iter().sum::<f32>() / (self.values.len() as f32)
     }
 
+    pub fn max_component(&self) -> f32 {
+        self.values.iter().copied().fold(f32::MIN, f32::max)
+    }
+
     pub fn bool(&self) -> bool {
         self.values.iter().any(|&x| x != 0.)
     }
@@ -166,6 +197,23 @@ impl std::ops::Div for SampledSpectrum {
     }
 }
 
+impl std::ops::Mul<f32> for SampledSpectrum {
+    type Output = Self;
+    fn mul(self, rhs: f32) -> Self {
+        SampledSpectrum {
+            values: self.values.map(|v| v * rhs),
+        }
+    }
+}
+impl std::ops::Div<f32> for SampledSpectrum {
+    type Output = Self;
+    fn div(self, rhs: f32) -> Self {
+        SampledSpectrum {
+            values: self.values.map(|v| v / rhs),
+        }
+    }
+}
+
 impl std::ops::DivAssign for SampledSpectrum {
     fn div_assign(&mut self, rhs: Self) {
         for i in 0..NUM_SPECTRUM_SAMPLES {
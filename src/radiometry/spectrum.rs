@@ -1,9 +1,11 @@
 #![allow(dead_code)]
 use crate::core::utils::lerp;
-use crate::radiometry::color::XYZ;
+use crate::radiometry::color::{CIE_Y_INT, XYZ};
 use crate::radiometry::sampling::{SampledSpectrum, SampledWavelengths, NUM_SPECTRUM_SAMPLES};
 use crate::radiometry::utils::{blackbody, find_interval, LAMBDA_MAX, LAMBDA_MIN};
 
+use std::sync::OnceLock;
+
 /// Implementation of a spectral distributions
 pub trait Spectrum: Sync + Send {
     /// Takes in a wavelength and returns the value of the distribution at that wavelength
@@ -21,15 +23,18 @@ fn inner_product(f: &dyn Spectrum, g: &dyn Spectrum) -> f32 {
     }
     integral
 }
-fn spectrum_to_xyz(s: &dyn Spectrum) -> XYZ {
+
+/// The color of light with the spectral distribution `s`, with the same scale as
+/// `SampledSpectrum::to_xyz`
+pub fn spectrum_to_xyz(s: &dyn Spectrum) -> XYZ {
     XYZ {
-        x: inner_product(&DenselySampledSpectrum::x(), s),
-        y: inner_product(&DenselySampledSpectrum::y(), s),
-        z: inner_product(&DenselySampledSpectrum::z(), s),
-    }
+        x: inner_product(DenselySampledSpectrum::x(), s),
+        y: inner_product(DenselySampledSpectrum::y(), s),
+        z: inner_product(DenselySampledSpectrum::z(), s),
+    } / CIE_Y_INT
 }
 
-struct ConstantSpectrum {
+pub struct ConstantSpectrum {
     c: f32,
 }
 impl ConstantSpectrum {
@@ -46,7 +51,7 @@ impl Spectrum for ConstantSpectrum {
         self.c
     }
     fn sample(&self, _: &SampledWavelengths) -> SampledSpectrum {
-        SampledSpectrum::default()
+        SampledSpectrum::new(&[self.c; NUM_SPECTRUM_SAMPLES])
     }
 }
 
@@ -69,7 +74,7 @@ impl DenselySampledSpectrum {
         Self {
             lambda_min: lambda_min as isize,
             lambda_max: lambda_max as isize,
-            values: vec![0.; (lambda_min - lambda_max) + 1],
+            values: vec![0.; (lambda_max - lambda_min) + 1],
         }
     }
 
@@ -93,17 +98,61 @@ impl DenselySampledSpectrum {
         }
     }
 
-    pub fn x() -> Self {
-        todo!()
+    /// The CIE 1931 x color matching function
+    pub fn x() -> &'static Self {
+        static X: OnceLock<DenselySampledSpectrum> = OnceLock::new();
+        X.get_or_init(|| {
+            Self::from_fn(|lambda| {
+                1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+                    + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+                    - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2)
+            })
+        })
+    }
+
+    /// The CIE 1931 y color matching function, i.e. the luminous efficiency
+    pub fn y() -> &'static Self {
+        static Y: OnceLock<DenselySampledSpectrum> = OnceLock::new();
+        Y.get_or_init(|| {
+            Self::from_fn(|lambda| {
+                0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+                    + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1)
+            })
+        })
     }
-    pub fn y() -> Self {
-        todo!()
+
+    /// The CIE 1931 z color matching function
+    pub fn z() -> &'static Self {
+        static Z: OnceLock<DenselySampledSpectrum> = OnceLock::new();
+        Z.get_or_init(|| {
+            Self::from_fn(|lambda| {
+                1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+                    + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8)
+            })
+        })
     }
-    pub fn z() -> Self {
-        todo!()
+
+    /// Tabulates `f` at every nanometer of the visible range
+    fn from_fn(f: impl Fn(f32) -> f32) -> Self {
+        let mut spectrum = Self::default();
+        for (i, value) in spectrum.values.iter_mut().enumerate() {
+            *value = f((spectrum.lambda_min + i as isize) as f32);
+        }
+        spectrum
     }
 }
 
+/// A Gaussian lobe with a different width on each side of its peak
+///
+/// Sums of these fit the CIE 1931 standard observer to within the variability between real
+/// observers, after Wyman, Sloan and Shirley, "Simple Analytic Approximations to the CIE XYZ
+/// Color Matching Functions".
+fn piecewise_gaussian(lambda: f32, mu: f32, sigma_low: f32, sigma_high: f32) -> f32 {
+    let sigma = if lambda < mu { sigma_low } else { sigma_high };
+    let t = (lambda - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
 impl Spectrum for DenselySampledSpectrum {
     fn call(&self, lambda: f32) -> f32 {
         let offset = lambda.round() as isize - self.lambda_min;
@@ -129,7 +178,7 @@ impl Spectrum for DenselySampledSpectrum {
             if offset < 0 || offset >= self.values.len() as isize {
                 s.values[i] = 0.;
             } else {
-                s.values[i] = self.values[i];
+                s.values[i] = self.values[offset as usize];
             }
         }
         s
@@ -166,6 +215,70 @@ pub struct PiecewiseLinearSpectrum {
     values: Vec<f32>,
 }
 
+impl PiecewiseLinearSpectrum {
+    /// Returns an error unless there is a value for each of at least two wavelengths, in
+    /// increasing order
+    pub fn new(lambdas: Vec<f32>, values: Vec<f32>) -> Result<Self, String> {
+        if lambdas.len() != values.len() {
+            return Err(format!(
+                "spectrum has {} wavelengths but {} values",
+                lambdas.len(),
+                values.len()
+            ));
+        }
+        if lambdas.len() < 2 {
+            return Err("spectrum needs at least two wavelengths".to_string());
+        }
+        if lambdas.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("spectrum wavelengths must be increasing".to_string());
+        }
+        Ok(Self { lambdas, values })
+    }
+
+    /// Loads a spectrum from a CSV file, see `PiecewiseLinearSpectrum::parse_csv`
+    pub fn load_csv(path: &str) -> Result<Self, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("couldn't read '{path}': {e}"))?;
+        Self::parse_csv(&contents).map_err(|e| format!("{path}: {e}"))
+    }
+
+    /// Parses lines of `wavelength,value` pairs, with wavelengths in nanometers
+    ///
+    /// Blank lines, lines starting with `#` and a header line are skipped.
+    pub fn parse_csv(contents: &str) -> Result<Self, String> {
+        let (mut lambdas, mut values) = (Vec::new(), Vec::new());
+        let mut header = true;
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let parsed = match fields[..] {
+                [lambda, value] => lambda.parse::<f32>().and_then(|l| Ok((l, value.parse()?))),
+                _ => return Err(format!("line {}: expected 'wavelength,value'", i + 1)),
+            };
+            let first = std::mem::replace(&mut header, false);
+            match parsed {
+                Ok((lambda, value)) => {
+                    lambdas.push(lambda);
+                    values.push(value);
+                }
+                // A header naming the columns
+                Err(_) if first => continue,
+                Err(e) => return Err(format!("line {}: {e}", i + 1)),
+            }
+        }
+        Self::new(lambdas, values)
+    }
+
+    pub fn scale(&mut self, s: f32) {
+        for value in &mut self.values {
+            *value *= s;
+        }
+    }
+}
+
 impl Spectrum for PiecewiseLinearSpectrum {
     fn call(&self, wavelength: f32) -> f32 {
         if self.lambdas.is_empty()
@@ -178,13 +291,13 @@ impl Spectrum for PiecewiseLinearSpectrum {
             self.lambdas[lambda] < wavelength
         });
         let t: f32 = (wavelength - self.lambdas[o]) / (self.lambdas[o + 1] - self.lambdas[o]);
-        lerp(t, self.lambdas[o], self.lambdas[o + 1])
+        lerp(t, self.values[o], self.values[o + 1])
     }
 
-    fn sample(&self, _: &SampledWavelengths) -> SampledSpectrum {
+    fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
         let mut s: SampledSpectrum = Default::default();
         for i in 0..NUM_SPECTRUM_SAMPLES {
-            s.values[i] = self.call(i as f32);
+            s.values[i] = self.call(wavelengths.lambdas[i]);
         }
         s
     }
@@ -199,16 +312,18 @@ impl Spectrum for PiecewiseLinearSpectrum {
 }
 
 /// A spectrum corresponding to a blackbody illuminator at a specific temperature
+/// It is normalized to a peak value of 1, so the temperature only changes the color
 pub struct BlackbodySpectrum {
     temp: f32,
     norm_factor: f32,
 }
 impl BlackbodySpectrum {
     pub fn new(temp: f32) -> Self {
+        // Wien's displacement law gives the peak wavelength in meters
         let lambda_max = 2.897_772e-3 / temp;
         Self {
             temp,
-            norm_factor: blackbody(lambda_max * 1e-9, temp),
+            norm_factor: 1. / blackbody(lambda_max * 1e9, temp),
         }
     }
 }
@@ -219,7 +334,7 @@ impl Spectrum for BlackbodySpectrum {
     }
 
     fn max_value(&self) -> f32 {
-        todo!()
+        1.
     }
 
     fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
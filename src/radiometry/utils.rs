@@ -11,8 +11,7 @@ pub fn blackbody(wavelength: f32, temp: f32) -> f32 {
 
     let l_nm = wavelength * 1e-9;
 
-    (2. * H * C.powi(2))
-        / (l_nm.powi(5) * (std::f32::consts::E.powf(H * C / l_nm * KB * temp) - 1.))
+    (2. * H * C.powi(2)) / (l_nm.powi(5) * ((H * C / (l_nm * KB * temp)).exp() - 1.))
 }
 
 pub fn find_interval<F>(sz: usize, pred: F) -> usize
@@ -0,0 +1,168 @@
+use crate::rng::hash;
+use crate::{Rng, Sampler};
+
+use std::sync::OnceLock;
+
+const MASK_SIZE: usize = 64;
+const MASK_SIGMA: f32 = 1.9;
+
+/// Generalized golden ratio steps of the R1 and R2 sequences
+const R1: f64 = 0.618_033_988_749_894_9;
+const R2: (f64, f64) = (0.754_877_666_246_692_7, 0.569_840_290_998_053_2);
+
+/// Blue-noise dithered sampling, after Georgiev and Fajardo
+///
+/// Each pixel takes the R1 and R2 low-discrepancy sequences and rotates them by a value from a
+/// void-and-cluster blue-noise mask. Neighbouring pixels get very different rotations, so the
+/// remaining error is pushed to high frequencies where it is much less visible than white noise.
+/// Every dimension reads the mask at its own toroidal offset.
+pub struct BlueNoiseSampler {
+    seed: u64,
+    x: usize,
+    y: usize,
+    sample_index: u64,
+    dimension: u64,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            x: 0,
+            y: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Mask value for this pixel in the next dimension
+    fn next_rotation(&mut self) -> f64 {
+        let offset = hash(&[self.seed, self.dimension]);
+        self.dimension += 1;
+        let x = (self.x + (offset % MASK_SIZE as u64) as usize) % MASK_SIZE;
+        let y = (self.y + ((offset >> 32) % MASK_SIZE as u64) as usize) % MASK_SIZE;
+        blue_noise_mask()[y * MASK_SIZE + x] as f64
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample_index: usize) {
+        self.x = x;
+        self.y = y;
+        self.sample_index = sample_index as u64;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let rotation = self.next_rotation();
+        fract(rotation + self.sample_index as f64 * R1)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let rotation_x = self.next_rotation();
+        let rotation_y = self.next_rotation();
+        let i = self.sample_index as f64;
+        (fract(rotation_x + i * R2.0), fract(rotation_y + i * R2.1))
+    }
+}
+
+fn fract(x: f64) -> f32 {
+    ((x - x.floor()) as f32).min(super::ONE_MINUS_EPSILON)
+}
+
+/// A tileable `MASK_SIZE` x `MASK_SIZE` blue-noise mask with values in [0, 1), built once
+fn blue_noise_mask() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(|| void_and_cluster(MASK_SIZE, MASK_SIGMA))
+}
+
+/// Ulichney's void-and-cluster method
+///
+/// Pixels are ranked by the order they are added to a binary pattern, always filling the largest
+/// void, i.e. the pixel with the least Gaussian-weighted energy from the pixels already set.
+/// Ranks below the initial pattern are found by removing its tightest clusters instead.
+fn void_and_cluster(size: usize, sigma: f32) -> Vec<f32> {
+    let n = size * size;
+
+    // Gaussian energy as a function of toroidal offset
+    let mut kernel = vec![0.; n];
+    for dy in 0..size {
+        for dx in 0..size {
+            let wx = dx.min(size - dx) as f32;
+            let wy = dy.min(size - dy) as f32;
+            kernel[dy * size + dx] = (-(wx * wx + wy * wy) / (2. * sigma * sigma)).exp();
+        }
+    }
+
+    let toggle = |pattern: &mut [bool], energy: &mut [f32], i: usize, on: bool| {
+        pattern[i] = on;
+        let sign = if on { 1. } else { -1. };
+        let (ix, iy) = (i % size, i / size);
+        for y in 0..size {
+            let dy = (y + size - iy) % size;
+            for x in 0..size {
+                let dx = (x + size - ix) % size;
+                energy[y * size + x] += sign * kernel[dy * size + dx];
+            }
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // Random initial pattern covering a tenth of the pixels
+    let mut rng = Rng::new(0, 0);
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.; n];
+    let initial = n / 10;
+    let mut placed = 0;
+    while placed < initial {
+        let i = rng.uniform_below(n);
+        if !pattern[i] {
+            toggle(&mut pattern, &mut energy, i, true);
+            placed += 1;
+        }
+    }
+
+    // Spread it out by moving the tightest cluster into the largest void until that is stable
+    for _ in 0..n {
+        let cluster = tightest_cluster(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, cluster, false);
+        let void = largest_void(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; n];
+
+    let (mut removing, mut removing_energy) = (pattern.clone(), energy.clone());
+    for rank in (0..initial).rev() {
+        let cluster = tightest_cluster(&removing, &removing_energy);
+        toggle(&mut removing, &mut removing_energy, cluster, false);
+        ranks[cluster] = rank;
+    }
+
+    // Filling the largest void of the set pixels is the same as taking the tightest cluster of
+    // the unset ones, so this also covers the second half of the ranks
+    for rank in initial..n {
+        let void = largest_void(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, void, true);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / n as f32)
+        .collect()
+}
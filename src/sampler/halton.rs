@@ -0,0 +1,87 @@
+use super::ONE_MINUS_EPSILON;
+use crate::rng::hash;
+use crate::{Rng, Sampler};
+
+/// Bases for the first dimensions. Later dimensions fall back to independent random numbers,
+/// since Halton points in large prime bases are poorly distributed anyway.
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// The Halton sequence with random digit scrambling
+///
+/// Dimension `d` is the radical inverse of the sample index in the `d`-th prime base. Every digit
+/// is shifted by a hashed amount that depends on the seed, the pixel and the dimension, which
+/// decorrelates neighbouring pixels while keeping each pixel's points well stratified.
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: u64,
+    sample_index: u64,
+    dimension: usize,
+    rng: Rng,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            sample_index: 0,
+            dimension: 0,
+            rng: Rng::new(seed, 0),
+        }
+    }
+
+    fn sample_dimension(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        match PRIMES.get(dimension) {
+            Some(&base) => {
+                let scramble = hash(&[self.seed, self.pixel, dimension as u64]);
+                scrambled_radical_inverse(base, self.sample_index, scramble)
+            }
+            None => self.rng.uniform(),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample_index: usize) {
+        self.pixel = ((y as u64) << 32) | x as u64;
+        self.sample_index = sample_index as u64;
+        self.dimension = 0;
+        self.rng = Rng::for_sample(self.seed, self.pixel, self.sample_index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.sample_dimension()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.sample_dimension(), self.sample_dimension())
+    }
+}
+
+/// Radical inverse of `index` in `base`, with each digit shifted by a pseudorandom amount
+///
+/// The leading zero digits are shifted as well, so the result is randomized down to the last bit
+/// of precision rather than only in the digits that `index` uses.
+fn scrambled_radical_inverse(base: u64, mut index: u64, scramble: u64) -> f32 {
+    let inv_base = 1. / base as f64;
+    let mut inv_base_m = 1.;
+    let mut reversed = 0u64;
+    let mut digit_index = 0;
+    while inv_base_m > 1e-9 {
+        let next = index / base;
+        let digit = index - next * base;
+        let shift = hash(&[scramble, digit_index]) % base;
+        reversed = reversed * base + (digit + shift) % base;
+        inv_base_m *= inv_base;
+        index = next;
+        digit_index += 1;
+    }
+    ((reversed as f64 * inv_base_m) as f32).min(ONE_MINUS_EPSILON)
+}
@@ -0,0 +1,31 @@
+use crate::{Rng, Sampler};
+
+/// Uniform random numbers with no correlation between dimensions or samples
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Rng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: Rng::new(seed, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample_index: usize) {
+        let pixel = ((y as u64) << 32) | x as u64;
+        self.rng = Rng::for_sample(self.seed, pixel, sample_index as u64);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.uniform()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.rng.uniform(), self.rng.uniform())
+    }
+}
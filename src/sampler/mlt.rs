@@ -0,0 +1,140 @@
+use crate::utils::erf_inv;
+use crate::{Rng, Sampler};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// One dimension of the primary sample vector, with what is needed to undo a mutation
+#[derive(Clone, Copy, Default)]
+struct PrimarySample {
+    value: f32,
+    last_modification_iteration: u64,
+    value_backup: f32,
+    modify_backup: u64,
+}
+
+/// Primary sample space mutations for Metropolis light transport, after Kelemen et al.
+///
+/// The sampler keeps the vector of every value drawn during a path sample, so the same path can
+/// be traced again, or traced with the vector slightly changed. Each iteration is either a large
+/// step, which draws fresh uniform values, or a small step, which moves every value by a
+/// normally distributed amount with standard deviation `sigma` and wraps it into [0, 1).
+/// Dimensions are only mutated once they are read, catching up on the iterations they missed.
+///
+/// It doesn't split its values by pixel: `start_pixel_sample` only restarts the vector at the
+/// first dimension.
+pub struct MltSampler {
+    rng: Rng,
+    sigma: f32,
+    large_step_probability: f32,
+    x: Vec<PrimarySample>,
+    current_iteration: u64,
+    large_step: bool,
+    last_large_step_iteration: u64,
+    dimension: usize,
+}
+
+impl MltSampler {
+    /// A sampler whose first iteration is a large step drawn from stream `stream` of `seed`
+    pub fn new(seed: u64, stream: u64, sigma: f32, large_step_probability: f32) -> Self {
+        Self {
+            // Streams of one seed are offsets of the same sequence, so mix the stream into the
+            // state as well
+            rng: Rng::for_sample(seed, 0, stream),
+            sigma,
+            large_step_probability,
+            x: Vec::new(),
+            current_iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Starts the next proposal, deciding whether it is a large step
+    pub fn start_iteration(&mut self) {
+        self.current_iteration += 1;
+        self.large_step = self.rng.uniform() < self.large_step_probability;
+        self.dimension = 0;
+    }
+
+    /// Keeps the values of the current proposal
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.current_iteration;
+        }
+    }
+
+    /// Restores the values from before the current proposal
+    pub fn reject(&mut self) {
+        for xi in self.x.iter_mut() {
+            if xi.last_modification_iteration == self.current_iteration {
+                xi.value = xi.value_backup;
+                xi.last_modification_iteration = xi.modify_backup;
+            }
+        }
+        self.current_iteration -= 1;
+    }
+
+    /// Brings dimension `index` up to date with the current iteration
+    fn ensure_ready(&mut self, index: usize) {
+        if index >= self.x.len() {
+            self.x.resize(index + 1, PrimarySample::default());
+        }
+        let xi = &mut self.x[index];
+
+        // The last large step replaced every value, including those not read since
+        if xi.last_modification_iteration < self.last_large_step_iteration {
+            xi.value = self.rng.uniform();
+            xi.last_modification_iteration = self.last_large_step_iteration;
+        }
+
+        xi.value_backup = xi.value;
+        xi.modify_backup = xi.last_modification_iteration;
+        if self.large_step {
+            xi.value = self.rng.uniform();
+        } else {
+            // Small steps missed since the value was last read add up to one wider step
+            let small_steps = (self.current_iteration - xi.last_modification_iteration) as f32;
+            let normal = std::f32::consts::SQRT_2 * erf_inv(2. * self.rng.uniform() - 1.);
+            xi.value += normal * self.sigma * small_steps.sqrt();
+            xi.value -= xi.value.floor();
+            // A tiny negative value rounds to exactly 1 when wrapped
+            xi.value = xi.value.min(super::ONE_MINUS_EPSILON);
+        }
+        xi.last_modification_iteration = self.current_iteration;
+    }
+}
+
+impl Sampler for MltSampler {
+    fn start_pixel_sample(&mut self, _x: usize, _y: usize, _sample_index: usize) {
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.ensure_ready(self.dimension);
+        let value = self.x[self.dimension].value;
+        self.dimension += 1;
+        value
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Lets the Metropolis integrator install the sampler on its thread and still drive its
+/// iterations
+impl Sampler for Rc<RefCell<MltSampler>> {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample_index: usize) {
+        self.borrow_mut().start_pixel_sample(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.borrow_mut().get_1d()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        self.borrow_mut().get_2d()
+    }
+}
@@ -0,0 +1,102 @@
+pub(crate) mod independent;
+pub use independent::IndependentSampler;
+
+pub(crate) mod stratified;
+pub use stratified::StratifiedSampler;
+
+pub(crate) mod halton;
+pub use halton::HaltonSampler;
+
+pub(crate) mod sobol;
+pub use sobol::SobolSampler;
+
+pub(crate) mod blue_noise;
+pub use blue_noise::BlueNoiseSampler;
+
+pub(crate) mod mlt;
+pub use mlt::MltSampler;
+
+use std::cell::RefCell;
+
+/// Supplies the random numbers for the samples of a pixel
+///
+/// Every value drawn during one pixel sample comes from the next dimension of the sampler: first
+/// the position inside the pixel, then the lens and time, and then whatever the materials and
+/// lights ask for along the path.
+pub trait Sampler {
+    /// Starts sample `sample_index` of pixel (`x`, `y`) at the first dimension
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample_index: usize);
+
+    /// The next dimension, in [0, 1)
+    fn get_1d(&mut self) -> f32;
+
+    /// The next two dimensions as a well-distributed pair, each in [0, 1)
+    fn get_2d(&mut self) -> (f32, f32);
+
+    /// Position inside the pixel, in [0, 1)^2
+    fn get_pixel_2d(&mut self) -> (f32, f32) {
+        self.get_2d()
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerType {
+    /// Uncorrelated random numbers
+    Independent,
+    /// Jittered strata, shuffled independently for every dimension
+    #[default]
+    Stratified,
+    /// The scrambled Halton sequence
+    Halton,
+    /// The Owen-scrambled Sobol sequence
+    Sobol,
+    /// Low-discrepancy sequences rotated per pixel by a blue-noise mask
+    BlueNoise,
+}
+
+impl SamplerType {
+    pub fn build(&self, samples_per_pixel: usize, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerType::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerType::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerType::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerType::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_SAMPLER: RefCell<Box<dyn Sampler>> =
+        RefCell::new(Box::new(IndependentSampler::new(0)));
+}
+
+/// Replaces the sampler behind `utils::random_double` on the current thread
+///
+/// Samplers are restarted for every sample, so a sample's random numbers only depend on the
+/// seed, the pixel and the sample index, not on which rayon thread happens to trace it.
+pub fn set_thread_sampler(sampler: Box<dyn Sampler>) {
+    THREAD_SAMPLER.with(|s| *s.borrow_mut() = sampler);
+}
+
+/// Swaps `sampler` with the one behind `utils::random_double` on the current thread
+///
+/// Swapping it in for some work and back out afterwards lends a sampler to the thread without
+/// building a new one, and leaves the thread's own sampler as it was for whatever runs next.
+pub fn swap_thread_sampler(sampler: &mut Box<dyn Sampler>) {
+    THREAD_SAMPLER.with(|s| std::mem::swap(&mut *s.borrow_mut(), sampler));
+}
+
+/// Runs `f` with the current thread's sampler
+pub fn with_thread_sampler<T>(f: impl FnOnce(&mut dyn Sampler) -> T) -> T {
+    THREAD_SAMPLER.with(|s| f(s.borrow_mut().as_mut()))
+}
+
+/// The largest float below 1
+pub(crate) const ONE_MINUS_EPSILON: f32 = 1. - f32::EPSILON / 2.;
+
+/// Converts the high bits of a 32-bit integer to a float in [0, 1)
+pub(crate) fn u32_to_unit_float(x: u32) -> f32 {
+    (x >> 8) as f32 * (1. / (1u32 << 24) as f32)
+}
@@ -0,0 +1,91 @@
+use super::u32_to_unit_float;
+use crate::rng::hash;
+use crate::Sampler;
+
+/// The Owen-scrambled Sobol sequence, following Burley's "Practical Hash-based Owen Scrambling"
+///
+/// Only the first two Sobol dimensions are used. Every sampler dimension gets its own shuffled
+/// and scrambled copy of them, so there is no limit on the number of dimensions and no
+/// correlation between them, while each 1D and 2D projection keeps the Sobol stratification.
+pub struct SobolSampler {
+    seed: u64,
+    pixel: u64,
+    sample_index: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    /// Shuffled sample index and scramble seed for the next dimension
+    fn next_dimension(&mut self) -> (u32, u64) {
+        let seed = hash(&[self.seed, self.pixel, self.dimension]);
+        self.dimension += 1;
+        let index = nested_uniform_scramble(self.sample_index, seed as u32);
+        (index, seed)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample_index: usize) {
+        self.pixel = ((y as u64) << 32) | x as u64;
+        self.sample_index = sample_index as u32;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (index, seed) = self.next_dimension();
+        let x = nested_uniform_scramble(sobol_dimension_0(index), (seed >> 32) as u32);
+        u32_to_unit_float(x)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let (index, seed) = self.next_dimension();
+        let seed_y = hash(&[seed]);
+        let x = nested_uniform_scramble(sobol_dimension_0(index), (seed >> 32) as u32);
+        let y = nested_uniform_scramble(sobol_dimension_1(index), seed_y as u32);
+        (u32_to_unit_float(x), u32_to_unit_float(y))
+    }
+}
+
+/// The first Sobol dimension, the base-2 van der Corput sequence
+fn sobol_dimension_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// The second Sobol dimension, generated by the primitive polynomial x + 1
+fn sobol_dimension_1(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+/// Laine-Karras style hash that only lets each bit affect the bits above it
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+/// Owen scrambling of a 32-bit fixed point value: each bit is flipped based on a hash of the
+/// bits above it
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
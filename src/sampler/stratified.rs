@@ -0,0 +1,120 @@
+use super::ONE_MINUS_EPSILON;
+use crate::rng::hash;
+use crate::{Rng, Sampler};
+
+/// Jittered stratified sampling
+///
+/// The samples of a pixel are split into `samples_per_pixel` strata for 1D values and into an
+/// `nx` by `ny` grid for 2D values, using the most square grid that has exactly
+/// `samples_per_pixel` cells. Each dimension visits the strata in its own shuffled order so
+/// dimensions don't correlate with each other.
+///
+/// A prime `samples_per_pixel` only divides into a 1 by `samples_per_pixel` grid of thin
+/// bands, which stratifies 2D values along one axis and leaves them jittered at random along the
+/// other. Counts with a squarer grid, such as 16 or 64, stratify both.
+pub struct StratifiedSampler {
+    samples_per_pixel: usize,
+    nx: usize,
+    ny: usize,
+    seed: u64,
+    pixel: u64,
+    sample_index: usize,
+    dimension: u64,
+    rng: Rng,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let nx = (1..=(samples_per_pixel as f64).sqrt() as usize)
+            .rev()
+            .find(|&nx| samples_per_pixel.is_multiple_of(nx))
+            .unwrap_or(1);
+        Self {
+            samples_per_pixel,
+            nx,
+            ny: samples_per_pixel / nx,
+            seed,
+            pixel: 0,
+            sample_index: 0,
+            dimension: 0,
+            rng: Rng::new(seed, 0),
+        }
+    }
+
+    /// The stratum this sample falls in for the current dimension
+    fn stratum(&mut self) -> usize {
+        let scramble = hash(&[self.seed, self.pixel, self.dimension]) as u32;
+        self.dimension += 1;
+        // Samples past `samples_per_pixel` wrap around to the start of a new set of strata
+        let set = self.sample_index / self.samples_per_pixel;
+        permutation_element(
+            (self.sample_index % self.samples_per_pixel) as u32,
+            self.samples_per_pixel as u32,
+            scramble ^ set as u32,
+        ) as usize
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, sample_index: usize) {
+        self.pixel = ((y as u64) << 32) | x as u64;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+        self.rng = Rng::for_sample(self.seed, self.pixel, sample_index as u64);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let stratum = self.stratum();
+        ((stratum as f32 + self.rng.uniform()) / self.samples_per_pixel as f32)
+            .min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let stratum = self.stratum();
+        // A pair uses up two dimensions
+        self.dimension += 1;
+        let (sx, sy) = (stratum % self.nx, stratum / self.nx);
+        (
+            ((sx as f32 + self.rng.uniform()) / self.nx as f32).min(ONE_MINUS_EPSILON),
+            ((sy as f32 + self.rng.uniform()) / self.ny as f32).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+/// Element `i` of a pseudorandom permutation of [0, len) selected by `p`
+///
+/// Kensler's hash-based permutation from "Correlated Multi-Jittered Sampling", which shuffles
+/// without storing the permutation.
+pub(crate) fn permutation_element(mut i: u32, len: u32, p: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            break;
+        }
+    }
+    i.wrapping_add(p) % len
+}
@@ -1,6 +1,7 @@
-use crate::{HitRecord, Ray};
-use crate::{Hittable, Interval, Material, Point3, Vec3, AABB};
+use crate::{utils, vec3, HitRecord, Point3, Ray};
+use crate::{Hittable, Interval, Material, Vec3, AABB};
 
+use std::f32::consts::PI;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -38,13 +39,14 @@ impl Cylinder {
 
         let a = dx * dx + dz * dz;
         let b = 2. * (ox * dx + oz * dz);
-        let c = ox * ox + oz * oz - self.radius * self.radius;
 
         if a.abs() < f32::EPSILON {
             return false;
         }
 
-        let discriminant = b * b - 4.0 * a * c;
+        // Equal to b² - 4ac, without the cancellation that loses rays grazing the sides
+        let cross = ox * dz - oz * dx;
+        let discriminant = 4.0 * (a * self.radius * self.radius - cross * cross);
         if discriminant < 0.0 {
             return false;
         }
@@ -88,16 +90,7 @@ impl Cylinder {
             );
 
             rec.set_face_normal(r, outward_normal);
-
-            // Calculate UV coordinates for cylindrical surface
-            self.get_sides_uv(
-                &outward_normal,
-                best_point.y(),
-                &mut rec.u,
-                &mut rec.v,
-            );
-
-            rec.mat = self.mat.clone();
+            self.set_side_details(outward_normal, best_point.y(), rec);
 
             return true;
         }
@@ -105,6 +98,18 @@ impl Cylinder {
         false
     }
 
+    /// Sets the texture coordinates, tangents and material of a point on the sides
+    fn set_side_details(&self, outward_normal: Vec3, y: f32, rec: &mut HitRecord) {
+        // Calculate UV coordinates for cylindrical surface
+        self.get_sides_uv(&outward_normal, y, &mut rec.u, &mut rec.v);
+        rec.dpdu = 2. * PI * self.radius * Vec3::new(outward_normal.z(), 0., -outward_normal.x());
+        rec.dpdv = Vec3::new(0., self.height, 0.);
+        // The normal turns with the point around the axis, and not at all along it
+        rec.dndu = rec.dpdu / self.radius;
+        rec.dndv = Vec3::default();
+        rec.mat = self.mat.clone();
+    }
+
     fn get_sides_uv(&self, outward_normal: &Vec3, hit_y: f32, u: &mut f32, v: &mut f32) {
         let theta = (-outward_normal.z()).atan2(outward_normal.x()) + std::f32::consts::PI;
         *u = theta / (2.0 * std::f32::consts::PI);
@@ -119,7 +124,12 @@ impl Cylinder {
         let y_min = self.center.y();
         let y_max = self.center.y() + self.height;
 
-        for &(y_plane, normal_y) in &[(y_min, -1.0), (y_max, 1.0)] {
+        // Both caps can be in the way, so the nearer one is checked first
+        let mut caps = [(y_min, -1.0), (y_max, 1.0)];
+        if r.direction().y() < 0. {
+            caps.reverse();
+        }
+        for &(y_plane, normal_y) in &caps {
             let t = (y_plane - r.origin().y()) / r.direction().y();
 
             if !ray_t.surrounds(t) {
@@ -137,8 +147,7 @@ impl Cylinder {
                 rec.p = hit_point;
                 let outward_normal = Vec3::new(0.0, normal_y, 0.0);
                 rec.set_face_normal(r, outward_normal);
-                self.get_cap_uv(&hit_point, &mut rec.u, &mut rec.v);
-                rec.mat = self.mat.clone();
+                self.set_cap_details(&hit_point, rec);
                 return true;
             }
         }
@@ -146,11 +155,28 @@ impl Cylinder {
         false
     }
 
+    /// Sets the texture coordinates, tangents and material of a point on a cap
+    fn set_cap_details(&self, p: &Point3, rec: &mut HitRecord) {
+        self.get_cap_uv(p, &mut rec.u, &mut rec.v);
+        rec.dpdu = Vec3::new(2. * self.radius, 0., 0.);
+        rec.dpdv = Vec3::new(0., 0., 2. * self.radius);
+        (rec.dndu, rec.dndv) = (Vec3::default(), Vec3::default());
+        rec.mat = self.mat.clone();
+    }
+
+    fn side_area(&self) -> f32 {
+        2. * PI * self.radius * self.height
+    }
+
+    fn cap_area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+
     fn get_cap_uv(&self, hit_point: &Vec3, u: &mut f32, v: &mut f32) {
         let dx = hit_point.x() - self.center.x();
-        let dy = hit_point.y() - self.center.y();
+        let dz = hit_point.z() - self.center.z();
         *u = (dx / self.radius + 1.0) * 0.5;
-        *v = (dy / self.radius + 1.0) * 0.5;
+        *v = (dz / self.radius + 1.0) * 0.5;
     }
 }
 
@@ -164,21 +190,89 @@ impl Hittable for Cylinder {
         let mut found_hit = false;
         let mut temp_rec = HitRecord::default();
 
-        if self.hit_sides(r, ray_t, &mut temp_rec) {
-            if temp_rec.t < closest_t && ray_t.surrounds(temp_rec.t) {
-                closest_t = temp_rec.t;
-                *rec = temp_rec.clone();
-                found_hit = true;
-            }
+        if self.hit_sides(r, ray_t, &mut temp_rec)
+            && temp_rec.t < closest_t
+            && ray_t.surrounds(temp_rec.t)
+        {
+            closest_t = temp_rec.t;
+            *rec = temp_rec.clone();
+            found_hit = true;
         }
 
-        if self.hit_caps(r, ray_t, &mut temp_rec) {
-            if temp_rec.t < closest_t && ray_t.surrounds(temp_rec.t) {
-                *rec = temp_rec.clone();
-                found_hit = true;
-            }
+        if self.hit_caps(r, ray_t, &mut temp_rec)
+            && temp_rec.t < closest_t
+            && ray_t.surrounds(temp_rec.t)
+        {
+            *rec = temp_rec.clone();
+            found_hit = true;
         }
 
         found_hit
     }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let (rec, _) = self
+            .sample_surface(0.)
+            .expect("cylinders can always be sampled");
+        rec.p - origin
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        // Points are sampled by area, and a line through the cylinder crosses its surface twice,
+        // so the density of a direction adds up both points it could have come from
+        let ray = Ray::new(origin, vec3::unit_vector(direction), 0.);
+        let area = self.area();
+        let mut pdf = 0.;
+        let mut t_min = 0.001;
+        let mut rec = HitRecord::default();
+        for _ in 0..2 {
+            if !self.hit(&ray, &mut Interval::new(t_min, f32::INFINITY), &mut rec) {
+                break;
+            }
+            let cosine = vec3::dot(ray.direction(), rec.normal).abs();
+            if cosine > 0. {
+                pdf += rec.t * rec.t / (cosine * area);
+            }
+            t_min = rec.t + 1e-4;
+        }
+        pdf
+    }
+
+    fn sample_surface(&self, _time: f32) -> Option<(HitRecord, f32)> {
+        let area = self.area();
+        let (r1, r2) = utils::random_2d();
+        let choice = utils::random_double() * area;
+        let mut rec = HitRecord {
+            front_face: true,
+            ..Default::default()
+        };
+        if choice < self.side_area() {
+            let phi = 2. * PI * r1;
+            let outward_normal = Vec3::new(phi.cos(), 0., phi.sin());
+            let y = self.center.y() + r2 * self.height;
+            rec.p = Point3::new(self.center.x(), y, self.center.z()) + self.radius * outward_normal;
+            rec.normal = outward_normal;
+            self.set_side_details(outward_normal, y, &mut rec);
+        } else {
+            let (y, normal_y) = if choice < self.side_area() + self.cap_area() {
+                (self.center.y(), -1.)
+            } else {
+                (self.center.y() + self.height, 1.)
+            };
+            let (r, phi) = (self.radius * r1.sqrt(), 2. * PI * r2);
+            rec.p = Point3::new(
+                self.center.x() + r * phi.cos(),
+                y,
+                self.center.z() + r * phi.sin(),
+            );
+            rec.normal = Vec3::new(0., normal_y, 0.);
+            let p = rec.p;
+            self.set_cap_details(&p, &mut rec);
+        }
+        Some((rec, 1. / area))
+    }
+
+    fn area(&self) -> f32 {
+        self.side_area() + 2. * self.cap_area()
+    }
 }
@@ -7,14 +7,33 @@ pub use sphere::Sphere;
 pub mod cylinder;
 pub use cylinder::Cylinder;
 
-use crate::{MaterialRef, Point3, RotateY, Translate, Vec3};
+pub mod triangle;
+pub use triangle::{Triangle, TriangleMesh};
+
+pub mod obj;
+pub use obj::load_obj;
+
+pub mod ply;
+pub use ply::load_ply;
+
+pub mod stl;
+pub use stl::load_stl;
+
+use crate::{
+    BvhNode, ConstantMedium, HittableList, Instance, MaterialRef, Matrix4, Point3, Quaternion,
+    Texture, TextureRef, Transform, Vec3,
+};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
-#[serde(tag = "type", rename_all = "lowercase")]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ObjectConfig {
+    /// A sphere, moving linearly to `center2` over the shutter interval when it is given
     Sphere {
         center: Point3,
+        #[serde(default)]
+        center2: Option<Point3>,
         radius: f32,
         material: MaterialRef,
         #[serde(default)]
@@ -35,23 +54,85 @@ pub enum ObjectConfig {
         #[serde(default)]
         transform: TransformConfig,
     },
+    /// A Y-aligned cylinder whose bottom cap is centred on `center`
+    Cylinder {
+        center: Point3,
+        radius: f32,
+        height: f32,
+        material: MaterialRef,
+        #[serde(default)]
+        transform: TransformConfig,
+    },
+    Triangle {
+        vertices: [Point3; 3],
+        material: MaterialRef,
+        #[serde(default)]
+        transform: TransformConfig,
+    },
+    /// A triangle mesh loaded from an OBJ, PLY or binary STL file. The material may be left out
+    /// for PLY files with vertex colours.
+    Mesh {
+        path: String,
+        #[serde(default)]
+        material: Option<MaterialRef>,
+        #[serde(default)]
+        transform: TransformConfig,
+    },
+    /// A participating medium of constant `density` filling the `boundary` object
+    ConstantMedium {
+        boundary: Box<ObjectConfig>,
+        density: f32,
+        #[serde(alias = "color")]
+        texture: TextureRef,
+        #[serde(default)]
+        transform: TransformConfig,
+    },
+    /// Several objects sharing one transform, optionally wrapped in their own BVH
+    Group {
+        objects: Vec<ObjectConfig>,
+        #[serde(default = "ObjectConfig::default_bvh")]
+        bvh: bool,
+        #[serde(default)]
+        transform: TransformConfig,
+    },
 }
 
 impl ObjectConfig {
+    fn default_bvh() -> bool {
+        true
+    }
+
+    pub fn transform(&self) -> &TransformConfig {
+        match self {
+            ObjectConfig::Sphere { transform, .. }
+            | ObjectConfig::Quad { transform, .. }
+            | ObjectConfig::Box { transform, .. }
+            | ObjectConfig::Cylinder { transform, .. }
+            | ObjectConfig::Triangle { transform, .. }
+            | ObjectConfig::Mesh { transform, .. }
+            | ObjectConfig::ConstantMedium { transform, .. }
+            | ObjectConfig::Group { transform, .. } => transform,
+        }
+    }
+
     pub fn to_hittable(
         &self,
-        materials: &std::collections::HashMap<String, crate::MaterialConfig>,
+        materials: &HashMap<String, crate::MaterialConfig>,
+        textures: &HashMap<String, Arc<dyn Texture>>,
     ) -> std::result::Result<std::sync::Arc<dyn crate::Hittable>, String> {
         let base_object: std::sync::Arc<dyn crate::Hittable> = match self {
             &ObjectConfig::Sphere {
                 center,
+                center2,
                 radius,
                 ref material,
                 ..
             } => {
-                // let material = crate::MaterialRef::Reference(material.clone());
-                let material = material.resolve(materials)?;
-                std::sync::Arc::new(Sphere::stationary(center, radius, material.into()))
+                let material = material.build(materials, textures)?;
+                match center2 {
+                    Some(center2) => Arc::new(Sphere::moving(center, center2, radius, material)),
+                    None => Arc::new(Sphere::stationary(center, radius, material)),
+                }
             }
             &ObjectConfig::Quad {
                 corner,
@@ -60,8 +141,8 @@ impl ObjectConfig {
                 ref material,
                 ..
             } => {
-                let material = material.resolve(materials)?;
-                std::sync::Arc::new(Quadrilateral::new(corner, u, v, material.into()))
+                let material = material.build(materials, textures)?;
+                std::sync::Arc::new(Quadrilateral::new(corner, u, v, material))
             }
             ObjectConfig::Box {
                 min,
@@ -69,21 +150,156 @@ impl ObjectConfig {
                 ref material,
                 transform: _,
             } => {
-                let resolved_material = material.resolve(materials)?;
-                Arc::new(new_box(*min, *max, resolved_material.into()))
+                let resolved_material = material.build(materials, textures)?;
+                Arc::new(new_box(*min, *max, resolved_material))
+            }
+            &ObjectConfig::Cylinder {
+                center,
+                radius,
+                height,
+                ref material,
+                ..
+            } => {
+                let material = material.build(materials, textures)?;
+                Arc::new(Cylinder::new(center, radius, height, material))
+            }
+            ObjectConfig::Triangle {
+                vertices, material, ..
+            } => {
+                let material = material.build(materials, textures)?;
+                let mesh =
+                    TriangleMesh::new(vertices.to_vec(), None, None, vec![[0, 1, 2]], material)?;
+                Arc::new(Triangle::new(Arc::new(mesh), 0))
+            }
+            ObjectConfig::Mesh { path, material, .. } => {
+                let material = match material {
+                    Some(material) => Some(material.build(materials, textures)?),
+                    None => None,
+                };
+                let mesh = Arc::new(TriangleMesh::load(path, material)?);
+                Arc::new(mesh.to_bvh()?)
+            }
+            ObjectConfig::ConstantMedium {
+                boundary,
+                density,
+                texture,
+                ..
+            } => {
+                let boundary = boundary.to_hittable(materials, textures)?;
+                Arc::new(ConstantMedium::new(
+                    boundary,
+                    *density,
+                    texture.resolve(textures)?,
+                ))
+            }
+            ObjectConfig::Group { objects, bvh, .. } => {
+                let mut list = HittableList::new();
+                for object in objects {
+                    list.add(object.to_hittable(materials, textures)?);
+                }
+                match (*bvh, list.objects.is_empty()) {
+                    (_, true) => return Err("group must contain at least one object".into()),
+                    (true, false) => Arc::new(BvhNode::from_list(list)),
+                    (false, false) => Arc::new(list),
+                }
             }
         };
 
-        let transformed_object = match self {
-            ObjectConfig::Sphere { transform, .. }
-            | ObjectConfig::Quad { transform, .. }
-            | ObjectConfig::Box { transform, .. } => transform.apply_to_hittable(base_object),
-        };
+        self.transform().apply_to_hittable(base_object)
+    }
+}
 
-        Ok(transformed_object)
+/// Rotation by `degrees` about an arbitrary `axis`
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+pub struct AxisAngleConfig {
+    pub axis: Vec3,
+    pub degrees: f32,
+}
+
+/// Orients an object so that its local +Z axis points at `target`, with its local +Y axis as
+/// close to `up` as possible
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+pub struct LookAtConfig {
+    pub target: Point3,
+    #[serde(default = "LookAtConfig::default_up")]
+    pub up: Vec3,
+}
+
+impl LookAtConfig {
+    fn default_up() -> Vec3 {
+        Vec3::new(0., 1., 0.)
+    }
+
+    /// Rotation about `position` that points the object at the target
+    fn to_matrix(self, position: Point3) -> Result<Matrix4, String> {
+        let rotation =
+            Matrix4::look_rotation(self.target - position, self.up).ok_or_else(|| {
+                format!(
+                    "look_at target {:?} is at the object position or straight along up",
+                    self.target
+                )
+            })?;
+        Ok(Matrix4::translate(position) * rotation * Matrix4::translate(-position))
     }
 }
 
+/// A single step of an ordered `transforms = [...]` list
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformStep {
+    Translate(Vec3),
+    Scale(Vec3),
+    RotateX(f32),
+    RotateY(f32),
+    RotateZ(f32),
+    Rotate(AxisAngleConfig),
+    Quaternion(Quaternion),
+    /// Rotates about the point the object's origin has been moved to by the preceding steps
+    LookAt(LookAtConfig),
+    /// A raw row-major affine matrix, whose last row must be `[0, 0, 0, 1]`
+    Matrix([[f32; 4]; 4]),
+}
+
+impl TransformStep {
+    /// Applies this step after the transform `m` built so far
+    fn apply(&self, m: Matrix4) -> Result<Matrix4, String> {
+        let step = match *self {
+            TransformStep::Translate(offset) => Matrix4::translate(offset),
+            TransformStep::Scale(factors) => Matrix4::scale(factors),
+            TransformStep::RotateX(degrees) => Matrix4::rotate_x(degrees),
+            TransformStep::RotateY(degrees) => Matrix4::rotate_y(degrees),
+            TransformStep::RotateZ(degrees) => Matrix4::rotate_z(degrees),
+            TransformStep::Rotate(AxisAngleConfig { axis, degrees }) => {
+                if axis.near_zero() {
+                    return Err("rotation axis must not be zero".into());
+                }
+                Matrix4::rotate(axis, degrees)
+            }
+            TransformStep::Quaternion(q) => q.to_matrix().ok_or("quaternion must not be zero")?,
+            TransformStep::LookAt(look_at) => {
+                look_at.to_matrix(m.transform_point(Point3::default()))?
+            }
+            TransformStep::Matrix(rows) => {
+                // Instances map points and normals as affine transforms, with no divide by w
+                if rows[3] != [0., 0., 0., 1.] {
+                    return Err(format!(
+                        "matrix must be affine, with a last row of [0, 0, 0, 1], not {:?}",
+                        rows[3]
+                    ));
+                }
+                Matrix4::new(rows)
+            }
+        };
+        Ok(step * m)
+    }
+}
+
+/// Placement of an object in the world
+///
+/// The individual fields are applied in a fixed order: `scale`, `rotate_x`, `rotate_y`,
+/// `rotate_z`, `rotate`, `quaternion`, `look_at` and finally `translate`. The `look_at`
+/// orientation is computed from the translated position. Any `transforms` steps are then applied
+/// in the order they are listed. Everything is composed into a single `Instance`.
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
 pub struct TransformConfig {
     #[serde(default)]
@@ -96,36 +312,64 @@ pub struct TransformConfig {
     pub rotate_z: Option<f32>, // degrees
     #[serde(default)]
     pub scale: Option<Vec3>,
+    #[serde(default)]
+    pub rotate: Option<AxisAngleConfig>,
+    #[serde(default)]
+    pub quaternion: Option<Quaternion>,
+    #[serde(default)]
+    pub look_at: Option<LookAtConfig>,
+    #[serde(default)]
+    pub transforms: Vec<TransformStep>,
 }
 
 impl TransformConfig {
-    pub fn apply_to_hittable(
-        &self,
-        hittable: Arc<dyn crate::Hittable>,
-    ) -> Arc<dyn crate::Hittable> {
-        let mut result = hittable;
-
-        // Apply rotations first (in order: X, Y, Z)
-        //if let Some(angle_x) = self.rotate_x {
-        //    result = Arc::new(RotateX::new(result, angle_x));
-        //}
-        if let Some(angle_y) = self.rotate_y {
-            result = Arc::new(RotateY::new(result, angle_y));
-        }
-        //if let Some(angle_z) = self.rotate_z {
-        //    result = Arc::new(RotateZ::new(result, angle_z));
-        //}
+    pub fn is_identity(&self) -> bool {
+        self.translate.is_none()
+            && self.rotate_x.is_none()
+            && self.rotate_y.is_none()
+            && self.rotate_z.is_none()
+            && self.scale.is_none()
+            && self.rotate.is_none()
+            && self.quaternion.is_none()
+            && self.look_at.is_none()
+            && self.transforms.is_empty()
+    }
 
-        // Apply scale (if you have it implemented)
-        // if let Some(scale_vec) = self.scale {
-        //     result = Arc::new(Scale::new(result, scale_vec));
-        // }
+    pub fn to_transform(&self) -> Result<Transform, String> {
+        let fields = [
+            self.scale.map(TransformStep::Scale),
+            self.rotate_x.map(TransformStep::RotateX),
+            self.rotate_y.map(TransformStep::RotateY),
+            self.rotate_z.map(TransformStep::RotateZ),
+            self.rotate.map(TransformStep::Rotate),
+            self.quaternion.map(TransformStep::Quaternion),
+        ];
 
-        // Apply translation last
+        let mut m = Matrix4::identity();
+        for step in fields.iter().flatten() {
+            m = step.apply(m)?;
+        }
         if let Some(translation) = self.translate {
-            result = Arc::new(Translate::new(result, translation));
+            m = Matrix4::translate(translation) * m;
+        }
+        // Rotating about the translated position is the same as rotating before translating
+        if let Some(look_at) = self.look_at {
+            m = look_at.to_matrix(self.translate.unwrap_or_default())? * m;
         }
+        for step in &self.transforms {
+            m = step.apply(m)?;
+        }
+
+        Transform::new(m).ok_or_else(|| format!("transform {self:?} is not invertible"))
+    }
 
-        result
+    pub fn apply_to_hittable(
+        &self,
+        hittable: Arc<dyn crate::Hittable>,
+    ) -> Result<Arc<dyn crate::Hittable>, String> {
+        if self.is_identity() {
+            return Ok(hittable);
+        }
+        Ok(Arc::new(Instance::new(hittable, self.to_transform()?)))
     }
 }
@@ -0,0 +1,139 @@
+use crate::{Material, Point3, TriangleMesh, Vec3};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Loads a Wavefront OBJ file into a `TriangleMesh`
+///
+/// Only geometry is read: `v`, `vt`, `vn` and `f` statements. Polygons with more than three
+/// vertices are fan-triangulated, and everything else (groups, smoothing, `usemtl`, ...) is
+/// ignored.
+pub fn load_obj(path: &str, material: Arc<dyn Material>) -> Result<TriangleMesh, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("couldn't read '{path}': {e}"))?;
+    parse_obj(&contents, material).map_err(|e| format!("{path}: {e}"))
+}
+
+pub fn parse_obj(contents: &str, material: Arc<dyn Material>) -> Result<TriangleMesh, String> {
+    let mut obj_positions: Vec<Point3> = Vec::new();
+    let mut obj_uvs: Vec<[f32; 2]> = Vec::new();
+    let mut obj_normals: Vec<Vec3> = Vec::new();
+
+    // OBJ indexes positions, uvs and normals separately, so every distinct combination becomes
+    // its own vertex in the mesh
+    let mut vertex_lookup: HashMap<(usize, Option<usize>, Option<usize>), usize> = HashMap::new();
+    let mut vertices: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+    let mut indices: Vec<[usize; 3]> = Vec::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line_number = line_number + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => obj_positions.push(parse_vec3(&mut tokens, line_number)?),
+            "vn" => obj_normals.push(parse_vec3(&mut tokens, line_number)?),
+            "vt" => {
+                let u = parse_float(tokens.next(), line_number)?;
+                let v = match tokens.next() {
+                    Some(token) => parse_float(Some(token), line_number)?,
+                    None => 0.,
+                };
+                obj_uvs.push([u, v]);
+            }
+            "f" => {
+                let mut face: Vec<usize> = Vec::new();
+                for token in tokens {
+                    let key = parse_face_vertex(
+                        token,
+                        (obj_positions.len(), obj_uvs.len(), obj_normals.len()),
+                        line_number,
+                    )?;
+                    let index = *vertex_lookup.entry(key).or_insert_with(|| {
+                        vertices.push(key);
+                        vertices.len() - 1
+                    });
+                    face.push(index);
+                }
+                if face.len() < 3 {
+                    return Err(format!(
+                        "line {line_number}: face needs at least 3 vertices"
+                    ));
+                }
+                for i in 1..face.len() - 1 {
+                    indices.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Only keep normals/uvs when every vertex has one, otherwise interpolation is meaningless
+    let positions = vertices.iter().map(|&(p, _, _)| obj_positions[p]).collect();
+    let uvs = vertices
+        .iter()
+        .map(|&(_, uv, _)| uv.map(|uv| obj_uvs[uv]))
+        .collect::<Option<Vec<_>>>();
+    let normals = vertices
+        .iter()
+        .map(|&(_, _, n)| n.map(|n| obj_normals[n]))
+        .collect::<Option<Vec<_>>>();
+
+    TriangleMesh::new(positions, normals, uvs, indices, material)
+}
+
+fn parse_float(token: Option<&str>, line_number: usize) -> Result<f32, String> {
+    let token = token.ok_or_else(|| format!("line {line_number}: missing value"))?;
+    token
+        .parse::<f32>()
+        .map_err(|_| format!("line {line_number}: '{token}' is not a number"))
+}
+
+fn parse_vec3<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    line_number: usize,
+) -> Result<Vec3, String> {
+    Ok(Vec3::new(
+        parse_float(tokens.next(), line_number)?,
+        parse_float(tokens.next(), line_number)?,
+        parse_float(tokens.next(), line_number)?,
+    ))
+}
+
+/// Resolves a 1-based (or negative, relative) OBJ index to a 0-based one
+fn resolve_index(token: &str, count: usize, line_number: usize) -> Result<usize, String> {
+    let index = token
+        .parse::<isize>()
+        .map_err(|_| format!("line {line_number}: '{token}' is not a valid index"))?;
+    let resolved = if index < 0 {
+        count as isize + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as isize {
+        return Err(format!("line {line_number}: index {index} is out of range"));
+    }
+    Ok(resolved as usize)
+}
+
+/// Parses one of `v`, `v/vt`, `v//vn` or `v/vt/vn`
+fn parse_face_vertex(
+    token: &str,
+    (positions, uvs, normals): (usize, usize, usize),
+    line_number: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = token.split('/');
+    let p = resolve_index(parts.next().unwrap_or(""), positions, line_number)?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(uv) => Some(resolve_index(uv, uvs, line_number)?),
+    };
+    let n = match parts.next() {
+        Some("") | None => None,
+        Some(n) => Some(resolve_index(n, normals, line_number)?),
+    };
+    Ok((p, uv, n))
+}
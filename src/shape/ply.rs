@@ -0,0 +1,437 @@
+use crate::{Color, Material, Point3, TriangleMesh, Vec3};
+
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return Err(format!("unknown property type '{name}'")),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// The value that maps to full intensity when this type stores a colour channel
+    fn color_scale(self) -> f64 {
+        match self {
+            Self::U8 | Self::I8 => 255.,
+            Self::U16 | Self::I16 => 65535.,
+            _ => 1.,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar {
+        name: String,
+        ty: ScalarType,
+    },
+    List {
+        name: String,
+        count: ScalarType,
+        item: ScalarType,
+    },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar { name, .. } | Property::List { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads property values out of the body of the file, whatever its encoding
+struct BodyReader<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    pos: usize,
+    /// Tokens left on the current line of an ASCII body
+    tokens: std::vec::IntoIter<&'a str>,
+    line: usize,
+}
+
+impl<'a> BodyReader<'a> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, String> {
+        match self.format {
+            Format::Ascii => {
+                let token = self.next_token()?;
+                token
+                    .parse::<f64>()
+                    .map_err(|_| format!("line {}: '{token}' is not a number", self.line))
+            }
+            Format::BinaryLittleEndian | Format::BinaryBigEndian => {
+                let size = ty.size();
+                let Some(raw) = self.bytes.get(self.pos..self.pos + size) else {
+                    return Err(format!(
+                        "file is truncated: needed {size} more bytes at offset {}",
+                        self.pos
+                    ));
+                };
+                self.pos += size;
+                let little = self.format == Format::BinaryLittleEndian;
+                macro_rules! decode {
+                    ($t:ty) => {{
+                        let raw = raw.try_into().unwrap();
+                        if little {
+                            <$t>::from_le_bytes(raw) as f64
+                        } else {
+                            <$t>::from_be_bytes(raw) as f64
+                        }
+                    }};
+                }
+                Ok(match ty {
+                    ScalarType::I8 => decode!(i8),
+                    ScalarType::U8 => decode!(u8),
+                    ScalarType::I16 => decode!(i16),
+                    ScalarType::U16 => decode!(u16),
+                    ScalarType::I32 => decode!(i32),
+                    ScalarType::U32 => decode!(u32),
+                    ScalarType::F32 => decode!(f32),
+                    ScalarType::F64 => decode!(f64),
+                })
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<&'a str, String> {
+        loop {
+            if let Some(token) = self.tokens.next() {
+                return Ok(token);
+            }
+            let rest = &self.bytes[self.pos..];
+            if rest.is_empty() {
+                return Err(format!(
+                    "file is truncated: expected more values after line {}",
+                    self.line
+                ));
+            }
+            let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            let line = std::str::from_utf8(&rest[..end])
+                .map_err(|_| format!("line {}: body is not valid text", self.line + 1))?;
+            self.pos += (end + 1).min(rest.len());
+            self.line += 1;
+            self.tokens = line.split_whitespace().collect::<Vec<_>>().into_iter();
+        }
+    }
+
+    /// ASCII elements occupy exactly one line each
+    fn end_element(&mut self) -> Result<(), String> {
+        if self.format == Format::Ascii && self.tokens.next().is_some() {
+            return Err(format!("line {}: too many values for element", self.line));
+        }
+        Ok(())
+    }
+}
+
+/// Loads a PLY file (ASCII, binary little-endian or binary big-endian) into a `TriangleMesh`
+///
+/// Vertex normals, texture coordinates and colours are read when the file provides them. If
+/// `material` is `None`, the mesh is shaded with its vertex colours, which must then be present.
+pub fn load_ply(path: &str, material: Option<Arc<dyn Material>>) -> Result<TriangleMesh, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("couldn't read '{path}': {e}"))?;
+    parse_ply(&bytes, material).map_err(|e| format!("{path}: {e}"))
+}
+
+pub fn parse_ply(
+    bytes: &[u8],
+    material: Option<Arc<dyn Material>>,
+) -> Result<TriangleMesh, String> {
+    let (format, elements, body_start, header_lines) = parse_header(bytes)?;
+
+    let mut reader = BodyReader {
+        format,
+        bytes,
+        pos: body_start,
+        tokens: Vec::new().into_iter(),
+        line: header_lines,
+    };
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut colors: Vec<Color> = Vec::new();
+    let mut indices: Vec<[usize; 3]> = Vec::new();
+    let (mut has_normals, mut has_uvs, mut has_colors) = (false, false, false);
+
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let find = |names: &[&str]| {
+                    element
+                        .properties
+                        .iter()
+                        .position(|p| names.contains(&p.name()))
+                };
+                let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+                let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+                let uv = [
+                    find(&["u", "s", "texture_u", "texture_s"]),
+                    find(&["v", "t", "texture_v", "texture_t"]),
+                ];
+                let color = [
+                    find(&["red", "r", "diffuse_red"]),
+                    find(&["green", "g", "diffuse_green"]),
+                    find(&["blue", "b", "diffuse_blue"]),
+                ];
+                if position.iter().any(Option::is_none) {
+                    return Err("vertex element is missing x, y or z".into());
+                }
+                has_normals = normal.iter().all(Option::is_some);
+                has_uvs = uv.iter().all(Option::is_some);
+                has_colors = color.iter().all(Option::is_some);
+
+                let mut values = vec![0.; element.properties.len()];
+                for _ in 0..element.count {
+                    for (value, property) in values.iter_mut().zip(&element.properties) {
+                        *value = match property {
+                            Property::Scalar { ty, .. } => reader.read(*ty)?,
+                            Property::List { .. } => {
+                                skip_list(&mut reader, property)?;
+                                0.
+                            }
+                        };
+                    }
+                    reader.end_element()?;
+
+                    let get = |i: Option<usize>| values[i.unwrap()] as f32;
+                    positions.push(Vec3::new(
+                        get(position[0]),
+                        get(position[1]),
+                        get(position[2]),
+                    ));
+                    if has_normals {
+                        normals.push(Vec3::new(get(normal[0]), get(normal[1]), get(normal[2])));
+                    }
+                    if has_uvs {
+                        uvs.push([get(uv[0]), get(uv[1])]);
+                    }
+                    if has_colors {
+                        let channel = |i: Option<usize>| {
+                            let scale = match &element.properties[i.unwrap()] {
+                                Property::Scalar { ty, .. } => ty.color_scale(),
+                                Property::List { .. } => 1.,
+                            };
+                            (values[i.unwrap()] / scale) as f32
+                        };
+                        colors.push(Color::new(
+                            channel(color[0]),
+                            channel(color[1]),
+                            channel(color[2]),
+                        ));
+                    }
+                }
+            }
+            "face" => {
+                let list = element
+                    .properties
+                    .iter()
+                    .position(|p| {
+                        matches!(p, Property::List { name, .. }
+                            if name == "vertex_indices" || name == "vertex_index")
+                    })
+                    .ok_or("face element has no vertex_indices list")?;
+
+                for face_number in 0..element.count {
+                    let mut face: Vec<usize> = Vec::new();
+                    for (i, property) in element.properties.iter().enumerate() {
+                        match property {
+                            Property::List { count, item, .. } if i == list => {
+                                let n = reader.read(*count)?;
+                                for _ in 0..n as usize {
+                                    let index = reader.read(*item)?;
+                                    if index < 0. {
+                                        return Err(format!(
+                                            "face {face_number} has a negative vertex index"
+                                        ));
+                                    }
+                                    // Float lists are allowed, but only hold whole indices
+                                    if index.fract() != 0. {
+                                        return Err(format!(
+                                            "face {face_number} has the vertex index {index}, \
+                                             which is not a whole number"
+                                        ));
+                                    }
+                                    if index >= usize::MAX as f64 {
+                                        return Err(format!(
+                                            "face {face_number} has the vertex index {index}, \
+                                             which is too large"
+                                        ));
+                                    }
+                                    face.push(index as usize);
+                                }
+                            }
+                            Property::List { .. } => skip_list(&mut reader, property)?,
+                            Property::Scalar { ty, .. } => {
+                                reader.read(*ty)?;
+                            }
+                        }
+                    }
+                    reader.end_element()?;
+
+                    if face.len() < 3 {
+                        return Err(format!("face {face_number} has fewer than 3 vertices"));
+                    }
+                    for i in 1..face.len() - 1 {
+                        indices.push([face[0], face[i], face[i + 1]]);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        match property {
+                            Property::Scalar { ty, .. } => {
+                                reader.read(*ty)?;
+                            }
+                            Property::List { .. } => skip_list(&mut reader, property)?,
+                        }
+                    }
+                    reader.end_element()?;
+                }
+            }
+        }
+    }
+
+    TriangleMesh::with_vertex_colors(
+        positions,
+        has_normals.then_some(normals),
+        has_uvs.then_some(uvs),
+        has_colors.then_some(colors),
+        indices,
+        material,
+    )
+}
+
+fn skip_list(reader: &mut BodyReader, property: &Property) -> Result<(), String> {
+    if let Property::List { count, item, .. } = property {
+        let n = reader.read(*count)?;
+        for _ in 0..n as usize {
+            reader.read(*item)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the format, the declared elements, the byte offset of the body and the number of
+/// header lines
+fn parse_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize, usize), String> {
+    // The header ends at the first line that is exactly `end_header`, which can't be searched
+    // for directly since comments may contain it too
+    let mut header_lines: Vec<&str> = Vec::new();
+    let mut pos = 0;
+    let body_start = loop {
+        let rest = &bytes[pos..];
+        if rest.is_empty() && header_lines.is_empty() {
+            return Err("not a PLY file (missing 'ply' magic)".into());
+        } else if rest.is_empty() {
+            return Err("missing 'end_header'".into());
+        }
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        let line = std::str::from_utf8(&rest[..end])
+            .map_err(|_| format!("line {}: header is not valid text", header_lines.len() + 1))?;
+        pos += (end + 1).min(rest.len());
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if header_lines.is_empty() && line.trim() != "ply" {
+            return Err("not a PLY file (missing 'ply' magic)".into());
+        }
+        if line.trim() == "end_header" {
+            break pos;
+        }
+        header_lines.push(line);
+    };
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for (line_number, line) in header_lines.iter().enumerate().skip(1) {
+        let line_number = line_number + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => (),
+            ["format", kind, _version] => {
+                format = Some(match *kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("line {line_number}: unknown format '{kind}'")),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("line {line_number}: invalid element count '{count}'"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| format!("line {line_number}: property outside of an element"))?
+                .properties
+                .push(Property::List {
+                    name: name.to_string(),
+                    count: ScalarType::parse(count)
+                        .map_err(|e| format!("line {line_number}: {e}"))?,
+                    item: ScalarType::parse(item)
+                        .map_err(|e| format!("line {line_number}: {e}"))?,
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| format!("line {line_number}: property outside of an element"))?
+                .properties
+                .push(Property::Scalar {
+                    name: name.to_string(),
+                    ty: ScalarType::parse(ty).map_err(|e| format!("line {line_number}: {e}"))?,
+                }),
+            _ => {
+                return Err(format!(
+                    "line {line_number}: malformed header line '{line}'"
+                ))
+            }
+        }
+    }
+
+    let format = format.ok_or("header has no 'format' line")?;
+    Ok((format, elements, body_start, header_lines.len() + 1))
+}
@@ -1,9 +1,23 @@
+use crate::vecmath::{angle_between, spherical_quad_area};
 use crate::{
     utils, vec3, HitRecord, Hittable, HittableList, Interval, Material, Point3, Ray, Vec3, AABB,
 };
 
+use std::f32::consts::PI;
 use std::sync::Arc;
 
+/// Solid angles below which rectangles are sampled by area, since they lose too much precision
+/// to be sampled as spherical rectangles and look the same either way
+const MIN_SPHERICAL_SAMPLE_AREA: f32 = 3e-4;
+/// Solid angles above which rectangles are sampled by area, since they nearly surround the
+/// point they are seen from and sampling them as spherical rectangles becomes unstable
+const MAX_SPHERICAL_SAMPLE_AREA: f32 = 6.22;
+
+/// A parallelogram spanned by `u` and `v` from the corner `q`
+///
+/// As a light, a rectangle is sampled uniformly over the solid angle it covers, following
+/// Ureña et al., "An Area-Preserving Parametrization for Spherical Rectangles". Other
+/// parallelograms are sampled by area.
 pub struct Quadrilateral {
     q: Point3,
     u: Vec3,
@@ -14,6 +28,8 @@ pub struct Quadrilateral {
     normal: Vec3,
     d: f32,
     area: f32,
+    /// Whether `u` and `v` are perpendicular
+    rectangle: bool,
 }
 
 impl Quadrilateral {
@@ -24,6 +40,7 @@ impl Quadrilateral {
         let w = n / vec3::dot(n, n);
 
         let area = n.length();
+        let rectangle = vec3::dot(u, v).abs() <= 1e-4 * u.length() * v.length();
 
         let bbox_diagonal_1 = AABB::around_points(q, q + u + v);
         let bbox_diagonal_2 = AABB::around_points(q + u, q + v);
@@ -39,9 +56,83 @@ impl Quadrilateral {
             normal,
             d,
             area,
+            rectangle,
         }
     }
 
+    /// The solid angle the quad covers from `origin`, when it is a rectangle that is sampled
+    /// over it
+    fn spherical_area(&self, origin: Point3) -> Option<f32> {
+        if !self.rectangle {
+            return None;
+        }
+        let [a, b, c, d] = [
+            self.q,
+            self.q + self.u,
+            self.q + self.u + self.v,
+            self.q + self.v,
+        ]
+        .map(|corner| vec3::unit_vector(corner - origin));
+        let area = spherical_quad_area(a, b, c, d);
+        (MIN_SPHERICAL_SAMPLE_AREA..=MAX_SPHERICAL_SAMPLE_AREA)
+            .contains(&area)
+            .then_some(area)
+    }
+
+    /// Maps `(s, t)` in the unit square to a point of the rectangle, uniformly over the solid
+    /// angle it covers from `origin`
+    fn sample_spherical_rectangle(&self, origin: Point3, s: f32, t: f32) -> Point3 {
+        let (ex, ey) = (self.u.length(), self.v.length());
+        let (x, y) = (self.u / ex, self.v / ey);
+        let mut z = vec3::cross(x, y);
+
+        // The rectangle in a frame around `origin`, with `z` pointing away from it
+        let d = self.q - origin;
+        let mut z0 = vec3::dot(d, z);
+        if z0 > 0. {
+            z = -z;
+            z0 = -z0;
+        }
+        let (x0, y0) = (vec3::dot(d, x), vec3::dot(d, y));
+        let (x1, y1) = (x0 + ex, y0 + ey);
+
+        // Normals of the planes through `origin` and each edge, and the angles between them
+        let v00 = Vec3::new(x0, y0, z0);
+        let v01 = Vec3::new(x0, y1, z0);
+        let v10 = Vec3::new(x1, y0, z0);
+        let v11 = Vec3::new(x1, y1, z0);
+        let n0 = vec3::unit_vector(vec3::cross(v00, v10));
+        let n1 = vec3::unit_vector(vec3::cross(v10, v11));
+        let n2 = vec3::unit_vector(vec3::cross(v11, v01));
+        let n3 = vec3::unit_vector(vec3::cross(v01, v00));
+        let g0 = angle_between(-n0, n1);
+        let g1 = angle_between(-n1, n2);
+        let g2 = angle_between(-n2, n3);
+        let g3 = angle_between(-n3, n0);
+
+        // Picks the column `xu` that splits off a fraction `s` of the solid angle
+        let (b0, b1) = (n0.z(), n2.z());
+        let au = s * (g0 + g1 - 2. * PI) + (s - 1.) * (g2 + g3);
+        let fu = (au.cos() * b0 - b1) / au.sin();
+        let cu = (1. / (fu * fu + b0 * b0).sqrt())
+            .copysign(fu)
+            .clamp(-1. + f32::EPSILON, 1. - f32::EPSILON);
+        let xu = (-(cu * z0) / (1. - cu * cu).sqrt()).clamp(x0, x1);
+
+        // Then the row `yv` along it, uniformly in the cosine of the angle from the column
+        let dd = (xu * xu + z0 * z0).sqrt();
+        let h0 = y0 / (dd * dd + y0 * y0).sqrt();
+        let h1 = y1 / (dd * dd + y1 * y1).sqrt();
+        let hv = h0 + t * (h1 - h0);
+        let yv = if hv * hv < 1. - 1e-4 {
+            hv * dd / (1. - hv * hv).sqrt()
+        } else {
+            y1
+        };
+
+        origin + xu * x + yv * y + z0 * z
+    }
+
     fn is_interior(&self, alpha: f32, beta: f32, rec: &mut HitRecord) -> bool {
         let unit_interval = Interval::new(0.0, 1.0);
 
@@ -79,6 +170,9 @@ impl Hittable for Quadrilateral {
 
         rec.t = t;
         rec.p = intersection;
+        rec.dpdu = self.u;
+        rec.dpdv = self.v;
+        (rec.dndu, rec.dndv) = (Vec3::default(), Vec3::default());
         rec.mat = self.material.clone();
         rec.set_face_normal(r, self.normal);
 
@@ -90,7 +184,12 @@ impl Hittable for Quadrilateral {
     }
 
     fn random(&self, origin: Point3) -> Vec3 {
-        let p = self.q + (utils::random_double() * self.u) + (utils::random_double() * self.v);
+        let (a, b) = utils::random_2d();
+        let p = if self.spherical_area(origin).is_some() {
+            self.sample_spherical_rectangle(origin, a, b)
+        } else {
+            self.q + (a * self.u) + (b * self.v)
+        };
         p - origin
     }
 
@@ -103,11 +202,35 @@ impl Hittable for Quadrilateral {
         ) {
             return 0.;
         }
+        if let Some(area) = self.spherical_area(origin) {
+            return 1. / area;
+        }
         let distance_squared = rec.t * rec.t * direction.length_squared();
         let cosine = (vec3::dot(direction, rec.normal) / direction.length()).abs();
 
         distance_squared / (cosine * self.area)
     }
+
+    fn sample_surface(&self, _time: f32) -> Option<(HitRecord, f32)> {
+        let (a, b) = utils::random_2d();
+        let rec = HitRecord {
+            p: self.q + (a * self.u) + (b * self.v),
+            normal: self.normal,
+            mat: self.material.clone(),
+            t: 0.,
+            u: a,
+            v: b,
+            dpdu: self.u,
+            dpdv: self.v,
+            front_face: true,
+            ..Default::default()
+        };
+        Some((rec, 1. / self.area))
+    }
+
+    fn area(&self) -> f32 {
+        self.area
+    }
 }
 
 pub fn new_box(p1: Point3, p2: Point3, material: Arc<dyn Material>) -> HittableList {
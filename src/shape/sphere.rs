@@ -42,7 +42,10 @@ impl Sphere {
 
     pub fn moving(center1: Point3, center2: Point3, radius: f32, mat: Arc<dyn Material>) -> Self {
         let rvec = Vec3([radius, radius, radius]);
-        let bbox = AABB::around_points(center1 - rvec, center1 + rvec);
+        let bbox = AABB::around_boxes(
+            &AABB::around_points(center1 - rvec, center1 + rvec),
+            &AABB::around_points(center2 - rvec, center2 + rvec),
+        );
         let center_vec = center2 - center1;
         Sphere {
             center: center1,
@@ -65,9 +68,25 @@ impl Sphere {
         *v = theta / std::f32::consts::PI;
     }
 
+    /// Derivatives of the point at the unit normal `n` with respect to the `u` and `v` of
+    /// `get_sphere_uv`
+    fn sphere_tangents(n: Vec3, radius: f32) -> (Vec3, Vec3) {
+        use std::f32::consts::PI;
+        let theta = (-n.y()).clamp(-1., 1.).acos();
+        let phi = (-n.z()).atan2(n.x());
+        let dpdu = 2. * PI * radius * Vec3::new(n.z(), 0., -n.x());
+        let dpdv = PI
+            * radius
+            * Vec3::new(
+                theta.cos() * phi.cos(),
+                theta.sin(),
+                -theta.cos() * phi.sin(),
+            );
+        (dpdu, dpdv)
+    }
+
     fn random_to_sphere(radius: f32, distance_squared: f32) -> Vec3 {
-        let r1 = utils::random_double();
-        let r2 = utils::random_double();
+        let (r1, r2) = utils::random_2d();
         let z = 1. + r2 * ((1. - (radius * radius / distance_squared)).sqrt() - 1.);
 
         let phi = 2. * std::f32::consts::PI * r1;
@@ -108,6 +127,9 @@ impl Hittable for Sphere {
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(r, outward_normal);
         Self::get_sphere_uv(outward_normal, &mut rec.u, &mut rec.v);
+        (rec.dpdu, rec.dpdv) = Self::sphere_tangents(outward_normal, self.radius);
+        // The point is the center plus radius times the outward normal
+        (rec.dndu, rec.dndv) = (rec.dpdu / self.radius, rec.dpdv / self.radius);
         rec.mat = self.mat.clone();
 
         true
@@ -140,4 +162,22 @@ impl Hittable for Sphere {
 
         1. / solid_angle
     }
+
+    fn sample_surface(&self, time: f32) -> Option<(HitRecord, f32)> {
+        let outward_normal = vec3::random_unit_vector();
+        let mut rec = HitRecord {
+            p: self.sphere_center(time) + self.radius * outward_normal,
+            normal: outward_normal,
+            mat: self.mat.clone(),
+            front_face: true,
+            ..Default::default()
+        };
+        Self::get_sphere_uv(outward_normal, &mut rec.u, &mut rec.v);
+        (rec.dpdu, rec.dpdv) = Self::sphere_tangents(outward_normal, self.radius);
+        Some((rec, 1. / self.area()))
+    }
+
+    fn area(&self) -> f32 {
+        4. * std::f32::consts::PI * self.radius * self.radius
+    }
 }
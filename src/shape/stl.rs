@@ -0,0 +1,69 @@
+use crate::{Material, Point3, TriangleMesh, Vec3};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+/// Loads a binary STL file into a `TriangleMesh`
+///
+/// STL stores every triangle with its own copy of each vertex, so identical positions are merged
+/// to get a shared vertex buffer. The per-facet normals are ignored in favour of the geometric
+/// normal of each triangle.
+pub fn load_stl(path: &str, material: Arc<dyn Material>) -> Result<TriangleMesh, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("couldn't read '{path}': {e}"))?;
+    parse_stl(&bytes, material).map_err(|e| format!("{path}: {e}"))
+}
+
+pub fn parse_stl(bytes: &[u8], material: Arc<dyn Material>) -> Result<TriangleMesh, String> {
+    let Some(count) = bytes.get(HEADER_SIZE..HEADER_SIZE + 4) else {
+        if bytes.starts_with(b"solid") {
+            return Err("ASCII STL files are not supported, export as binary STL".into());
+        }
+        return Err(format!(
+            "file is truncated: {} bytes is too short for a binary STL header",
+            bytes.len()
+        ));
+    };
+    let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+
+    let expected = HEADER_SIZE + 4 + count * TRIANGLE_SIZE;
+    if bytes.len() < expected {
+        if bytes.starts_with(b"solid") {
+            return Err("ASCII STL files are not supported, export as binary STL".into());
+        }
+        return Err(format!(
+            "file is truncated: header declares {count} triangles ({expected} bytes) but the file is {} bytes",
+            bytes.len()
+        ));
+    }
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut lookup: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut indices: Vec<[usize; 3]> = Vec::with_capacity(count);
+
+    for triangle in bytes[HEADER_SIZE + 4..expected].chunks_exact(TRIANGLE_SIZE) {
+        let float = |i: usize| f32::from_le_bytes(triangle[i * 4..i * 4 + 4].try_into().unwrap());
+        let mut face = [0; 3];
+        // The first three floats are the facet normal
+        for (corner, index) in face.iter_mut().enumerate() {
+            let base = 3 + corner * 3;
+            let p = Vec3::new(float(base), float(base + 1), float(base + 2));
+            if !p.x().is_finite() || !p.y().is_finite() || !p.z().is_finite() {
+                return Err(format!(
+                    "triangle {} has a non-finite vertex",
+                    indices.len()
+                ));
+            }
+            let key = [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+            *index = *lookup.entry(key).or_insert_with(|| {
+                positions.push(p);
+                positions.len() - 1
+            });
+        }
+        indices.push(face);
+    }
+
+    TriangleMesh::new(positions, None, None, indices, material)
+}
@@ -0,0 +1,308 @@
+use crate::{
+    vec3, BvhNode, Color, HitRecord, Hittable, HittableList, Interval, Lambertian, Material,
+    Point3, Ray, Vec3, VertexColors, AABB,
+};
+
+use std::sync::Arc;
+
+/// Shared vertex data for a collection of triangles
+///
+/// Every vertex stores a position and, optionally, a normal, a texture coordinate and a colour.
+/// Faces index into these buffers, so vertices shared between faces are only stored once.
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    pub normals: Option<Vec<Vec3>>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    pub colors: Option<Vec<Color>>,
+    pub indices: Vec<[usize; 3]>,
+    /// When `None`, faces are shaded as Lambertian using the interpolated vertex colours
+    material: Option<Arc<dyn Material>>,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<[f32; 2]>>,
+        indices: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Result<Self, String> {
+        Self::with_vertex_colors(positions, normals, uvs, None, indices, Some(material))
+    }
+
+    pub fn with_vertex_colors(
+        positions: Vec<Point3>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<[f32; 2]>>,
+        colors: Option<Vec<Color>>,
+        indices: Vec<[usize; 3]>,
+        material: Option<Arc<dyn Material>>,
+    ) -> Result<Self, String> {
+        let vertex_count = positions.len();
+        if normals.as_ref().is_some_and(|n| n.len() != vertex_count) {
+            return Err(format!(
+                "mesh has {vertex_count} positions but a different number of normals"
+            ));
+        }
+        if uvs.as_ref().is_some_and(|uv| uv.len() != vertex_count) {
+            return Err(format!(
+                "mesh has {vertex_count} positions but a different number of uvs"
+            ));
+        }
+        if colors.as_ref().is_some_and(|c| c.len() != vertex_count) {
+            return Err(format!(
+                "mesh has {vertex_count} positions but a different number of colors"
+            ));
+        }
+        if material.is_none() && colors.is_none() {
+            return Err("mesh needs either a material or vertex colors".into());
+        }
+        if let Some(face) = indices
+            .iter()
+            .find(|f| f.iter().any(|&i| i >= vertex_count))
+        {
+            return Err(format!(
+                "face {face:?} references a vertex outside of the {vertex_count} available"
+            ));
+        }
+
+        Ok(Self {
+            positions,
+            normals,
+            uvs,
+            colors,
+            indices,
+            material,
+        })
+    }
+
+    /// Loads a mesh, picking the format from the file extension (`.obj`, `.ply` or `.stl`)
+    ///
+    /// Only PLY files can provide vertex colours, so the other formats require a `material`.
+    pub fn load(path: &str, material: Option<Arc<dyn Material>>) -> Result<Self, String> {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        let require_material = || {
+            material
+                .clone()
+                .ok_or_else(|| format!("{path}: a material is required for this mesh format"))
+        };
+        match extension.as_deref() {
+            Some("obj") => crate::load_obj(path, require_material()?),
+            Some("ply") => crate::load_ply(path, material),
+            Some("stl") => crate::load_stl(path, require_material()?),
+            _ => Err(format!(
+                "{path}: unsupported mesh format, expected .obj, .ply or .stl"
+            )),
+        }
+    }
+
+    /// Whether faces are shaded with the interpolated vertex colours instead of a material
+    pub fn uses_vertex_colors(&self) -> bool {
+        self.material.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Creates one `Triangle` per face, all sharing this mesh's vertex buffers
+    pub fn to_list(self: &Arc<Self>) -> HittableList {
+        let mut list = HittableList::new();
+        for face in 0..self.indices.len() {
+            list.add(Arc::new(Triangle::new(self.clone(), face)));
+        }
+        list
+    }
+
+    /// Same as `to_list`, but wraps the triangles in a BVH
+    pub fn to_bvh(self: &Arc<Self>) -> Result<BvhNode, String> {
+        if self.is_empty() {
+            return Err("cannot build a BVH for a mesh without faces".into());
+        }
+        Ok(BvhNode::from_list(self.to_list()))
+    }
+}
+
+/// A single face of a `TriangleMesh`
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+    material: Arc<dyn Material>,
+    bbox: AABB,
+}
+
+impl Triangle {
+    pub fn new(mesh: Arc<TriangleMesh>, face: usize) -> Self {
+        let [i0, i1, i2] = mesh.indices[face];
+        let (p0, p1, p2) = (mesh.positions[i0], mesh.positions[i1], mesh.positions[i2]);
+        let bbox = AABB::around_boxes(&AABB::around_points(p0, p1), &AABB::around_points(p2, p2));
+        let material: Arc<dyn Material> = match (&mesh.material, &mesh.colors) {
+            (Some(material), _) => material.clone(),
+            (None, Some(colors)) => {
+                Arc::new(Lambertian::with_texture(Arc::new(VertexColors::new([
+                    colors[i0], colors[i1], colors[i2],
+                ]))))
+            }
+            (None, None) => unreachable!("TriangleMesh always has a material or colors"),
+        };
+        Self {
+            mesh,
+            face,
+            material,
+            bbox,
+        }
+    }
+
+    /// Fills in the shading normal, texture coordinates, tangents and material at the
+    /// barycentric coordinates `b1` and `b2`, once `rec.normal` is the geometric normal on the
+    /// side the point is seen from
+    fn set_surface_details(&self, b1: f32, b2: f32, rec: &mut HitRecord) {
+        let [i0, i1, i2] = self.mesh.indices[self.face];
+        let (_, e1, e2) = self.edges();
+        let b0 = 1. - b1 - b2;
+
+        if let Some(normals) = &self.mesh.normals {
+            let shading = b0 * normals[i0] + b1 * normals[i1] + b2 * normals[i2];
+            if !shading.near_zero() {
+                // Keep the interpolated normal on the same side as the geometric one
+                let shading = vec3::unit_vector(shading);
+                rec.normal = if vec3::dot(shading, rec.normal) < 0. {
+                    -shading
+                } else {
+                    shading
+                };
+            }
+        }
+
+        // Vertex colours are looked up with the barycentric coordinates, so they take priority
+        (rec.dpdu, rec.dpdv) = (e1, e2);
+        (rec.dndu, rec.dndv) = (Vec3::default(), Vec3::default());
+        if let (Some(uvs), false) = (&self.mesh.uvs, self.mesh.uses_vertex_colors()) {
+            rec.u = b0 * uvs[i0][0] + b1 * uvs[i1][0] + b2 * uvs[i2][0];
+            rec.v = b0 * uvs[i0][1] + b1 * uvs[i1][1] + b2 * uvs[i2][1];
+
+            // Solves e1 = du1 dpdu + dv1 dpdv and e2 = du2 dpdu + dv2 dpdv, keeping the edges
+            // when the texture coordinates don't span the triangle
+            let (du1, dv1) = (uvs[i1][0] - uvs[i0][0], uvs[i1][1] - uvs[i0][1]);
+            let (du2, dv2) = (uvs[i2][0] - uvs[i0][0], uvs[i2][1] - uvs[i0][1]);
+            let uv_det = du1 * dv2 - du2 * dv1;
+            if uv_det.abs() > 1e-12 {
+                rec.dpdu = (dv2 * e1 - dv1 * e2) / uv_det;
+                rec.dpdv = (du1 * e2 - du2 * e1) / uv_det;
+            }
+        } else {
+            rec.u = b1;
+            rec.v = b2;
+        }
+        rec.mat = self.material.clone();
+    }
+
+    /// The first vertex of the face and the edges from it to the other two
+    fn edges(&self) -> (Point3, Vec3, Vec3) {
+        let [i0, i1, i2] = self.mesh.indices[self.face];
+        let p0 = self.mesh.positions[i0];
+        (
+            p0,
+            self.mesh.positions[i1] - p0,
+            self.mesh.positions[i2] - p0,
+        )
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: &mut Interval, rec: &mut HitRecord) -> bool {
+        let (p0, e1, e2) = self.edges();
+
+        // Möller–Trumbore intersection
+        let pvec = vec3::cross(r.direction(), e2);
+        let det = vec3::dot(e1, pvec);
+        if det.abs() < 1e-12 {
+            return false;
+        }
+        let inv_det = 1. / det;
+
+        let tvec = r.origin() - p0;
+        let b1 = vec3::dot(tvec, pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return false;
+        }
+
+        let qvec = vec3::cross(tvec, e1);
+        let b2 = vec3::dot(r.direction(), qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return false;
+        }
+
+        let t = vec3::dot(e2, qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, vec3::unit_vector(vec3::cross(e1, e2)));
+        self.set_surface_details(b1, b2, rec);
+
+        true
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+    fn random(&self, origin: Point3) -> Vec3 {
+        let (rec, _) = self
+            .sample_surface(0.)
+            .expect("triangles can always be sampled");
+        rec.p - origin
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let mut rec = HitRecord::default();
+        if !self.hit(
+            &Ray::new(origin, direction, 0.),
+            &mut Interval::new(0.001, f32::INFINITY),
+            &mut rec,
+        ) {
+            return 0.;
+        }
+        // The hit record carries the shading normal, but the light's area is measured in its plane
+        let (_, e1, e2) = self.edges();
+        let normal = vec3::unit_vector(vec3::cross(e1, e2));
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (vec3::dot(direction, normal) / direction.length()).abs();
+
+        distance_squared / (cosine * self.area())
+    }
+
+    fn sample_surface(&self, _time: f32) -> Option<(HitRecord, f32)> {
+        let (p0, e1, e2) = self.edges();
+
+        // Folding the unit square in half along its diagonal keeps the density uniform
+        let (mut b1, mut b2) = crate::utils::random_2d();
+        if b1 + b2 > 1. {
+            (b1, b2) = (1. - b1, 1. - b2);
+        }
+        let normal = vec3::unit_vector(vec3::cross(e1, e2));
+        let mut rec = HitRecord {
+            p: p0 + b1 * e1 + b2 * e2,
+            normal,
+            front_face: true,
+            ..Default::default()
+        };
+        self.set_surface_details(b1, b2, &mut rec);
+        // Lights weigh their samples by the cosine at the surface, so keep the geometric normal
+        rec.normal = normal;
+        Some((rec, 1. / self.area()))
+    }
+
+    fn area(&self) -> f32 {
+        let (_, e1, e2) = self.edges();
+        vec3::cross(e1, e2).length() / 2.
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Color, Point3, Texture};
+use crate::{Color, HitRecord, Point3, Texture};
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -18,7 +18,44 @@ impl Checkerboard {
     }
 }
 
+/// The average over `[x - width / 2, x + width / 2]` of the square wave that is `1` where
+/// `floor(x)` is even and `-1` where it is odd
+fn square_wave_average(x: f32, width: f32) -> f32 {
+    if width < 1e-5 {
+        return if x.floor().rem_euclid(2.) == 0. {
+            1.
+        } else {
+            -1.
+        };
+    }
+    // The integral of the square wave, a triangle wave
+    let integral = |x: f32| 1. - (x.rem_euclid(2.) - 1.).abs();
+    (integral(x + width / 2.) - integral(x - width / 2.)) / width
+}
+
 impl Texture for Checkerboard {
+    /// Box filters the checks over the bounding box of the pixel footprint, so that distant
+    /// checks fade to the average of the two textures instead of aliasing
+    fn filtered_value(&self, rec: &HitRecord) -> Color {
+        // A cell is even when the square waves along the three axes multiply to 1, and
+        // averages of the product over a box are products of the averages
+        let parity = (0..3)
+            .map(|i| {
+                let width = rec.dpdx.0[i].abs() + rec.dpdy.0[i].abs();
+                square_wave_average(self.inv_scale * rec.p.0[i], self.inv_scale.abs() * width)
+            })
+            .product::<f32>();
+        let even_weight = (1. + parity) / 2.;
+        if even_weight >= 1. {
+            self.even.filtered_value(rec)
+        } else if even_weight <= 0. {
+            self.odd.filtered_value(rec)
+        } else {
+            even_weight * self.even.filtered_value(rec)
+                + (1. - even_weight) * self.odd.filtered_value(rec)
+        }
+    }
+
     fn value(&self, u: f32, v: f32, p: Point3) -> Color {
         let x_int = (self.inv_scale * p.x()).floor() as i32;
         let y_int = (self.inv_scale * p.y()).floor() as i32;
@@ -0,0 +1,548 @@
+use crate::{Color, HitRecord, Point3, Texture};
+
+use std::path::Path;
+
+/// How an `ImageTexture` turns texture coordinates into a color
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFilter {
+    /// The pixel the coordinates fall in, for a blocky look
+    Nearest,
+    /// A blend of the four pixels around the coordinates
+    #[default]
+    Bilinear,
+    /// A blend of bilinear lookups in the two MIP levels closest in size to the pixel footprint,
+    /// which blurs along the footprint's longest side
+    Trilinear,
+    /// A Gaussian weighted average over the elliptical pixel footprint, which stays sharp at
+    /// grazing angles where trilinear filtering blurs
+    Ewa,
+}
+
+/// What an `ImageTexture` shows outside of the unit square of texture coordinates
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    /// Tiles the image
+    #[default]
+    Repeat,
+    /// Stretches the pixels on the edges
+    Clamp,
+    /// Tiles the image, flipping every other copy so that the edges meet
+    Mirror,
+}
+
+impl WrapMode {
+    /// Maps the pixel index `i` into `0..size`
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Clamp => i.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+        };
+        i as usize
+    }
+}
+
+/// How much longer than wide an elliptical footprint may get before EWA filtering widens it,
+/// which bounds the number of pixels it reads
+const MAX_ANISOTROPY: f32 = 8.;
+
+/// One level of an image's MIP pyramid
+#[derive(Clone)]
+struct MipLevel {
+    width: usize,
+    height: usize,
+    /// Linear colors, row by row from the top
+    pixels: Vec<Color>,
+}
+
+impl MipLevel {
+    /// The level half the size of `self`, rounded up, where each pixel averages the two by two
+    /// block it covers and blocks hanging over the edge repeat the last row or column
+    fn downsample(&self) -> Self {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let at = |x: usize, y: usize| {
+            self.pixels[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+        };
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                (at(2 * x, 2 * y)
+                    + at(2 * x + 1, 2 * y)
+                    + at(2 * x, 2 * y + 1)
+                    + at(2 * x + 1, 2 * y + 1))
+                    / 4.
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// A texture looked up from an image, with `(0, 0)` at the bottom left corner and `(1, 1)` at
+/// the top right
+#[derive(Clone)]
+pub struct ImageTexture {
+    /// The image at full size, then halved until it is a single pixel
+    levels: Vec<MipLevel>,
+    filter: ImageFilter,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    /// An image of `width` by `height` linear colors, row by row from the top
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Result<Self, String> {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return Err(format!(
+                "an image of {width} by {height} needs {} pixels, not {}",
+                width * height,
+                pixels.len()
+            ));
+        }
+        let mut levels = vec![MipLevel {
+            width,
+            height,
+            pixels,
+        }];
+        while let Some(last) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            levels.push(last.downsample());
+        }
+        Ok(Self {
+            levels,
+            filter: ImageFilter::default(),
+            wrap: WrapMode::default(),
+        })
+    }
+
+    /// Loads a PNG, JPEG, OpenEXR or Radiance HDR file, picked by its extension
+    ///
+    /// PNG and JPEG files usually store colors encoded with the sRGB curve, which `srgb` decodes
+    /// to linear ones. Leave it off for maps of other quantities, like roughness. OpenEXR and
+    /// HDR files are always linear.
+    pub fn load(path: &str, srgb: bool) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase());
+        let (width, height, mut pixels) = match extension.as_deref() {
+            Some("png") => load_png(path)?,
+            Some("jpg" | "jpeg") => load_jpeg(path)?,
+            Some("exr") => return Self::load_exr(path),
+            Some("hdr") => {
+                let (width, height, pixels) = load_hdr(path)?;
+                return Self::new(width, height, pixels)
+                    .map_err(|e| format!("couldn't read '{path}': {e}"));
+            }
+            _ => {
+                return Err(format!(
+                    "unknown image format of '{path}', expected a png, jpg, exr or hdr file"
+                ))
+            }
+        };
+        if srgb {
+            for pixel in &mut pixels {
+                *pixel = Color::new(
+                    srgb_to_linear(pixel.x()),
+                    srgb_to_linear(pixel.y()),
+                    srgb_to_linear(pixel.z()),
+                );
+            }
+        }
+        Self::new(width, height, pixels).map_err(|e| format!("couldn't read '{path}': {e}"))
+    }
+
+    fn load_exr(path: &str) -> Result<Self, String> {
+        let image = exr::prelude::read_first_rgba_layer_from_file(
+            path,
+            |resolution, _| {
+                let width = resolution.width();
+                (width, vec![Color::default(); width * resolution.height()])
+            },
+            |(width, pixels), position, (r, g, b, _): (f32, f32, f32, f32)| {
+                pixels[position.y() * *width + position.x()] = Color::new(r, g, b);
+            },
+        )
+        .map_err(|e| format!("couldn't read '{path}': {e}"))?;
+
+        let size = image.layer_data.size;
+        let (_, pixels) = image.layer_data.channel_data.pixels;
+        Self::new(size.width(), size.height(), pixels)
+            .map_err(|e| format!("couldn't read '{path}': {e}"))
+    }
+
+    pub fn with_filter(mut self, filter: ImageFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    /// The number of levels in the MIP pyramid, counting the full size image
+    pub fn mip_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The pixel in column `x` and row `y` from the top, wrapped into the image
+    pub fn texel(&self, x: i64, y: i64) -> Color {
+        self.level_texel(0, x, y)
+    }
+
+    fn level_texel(&self, level: usize, x: i64, y: i64) -> Color {
+        let level = &self.levels[level];
+        let x = self.wrap.apply(x, level.width);
+        let y = self.wrap.apply(y, level.height);
+        level.pixels[y * level.width + x]
+    }
+
+    /// Continuous pixel coordinates of `(s, t)` in `level`, where `t` runs down from the top
+    /// and pixel centres are at half-integers
+    fn scale(&self, level: usize, s: f32, t: f32) -> (f32, f32) {
+        let level = &self.levels[level];
+        (s * level.width as f32, t * level.height as f32)
+    }
+
+    fn bilinear(&self, level: usize, s: f32, t: f32) -> Color {
+        let (x, y) = self.scale(level, s, t);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        (1. - dx) * (1. - dy) * self.level_texel(level, x0, y0)
+            + dx * (1. - dy) * self.level_texel(level, x0 + 1, y0)
+            + (1. - dx) * dy * self.level_texel(level, x0, y0 + 1)
+            + dx * dy * self.level_texel(level, x0 + 1, y0 + 1)
+    }
+
+    /// The continuous MIP level whose pixels are `width` wide in texture coordinates
+    fn level_of(&self, width: f32) -> f32 {
+        let size = self.width().max(self.height()) as f32;
+        (width * size).max(1e-8).log2().max(0.)
+    }
+
+    /// Blends the two MIP levels around `level`
+    fn trilinear(&self, level: f32, s: f32, t: f32) -> Color {
+        let top = self.levels.len() - 1;
+        if level >= top as f32 {
+            return self.level_texel(top, 0, 0);
+        }
+        let below = level.floor();
+        let blend = level - below;
+        let below = below as usize;
+        if blend == 0. {
+            return self.bilinear(below, s, t);
+        }
+        (1. - blend) * self.bilinear(below, s, t) + blend * self.bilinear(below + 1, s, t)
+    }
+
+    /// Averages the image over the ellipse with axes `axis0` and `axis1` around `(s, t)`, after
+    /// Heckbert, "Fundamentals of Texture Mapping and Image Warping", and pbrt
+    fn ewa(&self, s: f32, t: f32, axis0: (f32, f32), axis1: (f32, f32)) -> Color {
+        let length = |(a, b): (f32, f32)| (a * a + b * b).sqrt();
+        let (major, mut minor) = if length(axis0) >= length(axis1) {
+            (axis0, axis1)
+        } else {
+            (axis1, axis0)
+        };
+        let (major_length, mut minor_length) = (length(major), length(minor));
+        // Widening too thin an ellipse blurs a little, rather than reading a long line of pixels
+        if minor_length * MAX_ANISOTROPY < major_length && minor_length > 0. {
+            let widen = major_length / (minor_length * MAX_ANISOTROPY);
+            minor = (minor.0 * widen, minor.1 * widen);
+            minor_length *= widen;
+        }
+        if minor_length == 0. {
+            return self.bilinear(0, s, t);
+        }
+
+        let level = self.level_of(minor_length);
+        let top = self.levels.len() - 1;
+        if level >= top as f32 {
+            return self.level_texel(top, 0, 0);
+        }
+        let below = level.floor();
+        let blend = level - below;
+        let below = below as usize;
+        (1. - blend) * self.ewa_level(below, s, t, major, minor)
+            + blend * self.ewa_level(below + 1, s, t, major, minor)
+    }
+
+    fn ewa_level(
+        &self,
+        level: usize,
+        s: f32,
+        t: f32,
+        axis0: (f32, f32),
+        axis1: (f32, f32),
+    ) -> Color {
+        let (x, y) = self.scale(level, s, t);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (a0x, a0y) = self.scale(level, axis0.0, axis0.1);
+        let (a1x, a1y) = self.scale(level, axis1.0, axis1.1);
+
+        // The implicit ellipse a dx² + b dx dy + c dy² < 1, grown by a pixel so that it always
+        // covers some pixel centres
+        let a = a0y * a0y + a1y * a1y + 1.;
+        let b = -2. * (a0x * a0y + a1x * a1y);
+        let c = a0x * a0x + a1x * a1x + 1.;
+        let inv_f = 1. / (a * c - b * b / 4.);
+        let (a, b, c) = (a * inv_f, b * inv_f, c * inv_f);
+
+        // The bounding box of the ellipse
+        let det = 4. * a * c - b * b;
+        let half_width = 2. * (det * c).sqrt() / det;
+        let half_height = 2. * (det * a).sqrt() / det;
+        let (x0, x1) = (
+            (x - half_width).ceil() as i64,
+            (x + half_width).floor() as i64,
+        );
+        let (y0, y1) = (
+            (y - half_height).ceil() as i64,
+            (y + half_height).floor() as i64,
+        );
+
+        let mut sum = Color::default();
+        let mut total_weight = 0.;
+        for py in y0..=y1 {
+            let dy = py as f32 - y;
+            for px in x0..=x1 {
+                let dx = px as f32 - x;
+                let r2 = a * dx * dx + b * dx * dy + c * dy * dy;
+                if r2 < 1. {
+                    // A Gaussian that falls to zero on the edge of the ellipse
+                    let weight = (-2. * r2).exp() - (-2f32).exp();
+                    sum += weight * self.level_texel(level, px, py);
+                    total_weight += weight;
+                }
+            }
+        }
+        if total_weight > 0. {
+            sum / total_weight
+        } else {
+            self.bilinear(level, s, t)
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f32, v: f32, _: Point3) -> Color {
+        let (s, t) = (u, 1. - v);
+        match self.filter {
+            ImageFilter::Nearest => {
+                let (x, y) = self.scale(0, s, t);
+                self.texel(x.floor() as i64, y.floor() as i64)
+            }
+            ImageFilter::Bilinear | ImageFilter::Trilinear | ImageFilter::Ewa => {
+                self.bilinear(0, s, t)
+            }
+        }
+    }
+
+    fn filtered_value(&self, rec: &HitRecord) -> Color {
+        // Rows run down from the top, so `t` moves against `v`
+        let (s, t) = (rec.u, 1. - rec.v);
+        let (dsdx, dtdx, dsdy, dtdy) = (rec.dudx, -rec.dvdx, rec.dudy, -rec.dvdy);
+        match self.filter {
+            ImageFilter::Nearest | ImageFilter::Bilinear => self.value(rec.u, rec.v, rec.p),
+            ImageFilter::Trilinear => {
+                let width = 2. * dsdx.abs().max(dtdx.abs()).max(dsdy.abs()).max(dtdy.abs());
+                if width == 0. {
+                    return self.bilinear(0, s, t);
+                }
+                self.trilinear(self.level_of(width), s, t)
+            }
+            ImageFilter::Ewa => self.ewa(s, t, (dsdx, dtdx), (dsdy, dtdy)),
+        }
+    }
+}
+
+/// Inverts the transfer curve of sRGB
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Colors of 8 or 16 bit samples with `channels` channels, where one or two channels are grey
+/// and alpha is dropped
+fn to_colors(samples: &[f32], channels: usize) -> Vec<Color> {
+    samples
+        .chunks_exact(channels)
+        .map(|s| match channels {
+            1 | 2 => Color::new(s[0], s[0], s[0]),
+            _ => Color::new(s[0], s[1], s[2]),
+        })
+        .collect()
+}
+
+fn load_png(path: &str) -> Result<(usize, usize, Vec<Color>), String> {
+    let error = |e: &dyn std::fmt::Display| format!("couldn't read '{path}': {e}");
+    let file = std::fs::File::open(path).map_err(|e| error(&e))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    // Palettes and samples of under 8 bits become 8 bit samples
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| error(&e))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| error(&e))?;
+    let buffer = &buffer[..info.buffer_size()];
+
+    let samples: Vec<f32> = match info.bit_depth {
+        png::BitDepth::Sixteen => buffer
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.)
+            .collect(),
+        _ => buffer.iter().map(|&b| b as f32 / 255.).collect(),
+    };
+    let channels = info.color_type.samples();
+    Ok((
+        info.width as usize,
+        info.height as usize,
+        to_colors(&samples, channels),
+    ))
+}
+
+fn load_jpeg(path: &str) -> Result<(usize, usize, Vec<Color>), String> {
+    use zune_jpeg::zune_core::{colorspace::ColorSpace, options::DecoderOptions};
+
+    let data = std::fs::read(path).map_err(|e| format!("couldn't read '{path}': {e}"))?;
+    let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGB);
+    let mut decoder = zune_jpeg::JpegDecoder::new_with_options(&data[..], options);
+    let buffer = decoder
+        .decode()
+        .map_err(|e| format!("couldn't read '{path}': {e:?}"))?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| format!("couldn't read '{path}'"))?;
+
+    // Grey images stay grey, whatever colorspace was asked for
+    let channels = decoder
+        .get_output_colorspace()
+        .map_or(3, |colorspace| colorspace.num_components());
+
+    let samples: Vec<f32> = buffer.iter().map(|&b| b as f32 / 255.).collect();
+    Ok((
+        info.width as usize,
+        info.height as usize,
+        to_colors(&samples, channels),
+    ))
+}
+
+/// Reads a Radiance HDR file, whose pixels share an exponent between their three channels and
+/// whose rows are usually run-length encoded one channel at a time
+fn load_hdr(path: &str) -> Result<(usize, usize, Vec<Color>), String> {
+    let error = |e: &str| format!("couldn't read '{path}': {e}");
+    let data = std::fs::read(path).map_err(|e| error(&e.to_string()))?;
+
+    // Text lines up to an empty one, then the resolution line
+    let mut start = 0;
+    let mut read_line = || {
+        let end = data[start..].iter().position(|&b| b == b'\n')?;
+        let line = &data[start..start + end];
+        start += end + 1;
+        Some(line)
+    };
+    let mut header = Vec::new();
+    loop {
+        match read_line() {
+            Some([]) => break,
+            Some(line) => header.push(line),
+            None => return Err(error("the header never ends")),
+        }
+    }
+    let resolution = read_line().ok_or_else(|| error("the resolution is missing"))?;
+    if !header.first().is_some_and(|l| l.starts_with(b"#?")) {
+        return Err(error("not a Radiance HDR file"));
+    }
+    if header
+        .iter()
+        .any(|l| l.starts_with(b"FORMAT=") && *l != b"FORMAT=32-bit_rle_rgbe")
+    {
+        return Err(error("only RGB pixels are supported"));
+    }
+    let resolution = String::from_utf8_lossy(resolution);
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (height.parse().ok(), width.parse().ok()),
+        _ => (None, None),
+    };
+    let (Some(height), Some(width)) = (height, width) else {
+        return Err(error(&format!(
+            "unsupported resolution line '{resolution}', expected '-Y <height> +X <width>'"
+        )));
+    };
+
+    let mut bytes = data[start..].iter().copied();
+    let mut next = || bytes.next().ok_or_else(|| error("the pixels end early"));
+    let mut rgbe = vec![[0u8; 4]; width * height];
+    for row in rgbe.chunks_exact_mut(width) {
+        let first = [next()?, next()?, next()?, next()?];
+        let encoded = (8..0x8000).contains(&width)
+            && first[0] == 2
+            && first[1] == 2
+            && usize::from(first[2]) << 8 | usize::from(first[3]) == width;
+        if !encoded {
+            row[0] = first;
+            for pixel in &mut row[1..] {
+                *pixel = [next()?, next()?, next()?, next()?];
+            }
+            continue;
+        }
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = next()?;
+                let (run, length) = if count > 128 {
+                    (true, usize::from(count - 128))
+                } else {
+                    (false, usize::from(count))
+                };
+                if length == 0 || x + length > width {
+                    return Err(error("a run overflows its row"));
+                }
+                let value = if run { next()? } else { 0 };
+                for pixel in &mut row[x..x + length] {
+                    pixel[channel] = if run { value } else { next()? };
+                }
+                x += length;
+            }
+        }
+    }
+
+    let pixels = rgbe
+        .iter()
+        .map(|&[r, g, b, e]| {
+            if e == 0 {
+                return Color::default();
+            }
+            let scale = 2f32.powi(i32::from(e) - 136);
+            Color::new(
+                (f32::from(r) + 0.5) * scale,
+                (f32::from(g) + 0.5) * scale,
+                (f32::from(b) + 0.5) * scale,
+            )
+        })
+        .collect();
+    Ok((width, height, pixels))
+}
@@ -4,5 +4,77 @@ pub use texture::{SolidColor, Texture};
 pub(crate) mod checkerboard;
 pub use checkerboard::Checkerboard;
 
+pub(crate) mod image;
+pub use image::{srgb_to_linear, ImageFilter, ImageTexture, WrapMode};
+
 pub(crate) mod noise;
 pub use noise::PerlinNoise;
+
+pub(crate) mod vertex_colors;
+pub use vertex_colors::VertexColors;
+
+use std::sync::Arc;
+
+/// Resolves a texture name from the scene's `textures` table
+pub type TextureLookup<'a> = dyn FnMut(&str) -> Result<Arc<dyn Texture>, String> + 'a;
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextureConfig {
+    SolidColor {
+        color: crate::Color,
+    },
+    Checkerboard {
+        even: crate::TextureRef,
+        odd: crate::TextureRef,
+        scale: f32,
+    },
+    PerlinNoise {
+        scale: f32,
+        /// Selects a different noise pattern
+        #[serde(default)]
+        seed: u64,
+    },
+    /// A PNG, JPEG, OpenEXR or Radiance HDR file, see `ImageTexture::load`
+    Image {
+        path: String,
+        #[serde(default)]
+        filter: ImageFilter,
+        #[serde(default)]
+        wrap: WrapMode,
+        /// Whether the colors of PNG and JPEG files are encoded with the sRGB curve
+        #[serde(default = "TextureConfig::default_srgb")]
+        srgb: bool,
+    },
+}
+
+impl TextureConfig {
+    fn default_srgb() -> bool {
+        true
+    }
+
+    /// Builds the texture, using `lookup` to resolve references to named textures
+    pub fn build(&self, lookup: &mut TextureLookup) -> Result<Arc<dyn Texture>, String> {
+        Ok(match self {
+            TextureConfig::SolidColor { color } => Arc::new(SolidColor::new(*color)),
+            TextureConfig::Checkerboard { even, odd, scale } => Arc::new(Checkerboard::new(
+                even.build(lookup)?,
+                odd.build(lookup)?,
+                *scale,
+            )),
+            TextureConfig::PerlinNoise { scale, seed } => {
+                Arc::new(PerlinNoise::with_seed(*scale, *seed))
+            }
+            TextureConfig::Image {
+                path,
+                filter,
+                wrap,
+                srgb,
+            } => Arc::new(
+                ImageTexture::load(path, *srgb)?
+                    .with_filter(*filter)
+                    .with_wrap(*wrap),
+            ),
+        })
+    }
+}
@@ -1,4 +1,4 @@
-use crate::Texture;
+use crate::{Rng, Texture};
 
 pub struct Perlin {
     rand_vec: Vec<crate::Vec3>,
@@ -8,14 +8,20 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    pub fn new() -> Self {
+    /// Builds the gradient and permutation tables from their own generator, so the pattern only
+    /// depends on `seed`
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng::new(seed, 0);
         let point_count = 256;
         let rand_vec = (0..point_count)
-            .map(|_| crate::vec3::unit_vector(crate::Vec3::random_range(-1.0, 1.0)))
+            .map(|_| {
+                let mut coord = || 2. * rng.uniform() - 1.;
+                crate::vec3::unit_vector(crate::Vec3::new(coord(), coord(), coord()))
+            })
             .collect();
-        let perm_x = Self::perlin_generate_perm();
-        let perm_y = Self::perlin_generate_perm();
-        let perm_z = Self::perlin_generate_perm();
+        let perm_x = Self::perlin_generate_perm(&mut rng);
+        let perm_y = Self::perlin_generate_perm(&mut rng);
+        let perm_z = Self::perlin_generate_perm(&mut rng);
         Self {
             rand_vec,
             perm_x,
@@ -82,15 +88,15 @@ impl Perlin {
         accum
     }
 
-    fn perlin_generate_perm() -> Vec<i32> {
+    fn perlin_generate_perm(rng: &mut Rng) -> Vec<i32> {
         let mut p = (0..256).collect::<Vec<i32>>();
-        Self::permute(&mut p, 256);
+        Self::permute(&mut p, 256, rng);
         p
     }
 
-    fn permute(p: &mut [i32], n: i32) {
+    fn permute(p: &mut [i32], n: i32, rng: &mut Rng) {
         for i in (1..n).rev() {
-            let target = (rand::random::<f32>() * (i + 1) as f32) as usize;
+            let target = rng.uniform_below(i as usize + 1);
             p.swap(i as usize, target);
         }
     }
@@ -102,8 +108,12 @@ pub struct PerlinNoise {
 }
 impl PerlinNoise {
     pub fn new(scale: f32) -> Self {
+        Self::with_seed(scale, 0)
+    }
+
+    pub fn with_seed(scale: f32, seed: u64) -> Self {
         Self {
-            noise: Perlin::new(),
+            noise: Perlin::new(seed),
             scale,
         }
     }
@@ -1,7 +1,14 @@
-use crate::{Color, Point3};
+use crate::{Color, HitRecord, Point3};
 
 pub trait Texture: Send + Sync {
     fn value(&self, u: f32, v: f32, p: Point3) -> Color;
+
+    /// The texture averaged over the footprint of a pixel at `rec`, for materials to use so
+    /// that detail finer than a pixel doesn't alias. Textures without such detail can keep the
+    /// plain lookup at the hit point.
+    fn filtered_value(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u, rec.v, rec.p)
+    }
 }
 
 #[derive(Default, Clone)]
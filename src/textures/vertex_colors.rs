@@ -0,0 +1,20 @@
+use crate::{Color, Point3, Texture};
+
+/// Colours of a triangle's three vertices, blended with the barycentric coordinates that the
+/// triangle reports as `(u, v)`
+#[derive(Clone)]
+pub struct VertexColors {
+    colors: [Color; 3],
+}
+
+impl VertexColors {
+    pub fn new(colors: [Color; 3]) -> Self {
+        Self { colors }
+    }
+}
+
+impl Texture for VertexColors {
+    fn value(&self, u: f32, v: f32, _: Point3) -> Color {
+        (1. - u - v) * self.colors[0] + u * self.colors[1] + v * self.colors[2]
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{HitRecord, Hittable, HittableList, Interval, Point3, Ray, Vec3, AABB};
+use crate::{utils, HitRecord, Hittable, HittableList, Interval, Point3, Ray, Vec3, AABB};
 
 use std::sync::Arc;
 
@@ -6,6 +6,9 @@ pub struct BvhNode {
     left: Arc<dyn Hittable>,
     right: Arc<dyn Hittable>,
     bbox: AABB,
+    /// Chance of picking the left child as a light, by its share of the area
+    left_weight: f32,
+    area: f32,
 }
 
 impl BvhNode {
@@ -30,20 +33,12 @@ impl BvhNode {
         match objects.len() {
             1 => {
                 let single_node = objects[0].clone();
-                return Self {
-                    left: single_node.clone(),
-                    right: single_node,
-                    bbox,
-                };
+                return Self::with_children(single_node.clone(), single_node, bbox);
             }
             2 => {
                 let left_node = objects[0].clone();
                 let right_node = objects[1].clone();
-                return Self {
-                    left: left_node,
-                    right: right_node,
-                    bbox,
-                };
+                return Self::with_children(left_node, right_node, bbox);
             }
             _ => (),
         };
@@ -93,7 +88,27 @@ impl BvhNode {
             Arc::new(Self::new_optimized(&mut objects[min_sah_idx + 1..]))
         };
 
-        Self { left, right, bbox }
+        Self::with_children(left, right, bbox)
+    }
+
+    fn with_children(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>, bbox: AABB) -> Self {
+        let (left_weight, area) = if Arc::ptr_eq(&left, &right) {
+            (1., left.area())
+        } else {
+            // Children without any area are still picked now and then, so nothing goes unlit
+            let (left_area, right_area) = (left.area(), right.area());
+            match left_area + right_area {
+                area if area > 0. => (left_area / area, area),
+                _ => (0.5, 0.),
+            }
+        };
+        Self {
+            left,
+            right,
+            bbox,
+            left_weight,
+            area,
+        }
     }
 
     fn box_compare(
@@ -139,11 +154,48 @@ impl Hittable for BvhNode {
         &self.bbox
     }
 
-    fn random(&self, _origin: Point3) -> Vec3 {
-        Vec3::new(1., 0., 0.)
+    // As a light, each node picks either child by its share of the area, so the points of a mesh
+    // are sampled evenly. A node over a single object holds it on both sides, and leaves it to
+    // that object.
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        if Arc::ptr_eq(&self.left, &self.right) || utils::random_double() < self.left_weight {
+            self.left.random(origin)
+        } else {
+            self.right.random(origin)
+        }
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        // Directions that miss the box miss everything in it
+        let ray = Ray::new(origin, direction, 0.);
+        if !self
+            .bbox
+            .hit(&ray, &mut Interval::new(0.001, f32::INFINITY))
+        {
+            return 0.;
+        }
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return self.left.pdf_value(origin, direction);
+        }
+        self.left_weight * self.left.pdf_value(origin, direction)
+            + (1. - self.left_weight) * self.right.pdf_value(origin, direction)
+    }
+
+    fn sample_surface(&self, time: f32) -> Option<(HitRecord, f32)> {
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return self.left.sample_surface(time);
+        }
+        let (child, weight) = if utils::random_double() < self.left_weight {
+            (&self.left, self.left_weight)
+        } else {
+            (&self.right, 1. - self.left_weight)
+        };
+        let (rec, pdf) = child.sample_surface(time)?;
+        Some((rec, pdf * weight))
     }
 
-    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f32 {
-        0.
+    fn area(&self) -> f32 {
+        self.area
     }
 }
@@ -0,0 +1,94 @@
+use crate::{Point3, AABB};
+
+/// A balanced kd-tree over points, for finding everything within a radius of a query point
+///
+/// The tree is stored implicitly: each subslice of `items` keeps its splitting item in the
+/// middle, with the items on the low side of the split before it and the rest after it.
+pub struct KdTree<T> {
+    items: Vec<(Point3, T)>,
+    axes: Vec<u8>,
+}
+
+impl<T> KdTree<T> {
+    pub fn new(mut items: Vec<(Point3, T)>) -> Self {
+        let mut axes = vec![0; items.len()];
+        Self::build(&mut items, &mut axes);
+        Self { items, axes }
+    }
+
+    fn build(items: &mut [(Point3, T)], axes: &mut [u8]) {
+        if items.len() <= 1 {
+            return;
+        }
+
+        let bbox = items.iter().fold(AABB::empty(), |acc, (p, _)| {
+            AABB::around_boxes(&acc, &AABB::around_points(*p, *p))
+        });
+        let axis = bbox.longest_axis();
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| a.0 .0[axis].total_cmp(&b.0 .0[axis]));
+        axes[mid] = axis as u8;
+
+        let (low_items, high_items) = items.split_at_mut(mid);
+        let (low_axes, high_axes) = axes.split_at_mut(mid);
+        Self::build(low_items, low_axes);
+        Self::build(&mut high_items[1..], &mut high_axes[1..]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Calls `f` with every item whose point is within `radius` of `p`
+    pub fn for_each_within(&self, p: Point3, radius: f32, mut f: impl FnMut(Point3, &T)) {
+        Self::search(&self.items, &self.axes, p, radius * radius, &mut f);
+    }
+
+    fn search(
+        items: &[(Point3, T)],
+        axes: &[u8],
+        p: Point3,
+        radius_squared: f32,
+        f: &mut impl FnMut(Point3, &T),
+    ) {
+        if items.is_empty() {
+            return;
+        }
+
+        let mid = items.len() / 2;
+        let (point, item) = &items[mid];
+        if (*point - p).length_squared() <= radius_squared {
+            f(*point, item);
+        }
+
+        // Always visit the side of the split that `p` is on, and the other side only if the
+        // sphere around `p` reaches across
+        let axis = axes[mid] as usize;
+        let offset = p.0[axis] - point.0[axis];
+        let (near, far) = if offset <= 0. {
+            ((0, mid), (mid + 1, items.len()))
+        } else {
+            ((mid + 1, items.len()), (0, mid))
+        };
+        Self::search(
+            &items[near.0..near.1],
+            &axes[near.0..near.1],
+            p,
+            radius_squared,
+            f,
+        );
+        if offset * offset <= radius_squared {
+            Self::search(
+                &items[far.0..far.1],
+                &axes[far.0..far.1],
+                p,
+                radius_squared,
+                f,
+            );
+        }
+    }
+}
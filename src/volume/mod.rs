@@ -4,3 +4,5 @@ pub mod aabb;
 pub use aabb::AABB;
 pub mod constant_medium;
 pub use constant_medium::ConstantMedium;
+pub mod kdtree;
+pub use kdtree::KdTree;
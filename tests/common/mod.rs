@@ -0,0 +1,42 @@
+//! Helpers shared by the integration tests. Each test crate only uses some of them.
+
+#![allow(dead_code)]
+
+use ray_tracing::{HitRecord, Hittable, Interval, Lambertian, Material, Point3, Ray, Vec3};
+
+use std::sync::Arc;
+
+/// A plain grey diffuse material, for tests that only care about geometry
+pub fn material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(ray_tracing::Color::new(0.5, 0.5, 0.5)))
+}
+
+/// The closest hit of `object` along `r`, if there is one
+pub fn hit_ray(object: &dyn Hittable, r: &Ray) -> Option<HitRecord> {
+    let mut rec = HitRecord::default();
+    object
+        .hit(r, &mut Interval::new(0.001, f32::INFINITY), &mut rec)
+        .then_some(rec)
+}
+
+/// The closest hit of `object` along the ray from `origin` in `direction` at time 0
+pub fn hit(object: &dyn Hittable, origin: Point3, direction: Vec3) -> Option<HitRecord> {
+    hit_ray(object, &Ray::new(origin, direction, 0.))
+}
+
+pub fn assert_close(actual: Vec3, expected: Vec3, what: &str) {
+    assert_close_within(actual, expected, 1e-5, what);
+}
+
+pub fn assert_close_within(actual: Vec3, expected: Vec3, tolerance: f32, what: &str) {
+    assert!(
+        (actual - expected).length() < tolerance,
+        "{what}: expected {expected:?}, got {actual:?}"
+    );
+}
+
+/// A path in the temporary directory that no other test writes to
+pub fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("ray-tracing-{}-{name}", std::process::id()));
+    path.to_string_lossy().into_owned()
+}
@@ -0,0 +1,191 @@
+//! Checks that ray differentials give the footprint of a pixel on surfaces and follow the ray
+//! through mirrors and glass.
+
+mod common;
+
+use common::{assert_close, material};
+use ray_tracing::{
+    vec3, Cylinder, HitRecord, Hittable, Instance, Matrix4, Point3, Quadrilateral, Ray,
+    RayDifferentials, Sphere, Transform, Vec3,
+};
+
+use std::sync::Arc;
+
+/// A 4 by 2 quadrilateral in the plane `z = 0`, facing `+z`
+fn floor() -> Quadrilateral {
+    Quadrilateral::new(
+        Point3::new(-2., -1., 0.),
+        Vec3::new(4., 0., 0.),
+        Vec3::new(0., 2., 0.),
+        material(),
+    )
+}
+
+/// A ray from `origin` along `direction`, with neighbours turned by `spread` along `x` and `y`
+fn ray(origin: Point3, direction: Vec3, spread: f32) -> Ray {
+    Ray::new(origin, direction, 0.).with_differentials(Some(RayDifferentials {
+        rx_origin: origin,
+        rx_direction: direction + Vec3::new(spread, 0., 0.),
+        ry_origin: origin,
+        ry_direction: direction + Vec3::new(0., spread, 0.),
+    }))
+}
+
+fn hit(object: &dyn Hittable, r: &Ray) -> HitRecord {
+    let mut rec = common::hit_ray(object, r).expect("missed");
+    rec.compute_differentials(r);
+    rec
+}
+
+#[test]
+fn differentials_give_the_footprint_on_a_surface() {
+    let r = ray(Point3::new(0.5, 0., 2.), Vec3::new(0., 0., -1.), 0.01);
+    let rec = hit(&floor(), &r);
+    assert_close(rec.dpdx, Vec3::new(0.02, 0., 0.), "dpdx");
+    assert_close(rec.dpdy, Vec3::new(0., 0.02, 0.), "dpdy");
+    // The quadrilateral is 4 wide and 2 high in texture space
+    assert!((rec.dudx - 0.005).abs() < 1e-5 && rec.dvdx.abs() < 1e-5);
+    assert!(rec.dudy.abs() < 1e-5 && (rec.dvdy - 0.01).abs() < 1e-5);
+
+    // Grazing rays stretch the footprint
+    let origin = Point3::new(0., -1., 0.2);
+    let direction = Vec3::new(0., 1., -0.2);
+    let grazing = Ray::new(origin, direction, 0.).with_differentials(Some(RayDifferentials {
+        rx_origin: origin,
+        rx_direction: direction + Vec3::new(0.01, 0., 0.),
+        ry_origin: origin,
+        ry_direction: direction + Vec3::new(0., 0., 0.01),
+    }));
+    let rec = hit(&floor(), &grazing);
+    assert!(rec.dpdy.length() > 5. * rec.dpdx.length());
+
+    // Rays without differentials have no footprint
+    let plain = Ray::new(Point3::new(0.5, 0., 2.), Vec3::new(0., 0., -1.), 0.);
+    let rec = hit(&floor(), &plain);
+    assert_eq!((rec.dudx, rec.dvdx, rec.dudy, rec.dvdy), (0., 0., 0., 0.));
+}
+
+#[test]
+fn differentials_follow_mirrors_and_glass() {
+    let origin = Point3::new(0.3, -0.2, 2.);
+    let direction = Vec3::new(0.1, 0.2, -1.);
+    let spread = 0.01;
+    let r = ray(origin, direction, spread);
+    let rec = hit(&floor(), &r);
+    let d = r.differentials().unwrap();
+    // Where the neighbouring rays land on the floor
+    let landing = |direction: Vec3| origin + (-origin.z() / direction.z()) * direction;
+
+    // Flat mirrors reflect the neighbouring rays exactly
+    let reflected = rec.reflected_ray(&r, vec3::reflect(direction, rec.normal));
+    let rd = reflected
+        .differentials()
+        .expect("the reflection lost its differentials");
+    assert_close(rd.rx_origin, landing(d.rx_direction), "reflected x origin");
+    assert_close(
+        vec3::unit_vector(rd.rx_direction),
+        vec3::unit_vector(vec3::reflect(d.rx_direction, rec.normal)),
+        "reflected x direction",
+    );
+    assert_close(
+        vec3::unit_vector(rd.ry_direction),
+        vec3::unit_vector(vec3::reflect(d.ry_direction, rec.normal)),
+        "reflected y direction",
+    );
+
+    // Refraction bends the neighbours a little differently, which the differentials follow
+    // to first order
+    let eta = 1. / 1.5;
+    let refract = |d: Vec3| vec3::refract(vec3::unit_vector(d), rec.normal, eta);
+    let refracted = rec.refracted_ray(&r, refract(direction), eta);
+    let rd = refracted
+        .differentials()
+        .expect("the refraction lost its differentials");
+    assert_close(rd.ry_origin, landing(d.ry_direction), "refracted y origin");
+    for (actual, neighbour) in [
+        (rd.rx_direction, d.rx_direction),
+        (rd.ry_direction, d.ry_direction),
+    ] {
+        let expected = vec3::unit_vector(refract(neighbour));
+        let error = (vec3::unit_vector(actual) - expected).length();
+        let change = (expected - vec3::unit_vector(refract(direction))).length();
+        assert!(
+            error < 0.05 * change,
+            "refracted to {actual:?} instead of {expected:?}"
+        );
+    }
+}
+
+/// Checks that the differentials of `r` mirrored off `object`, or refracted into it with `eta`
+/// if there is one, point where the neighbouring rays really go, to first order, and returns
+/// how far they turn from the scattered ray
+fn assert_scattered_neighbours(
+    name: &str,
+    object: &dyn Hittable,
+    r: &Ray,
+    eta: Option<f32>,
+) -> f32 {
+    let bend = |direction: Vec3, normal: Vec3| match eta {
+        Some(eta) => vec3::refract(vec3::unit_vector(direction), normal, eta),
+        None => vec3::reflect(direction, normal),
+    };
+    let rec = hit(object, r);
+    let direction = bend(r.direction(), rec.normal);
+    let scattered = match eta {
+        Some(eta) => rec.refracted_ray(r, direction, eta),
+        None => rec.reflected_ray(r, direction),
+    };
+    let sd = scattered
+        .differentials()
+        .expect("the scattered ray lost its differentials");
+    let d = r.differentials().unwrap();
+    let wi = vec3::unit_vector(direction);
+    let mut spread: f32 = 0.;
+    for (origin, direction, actual) in [
+        (d.rx_origin, d.rx_direction, sd.rx_direction),
+        (d.ry_origin, d.ry_direction, sd.ry_direction),
+    ] {
+        let neighbour = Ray::new(origin, direction, 0.);
+        let expected = vec3::unit_vector(bend(direction, hit(object, &neighbour).normal));
+        let error = (vec3::unit_vector(actual) - expected).length();
+        let change = (expected - wi).length();
+        assert!(
+            error < 0.05 * change,
+            "{name}: scattered to {actual:?} instead of {expected:?}"
+        );
+        spread = spread.max(change);
+    }
+    spread
+}
+
+#[test]
+fn differentials_follow_the_curvature_of_mirrors_and_glass() {
+    let spread = 0.001;
+    let r = ray(Point3::new(0.2, 0.1, 5.), Vec3::new(0., 0., -1.), spread);
+
+    // A convex mirror spreads the neighbouring rays far more than a flat one would
+    let sphere = Sphere::stationary(Point3::new(0., 0., 0.), 1., material());
+    let turned = assert_scattered_neighbours("sphere", &sphere, &r, None);
+    assert!(turned > 5. * spread, "the sphere turned them by {turned}");
+
+    // Seen from inside, the mirror is concave and the normal is flipped
+    let inside = ray(Point3::new(0.2, 0.1, 0.), Vec3::new(0.1, 0., -1.), spread);
+    assert_scattered_neighbours("inside of the sphere", &sphere, &inside, None);
+
+    // A glass ball focuses them, from either side
+    assert_scattered_neighbours("glass sphere", &sphere, &r, Some(1. / 1.5));
+    assert_scattered_neighbours("inside of the glass sphere", &sphere, &inside, Some(1.5));
+
+    // The sides of a cylinder only curve around its axis
+    let cylinder = Cylinder::new(Point3::new(0., -1., 0.), 1., 2., material());
+    assert_scattered_neighbours("cylinder", &cylinder, &r, None);
+    assert_scattered_neighbours("glass cylinder", &cylinder, &r, Some(1. / 1.5));
+
+    // And instances carry the curvature of what they place, stretched and turned
+    let ellipsoid = Instance::new(
+        Arc::new(sphere),
+        Transform::new(Matrix4::rotate_y(30.) * Matrix4::scale(Vec3::new(2., 1., 0.5))).unwrap(),
+    );
+    assert_scattered_neighbours("ellipsoid", &ellipsoid, &r, None);
+    assert_scattered_neighbours("glass ellipsoid", &ellipsoid, &r, Some(1. / 1.5));
+}
@@ -0,0 +1,354 @@
+//! Checks that environment maps and skies are sampled in proportion to their brightness and
+//! light scenes the same whether or not light sampling finds them.
+
+mod common;
+
+use common::temp_path;
+use ray_tracing::{
+    utils, vec3, BvhNode, Color, Config, Distribution2D, Environment, EnvironmentConfig,
+    HittableList, ImageEnvironment, ImageTexture, SkyEnvironment, Vec3,
+};
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+fn luminance(c: Color) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+/// A dim 32 by 16 sky with a bright 2 by 2 sun above the horizon, row by row from the top
+fn sky_pixels() -> Vec<Color> {
+    (0..32 * 16)
+        .map(|i| {
+            let (x, y) = (i % 32, i / 32);
+            if (8..10).contains(&x) && (4..6).contains(&y) {
+                Color::new(400., 380., 350.)
+            } else {
+                Color::new(0.2, 0.3, 0.5)
+            }
+        })
+        .collect()
+}
+
+fn sky() -> ImageEnvironment {
+    ImageEnvironment::new(ImageTexture::new(32, 16, sky_pixels()).unwrap())
+}
+
+#[test]
+fn distributions_sample_in_proportion() {
+    let distribution = Distribution2D::new(&[0., 1., 0., 3.], 2);
+    let mut counts = [0; 4];
+    let n = 20000;
+    for _ in 0..n {
+        let ((x, y), pdf) =
+            distribution.sample_continuous(utils::random_double(), utils::random_double());
+        let cell = (y * 2.) as usize * 2 + (x * 2.) as usize;
+        counts[cell] += 1;
+        assert!((pdf - distribution.pdf(x, y)).abs() < 1e-4);
+    }
+    assert_eq!((counts[0], counts[2]), (0, 0), "{counts:?}");
+    let fraction = counts[3] as f32 / n as f32;
+    assert!((fraction - 0.75).abs() < 0.02, "{counts:?}");
+    // Each cell is a quarter of the square
+    assert!((distribution.pdf(0.75, 0.75) - 3.).abs() < 1e-4);
+}
+
+#[test]
+fn image_environments_sample_their_bright_spots() {
+    let sky = sky();
+    let n = 2000;
+    let bright = (0..n)
+        .filter(|_| luminance(sky.radiance(sky.sample())) > 100.)
+        .count();
+    assert!(
+        bright as f32 > 0.7 * n as f32,
+        "only {bright} of {n} samples found the sun"
+    );
+
+    // The density integrates to one over the sphere
+    let n = 200000;
+    let integral: f32 = (0..n)
+        .map(|_| sky.pdf(ray_tracing::vec3::random_unit_vector()))
+        .sum::<f32>()
+        * 4.
+        * PI
+        / n as f32;
+    assert!(
+        (integral - 1.).abs() < 0.05,
+        "the density integrates to {integral}"
+    );
+
+    // Directions towards the sun are far more likely than uniform sampling would make them
+    let sun = (0..)
+        .map(|_| sky.sample())
+        .find(|&d| luminance(sky.radiance(d)) > 350.)
+        .unwrap();
+    assert!(sky.pdf(sun) > 10. / (4. * PI));
+}
+
+#[test]
+fn rotation_turns_the_image_around_the_up_axis() {
+    let sky = sky();
+    let turned = self::sky().with_rotation(90.).with_intensity(2.);
+    // The same way as `RotateY`
+    let turn = |d: Vec3| Vec3::new(d.z(), d.y(), -d.x());
+    for _ in 0..100 {
+        let d = ray_tracing::vec3::random_unit_vector();
+        let (expected, actual) = (2. * sky.radiance(d), turned.radiance(turn(d)));
+        assert!(
+            (expected - actual).length() < 1e-3 * expected.length(),
+            "{actual:?} instead of {expected:?}"
+        );
+        assert!((sky.pdf(d) - turned.pdf(turn(d))).abs() < 1e-3 * sky.pdf(d));
+    }
+}
+
+/// Renders a white floor under the environment, from above, with the environment among the
+/// lights or not
+fn render_floor(environment: &str, light_sampling: bool) -> Vec<Color> {
+    render_floor_with(environment, light_sampling, "path")
+}
+
+fn render_floor_with(environment: &str, light_sampling: bool, integrator: &str) -> Vec<Color> {
+    let toml_string = format!(
+        r#"
+        [camera]
+        image_width = 16
+        aspect_ratio = 1.0
+        samples_per_pixel = 64
+        max_depth = 4
+        vfov = 40.0
+        look_from = [0.0, 1.0, 0.0]
+        look_at = [0.0, 0.0, 0.0]
+        vup = [0.0, 0.0, -1.0]
+        environment = {environment}
+        integrator = {{ type = "{integrator}" }}
+
+        [scene]
+        lights = []
+
+        [[scene.objects]]
+        type = "quad"
+        corner = [-5.0, 0.0, -5.0]
+        u = [10.0, 0.0, 0.0]
+        v = [0.0, 0.0, 10.0]
+        material = {{ type = "lambertian", color = [0.5, 0.5, 0.5] }}
+        "#
+    );
+    let config: Config = toml::from_str(&toml_string).expect("invalid scene file");
+    let (mut camera, world, lights) = config.to_scene().expect("invalid scene");
+    let lights = if light_sampling {
+        lights
+    } else {
+        HittableList::new()
+    };
+    let world = BvhNode::from_list(world);
+    camera.render_image(&world, Arc::new(lights))
+}
+
+fn mean_and_deviation(image: &[Color]) -> (f32, f32) {
+    let n = image.len() as f32;
+    let mean = image.iter().map(|&c| luminance(c)).sum::<f32>() / n;
+    let variance = image
+        .iter()
+        .map(|&c| (luminance(c) - mean).powi(2))
+        .sum::<f32>()
+        / n;
+    (mean, variance.sqrt())
+}
+
+fn write_exr(path: &str, width: usize, height: usize, pixels: &[Color]) {
+    use exr::prelude::*;
+    Image::from_channels(
+        (width, height),
+        SpecificChannels::rgb(|Vec2(x, y)| {
+            let c: Color = pixels[y * width + x];
+            (c.x(), c.y(), c.z())
+        }),
+    )
+    .write()
+    .to_file(path)
+    .unwrap();
+}
+
+#[test]
+fn white_environments_light_diffuse_floors_by_their_albedo() {
+    let path = temp_path("white.exr");
+    write_exr(&path, 8, 4, &[Color::new(1., 1., 1.); 32]);
+    let image = render_floor(&format!("{{ type = \"image\", path = '{path}' }}"), true);
+    let (mean, _) = mean_and_deviation(&image);
+    assert!(
+        (mean - 0.5).abs() < 0.01,
+        "the floor is {mean} instead of 0.5"
+    );
+
+    for integrator in ["direct", "bdpt", "spectral", "sppm"] {
+        let image = render_floor_with(
+            &format!("{{ type = \"image\", path = '{path}' }}"),
+            true,
+            integrator,
+        );
+        let (mean, _) = mean_and_deviation(&image);
+        assert!(
+            (mean - 0.5).abs() < 0.05,
+            "{integrator}: the floor is {mean} instead of 0.5"
+        );
+    }
+}
+
+#[test]
+fn light_sampling_finds_the_sun() {
+    let path = temp_path("sky.exr");
+    write_exr(&path, 32, 16, &sky_pixels());
+    let environment = format!("{{ type = \"image\", path = '{path}', rotation = 30.0 }}");
+
+    let (sampled, sampled_deviation) = mean_and_deviation(&render_floor(&environment, true));
+    let (unsampled, unsampled_deviation) = mean_and_deviation(&render_floor(&environment, false));
+    assert!(
+        (sampled - unsampled).abs() < 0.1 * sampled,
+        "{sampled} with light sampling, {unsampled} without"
+    );
+    assert!(
+        sampled_deviation < 0.25 * unsampled_deviation,
+        "light sampling only brought the noise from {unsampled_deviation} to {sampled_deviation}"
+    );
+}
+
+#[test]
+fn the_sun_rises_in_the_east_and_stands_in_the_south_at_noon() {
+    // At 45° north on the equinox, the sun culminates 45° up
+    let noon = SkyEnvironment::sun_position(45., 81., 12.);
+    let expected = Vec3::new(0., 1., 1.) / 2f32.sqrt();
+    assert!((noon - expected).length() < 1e-3, "{noon:?} at noon");
+
+    let morning = SkyEnvironment::sun_position(45., 81., 6.);
+    assert!(
+        morning.x() > 0.99 && morning.y().abs() < 0.01,
+        "{morning:?} at sunrise"
+    );
+    let evening = SkyEnvironment::sun_position(45., 81., 18.);
+    assert!(evening.x() < -0.99, "{evening:?} at sunset");
+
+    // Higher in summer, and below the horizon at night
+    assert!(SkyEnvironment::sun_position(45., 172., 12.).y() > 0.9);
+    assert!(SkyEnvironment::sun_position(45., 172., 0.).y() < 0.);
+}
+
+#[test]
+fn skies_are_blue_and_sunsets_are_red() {
+    let up = Vec3::new(0., 1., 0.);
+    let noon = SkyEnvironment::new(Vec3::new(0., 1., 1.), 3.).unwrap();
+    let zenith = noon.radiance(up);
+    assert!(zenith.z() > zenith.x(), "the sky is {zenith:?}");
+    let near_sun = noon.radiance(Vec3::new(0., 1., 1.2));
+    let away = noon.radiance(Vec3::new(0., 1., -1.2));
+    assert!(luminance(near_sun) > luminance(away));
+
+    let sun = noon.sun_radiance();
+    let sunset = SkyEnvironment::new(Vec3::new(1., 0.05, 0.), 3.)
+        .unwrap()
+        .sun_radiance();
+    assert!(sunset.x() / sunset.z() > 2. * sun.x() / sun.z());
+    assert!(luminance(sunset) < luminance(sun));
+    let night = SkyEnvironment::new(Vec3::new(1., -0.1, 0.), 3.).unwrap();
+    assert_eq!(luminance(night.sun_radiance()), 0.);
+
+    // The sun is seen along its direction, unless it's turned off
+    assert!(luminance(noon.radiance(Vec3::new(0., 1., 1.))) > 1000.);
+    let sunless = SkyEnvironment::new(Vec3::new(0., 1., 1.), 3.)
+        .unwrap()
+        .with_sun(false);
+    assert!(luminance(sunless.radiance(Vec3::new(0., 1., 1.))) < 10.);
+}
+
+#[test]
+fn skies_sample_their_sun_and_light_the_ground() {
+    // A white ground reflects the irradiance of a horizontal surface over π, which sampling the
+    // sky by its density has to estimate without missing the sun
+    for (sun_direction, turbidity) in [
+        (Vec3::new(0., 1., 0.4), 2.),
+        (Vec3::new(0.3, 0.5, -1.), 5.),
+        (Vec3::new(-1., 0.04, 0.), 3.),
+    ] {
+        let sky = SkyEnvironment::new(sun_direction, turbidity)
+            .unwrap()
+            .with_ground_albedo(Color::new(1., 1., 1.))
+            .with_rotation(25.);
+        let n = 100_000;
+        let mut irradiance = 0.;
+        for _ in 0..n {
+            let direction = sky.sample();
+            let pdf = sky.pdf(direction);
+            assert!(pdf > 0., "sampled {direction:?} with no density");
+            irradiance += luminance(sky.radiance(direction)) * direction.y().max(0.) / pdf;
+        }
+        let estimate = irradiance / n as f32 / PI;
+        let ground = luminance(sky.radiance(Vec3::new(0., -1., 0.)));
+        assert!(
+            (estimate - ground).abs() < 0.03 * ground,
+            "estimated {estimate} under a sun along {sun_direction:?}, but the ground is {ground}"
+        );
+    }
+
+    // The density integrates to 1 once the sun is out of the picture
+    let sky = SkyEnvironment::new(Vec3::new(0.2, 0.6, 1.), 4.)
+        .unwrap()
+        .with_sun(false);
+    let n = 100_000;
+    let integral = (0..n)
+        .map(|_| sky.pdf(vec3::random_unit_vector()))
+        .sum::<f32>()
+        * 4.
+        * PI
+        / n as f32;
+    assert!(
+        (integral - 1.).abs() < 0.02,
+        "the density integrates to {integral}"
+    );
+}
+
+#[test]
+fn sky_configs_place_the_sun_one_way() {
+    let sky = |fields: &str| {
+        toml::from_str::<EnvironmentConfig>(&format!("type = \"sky\"\n{fields}"))
+            .expect("invalid environment")
+            .build()
+    };
+    assert!(sky("sun_direction = [0.0, 1.0, 1.0]").is_ok());
+    assert!(sky("latitude = 52.0\nday = 172.0\ntime = 9.5\nturbidity = 6.0").is_ok());
+    assert!(sky("").is_err());
+    assert!(sky("sun_direction = [0.0, 1.0, 1.0]\ntime = 9.5").is_err());
+    assert!(sky("latitude = 52.0\ntime = 9.5").is_err());
+    assert!(sky("sun_direction = [0.0, 0.0, 0.0]").is_err());
+    assert!(sky("sun_direction = [0.0, 1.0, 1.0]\nturbidity = 1.0").is_err());
+    assert!(sky("sun_direction = [0.0, 1.0, 1.0]\nturbidity = 10.5").is_err());
+    assert!(SkyEnvironment::new(Vec3::new(0., 1., 1.), 1.).is_err());
+}
+
+#[test]
+fn skies_light_scenes_the_same_with_or_without_light_sampling() {
+    // With the sun turned off, paths that find the sky on their own don't miss much
+    let environment = "{ type = \"sky\", latitude = 40.0, day = 200.0, time = 16.0, sun = false }";
+    let (sampled, sampled_deviation) = mean_and_deviation(&render_floor(environment, true));
+    let (unsampled, _) = mean_and_deviation(&render_floor(environment, false));
+    assert!(
+        (sampled - unsampled).abs() < 0.05 * sampled,
+        "{sampled} with light sampling, {unsampled} without"
+    );
+    assert!(sampled_deviation < 0.1 * sampled);
+
+    // The ground is white, so the floor reflects half of the ground's radiance
+    let sun =
+        "{ type = \"sky\", sun_direction = [0.3, 1.0, 0.2], ground_albedo = [1.0, 1.0, 1.0] }";
+    let (floor, _) = mean_and_deviation(&render_floor(sun, true));
+    let ground = luminance(
+        SkyEnvironment::new(Vec3::new(0.3, 1., 0.2), 3.)
+            .unwrap()
+            .with_ground_albedo(Color::new(1., 1., 1.))
+            .radiance(Vec3::new(0., -1., 0.)),
+    );
+    assert!(
+        (floor - 0.5 * ground).abs() < 0.05 * ground,
+        "the floor is {floor}, against a ground of {ground}"
+    );
+}
@@ -0,0 +1,175 @@
+//! Renders every scene in `examples/` at a low resolution and compares it with a stored reference
+//! image in `tests/references/`.
+//!
+//! Run with `UPDATE_REFERENCES=1` to re-render the references after an intentional change.
+
+use ray_tracing::{BvhNode, Camera, Color, Config};
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const IMAGE_WIDTH: usize = 48;
+const SAMPLES_PER_PIXEL: usize = 64;
+const MAX_DEPTH: usize = 8;
+const SEED: u64 = 1;
+
+/// Side length of the square blocks that are averaged before comparing. Renders are seeded, but
+/// this keeps small floating point differences between platforms from failing the test.
+const BLOCK_SIZE: usize = 8;
+/// Largest allowed mean difference between corresponding blocks, after tone mapping to [0, 1)
+const TOLERANCE: f32 = 0.01;
+
+fn manifest_path(relative: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+fn render(name: &str, seed: u64) -> (Camera, Vec<Color>) {
+    let path = manifest_path(&format!("examples/{name}.toml"));
+    let toml_string = std::fs::read_to_string(&path).expect("couldn't open scene file");
+    let mut config: Config = toml::from_str(&toml_string).expect("invalid scene file");
+    config.camera.image_width = IMAGE_WIDTH;
+    config.camera.samples_per_pixel = SAMPLES_PER_PIXEL;
+    config.camera.max_depth = MAX_DEPTH;
+    config.camera.seed = seed;
+    config.camera.file_path = manifest_path(&format!("tests/references/{name}.exr"))
+        .to_string_lossy()
+        .into_owned();
+
+    let (mut camera, world, lights) = config.to_scene().expect("invalid scene");
+    let world = BvhNode::from_list(world);
+    let image = camera.render_image(&world, Arc::new(lights));
+    (camera, image)
+}
+
+fn load_reference(name: &str) -> (usize, usize, Vec<Color>) {
+    let path = manifest_path(&format!("tests/references/{name}.exr"));
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        &path,
+        |resolution, _| {
+            let width = resolution.width();
+            (width, vec![Color::default(); width * resolution.height()])
+        },
+        |(width, pixels), position, (r, g, b, _): (f32, f32, f32, f32)| {
+            pixels[position.y() * *width + position.x()] = Color::new(r, g, b);
+        },
+    )
+    .unwrap_or_else(|e| {
+        panic!(
+            "couldn't read {}: {e}. Run with UPDATE_REFERENCES=1 to create it",
+            path.display()
+        )
+    });
+
+    let size = image.layer_data.size;
+    let (_, pixels) = image.layer_data.channel_data.pixels;
+    (size.width(), size.height(), pixels)
+}
+
+fn block_averages(width: usize, height: usize, image: &[Color]) -> Vec<Color> {
+    let tone_map = |c: f32| c / (1. + c);
+    let mut blocks = Vec::new();
+    for by in (0..height).step_by(BLOCK_SIZE) {
+        for bx in (0..width).step_by(BLOCK_SIZE) {
+            let mut sum = Color::default();
+            let mut count = 0;
+            for y in by..(by + BLOCK_SIZE).min(height) {
+                for x in bx..(bx + BLOCK_SIZE).min(width) {
+                    let c = image[y * width + x];
+                    sum += Color::new(tone_map(c.x()), tone_map(c.y()), tone_map(c.z()));
+                    count += 1;
+                }
+            }
+            blocks.push(sum / count as f32);
+        }
+    }
+    blocks
+}
+
+fn check_scene(name: &str) {
+    let (camera, image) = render(name, SEED);
+    if std::env::var_os("UPDATE_REFERENCES").is_some() {
+        std::fs::create_dir_all(manifest_path("tests/references")).unwrap();
+        camera.write_image(&image);
+    }
+
+    let (width, height) = (camera.image_width, camera.image_height());
+    let (ref_width, ref_height, reference) = load_reference(name);
+    assert_eq!(
+        (width, height),
+        (ref_width, ref_height),
+        "{name}: image size differs from the reference"
+    );
+
+    let rendered = block_averages(width, height, &image);
+    let expected = block_averages(width, height, &reference);
+    let total: f32 = rendered
+        .iter()
+        .zip(&expected)
+        .map(|(a, b)| {
+            let d = *a - *b;
+            (d.x().abs() + d.y().abs() + d.z().abs()) / 3.
+        })
+        .sum();
+    let difference = total / rendered.len() as f32;
+
+    assert!(
+        difference <= TOLERANCE,
+        "{name}: mean block difference {difference} exceeds {TOLERANCE}"
+    );
+}
+
+#[test]
+fn bouncing_spheres() {
+    check_scene("bouncing-spheres");
+}
+
+#[test]
+fn checkered_spheres() {
+    check_scene("checkered-spheres");
+}
+
+#[test]
+fn simple_light() {
+    check_scene("simple-light");
+}
+
+#[test]
+fn quads() {
+    check_scene("quads");
+}
+
+#[test]
+fn cornell_box() {
+    check_scene("cornell-box");
+}
+
+#[test]
+fn cornell_smoke() {
+    check_scene("cornell-smoke");
+}
+
+#[test]
+fn perlin_spheres() {
+    check_scene("perlin-spheres");
+}
+
+#[test]
+fn final_scene() {
+    check_scene("final-scene");
+}
+
+#[test]
+fn renders_are_deterministic() {
+    let bits = |image: Vec<Color>| -> Vec<[u32; 3]> {
+        image
+            .iter()
+            .map(|c| [c.x().to_bits(), c.y().to_bits(), c.z().to_bits()])
+            .collect()
+    };
+    let (_, first) = render("cornell-smoke", 7);
+    let (_, second) = render("cornell-smoke", 7);
+    assert!(
+        bits(first) == bits(second),
+        "same seed gave different images"
+    );
+}
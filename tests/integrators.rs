@@ -0,0 +1,378 @@
+//! Renders the Cornell box with every integrator and checks that the results are plausible.
+
+use ray_tracing::{BvhNode, Color, Config, Film, HittableList, IntegratorConfig};
+
+use std::sync::Arc;
+
+const IMAGE_WIDTH: usize = 32;
+const SAMPLES_PER_PIXEL: usize = 16;
+
+fn render(integrator: IntegratorConfig, max_depth: usize) -> Vec<Color> {
+    render_with_samples(integrator, max_depth, SAMPLES_PER_PIXEL)
+}
+
+fn render_with_samples(
+    integrator: IntegratorConfig,
+    max_depth: usize,
+    samples_per_pixel: usize,
+) -> Vec<Color> {
+    let toml_string = include_str!("../examples/cornell-box.toml");
+    render_scene(toml_string, integrator, max_depth, samples_per_pixel)
+}
+
+fn render_scene(
+    toml_string: &str,
+    integrator: IntegratorConfig,
+    max_depth: usize,
+    samples_per_pixel: usize,
+) -> Vec<Color> {
+    let mut config: Config = toml::from_str(toml_string).expect("invalid scene file");
+    config.camera.image_width = IMAGE_WIDTH;
+    config.camera.samples_per_pixel = samples_per_pixel;
+    config.camera.max_depth = max_depth;
+    config.camera.integrator = integrator;
+
+    let (mut camera, world, lights) = config.to_scene().expect("invalid scene");
+    let world = BvhNode::from_list(world);
+    camera.render_image(&world, Arc::new(lights))
+}
+
+fn mean(image: &[Color]) -> f32 {
+    let sum: f32 = image.iter().map(|c| c.x() + c.y() + c.z()).sum();
+    sum / (3 * image.len()) as f32
+}
+
+#[test]
+fn every_integrator_renders_from_toml() {
+    let integrators = [
+        "type = \"path\"",
+        "type = \"direct\"",
+        "type = \"bdpt\"",
+        "type = \"spectral\"",
+        "type = \"mlt\"\nmutations_per_pixel = 4\nbootstrap_samples = 1000\nchains = 16",
+        "type = \"sppm\"\nphotons_per_iteration = 2000",
+        "type = \"ambient_occlusion\"\ndistance = 200.0",
+        "type = \"normals\"",
+        "type = \"uv\"",
+        "type = \"depth\"",
+        "type = \"material_id\"",
+    ];
+    for toml_string in integrators {
+        let integrator: IntegratorConfig = toml::from_str(toml_string).unwrap();
+        let bounded = !matches!(
+            integrator,
+            IntegratorConfig::Path
+                | IntegratorConfig::Direct
+                | IntegratorConfig::Bdpt
+                | IntegratorConfig::Spectral
+                | IntegratorConfig::Mlt { .. }
+                | IntegratorConfig::Sppm { .. }
+                | IntegratorConfig::Depth
+        );
+        // A few wavelengths at a time can fall just outside the sRGB gamut
+        let in_gamut = integrator != IntegratorConfig::Spectral;
+        let image = render(integrator, 8);
+
+        assert!(mean(&image) > 0., "{toml_string}: image is black");
+        for c in &image {
+            for channel in [c.x(), c.y(), c.z()] {
+                assert!(channel.is_finite(), "{toml_string}: {channel}");
+                if in_gamut {
+                    assert!(channel >= 0., "{toml_string}: {channel} is negative");
+                } else {
+                    assert!(channel >= -0.01, "{toml_string}: {channel} is negative");
+                }
+                if bounded {
+                    assert!(channel <= 1., "{toml_string}: {channel} is above 1");
+                }
+            }
+        }
+    }
+}
+
+/// With only one bounce the path tracer computes exactly direct lighting. The Cornell box has no
+/// specular surfaces, so both integrators should agree up to noise.
+#[test]
+fn direct_lighting_matches_single_bounce_path_tracing() {
+    let direct = mean(&render(IntegratorConfig::Direct, 8));
+    let path = mean(&render(IntegratorConfig::Path, 2));
+    let full = mean(&render(IntegratorConfig::Path, 8));
+
+    assert!(
+        (direct - path).abs() <= 0.02 * path,
+        "direct lighting {direct} differs from single bounce path tracing {path}"
+    );
+    assert!(
+        full > path,
+        "indirect light should brighten the image: {full} <= {path}"
+    );
+}
+
+/// Both integrators are unbiased, so they should converge to the same image. Bidirectional
+/// paths are more expensive but much less noisy here, so fewer samples suffice.
+#[test]
+fn bdpt_matches_path_tracing() {
+    let path = mean(&render_with_samples(IntegratorConfig::Path, 4, 128));
+    let bdpt = render_with_samples(IntegratorConfig::Bdpt, 4, 32);
+
+    assert!(
+        (mean(&bdpt) - path).abs() <= 0.01 * path,
+        "bidirectional path tracing {} differs from path tracing {path}",
+        mean(&bdpt)
+    );
+
+    // Light paths splat onto other pixels from every thread, which must not affect the result
+    let again = render_with_samples(IntegratorConfig::Bdpt, 4, 32);
+    assert!(
+        bdpt.iter()
+            .zip(&again)
+            .all(|(a, b)| a.0.map(f32::to_bits) == b.0.map(f32::to_bits)),
+        "bidirectional renders with the same seed differ"
+    );
+}
+
+/// RGB colors are uplifted so that they render back to themselves, so only light that
+/// bounces between colored surfaces can differ a little.
+#[test]
+fn spectral_matches_path_tracing() {
+    let path = mean(&render_with_samples(IntegratorConfig::Path, 4, 128));
+    let spectral = mean(&render_with_samples(IntegratorConfig::Spectral, 4, 128));
+
+    assert!(
+        (spectral - path).abs() <= 0.02 * path,
+        "spectral rendering {spectral} differs from path tracing {path}"
+    );
+}
+
+/// Metropolis sampling only redistributes the samples of the path tracer, and the bootstrap
+/// normalization makes the average brightness match. Chains splat onto every pixel at once, which
+/// must not affect the result.
+#[test]
+fn mlt_matches_path_tracing() {
+    let path = mean(&render_with_samples(IntegratorConfig::Path, 4, 128));
+    let mlt_config = IntegratorConfig::Mlt {
+        mutations_per_pixel: 64,
+        bootstrap_samples: 100_000,
+        chains: 256,
+        sigma: 0.01,
+        large_step_probability: 0.3,
+    };
+    let mlt = render(mlt_config.clone(), 4);
+
+    assert!(
+        (mean(&mlt) - path).abs() <= 0.02 * path,
+        "Metropolis light transport {} differs from path tracing {path}",
+        mean(&mlt)
+    );
+
+    let again = render(mlt_config, 4);
+    assert!(
+        mlt.iter()
+            .zip(&again)
+            .all(|(a, b)| a.0.map(f32::to_bits) == b.0.map(f32::to_bits)),
+        "Metropolis renders with the same seed differ"
+    );
+}
+
+#[test]
+fn film_splats_sum_exactly_and_saturate_instead_of_wrapping() {
+    let film = Film::new(2, 2);
+    film.add_splat(1, 0, Color::new(1.5, 0.25, -0.5));
+    film.add_splat(1, 0, Color::new(1.5, f32::NAN, f32::INFINITY));
+    let color = film.splat(1, 0);
+    assert_eq!((color.x(), color.y(), color.z()), (3., 0.25, -0.5));
+    assert_eq!(film.splat(0, 1).x(), 0.);
+
+    // Huge splats are clamped, and sums past the fixed point range stay at its top
+    for _ in 0..4 {
+        film.add_splat(0, 0, Color::new(f32::MAX, 1e9, -1e9));
+    }
+    let color = film.splat(0, 0);
+    for (channel, expected) in [
+        (color.x(), 2.147e9),
+        (color.y(), 2.147e9),
+        (color.z(), -2.147e9),
+    ] {
+        assert!(
+            (channel - expected).abs() < 1e6,
+            "saturated at {channel} instead of {expected}"
+        );
+    }
+}
+
+/// Photon mapping is only consistent, but with diffuse walls the blur of the shrinking gather
+/// radius barely changes the average brightness.
+#[test]
+fn sppm_matches_path_tracing() {
+    let path = mean(&render_with_samples(IntegratorConfig::Path, 4, 128));
+    let sppm_config = IntegratorConfig::Sppm {
+        photons_per_iteration: 20_000,
+        initial_radius: None,
+        alpha: 2. / 3.,
+    };
+    let sppm = mean(&render_with_samples(sppm_config, 4, 16));
+
+    assert!(
+        (sppm - path).abs() <= 0.02 * path,
+        "photon mapping {sppm} differs from path tracing {path}"
+    );
+}
+
+/// The Cornell box with a rough gold block and a frosted glass block. Bidirectional path tracing
+/// also evaluates their BSDFs for light going the other way, and the density of scattering back
+/// through the glass.
+#[test]
+fn microfacet_materials_match_between_integrators() {
+    let toml_string = include_str!("../examples/cornell-box.toml")
+        .replace(
+            "green = {",
+            "gold = { type = \"conductor\", metal = \"gold\", roughness = 0.4 }\n\
+             frosted = { type = \"rough_dielectric\", refraction_index = 1.5, roughness = 0.3 }\n\
+             green = {",
+        )
+        .replacen(
+            "material = \"white\"\ntransform",
+            "material = \"gold\"\ntransform",
+            1,
+        )
+        .replacen(
+            "material = \"white\"\ntransform",
+            "material = \"frosted\"\ntransform",
+            1,
+        );
+    assert!(
+        toml_string.contains("\"gold\"\ntransform")
+            && toml_string.contains("\"frosted\"\ntransform")
+    );
+
+    let path = mean(&render_scene(&toml_string, IntegratorConfig::Path, 6, 128));
+    let bdpt = mean(&render_scene(&toml_string, IntegratorConfig::Bdpt, 6, 32));
+    assert!(
+        (bdpt - path).abs() <= 0.02 * path,
+        "bidirectional path tracing {bdpt} differs from path tracing {path}"
+    );
+}
+
+/// A lamp inside a frosted glass globe over a white floor, so all the light leaves through the
+/// rough glass. Light subpaths and photons refract out of the globe, carrying importance.
+#[test]
+fn light_through_frosted_glass_matches_between_integrators() {
+    let toml_string = r#"
+        [camera]
+        aspect_ratio = 1.0
+        vfov = 40.0
+        look_from = [0.0, 1.5, 3.0]
+        look_at = [0.0, 0.5, 0.0]
+        background = [0.0, 0.0, 0.0]
+
+        [[scene.objects]]
+        type = "quad"
+        corner = [-3.0, 0.0, -3.0]
+        u = [6.0, 0.0, 0.0]
+        v = [0.0, 0.0, 6.0]
+        material = { type = "lambertian", color = [0.8, 0.8, 0.8] }
+
+        [[scene.objects]]
+        type = "sphere"
+        center = [0.0, 1.0, 0.0]
+        radius = 0.6
+        material = { type = "rough_dielectric", refraction_index = 1.5, roughness = 0.3 }
+
+        [[scene.lights]]
+        type = "sphere"
+        center = [0.0, 1.0, 0.0]
+        radius = 0.25
+        material = { type = "diffuse_light", color = [4.0, 4.0, 4.0] }
+        "#;
+
+    let path = mean(&render_scene(toml_string, IntegratorConfig::Path, 8, 256));
+    let bdpt = mean(&render_scene(toml_string, IntegratorConfig::Bdpt, 8, 64));
+    let sppm_config = IntegratorConfig::Sppm {
+        photons_per_iteration: 20_000,
+        initial_radius: None,
+        alpha: 2. / 3.,
+    };
+    let sppm = mean(&render_scene(toml_string, sppm_config, 8, 16));
+    assert!(
+        (bdpt - path).abs() <= 0.03 * path,
+        "bidirectional path tracing {bdpt} differs from path tracing {path}"
+    );
+    assert!(
+        (sppm - path).abs() <= 0.03 * path,
+        "photon mapping {sppm} differs from path tracing {path}"
+    );
+}
+
+/// A small light reflected in a fuzzy metal floor, rendered with `seed`, either with the light
+/// in the lights list or found only by sampling the metal
+fn render_highlight(integrator: &str, light_sampling: bool, seed: u64) -> Vec<Color> {
+    let toml_string = format!(
+        r#"
+        [camera]
+        image_width = 16
+        aspect_ratio = 1.0
+        samples_per_pixel = 256
+        max_depth = 2
+        vfov = 30.0
+        look_from = [0.0, 1.0, 1.0]
+        look_at = [0.0, 0.0, 0.0]
+        seed = {seed}
+        integrator = {{ type = "{integrator}" }}
+
+        [[scene.objects]]
+        type = "quad"
+        corner = [-5.0, 0.0, -5.0]
+        u = [10.0, 0.0, 0.0]
+        v = [0.0, 0.0, 10.0]
+        material = {{ type = "metal", color = [0.9, 0.8, 0.7], fuzz = 0.2 }}
+
+        [[scene.lights]]
+        type = "sphere"
+        center = [0.0, 1.0, -1.0]
+        radius = 0.1
+        material = {{ type = "diffuse_light", color = [20.0, 20.0, 20.0] }}
+        "#
+    );
+    let config: Config = toml::from_str(&toml_string).expect("invalid scene file");
+    let (mut camera, world, lights) = config.to_scene().expect("invalid scene");
+    let lights = if light_sampling {
+        lights
+    } else {
+        HittableList::new()
+    };
+    let world = BvhNode::from_list(world);
+    camera.render_image(&world, Arc::new(lights))
+}
+
+/// Fuzzy metal takes part in light sampling, which must find the same highlight as sampling the
+/// metal alone, with less noise
+#[test]
+fn fuzzy_metal_highlights_match_with_and_without_light_sampling() {
+    // How far apart two renders with different seeds are, per pixel
+    let noise = |a: &[Color], b: &[Color]| {
+        let sum: f32 = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| (a.x() - b.x()).abs() + (a.y() - b.y()).abs() + (a.z() - b.z()).abs())
+            .sum();
+        sum / (3 * a.len()) as f32
+    };
+
+    for integrator in ["path", "direct"] {
+        let mis = [1, 2].map(|seed| render_highlight(integrator, true, seed));
+        let bsdf = [1, 2].map(|seed| render_highlight(integrator, false, seed));
+        let mis_mean = (mean(&mis[0]) + mean(&mis[1])) / 2.;
+        let bsdf_mean = (mean(&bsdf[0]) + mean(&bsdf[1])) / 2.;
+        assert!(mis_mean > 0.05, "{integrator}: there's no highlight");
+        assert!(
+            (mis_mean - bsdf_mean).abs() <= 0.03 * bsdf_mean,
+            "{integrator}: with light sampling {mis_mean}, without {bsdf_mean}"
+        );
+
+        let (mis_noise, bsdf_noise) = (noise(&mis[0], &mis[1]), noise(&bsdf[0], &bsdf[1]));
+        assert!(
+            mis_noise < 0.75 * bsdf_noise,
+            "{integrator}: light sampling has noise {mis_noise}, against {bsdf_noise} without"
+        );
+    }
+}
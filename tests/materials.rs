@@ -0,0 +1,488 @@
+//! Checks that the microfacet materials sample directions with the density they report.
+
+use ray_tracing::{
+    CoatedMaterial, Color, Conductor, HitRecord, Lambertian, Material, Microfacet,
+    MicrofacetDistribution, MixMaterial, Principled, PrincipledParameters, Ray, RoughDielectric,
+    ScatterRecord, SolidColor, Texture, Vec3,
+};
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+const DISTRIBUTIONS: [MicrofacetDistribution; 2] = [
+    MicrofacetDistribution::Ggx,
+    MicrofacetDistribution::Beckmann,
+];
+
+/// Integrates `f` over the sphere of directions on a grid that is uniform in solid angle
+fn integrate_sphere(f: impl Fn(Vec3) -> f32) -> f32 {
+    let (n_z, n_phi) = (1000, 500);
+    let mut sum = 0.;
+    for i in 0..n_z {
+        let z = -1. + 2. * (i as f32 + 0.5) / n_z as f32;
+        let r = (1. - z * z).sqrt();
+        for j in 0..n_phi {
+            let phi = 2. * PI * (j as f32 + 0.5) / n_phi as f32;
+            sum += f(Vec3::new(r * phi.cos(), r * phi.sin(), z));
+        }
+    }
+    sum * 4. * PI / (n_z * n_phi) as f32
+}
+
+fn direction(theta_degrees: f32) -> Vec3 {
+    let theta = theta_degrees.to_radians();
+    Vec3::new(theta.sin(), 0., theta.cos())
+}
+
+fn hit(front_face: bool) -> HitRecord {
+    HitRecord {
+        normal: Vec3::new(0., 0., 1.),
+        // Along neither axis of the arbitrary frame, so stretched lobes are turned
+        dpdu: Vec3::new(1., 2., 0.),
+        front_face,
+        ..Default::default()
+    }
+}
+
+/// Isotropic and stretched distributions of both kinds
+fn microfacets() -> Vec<Microfacet> {
+    DISTRIBUTIONS
+        .into_iter()
+        .flat_map(|d| {
+            [
+                Microfacet::new(d, 0.5),
+                Microfacet::anisotropic(d, 0.7, 0.35),
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn microfacet_distributions_are_normalized() {
+    for microfacet in microfacets() {
+        let projected = integrate_sphere(|wm| {
+            if wm.z() > 0. {
+                microfacet.d(wm) * wm.z()
+            } else {
+                0.
+            }
+        });
+        assert!((projected - 1.).abs() < 0.01, "{microfacet:?}: {projected}");
+
+        for theta in [0., 40., 80.] {
+            let w = direction(theta);
+            let visible = integrate_sphere(|wm| {
+                if wm.z() > 0. {
+                    microfacet.d_visible(w, wm)
+                } else {
+                    0.
+                }
+            });
+            assert!(
+                (visible - 1.).abs() < 0.02,
+                "{microfacet:?} at {theta}°: {visible}"
+            );
+        }
+    }
+}
+
+/// Averages over sampled visible normals must match integrals over their density
+#[test]
+fn visible_normals_are_sampled_with_their_density() {
+    let n = 512;
+    for microfacet in microfacets() {
+        for theta in [0., 40., 80.] {
+            let w = direction(theta);
+            let moments = |wm: Vec3| [wm.x(), wm.z(), wm.x() * wm.x()];
+
+            let mut sampled = [0.; 3];
+            for i in 0..n {
+                for j in 0..n {
+                    let u = ((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32);
+                    let wm = microfacet.sample_wm(w, u);
+                    assert!(wm.z() > 0. && ray_tracing::vec3::dot(w, wm) >= 0.);
+                    for (s, m) in sampled.iter_mut().zip(moments(wm)) {
+                        *s += m / (n * n) as f32;
+                    }
+                }
+            }
+
+            for (k, s) in sampled.into_iter().enumerate() {
+                let expected = integrate_sphere(|wm| {
+                    if wm.z() > 0. {
+                        moments(wm)[k] * microfacet.d_visible(w, wm)
+                    } else {
+                        0.
+                    }
+                });
+                assert!(
+                    (s - expected).abs() < 0.01,
+                    "{microfacet:?} at {theta}°: moment {k} is {s}, expected {expected}"
+                );
+            }
+        }
+    }
+}
+
+/// Scatters until the material picks a lobe that isn't specular, like the mirror reflection of a
+/// smooth coating
+fn scatter_diffusely(material: &dyn Material, rec: &HitRecord, r_in: &Ray) -> ScatterRecord {
+    for _ in 0..1000 {
+        let mut srec = ScatterRecord::default();
+        assert!(material.scatter(r_in, rec, &mut srec));
+        if !srec.skip_pdf {
+            return srec;
+        }
+    }
+    panic!("the material only scatters specularly");
+}
+
+/// Averages `eval / pdf` over sampled directions, which estimates the integral of `eval`
+fn sampled_albedo(material: &dyn Material, rec: &HitRecord, r_in: &Ray) -> Color {
+    let srec = scatter_diffusely(material, rec, r_in);
+    let n = 200_000;
+    let mut sum = Color::new(0., 0., 0.);
+    for _ in 0..n {
+        let scattered = Ray::new(rec.p, srec.pdf.generate(), 0.);
+        let pdf = srec.pdf.value(scattered.direction());
+        if pdf > 0. {
+            sum += material.eval(r_in, rec, &scattered) / pdf;
+        }
+    }
+    sum / n as f32
+}
+
+/// The sampled estimate must match a direct integration of `eval`, and the pdf must match what
+/// `scattering_pdf` reports
+///
+/// Radiance can at most be concentrated by `max_albedo`, the square of the relative refractive
+/// index for light leaving a denser medium.
+fn check_sampling(name: &str, material: &dyn Material, front_face: bool, max_albedo: f32) {
+    let rec = hit(front_face);
+    for theta in [20., 60.] {
+        let wo = direction(theta);
+        let r_in = Ray::new(rec.p + wo, -wo, 0.);
+
+        let channel = |f: fn(&Color) -> f32| {
+            integrate_sphere(|wi| f(&material.eval(&r_in, &rec, &Ray::new(rec.p, wi, 0.))))
+        };
+        let expected = Color::new(channel(Color::x), channel(Color::y), channel(Color::z));
+        let sampled = sampled_albedo(material, &rec, &r_in);
+        for (e, s) in [
+            (expected.x(), sampled.x()),
+            (expected.y(), sampled.y()),
+            (expected.z(), sampled.z()),
+        ] {
+            assert!(
+                (e - s).abs() < 0.02 * e.max(0.1),
+                "{name} at {theta}°: sampled {sampled:?}, integrated {expected:?}"
+            );
+            assert!(
+                e <= max_albedo + 0.01,
+                "{name} at {theta}°: albedo {expected:?}"
+            );
+        }
+
+        let srec = scatter_diffusely(material, &rec, &r_in);
+        let total = integrate_sphere(|wi| srec.pdf.value(wi));
+        assert!(
+            total <= 1.01,
+            "{name} at {theta}°: pdf integrates to {total}"
+        );
+        let wi = direction(-theta - 5.);
+        let scattered = Ray::new(rec.p, wi, 0.);
+        assert_eq!(
+            srec.pdf.value(wi),
+            material.scattering_pdf(&r_in, &rec, &scattered)
+        );
+    }
+}
+
+#[test]
+fn rough_conductors_sample_their_brdf() {
+    let (eta, k) = ray_tracing::named_conductor("gold").unwrap();
+    for microfacet in microfacets() {
+        let conductor = Conductor::new(eta, k, microfacet).with_rotation(constant(0.1));
+        check_sampling(&format!("{microfacet:?} gold"), &conductor, true, 1.);
+    }
+}
+
+#[test]
+fn rough_dielectrics_sample_their_bsdf() {
+    for microfacet in microfacets() {
+        let glass = RoughDielectric::new(1.5, microfacet).with_rotation(constant(0.1));
+        check_sampling(&format!("{microfacet:?} glass, outside"), &glass, true, 1.);
+        check_sampling(
+            &format!("{microfacet:?} glass, inside"),
+            &glass,
+            false,
+            1.5 * 1.5,
+        );
+    }
+}
+
+#[test]
+fn anisotropic_highlights_follow_the_tangent() {
+    let (eta, k) = ray_tracing::named_conductor("silver").unwrap();
+    let rec = HitRecord {
+        normal: Vec3::new(0., 0., 1.),
+        dpdu: Vec3::new(2., 0., 0.5),
+        dpdv: Vec3::new(0., 3., 0.),
+        front_face: true,
+        ..Default::default()
+    };
+    let r_in = Ray::new(Vec3::new(0., 0., 1.), Vec3::new(0., 0., -1.), 0.);
+    let tilted = |x: f32, y: f32| {
+        let theta = 30f32.to_radians();
+        Ray::new(
+            Vec3::default(),
+            Vec3::new(x * theta.sin(), y * theta.sin(), theta.cos()),
+            0.,
+        )
+    };
+
+    for distribution in DISTRIBUTIONS {
+        // Brushed along the tangent, so highlights spread along it
+        let brushed = Conductor::new(eta, k, Microfacet::anisotropic(distribution, 0.6, 0.2));
+        let along = brushed.eval(&r_in, &rec, &tilted(1., 0.)).x();
+        let across = brushed.eval(&r_in, &rec, &tilted(0., 1.)).x();
+        assert!(along > 10. * across, "{distribution:?}: {along} {across}");
+
+        // A quarter turn brushes it along the bitangent
+        let turned = brushed.with_rotation(constant(0.25));
+        let along_turned = turned.eval(&r_in, &rec, &tilted(0., 1.)).x();
+        let across_turned = turned.eval(&r_in, &rec, &tilted(1., 0.)).x();
+        assert!(
+            (along_turned - along).abs() < 1e-3 * along,
+            "{distribution:?}: {along_turned} {along}"
+        );
+        assert!((across_turned - across).abs() < 1e-3 * along);
+    }
+}
+
+fn constant(value: f32) -> Arc<dyn Texture> {
+    Arc::new(SolidColor::new(Color::new(value, value, value)))
+}
+
+#[test]
+fn principled_materials_sample_their_bsdf() {
+    let base_color: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(0.8, 0.3, 0.1)));
+    let cases = [
+        ("plastic", PrincipledParameters::default()),
+        (
+            "brushed metal",
+            PrincipledParameters {
+                metallic: constant(1.),
+                roughness: constant(0.4),
+                anisotropic: constant(0.8),
+                ..Default::default()
+            },
+        ),
+        (
+            "varnished cloth",
+            PrincipledParameters {
+                roughness: constant(0.9),
+                sheen: constant(1.),
+                subsurface: constant(0.5),
+                clearcoat: constant(1.),
+                clearcoat_gloss: constant(0.7),
+                specular_tint: constant(0.5),
+                ..Default::default()
+            },
+        ),
+    ];
+    for (name, parameters) in cases {
+        let material = Principled::new(PrincipledParameters {
+            base_color: base_color.clone(),
+            ..parameters
+        });
+        check_sampling(name, &material, true, 1.);
+    }
+
+    for distribution in DISTRIBUTIONS {
+        let glass = Principled::new(PrincipledParameters {
+            base_color: base_color.clone(),
+            transmission: constant(0.9),
+            roughness: constant(0.6),
+            distribution,
+            ..Default::default()
+        });
+        check_sampling(
+            &format!("{distribution:?} glass, outside"),
+            &glass,
+            true,
+            1.,
+        );
+        check_sampling(
+            &format!("{distribution:?} glass, inside"),
+            &glass,
+            false,
+            1.5 * 1.5,
+        );
+    }
+}
+
+#[test]
+fn principled_configs_parse() {
+    use ray_tracing::MaterialConfig;
+
+    let materials: std::collections::HashMap<String, MaterialConfig> = toml::from_str(
+        r#"
+        default = { type = "principled" }
+        plastic = { type = "principled", color = [0.8, 0.1, 0.1], roughness = 0.3, clearcoat = 1 }
+        checkered = { type = "principled", metallic = { type = "checkerboard", scale = 2, even = 0, odd = 1 } }
+        named = { type = "principled", roughness = "wood" }
+        "#,
+    )
+    .expect("invalid materials");
+
+    let textures = Default::default();
+    for name in ["default", "plastic", "checkered"] {
+        assert!(
+            materials[name].build(&textures).is_ok(),
+            "{name} failed to build"
+        );
+    }
+    assert!(materials["named"].build(&textures).is_err());
+}
+
+#[test]
+fn anisotropic_configs_parse() {
+    use ray_tracing::MaterialConfig;
+
+    let materials: std::collections::HashMap<String, MaterialConfig> = toml::from_str(
+        r#"
+        brushed = { type = "conductor", metal = "aluminium", roughness = [0.4, 0.1] }
+        turned = { type = "conductor", metal = "gold", roughness = [0.4, 0.1], rotation = 0.25 }
+        frosted = { type = "rough_dielectric", refraction_index = 1.5, roughness = [0.3, 0.6], rotation = { type = "checkerboard", scale = 2, even = 0, odd = 0.5 } }
+        satin = { type = "principled", anisotropic = 0.8, anisotropic_rotation = 0.1 }
+        "#,
+    )
+    .expect("invalid materials");
+
+    let textures = Default::default();
+    for name in ["brushed", "turned", "frosted", "satin"] {
+        assert!(
+            materials[name].build(&textures).is_ok(),
+            "{name} failed to build"
+        );
+    }
+}
+
+#[test]
+fn mixed_materials_blend_by_amount() {
+    let red = Color::new(0.8, 0.1, 0.1);
+    let blue = Color::new(0.1, 0.1, 0.8);
+    let mix = MixMaterial::new(
+        Arc::new(Lambertian::new(red)),
+        Arc::new(Lambertian::new(blue)),
+        constant(0.3),
+    );
+
+    let wo = direction(30.);
+    let wi = direction(-40.);
+    let n = 100_000;
+    let mut sum = Color::new(0., 0., 0.);
+    for i in 0..n {
+        let rec = HitRecord {
+            p: Vec3::new(i as f32 * 0.01, 0.5, -(i as f32) * 0.003),
+            ..hit(true)
+        };
+        let r_in = Ray::new(rec.p + wo, -wo, 0.);
+        sum += mix.eval(&r_in, &rec, &Ray::new(rec.p, wi, 0.));
+    }
+    let expected = (red * 0.7 + blue * 0.3) * (wi.z() / PI);
+    let mean = sum / n as f32;
+    for (m, e) in [
+        (mean.x(), expected.x()),
+        (mean.y(), expected.y()),
+        (mean.z(), expected.z()),
+    ] {
+        assert!(
+            (m - e).abs() < 0.01 * e.max(0.1),
+            "{mean:?}, expected {expected:?}"
+        );
+    }
+}
+
+#[test]
+fn coated_materials_sample_their_bsdf() {
+    let base: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.8, 0.4, 0.1)));
+    for (name, roughness) in [("smooth", 0.), ("rough", 0.3)] {
+        let coated = CoatedMaterial::new(
+            base.clone(),
+            1.5,
+            Microfacet::new(MicrofacetDistribution::Ggx, roughness),
+            Color::new(0.9, 0.95, 1.),
+        );
+        check_sampling(&format!("{name} coating"), &coated, true, 1.);
+    }
+}
+
+/// Light that enters the coating of a white surface bounces around until it leaves, so it all
+/// comes back out, either reflected off the coating or through it
+#[test]
+fn coated_white_surfaces_conserve_energy() {
+    let coated = CoatedMaterial::new(
+        Arc::new(Lambertian::new(Color::new(1., 1., 1.))),
+        1.5,
+        Microfacet::new(MicrofacetDistribution::Ggx, 0.),
+        Color::new(1., 1., 1.),
+    );
+    let rec = hit(true);
+    for theta in [0., 45., 75.] {
+        let wo = direction(theta);
+        let r_in = Ray::new(rec.p + wo, -wo, 0.);
+        let n = 200_000;
+        let mut total = 0.;
+        for _ in 0..n {
+            let mut srec = ScatterRecord::default();
+            assert!(coated.scatter(&r_in, &rec, &mut srec));
+            if srec.skip_pdf {
+                total += srec.attenuation.x();
+                continue;
+            }
+            let wi = srec.pdf.generate();
+            let pdf = srec.pdf.value(wi);
+            if pdf > 0. {
+                total += coated.eval(&r_in, &rec, &Ray::new(rec.p, wi, 0.)).x() / pdf;
+            }
+        }
+        let total = total / n as f32;
+        assert!((total - 1.).abs() < 0.02, "at {theta}°: {total}");
+    }
+}
+
+#[test]
+fn layered_configs_parse() {
+    use ray_tracing::MaterialConfig;
+
+    let materials: std::collections::HashMap<String, MaterialConfig> = toml::from_str(
+        r#"
+        wood = { type = "lambertian", color = [0.5, 0.3, 0.1] }
+        varnished = { type = "coated", base = "wood", roughness = 0.1 }
+        masked = { type = "mix", materials = ["wood", { type = "metal", color = [1, 1, 1], fuzz = 0 }], amount = { type = "checkerboard", scale = 1, even = 0, odd = 1 } }
+        missing = { type = "coated", base = "nothing" }
+        cycle = { type = "mix", materials = ["wood", "cycle"] }
+        "#,
+    )
+    .expect("invalid materials");
+
+    let textures = Default::default();
+    for name in ["varnished", "masked"] {
+        assert!(
+            materials[name]
+                .build_with_materials(&materials, &textures)
+                .is_ok(),
+            "{name} failed to build"
+        );
+    }
+    for name in ["missing", "cycle"] {
+        assert!(
+            materials[name]
+                .build_with_materials(&materials, &textures)
+                .is_err(),
+            "{name} should fail to build"
+        );
+    }
+}
@@ -0,0 +1,398 @@
+//! Checks ray hits on mesh triangles and how the mesh loaders read their files.
+
+mod common;
+
+use common::{assert_close, hit, material};
+use ray_tracing::shape::obj::parse_obj;
+use ray_tracing::shape::ply::parse_ply;
+use ray_tracing::shape::stl::parse_stl;
+use ray_tracing::{vec3, Point3, Triangle, TriangleMesh, Vec3};
+
+use std::sync::Arc;
+
+/// A right triangle in the `xy` plane, with a different normal and texture coordinate at each
+/// corner
+fn triangle(normals: bool, uvs: bool) -> Triangle {
+    let mesh = TriangleMesh::new(
+        vec![
+            Point3::new(0., 0., 0.),
+            Point3::new(1., 0., 0.),
+            Point3::new(0., 1., 0.),
+        ],
+        normals.then(|| {
+            vec![
+                Vec3::new(0., 0., 1.),
+                Vec3::new(1., 0., 0.),
+                Vec3::new(0., 1., 0.),
+            ]
+        }),
+        uvs.then(|| vec![[0.5, 0.], [1., 0.], [0.5, 1.]]),
+        vec![[0, 1, 2]],
+        material(),
+    )
+    .unwrap();
+    Triangle::new(Arc::new(mesh), 0)
+}
+
+#[test]
+fn rays_hit_triangles_inside_their_edges() {
+    let triangle = triangle(false, false);
+
+    let rec = hit(
+        &triangle,
+        Point3::new(0.25, 0.25, 2.),
+        Vec3::new(0., 0., -1.),
+    )
+    .unwrap();
+    assert!((rec.t - 2.).abs() < 1e-5);
+    assert_close(rec.p, Point3::new(0.25, 0.25, 0.), "hit point");
+    assert_close(rec.normal, Vec3::new(0., 0., 1.), "normal");
+    assert!(rec.front_face);
+
+    // From behind, the normal turns towards the ray
+    let rec = hit(
+        &triangle,
+        Point3::new(0.25, 0.25, -2.),
+        Vec3::new(0., 0., 1.),
+    )
+    .unwrap();
+    assert_close(rec.normal, Vec3::new(0., 0., -1.), "normal from behind");
+    assert!(!rec.front_face);
+
+    // Past the hypotenuse, past either leg, parallel to the plane, and facing away
+    for (origin, direction) in [
+        (Point3::new(0.6, 0.6, 2.), Vec3::new(0., 0., -1.)),
+        (Point3::new(-0.1, 0.5, 2.), Vec3::new(0., 0., -1.)),
+        (Point3::new(0.5, -0.1, 2.), Vec3::new(0., 0., -1.)),
+        (Point3::new(-1., 0.25, 0.), Vec3::new(1., 0., 0.)),
+        (Point3::new(0.25, 0.25, 2.), Vec3::new(0., 0., 1.)),
+    ] {
+        assert!(
+            hit(&triangle, origin, direction).is_none(),
+            "hit from {origin:?} along {direction:?}"
+        );
+    }
+}
+
+#[test]
+fn triangles_interpolate_vertex_normals_and_uvs() {
+    let triangle = triangle(true, true);
+    let rec = hit(
+        &triangle,
+        Point3::new(0.25, 0.5, 2.),
+        Vec3::new(0., 0., -1.),
+    )
+    .unwrap();
+
+    // At barycentric coordinates (0.25, 0.25, 0.5)
+    assert_close(
+        rec.normal,
+        vec3::unit_vector(Vec3::new(0.25, 0.5, 0.25)),
+        "shading normal",
+    );
+    assert!((rec.u - 0.625).abs() < 1e-5, "u is {}", rec.u);
+    assert!((rec.v - 0.5).abs() < 1e-5, "v is {}", rec.v);
+
+    // Seen from behind, the shading normal flips with the geometric one
+    let rec = hit(
+        &triangle,
+        Point3::new(0.25, 0.5, -2.),
+        Vec3::new(0., 0., 1.),
+    )
+    .unwrap();
+    assert_close(
+        rec.normal,
+        -vec3::unit_vector(Vec3::new(0.25, 0.5, 0.25)),
+        "shading normal from behind",
+    );
+
+    // Without uvs, the texture coordinates are the barycentric ones
+    let rec = hit(
+        &self::triangle(false, false),
+        Point3::new(0.25, 0.5, 2.),
+        Vec3::new(0., 0., -1.),
+    )
+    .unwrap();
+    assert!((rec.u - 0.25).abs() < 1e-5 && (rec.v - 0.5).abs() < 1e-5);
+}
+
+#[test]
+fn obj_faces_reference_positions_uvs_and_normals() {
+    let obj = "
+        # A unit square, split by the loader
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vt 0 1
+        vn 0 0 1
+        g square
+        usemtl whatever
+        f 1/1/1 2/2/1 3/3/1 4/4/1
+    ";
+    let mesh = parse_obj(obj, material()).unwrap();
+    assert_eq!(mesh.len(), 2);
+    assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+    assert_eq!(mesh.positions.len(), 4);
+    assert_eq!(mesh.uvs.as_ref().unwrap()[2], [1., 1.]);
+    assert!(mesh
+        .normals
+        .as_ref()
+        .unwrap()
+        .iter()
+        .all(|&n| (n - Vec3::new(0., 0., 1.)).length() < 1e-6));
+
+    // Only positions and normals, which `v//vn` skips the uv of
+    let mesh = parse_obj(
+        "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n",
+        material(),
+    )
+    .unwrap();
+    assert!(mesh.uvs.is_none());
+    assert!(mesh.normals.is_some());
+
+    // Vertices that don't all have normals don't get interpolated ones
+    let mesh = parse_obj(
+        "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2 3\n",
+        material(),
+    )
+    .unwrap();
+    assert!(mesh.normals.is_none());
+}
+
+#[test]
+fn obj_negative_indices_count_back_from_the_latest_vertex() {
+    let obj = "
+        v 0 0 0
+        v 1 0 0
+        v 0 1 0
+        vt 0 0
+        vt 1 0
+        vt 0 1
+        f -3/-3 -2/-2 -1/-1
+        v 1 1 0
+        f -3 -2 -1
+    ";
+    let mesh = parse_obj(obj, material()).unwrap();
+    assert_eq!(mesh.len(), 2);
+    let corners = |face: usize| mesh.indices[face].map(|i| mesh.positions[i]);
+    let [a, b, c] = corners(0);
+    assert_close(a, Point3::new(0., 0., 0.), "first corner");
+    assert_close(b, Point3::new(1., 0., 0.), "second corner");
+    assert_close(c, Point3::new(0., 1., 0.), "third corner");
+    let [a, b, c] = corners(1);
+    assert_close(a, Point3::new(1., 0., 0.), "first corner");
+    assert_close(b, Point3::new(0., 1., 0.), "second corner");
+    assert_close(c, Point3::new(1., 1., 0.), "third corner");
+}
+
+#[test]
+fn obj_polygons_are_fan_triangulated() {
+    let obj = "
+        v 1 0 0
+        v 0.3 0.95 0
+        v -0.8 0.6 0
+        v -0.8 -0.6 0
+        v 0.3 -0.95 0
+        f 1 2 3 4 5
+    ";
+    let mesh = parse_obj(obj, material()).unwrap();
+    assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+
+    // Every triangle of the fan is hit through the pentagon
+    let bvh = Arc::new(mesh).to_bvh().unwrap();
+    for (x, y) in [(0.3, 0.5), (-0.5, 0.), (0.3, -0.5)] {
+        assert!(hit(&bvh, Point3::new(x, y, 1.), Vec3::new(0., 0., -1.)).is_some());
+    }
+}
+
+#[test]
+fn malformed_obj_files_are_rejected() {
+    for (obj, expected) in [
+        ("v 0 0 0\nv 1 0 0\nf 1 2\n", "at least 3 vertices"),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", "out of range"),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4\n", "out of range"),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n", "out of range"),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n", "out of range"),
+        ("v 0 0\n", "missing value"),
+        ("v 0 zero 0\n", "not a number"),
+        ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 x\n", "not a valid index"),
+    ] {
+        let error = parse_obj(obj, material()).err().unwrap();
+        assert!(error.contains(expected), "{obj:?} failed with '{error}'");
+    }
+}
+
+const PLY_HEADER: &str = "ply
+format ascii 1.0
+comment a comment mentioning end_header, which doesn't end it
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+/// A triangle as a binary little-endian PLY, with `face` as the face's index list
+fn binary_ply(face: &[i32]) -> Vec<u8> {
+    let mut bytes = PLY_HEADER
+        .replace("ascii", "binary_little_endian")
+        .into_bytes();
+    for p in [[0f32, 0., 0.], [1., 0., 0.], [0., 1., 0.]] {
+        bytes.extend(p.iter().flat_map(|x| x.to_le_bytes()));
+    }
+    bytes.push(face.len() as u8);
+    bytes.extend(face.iter().flat_map(|i| i.to_le_bytes()));
+    bytes
+}
+
+/// A binary STL with the triangles given by their corners
+fn binary_stl(triangles: &[[[f32; 3]; 3]]) -> Vec<u8> {
+    let mut bytes = vec![0u8; 80];
+    bytes.extend((triangles.len() as u32).to_le_bytes());
+    for triangle in triangles {
+        bytes.extend([0f32; 3].iter().flat_map(|x| x.to_le_bytes()));
+        for corner in triangle {
+            bytes.extend(corner.iter().flat_map(|x| x.to_le_bytes()));
+        }
+        bytes.extend([0u8; 2]);
+    }
+    bytes
+}
+
+#[test]
+fn well_formed_ply_and_stl_files_load() {
+    let ascii = format!("{PLY_HEADER}0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n");
+    let mesh = parse_ply(ascii.as_bytes(), Some(material())).unwrap();
+    assert_eq!(
+        (mesh.positions.len(), mesh.indices.clone()),
+        (3, vec![[0, 1, 2]])
+    );
+
+    let mesh = parse_ply(&binary_ply(&[0, 1, 2]), Some(material())).unwrap();
+    assert_eq!(
+        (mesh.positions.len(), mesh.indices.clone()),
+        (3, vec![[0, 1, 2]])
+    );
+
+    // The shared corners of the two triangles are merged
+    let mesh = parse_stl(
+        &binary_stl(&[
+            [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+            [[1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+        ]),
+        material(),
+    )
+    .unwrap();
+    assert_eq!(mesh.positions.len(), 4);
+    assert_eq!(mesh.indices, vec![[0, 1, 2], [1, 3, 2]]);
+}
+
+#[test]
+fn malformed_ply_files_are_rejected() {
+    let ascii = |body: &str| format!("{PLY_HEADER}{body}").into_bytes();
+    let binary = binary_ply(&[0, 1, 2]);
+    let cases: Vec<(&str, Vec<u8>, &str)> = vec![
+        ("truncated ASCII", ascii("0 0 0\n1 0 0\n"), "truncated"),
+        (
+            "truncated ASCII line",
+            ascii("0 0 0\n1 0 0\n0 1"),
+            "truncated",
+        ),
+        (
+            "truncated binary",
+            binary[..binary.len() - 3].to_vec(),
+            "truncated",
+        ),
+        (
+            "binary without a body",
+            binary[..binary.len() - 3 * 12 - 1 - 3 * 4].to_vec(),
+            "truncated",
+        ),
+        (
+            "out-of-range index",
+            ascii("0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n"),
+            "outside",
+        ),
+        (
+            "negative index",
+            ascii("0 0 0\n1 0 0\n0 1 0\n3 0 -1 2\n"),
+            "negative",
+        ),
+        ("negative binary index", binary_ply(&[0, 1, -2]), "negative"),
+        (
+            "fractional index",
+            ascii("0 0 0\n1 0 0\n0 1 0\n3 0 1.5 2\n"),
+            "face 0 has the vertex index 1.5, which is not a whole number",
+        ),
+        (
+            "index beyond any address",
+            ascii("0 0 0\n1 0 0\n0 1 0\n3 0 1 1e30\n"),
+            "face 0 has the vertex index 1000000000000000000000000000000, which is too large",
+        ),
+        (
+            "out-of-range binary index",
+            binary_ply(&[0, 1, 1 << 30]),
+            "outside",
+        ),
+        (
+            "two-vertex face",
+            ascii("0 0 0\n1 0 0\n0 1 0\n2 0 1\n"),
+            "fewer than 3",
+        ),
+        (
+            "missing end_header",
+            PLY_HEADER.replace("\nend_header\n", "\n").into_bytes(),
+            "end_header",
+        ),
+        (
+            "end_header inside a longer token",
+            PLY_HEADER
+                .replace("\nend_header\n", "\nend_headers\n")
+                .into_bytes(),
+            "end_header",
+        ),
+        (
+            "not a number",
+            ascii("0 0 0\n1 zero 0\n0 1 0\n3 0 1 2\n"),
+            "not a number",
+        ),
+        (
+            "no magic",
+            b"format ascii 1.0\nend_header\n".to_vec(),
+            "not a PLY",
+        ),
+        ("empty", Vec::new(), "not a PLY"),
+    ];
+    for (name, bytes, expected) in cases {
+        let error = parse_ply(&bytes, Some(material())).err().unwrap();
+        assert!(error.contains(expected), "{name} failed with '{error}'");
+    }
+}
+
+#[test]
+fn malformed_stl_files_are_rejected() {
+    let stl = binary_stl(&[[[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]]);
+    for (name, bytes, expected) in [
+        (
+            "truncated triangle",
+            stl[..stl.len() - 1].to_vec(),
+            "truncated",
+        ),
+        ("truncated header", stl[..40].to_vec(), "truncated"),
+        ("ascii", b"solid cube\nendsolid cube\n".to_vec(), "ASCII"),
+        (
+            "non-finite vertex",
+            binary_stl(&[[[0., 0., 0.], [f32::NAN, 0., 0.], [0., 1., 0.]]]),
+            "non-finite",
+        ),
+    ] {
+        let error = parse_stl(&bytes, material()).err().unwrap();
+        assert!(error.contains(expected), "{name} failed with '{error}'");
+    }
+}
@@ -0,0 +1,325 @@
+//! Checks the spectral building blocks against the RGB values they stand in for.
+
+use ray_tracing::radiometry::rgb::{RgbAlbedoSpectrum, RgbIlluminantSpectrum};
+use ray_tracing::radiometry::sampling::{SampledWavelengths, NUM_SPECTRUM_SAMPLES};
+use ray_tracing::radiometry::spectrum::{BlackbodySpectrum, DenselySampledSpectrum, Spectrum};
+use ray_tracing::{vec3, Color, Dielectric, HitRecord, Material, Point3, Ray, ScatterRecord, Vec3};
+
+use std::sync::Arc;
+
+const COLORS: [(f32, f32, f32); 4] = [
+    (1., 1., 1.),
+    (0.65, 0.05, 0.05),
+    (0.12, 0.45, 0.15),
+    (0.2, 0.4, 0.9),
+];
+
+/// Averages the linear sRGB color of `spectrum` over stratified wavelength samples
+fn render(spectrum: &dyn Spectrum) -> Color {
+    let n = 4096;
+    let mut sum = Color::new(0., 0., 0.);
+    for i in 0..n {
+        let lambda = SampledWavelengths::sample_visible((i as f32 + 0.5) / n as f32);
+        sum += Color::from(spectrum.sample(&lambda).to_xyz(&lambda));
+    }
+    sum / n as f32
+}
+
+#[test]
+fn white_albedo_is_flat() {
+    let white = RgbAlbedoSpectrum::new(Color::new(1., 1., 1.));
+    for lambda in (360..=830).step_by(10) {
+        let value = white.call(lambda as f32);
+        assert!((value - 1.).abs() < 1e-4, "{value} at {lambda} nm");
+    }
+}
+
+#[test]
+fn illuminants_render_back_to_their_color() {
+    for (r, g, b) in COLORS {
+        let color = render(&RgbIlluminantSpectrum::new(Color::new(r, g, b)));
+        for (got, expected) in [(color.x(), r), (color.y(), g), (color.z(), b)] {
+            assert!(
+                (got - expected).abs() < 2e-3,
+                "({r}, {g}, {b}) renders as {color:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn albedos_stay_in_range() {
+    for (r, g, b) in COLORS {
+        let albedo = RgbAlbedoSpectrum::new(Color::new(r, g, b));
+        for lambda in (360..=830).step_by(10) {
+            let value = albedo.call(lambda as f32);
+            assert!(
+                (0. ..=1.001).contains(&value),
+                "({r}, {g}, {b}) has {value} at {lambda} nm"
+            );
+        }
+    }
+}
+
+#[test]
+fn densely_sampled_spectra_sample_the_requested_wavelengths() {
+    let y = DenselySampledSpectrum::y();
+    let lambda = SampledWavelengths::sample_visible(0.3);
+    let sampled = y.sample(&lambda);
+    for i in 0..NUM_SPECTRUM_SAMPLES {
+        assert_eq!(sampled[i], y.call(lambda[i]));
+    }
+
+    // The luminous efficiency peaks in the green
+    assert!(y.call(555.) > 0.99 && y.call(555.) < 1.01);
+    assert!(y.call(400.) < 0.01);
+}
+
+#[test]
+fn blackbodies_peak_at_one() {
+    for temp in [2700., 5000., 6500.] {
+        let spectrum = BlackbodySpectrum::new(temp);
+        let peak = 2.897_772e6 / temp;
+        assert!((spectrum.call(peak) - 1.).abs() < 1e-3, "{temp} K");
+        assert!(spectrum.call(peak - 50.) < 1. && spectrum.call(peak + 50.) < 1.);
+    }
+}
+
+#[test]
+fn terminating_secondary_wavelengths_keeps_the_first() {
+    let mut lambda = SampledWavelengths::sample_visible(0.5);
+    assert!(!lambda.secondary_terminated());
+
+    let pdf = lambda.pdf;
+    lambda.terminate_secondary();
+    assert!(lambda.secondary_terminated());
+    assert_eq!(lambda.pdf[0], pdf[0] / NUM_SPECTRUM_SAMPLES as f32);
+}
+
+#[test]
+fn named_iors_match_catalog_values() {
+    use ray_tracing::radiometry::ior::{named_ior, NAMED_IORS, REFERENCE_WAVELENGTH};
+
+    let n_d = [1.5168, 1.4585, 2.4175, 1.3330];
+    for (name, expected) in NAMED_IORS.iter().zip(n_d) {
+        let ior = named_ior(name).unwrap();
+        let n = ior.call(REFERENCE_WAVELENGTH);
+        assert!(
+            (n - expected).abs() < 2e-3,
+            "{name}: n_d = {n}, expected {expected}"
+        );
+        // Normal dispersion: blue light bends more than red
+        assert!(ior.call(450.) > ior.call(650.), "{name} doesn't disperse");
+    }
+    assert!(named_ior("unobtainium").is_none());
+}
+
+#[test]
+fn dispersive_glass_refracts_each_wavelength_its_own_way() {
+    use ray_tracing::radiometry::ior::CauchySpectrum;
+
+    // Dense flint, with a strong dispersion
+    let ior = Arc::new(CauchySpectrum::new(1.7, 0.02, 0.));
+    let glass = Dielectric::with_ior(ior.clone());
+
+    let rec = HitRecord {
+        p: Point3::new(0., 0., 0.),
+        normal: Vec3::new(0., 0., 1.),
+        front_face: true,
+        ..Default::default()
+    };
+    let incoming = vec3::unit_vector(Vec3::new(1., 0., -1.));
+    let r_in = Ray::new(Point3::new(-1., 0., 1.), incoming, 0.);
+
+    // The sine of the angle each wavelength refracts at, from the rays that go into the glass
+    // rather than being reflected
+    let refracted_sine = |first: f32| -> f32 {
+        let mut sines = vec![];
+        for _ in 0..100 {
+            let mut lambda = SampledWavelengths {
+                lambdas: [first, 500., 550., 600.],
+                pdf: [1. / 300.; NUM_SPECTRUM_SAMPLES],
+            };
+            let mut srec = ScatterRecord::default();
+            assert!(glass.scatter_spectral(&r_in, &rec, &mut srec, &mut lambda));
+            assert!(
+                lambda.secondary_terminated(),
+                "{first} nm kept its companions"
+            );
+            assert_eq!(lambda.pdf[0], 1. / 300. / NUM_SPECTRUM_SAMPLES as f32);
+
+            let direction = vec3::unit_vector(srec.skip_pdf_ray.direction());
+            if direction.z() < 0. {
+                sines.push(direction.x());
+            }
+        }
+        assert!(!sines.is_empty(), "nothing refracted at {first} nm");
+        assert!(sines.iter().all(|&sine| (sine - sines[0]).abs() < 1e-6));
+        sines[0]
+    };
+
+    // Snell's law with the index at the first wavelength, where blue bends more than red
+    let (blue, red) = (refracted_sine(450.), refracted_sine(650.));
+    for (sine, wavelength) in [(blue, 450.), (red, 650.)] {
+        let expected = incoming.x() / ior.call(wavelength);
+        assert!(
+            (sine - expected).abs() < 1e-5,
+            "{wavelength} nm refracted at a sine of {sine}, expected {expected}"
+        );
+    }
+    assert!(blue < red - 1e-3, "blue at {blue} and red at {red}");
+
+    // Glass without dispersion keeps every wavelength
+    let mut lambda = SampledWavelengths::sample_visible(0.5);
+    let mut srec = ScatterRecord::default();
+    assert!(Dielectric::new(1.5).scatter_spectral(&r_in, &rec, &mut srec, &mut lambda));
+    assert!(!lambda.secondary_terminated());
+}
+
+#[test]
+fn ior_configs_parse() {
+    use ray_tracing::MaterialConfig;
+
+    let materials: std::collections::HashMap<String, MaterialConfig> = toml::from_str(
+        r#"
+        constant = { type = "dielectric", refraction_index = 1.5 }
+        named = { type = "dielectric", refraction_index = "bk7" }
+        cauchy = { type = "dielectric", refraction_index = { type = "cauchy", a = 1.5046, b = 0.0042 } }
+        sellmeier = { type = "dielectric", refraction_index = { type = "sellmeier", b = [1.0, 0.2], c = [0.006, 0.02] } }
+        unknown = { type = "dielectric", refraction_index = "unobtainium" }
+        mismatched = { type = "dielectric", refraction_index = { type = "sellmeier", b = [1.0, 0.2], c = [0.006] } }
+        "#,
+    )
+    .expect("invalid materials");
+
+    let textures = Default::default();
+    for name in ["constant", "named", "cauchy", "sellmeier"] {
+        assert!(
+            materials[name].build(&textures).is_ok(),
+            "{name} failed to build"
+        );
+    }
+    for name in ["unknown", "mismatched"] {
+        assert!(
+            materials[name].build(&textures).is_err(),
+            "{name} should fail to build"
+        );
+    }
+}
+
+#[test]
+fn d65_is_the_srgb_white_point() {
+    use ray_tracing::radiometry::illuminant::named_illuminant;
+    use ray_tracing::radiometry::spectrum::spectrum_to_xyz;
+
+    let xyz = spectrum_to_xyz(named_illuminant("d65").unwrap().as_ref());
+    let white = Color::from(xyz) / xyz.y;
+    for c in [white.x(), white.y(), white.z()] {
+        assert!((c - 1.).abs() < 0.03, "D65 renders as {white:?}");
+    }
+}
+
+#[test]
+fn fluorescent_illuminants_have_their_published_chromaticities() {
+    use ray_tracing::radiometry::illuminant::named_illuminant;
+    use ray_tracing::radiometry::spectrum::spectrum_to_xyz;
+
+    for (name, expected) in [
+        ("f2", (0.3721, 0.3751)),
+        ("f7", (0.3129, 0.3292)),
+        ("f11", (0.3805, 0.3769)),
+    ] {
+        let xyz = spectrum_to_xyz(named_illuminant(name).unwrap().as_ref());
+        let sum = xyz.x + xyz.y + xyz.z;
+        let (x, y) = (xyz.x / sum, xyz.y / sum);
+        assert!(
+            (x - expected.0).abs() < 0.002 && (y - expected.1).abs() < 0.002,
+            "{name} has the chromaticity ({x}, {y}), not {expected:?}"
+        );
+    }
+}
+
+/// The RGB fallback of a light must be the color the spectral integrator renders it as
+#[test]
+fn illuminant_colors_match_rendered_spectra() {
+    use ray_tracing::radiometry::illuminant::{named_illuminant, NAMED_ILLUMINANTS};
+    use ray_tracing::radiometry::spectrum::spectrum_to_xyz;
+
+    let mut spectra: Vec<Arc<dyn Spectrum>> = vec![Arc::new(BlackbodySpectrum::new(3200.))];
+    spectra.extend(NAMED_ILLUMINANTS.map(|name| named_illuminant(name).unwrap()));
+    for spectrum in spectra {
+        let expected = Color::from(spectrum_to_xyz(spectrum.as_ref()));
+        let rendered = render(spectrum.as_ref());
+        let scale = expected.x().max(expected.y()).max(expected.z());
+        for (e, r) in [
+            (expected.x(), rendered.x()),
+            (expected.y(), rendered.y()),
+            (expected.z(), rendered.z()),
+        ] {
+            assert!(
+                (e - r).abs() < 0.01 * scale,
+                "{expected:?} renders as {rendered:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn spectra_load_from_csv() {
+    use ray_tracing::radiometry::spectrum::PiecewiseLinearSpectrum;
+
+    let spectrum = PiecewiseLinearSpectrum::parse_csv(
+        "# a measured lamp\nwavelength,power\n400, 1\n500,3\n\n600,2\n",
+    )
+    .unwrap();
+    assert_eq!(spectrum.call(450.), 2.);
+    assert_eq!(spectrum.call(600.), 2.);
+    assert_eq!(spectrum.call(700.), 0.);
+    assert_eq!(spectrum.max_value(), 3.);
+
+    for invalid in [
+        "400,1\n",
+        "400,1\n300,2\n",
+        "400,1\n500\n",
+        "400,1\n500,x\n",
+    ] {
+        assert!(
+            PiecewiseLinearSpectrum::parse_csv(invalid).is_err(),
+            "{invalid:?}"
+        );
+    }
+}
+
+#[test]
+fn light_configs_parse() {
+    use ray_tracing::MaterialConfig;
+
+    let materials: std::collections::HashMap<String, MaterialConfig> = toml::from_str(
+        r#"
+        color = { type = "diffuse_light", color = [4, 4, 4] }
+        blackbody = { type = "diffuse_light", temperature = 3200, luminance = 4 }
+        named = { type = "diffuse_light", illuminant = "d65" }
+        fluorescent = { type = "diffuse_light", illuminant = "f11", luminance = 2 }
+        unknown = { type = "diffuse_light", illuminant = "f13" }
+        missing_file = { type = "diffuse_light", spectrum = "does-not-exist.csv" }
+        none = { type = "diffuse_light" }
+        both = { type = "diffuse_light", color = [1, 1, 1], temperature = 3200 }
+        scaled_color = { type = "diffuse_light", color = [1, 1, 1], luminance = 4 }
+        "#,
+    )
+    .expect("invalid materials");
+
+    let textures = Default::default();
+    for name in ["color", "blackbody", "named", "fluorescent"] {
+        assert!(
+            materials[name].build(&textures).is_ok(),
+            "{name} failed to build"
+        );
+    }
+    for name in ["unknown", "missing_file", "none", "both", "scaled_color"] {
+        assert!(
+            materials[name].build(&textures).is_err(),
+            "{name} should fail to build"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Checks that samplers stratify the samples of a pixel and stay inside [0, 1).
+
+use ray_tracing::{
+    HaltonSampler, IndependentSampler, Sampler, SamplerType, SobolSampler, StratifiedSampler,
+};
+
+/// The first `n` samples of pixel (`x`, `y`), each as the values of its first `dimensions` pairs
+fn pairs(
+    sampler: &mut dyn Sampler,
+    (x, y): (usize, usize),
+    n: usize,
+    dimensions: usize,
+) -> Vec<Vec<(f32, f32)>> {
+    (0..n)
+        .map(|i| {
+            sampler.start_pixel_sample(x, y, i);
+            (0..dimensions).map(|_| sampler.get_2d()).collect()
+        })
+        .collect()
+}
+
+/// Checks that every cell of an `nx` by `ny` grid holds exactly one of `points`
+fn assert_one_per_cell(name: &str, points: &[(f32, f32)], nx: usize, ny: usize) {
+    let mut counts = vec![0; nx * ny];
+    for &(u, v) in points {
+        counts[(v * ny as f32) as usize * nx + (u * nx as f32) as usize] += 1;
+    }
+    assert!(
+        counts.iter().all(|&count| count == 1),
+        "{name}: not one point per cell of a {nx}x{ny} grid: {counts:?}"
+    );
+}
+
+/// The largest difference between the fraction of `points` in a box `[0, a) x [0, b)` and its
+/// area, over a grid of boxes
+fn discrepancy(points: &[(f32, f32)]) -> f32 {
+    const STEPS: usize = 32;
+    let mut worst: f32 = 0.;
+    for i in 1..=STEPS {
+        for j in 1..=STEPS {
+            let (a, b) = (i as f32 / STEPS as f32, j as f32 / STEPS as f32);
+            let inside = points.iter().filter(|&&(u, v)| u < a && v < b).count();
+            worst = worst.max((inside as f32 / points.len() as f32 - a * b).abs());
+        }
+    }
+    worst
+}
+
+#[test]
+fn stratified_samples_cover_every_stratum() {
+    // Square, and not, where 200 is split into 10 by 20 strata and 7 into 1 by 7
+    for (spp, nx, ny) in [(16, 4, 4), (200, 10, 20), (7, 1, 7)] {
+        let mut sampler = StratifiedSampler::new(spp, 3);
+        for pixel in [(0, 0), (17, 5)] {
+            let samples = pairs(&mut sampler, pixel, spp, 3);
+            for dimension in 0..3 {
+                let points: Vec<_> = samples.iter().map(|s| s[dimension]).collect();
+                assert_one_per_cell(&format!("{spp} spp"), &points, nx, ny);
+            }
+
+            // And each 1D value falls in its own stratum
+            let mut strata: Vec<usize> = (0..spp)
+                .map(|i| {
+                    sampler.start_pixel_sample(pixel.0, pixel.1, i);
+                    (sampler.get_1d() * spp as f32) as usize
+                })
+                .collect();
+            strata.sort();
+            assert_eq!(strata, (0..spp).collect::<Vec<_>>(), "{spp} spp in 1D");
+        }
+
+        // Samples past the count start over on new strata
+        let points: Vec<_> = pairs(&mut sampler, (1, 1), 2 * spp, 1)[spp..]
+            .iter()
+            .map(|s| s[0])
+            .collect();
+        assert_one_per_cell(&format!("second set of {spp} spp"), &points, nx, ny);
+    }
+}
+
+#[test]
+fn halton_and_sobol_points_are_low_discrepancy() {
+    let mut halton = HaltonSampler::new(5);
+    let mut sobol = SobolSampler::new(5);
+    let mut independent = IndependentSampler::new(5);
+
+    // Halton's first pair is in bases 2 and 3, and stratifies 8 by 27 cells with 216 points
+    for pixel in [(0, 0), (3, 9)] {
+        let points: Vec<_> = pairs(&mut halton, pixel, 216, 1)
+            .iter()
+            .map(|s| s[0])
+            .collect();
+        assert_one_per_cell("halton", &points, 8, 27);
+    }
+
+    // Every pair of the scrambled Sobol sequence is a (0, 8, 2)-net with 256 points
+    for pixel in [(0, 0), (3, 9)] {
+        let samples = pairs(&mut sobol, pixel, 256, 4);
+        for dimension in 0..4 {
+            let points: Vec<_> = samples.iter().map(|s| s[dimension]).collect();
+            for (nx, ny) in [(1, 256), (2, 128), (16, 16), (128, 2), (256, 1)] {
+                assert_one_per_cell(&format!("sobol pair {dimension}"), &points, nx, ny);
+            }
+        }
+    }
+
+    // Both are far more even than random points
+    let first = |sampler: &mut dyn Sampler| -> Vec<(f32, f32)> {
+        pairs(sampler, (2, 2), 256, 1)
+            .iter()
+            .map(|s| s[0])
+            .collect()
+    };
+    let random = discrepancy(&first(&mut independent));
+    for (name, points) in [("halton", first(&mut halton)), ("sobol", first(&mut sobol))] {
+        let discrepancy = discrepancy(&points);
+        assert!(
+            discrepancy < 0.02 && discrepancy < random / 2.,
+            "{name} has a discrepancy of {discrepancy}, against {random} for random points"
+        );
+    }
+}
+
+#[test]
+fn samples_stay_inside_the_unit_interval() {
+    for sampler_type in [
+        SamplerType::Independent,
+        SamplerType::Stratified,
+        SamplerType::Halton,
+        SamplerType::Sobol,
+        SamplerType::BlueNoise,
+    ] {
+        for seed in 0..4 {
+            let mut sampler = sampler_type.build(64, seed);
+            for pixel in [(0, 0), (1, 0), (255, 17), (4095, 4095)] {
+                for sample in (0..70).chain([1 << 20, u32::MAX as usize]) {
+                    sampler.start_pixel_sample(pixel.0, pixel.1, sample);
+                    for _ in 0..40 {
+                        let (u, v) = sampler.get_2d();
+                        let w = sampler.get_1d();
+                        assert!(
+                            [u, v, w].iter().all(|x| (0. ..1.).contains(x)),
+                            "{sampler_type:?} gave {u}, {v}, {w}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
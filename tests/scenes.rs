@@ -0,0 +1,112 @@
+//! Checks that scene files parse and build every kind of object, material and texture.
+
+use ray_tracing::SceneConfig;
+
+fn scene(toml: &str) -> SceneConfig {
+    toml::from_str(toml).expect("invalid scene")
+}
+
+#[test]
+fn object_and_material_configs_parse() {
+    let config = scene(
+        r#"
+        [textures]
+        red = { type = "solid_color", color = [0.8, 0.1, 0.1] }
+        checker = { type = "checkerboard", scale = 0.5, even = "red", odd = [0.9, 0.9, 0.9] }
+        marble = { type = "perlin_noise", scale = 4, seed = 2 }
+        haze = { type = "solid_color", color = [0.9, 0.9, 0.9] }
+
+        [materials]
+        floor = { type = "lambertian", texture = "checker" }
+        stone = { type = "lambertian", texture = "marble" }
+        shiny = { type = "glossy", color = [0.7, 0.6, 0.5], roughness = 0.2, metallic = 0.5 }
+        misspelled = { type = "dieletric", refraction_index = 1.5 }
+        fog = { type = "isotropic", color = "haze" }
+        lamp = { type = "diffuse_light", color = [4, 4, 4] }
+
+        [[objects]]
+        type = "sphere"
+        center = [0, 1, 0]
+        center2 = [0, 1.5, 0]
+        radius = 1
+        material = "shiny"
+
+        [[objects]]
+        type = "cylinder"
+        center = [3, 0, 0]
+        radius = 0.5
+        height = 2
+        material = { type = "metal", color = [0.8, 0.8, 0.8], fuzz = 0.1 }
+
+        [[objects]]
+        type = "triangle"
+        vertices = [[-1, 0, 0], [1, 0, 0], [0, 1, 0]]
+        material = "misspelled"
+        transform = { translate = [0, 0, -3] }
+
+        [[objects]]
+        type = "constant_medium"
+        density = 0.5
+        texture = "haze"
+        boundary = { type = "sphere", center = [0, 0, 3], radius = 1, material = "fog" }
+
+        [[objects]]
+        type = "constant_medium"
+        density = 0.1
+        color = [0.2, 0.4, 0.9]
+        boundary = { type = "box", min = [-1, -1, -1], max = [1, 1, 1], material = { type = "none" } }
+        transform = { scale = [10, 10, 10] }
+
+        [[objects]]
+        type = "group"
+        transform = { rotate_y = 30, translate = [0, 0, 6] }
+        objects = [
+            { type = "quad", corner = [0, 0, 0], u = [1, 0, 0], v = [0, 1, 0], material = "floor" },
+            { type = "box", min = [2, 0, 0], max = [3, 1, 1], material = "stone" },
+        ]
+
+        [[objects]]
+        type = "group"
+        bvh = false
+        objects = [
+            { type = "sphere", center = [0, -100, 0], radius = 99, material = "floor" },
+        ]
+
+        [[lights]]
+        type = "quad"
+        corner = [-1, 5, -1]
+        u = [2, 0, 0]
+        v = [0, 0, 2]
+        material = "lamp"
+        "#,
+    );
+
+    let (world, lights) = config.process().expect("scene failed to build");
+    assert_eq!(world.objects.len(), 8);
+    assert_eq!(lights.objects.len(), 1);
+
+    // The moving sphere's box covers both of its centers
+    let bbox = world.objects[0].bounding_box();
+    assert!(bbox.y.min <= 0. && bbox.y.max >= 2.5, "{bbox:?}");
+}
+
+#[test]
+fn scenes_with_missing_references_fail_to_build() {
+    for (name, object) in [
+        (
+            "material",
+            r#"{ type = "sphere", center = [0, 0, 0], radius = 1, material = "nowhere" }"#,
+        ),
+        (
+            "texture",
+            r#"{ type = "sphere", center = [0, 0, 0], radius = 1, material = { type = "lambertian", texture = "nowhere" } }"#,
+        ),
+        (
+            "medium texture",
+            r#"{ type = "constant_medium", density = 1, texture = "nowhere", boundary = { type = "sphere", center = [0, 0, 0], radius = 1, material = { type = "none" } } }"#,
+        ),
+    ] {
+        let config = scene(&format!("objects = [{object}]\nlights = []"));
+        assert!(config.process().is_err(), "{name} built");
+    }
+}
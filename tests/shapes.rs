@@ -0,0 +1,457 @@
+//! Checks the tangents that shapes put in their hit records, and how they are sampled as
+//! lights.
+
+mod common;
+
+use common::{hit, material};
+use ray_tracing::{
+    vec3, vecmath, BvhNode, Color, Config, Cylinder, HitRecord, Hittable, HittableList, Instance,
+    Matrix4, Point3, Quadrilateral, RotateY, Sphere, Transform, Translate, Triangle, TriangleMesh,
+    Vec3,
+};
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Checks that `dpdu` and `dpdv` predict how the hit point moves with `u` and `v`, by nudging
+/// the ray along both axes perpendicular to `direction`
+fn check_tangents(name: &str, object: &dyn Hittable, origin: Point3, direction: Vec3) {
+    let rec =
+        hit(object, origin, direction).unwrap_or_else(|| panic!("{name}: missed from {origin:?}"));
+    assert!(
+        vec3::dot(vec3::unit_vector(rec.dpdu), rec.normal).abs() < 1e-3
+            && vec3::dot(vec3::unit_vector(rec.dpdv), rec.normal).abs() < 1e-3,
+        "{name}: the tangents aren't in the surface"
+    );
+
+    let frame = ray_tracing::ONB::new(direction);
+    for offset in [frame.u(), frame.v()] {
+        let nudged = hit(object, origin + 1e-3 * offset, direction)
+            .unwrap_or_else(|| panic!("{name}: missed from {origin:?}"));
+        let moved = nudged.p - rec.p;
+        let predicted = (nudged.u - rec.u) * rec.dpdu + (nudged.v - rec.v) * rec.dpdv;
+        assert!(
+            (moved - predicted).length() < 0.05 * moved.length(),
+            "{name}: moved {moved:?} but the tangents predict {predicted:?}"
+        );
+    }
+}
+
+#[test]
+fn shapes_have_tangents_along_their_parameterization() {
+    let sphere = Sphere::stationary(Point3::new(0., 0., 0.), 2., material());
+    check_tangents(
+        "sphere",
+        &sphere,
+        Point3::new(0.3, 0.5, 5.),
+        Vec3::new(0., 0., -1.),
+    );
+
+    let quad = Arc::new(Quadrilateral::new(
+        Point3::new(-1., -1., 0.),
+        Vec3::new(2., 0.5, 0.),
+        Vec3::new(0., 3., 1.),
+        material(),
+    ));
+    check_tangents(
+        "quadrilateral",
+        quad.as_ref(),
+        Point3::new(0.2, 0.7, 5.),
+        Vec3::new(0., -0.1, -1.),
+    );
+
+    let cylinder = Cylinder::new(Point3::new(0., 0., 0.), 1., 2., material());
+    check_tangents(
+        "cylinder side",
+        &cylinder,
+        Point3::new(0.3, 1., 5.),
+        Vec3::new(0., 0., -1.),
+    );
+    check_tangents(
+        "cylinder cap",
+        &cylinder,
+        Point3::new(0.3, 5., 0.2),
+        Vec3::new(0., -1., 0.),
+    );
+
+    let positions = vec![
+        Point3::new(-1., -1., 0.),
+        Point3::new(2., -1., 0.5),
+        Point3::new(0., 2., 0.),
+    ];
+    let indices = vec![[0, 1, 2]];
+    for uvs in [None, Some(vec![[0., 0.], [2., 0.5], [0.3, 1.]])] {
+        let mesh = TriangleMesh::new(positions.clone(), None, uvs, indices.clone(), material())
+            .expect("invalid mesh");
+        let triangle = Triangle::new(Arc::new(mesh), 0);
+        check_tangents(
+            "triangle",
+            &triangle,
+            Point3::new(0.1, 0., 5.),
+            Vec3::new(0., 0., -1.),
+        );
+    }
+
+    let rotated = RotateY::new(quad, 30.);
+    check_tangents(
+        "rotated quadrilateral",
+        &rotated,
+        Point3::new(0.2, 0.7, 5.),
+        Vec3::new(0., -0.1, -1.),
+    );
+
+    let transform = Transform::new(Matrix4::scale(Vec3::new(2., 1., 0.5)))
+        .unwrap()
+        .then(&Transform::new(Matrix4::rotate_z(40.)).unwrap());
+    let instance = Instance::new(Arc::new(sphere), transform);
+    check_tangents(
+        "stretched sphere",
+        &instance,
+        Point3::new(0.3, 0.5, 5.),
+        Vec3::new(0., 0., -1.),
+    );
+}
+
+#[test]
+fn tangent_frames_follow_dpdu() {
+    let rec = HitRecord {
+        normal: Vec3::new(0., 0., 1.),
+        dpdu: Vec3::new(3., 0., 1.),
+        ..Default::default()
+    };
+    let frame = rec.tangent_frame(0.);
+    assert!((frame.u() - Vec3::new(1., 0., 0.)).length() < 1e-6);
+    assert!((frame.v() - Vec3::new(0., 1., 0.)).length() < 1e-6);
+
+    let turned = rec.tangent_frame(std::f32::consts::FRAC_PI_2);
+    assert!((turned.u() - Vec3::new(0., 1., 0.)).length() < 1e-6);
+
+    // Without a tangent, any frame around the normal will do
+    let untextured = HitRecord {
+        normal: Vec3::new(0., 0., 1.),
+        ..Default::default()
+    };
+    let frame = untextured.tangent_frame(0.);
+    assert!((frame.w() - Vec3::new(0., 0., 1.)).length() < 1e-6);
+}
+
+#[test]
+fn spherical_quads_have_the_solid_angle_they_cover() {
+    // A face of a cube covers a sixth of the sphere around its center
+    let [a, b, c, d] = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+        .map(|(x, y)| vec3::unit_vector(Vec3::new(x, y, 1.)));
+    let face = vecmath::spherical_quad_area(a, b, c, d);
+    assert!((face - 4. * PI / 6.).abs() < 1e-4, "{face}");
+    assert!((vecmath::spherical_quad_area(d, c, b, a) - face).abs() < 1e-4);
+
+    // From far away, a square covers its area over the distance squared
+    let [a, b, c, d] = [(0., 0.), (0.1, 0.), (0.1, 0.1), (0., 0.1)]
+        .map(|(x, y)| vec3::unit_vector(Vec3::new(x, y, 10.)));
+    let far = vecmath::spherical_quad_area(a, b, c, d);
+    assert!((far - 1e-4).abs() < 2e-6, "{far}");
+}
+
+/// Checks that `random` and `pdf_value` agree, by estimating integrals over the directions the
+/// object covers both with its samples and with rays shot everywhere: its solid angle, and the
+/// average of a function leaning to one side, which catches a density that is off in places
+fn check_sampling(name: &str, object: &dyn Hittable, origin: Point3) {
+    let lean = |direction: Vec3| {
+        1. + 0.9 * vec3::dot(vec3::unit_vector(direction), Vec3::new(0.6, 0., 0.8))
+    };
+    let n = 200_000;
+    let mut uniform = [0., 0.];
+    for _ in 0..n {
+        let direction = vec3::random_unit_vector();
+        if object.pdf_value(origin, direction) > 0. {
+            uniform[0] += 4. * PI / n as f32;
+            uniform[1] += lean(direction) * 4. * PI / n as f32;
+        }
+    }
+
+    // Rays that graze the silhouette can round to missing the point they were aimed at
+    let (mut sampled, mut missed) = ([0., 0.], 0);
+    for _ in 0..n {
+        let direction = object.random(origin);
+        let pdf = object.pdf_value(origin, direction);
+        if pdf > 0. {
+            sampled[0] += 1. / pdf / n as f32;
+            sampled[1] += lean(direction) / pdf / n as f32;
+        } else {
+            missed += 1;
+        }
+    }
+    assert!(
+        missed < n / 10_000,
+        "{name}: {missed} samples have no density"
+    );
+
+    for (sampled, uniform) in sampled.into_iter().zip(uniform) {
+        assert!(
+            (sampled - uniform).abs() < 0.03 * uniform,
+            "{name}: samples estimate {sampled}, but rays find {uniform}"
+        );
+    }
+}
+
+#[test]
+fn every_shape_samples_the_directions_it_covers() {
+    let origin = Point3::new(0.2, 0.3, 0.);
+    let rectangle = Arc::new(Quadrilateral::new(
+        Point3::new(-1., -0.5, -1.),
+        Vec3::new(2., 0., 0.),
+        Vec3::new(0., 1.5, -0.5),
+        material(),
+    ));
+    check_sampling("rectangle", rectangle.as_ref(), origin);
+    let parallelogram = Quadrilateral::new(
+        Point3::new(-1., -0.5, -1.),
+        Vec3::new(2., 0.3, 0.),
+        Vec3::new(0.5, 1.5, -0.5),
+        material(),
+    );
+    check_sampling("parallelogram", &parallelogram, origin);
+
+    let cylinder = Arc::new(Cylinder::new(
+        Point3::new(0.5, -1., -2.),
+        0.8,
+        1.5,
+        material(),
+    ));
+    check_sampling("cylinder", cylinder.as_ref(), origin);
+    check_sampling(
+        "cylinder from above",
+        cylinder.as_ref(),
+        Point3::new(0.5, 2., -1.5),
+    );
+
+    let sphere = Sphere::stationary(Point3::new(0., 0., -2.), 1., material());
+    check_sampling("sphere", &sphere, origin);
+
+    let positions = vec![
+        Point3::new(-1., -1., -1.),
+        Point3::new(1., -1., -1.5),
+        Point3::new(1., 1., -1.),
+        Point3::new(-1., 1., -0.8),
+    ];
+    let mesh = Arc::new(
+        TriangleMesh::new(
+            positions,
+            None,
+            None,
+            vec![[0, 1, 2], [0, 2, 3]],
+            material(),
+        )
+        .expect("invalid mesh"),
+    );
+    check_sampling("triangle", &Triangle::new(mesh.clone(), 0), origin);
+    check_sampling("mesh", &mesh.to_bvh().unwrap(), origin);
+
+    check_sampling(
+        "translated cylinder",
+        &Translate::new(cylinder.clone(), Vec3::new(-1., 0.5, 0.)),
+        origin,
+    );
+    check_sampling(
+        "rotated rectangle",
+        &RotateY::new(rectangle.clone(), 40.),
+        origin,
+    );
+    let transform = Transform::new(Matrix4::scale(Vec3::new(1.5, 0.5, 1.)))
+        .unwrap()
+        .then(&Transform::new(Matrix4::rotate_x(30.)).unwrap());
+    check_sampling(
+        "stretched cylinder",
+        &Instance::new(cylinder, transform),
+        origin,
+    );
+}
+
+#[test]
+fn smooth_triangles_are_sampled_in_their_plane() {
+    // Vertex normals only shade, and the area light still lies flat in its plane
+    let positions = vec![
+        Point3::new(-1., -1., -1.),
+        Point3::new(1., -1., -1.5),
+        Point3::new(1., 1., -1.),
+    ];
+    let mesh = TriangleMesh::new(
+        positions.clone(),
+        Some(vec![
+            Vec3::new(-0.6, -0.6, 0.5),
+            Vec3::new(0.6, -0.6, 0.5),
+            Vec3::new(0.6, 0.6, 0.5),
+        ]),
+        None,
+        vec![[0, 1, 2]],
+        material(),
+    )
+    .expect("invalid mesh");
+    let triangle = Triangle::new(Arc::new(mesh), 0);
+    check_sampling(
+        "triangle with vertex normals",
+        &triangle,
+        Point3::new(0.2, 0.3, 0.),
+    );
+
+    let geometric = vec3::unit_vector(vec3::cross(
+        positions[1] - positions[0],
+        positions[2] - positions[0],
+    ));
+    for _ in 0..100 {
+        let (rec, _) = triangle.sample_surface(0.).unwrap();
+        assert!(
+            (rec.normal - geometric).length() < 1e-5,
+            "sampled a point with the normal {:?}",
+            rec.normal
+        );
+    }
+}
+
+#[test]
+fn meshes_sample_their_triangles_by_area() {
+    // A strip of triangles along x, each a quarter the size of the one before
+    let mut positions = vec![Point3::new(0., 0., -1.)];
+    let mut x = 0.;
+    for width in [2., 0.5, 0.125, 0.03125] {
+        x += width;
+        positions.push(Point3::new(x - width, 1., -1.));
+        positions.push(Point3::new(x, 0., -1.));
+    }
+    let faces = (0..4).map(|i| [2 * i, 2 * i + 1, 2 * i + 2]).collect();
+    let mesh = TriangleMesh::new(positions, None, None, faces, material()).expect("invalid mesh");
+    let bvh = Arc::new(mesh).to_bvh().unwrap();
+    check_sampling("strip", &bvh, Point3::new(0.2, 0.3, 0.));
+
+    let area = (2. + 0.5 + 0.125 + 0.03125) / 2.;
+    assert!((bvh.area() - area).abs() < 1e-5, "{}", bvh.area());
+    let n = 100_000;
+    let mut first = 0;
+    for _ in 0..n {
+        let (rec, pdf) = bvh.sample_surface(0.).unwrap();
+        assert!((pdf * area - 1.).abs() < 1e-4, "{pdf} at {:?}", rec.p);
+        if rec.p.x() < 2. {
+            first += 1;
+        }
+    }
+    let share = first as f32 / n as f32;
+    assert!((share - 1. / area).abs() < 0.01, "{share} of the points");
+}
+
+#[test]
+fn rectangles_are_sampled_evenly_over_their_solid_angle() {
+    let origin = Point3::new(0.3, 0.2, 0.);
+    let rectangle = Quadrilateral::new(
+        Point3::new(-1., -1., -1.),
+        Vec3::new(2., 0., 0.),
+        Vec3::new(0., 2., 0.),
+        material(),
+    );
+    let corners = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+        .map(|(x, y)| vec3::unit_vector(Point3::new(x, y, -1.) - origin));
+    let solid_angle = vecmath::spherical_quad_area(corners[0], corners[1], corners[2], corners[3]);
+
+    let mut left = 0;
+    for _ in 0..10_000 {
+        let direction = rectangle.random(origin);
+        let pdf = rectangle.pdf_value(origin, direction);
+        assert!(
+            (pdf * solid_angle - 1.).abs() < 1e-3,
+            "{pdf} along {direction:?}"
+        );
+        let p = origin + direction;
+        assert!(p.x().abs() <= 1. + 1e-4 && p.y().abs() <= 1. + 1e-4 && (p.z() + 1.).abs() < 1e-4);
+        if vec3::dot(vec3::unit_vector(direction), Vec3::new(1., 0., 0.)) < 0. {
+            left += 1;
+        }
+    }
+    // The directions fall on either side of the origin by the solid angle there
+    let left_corners = [(-1., -1.), (0.3, -1.), (0.3, 1.), (-1., 1.)]
+        .map(|(x, y)| vec3::unit_vector(Point3::new(x, y, -1.) - origin));
+    let left_angle = vecmath::spherical_quad_area(
+        left_corners[0],
+        left_corners[1],
+        left_corners[2],
+        left_corners[3],
+    );
+    let fraction = left as f32 / 10_000.;
+    assert!(
+        (fraction - left_angle / solid_angle).abs() < 0.02,
+        "{fraction} of the samples are on the left"
+    );
+}
+
+/// Renders a white floor lit by an emissive cylinder, in the lights list or not
+fn render_cylinder_light(light_sampling: bool, seed: u64) -> Vec<Color> {
+    let (objects, lights) = if light_sampling {
+        ("", "lights = [{ type = \"cylinder\", center = [0.0, 0.5, 0.0], radius = 0.3, height = 0.5, material = \"light\" }]")
+    } else {
+        (
+            "[[scene.objects]]\ntype = \"cylinder\"\ncenter = [0.0, 0.5, 0.0]\nradius = 0.3\nheight = 0.5\nmaterial = \"light\"",
+            "lights = []",
+        )
+    };
+    let toml_string = format!(
+        r#"
+        [camera]
+        image_width = 16
+        aspect_ratio = 1.0
+        samples_per_pixel = 64
+        max_depth = 2
+        vfov = 60.0
+        look_from = [0.0, 3.0, 3.0]
+        look_at = [0.0, 0.0, 0.0]
+        seed = {seed}
+
+        [scene]
+        {lights}
+
+        [scene.materials]
+        light = {{ type = "diffuse_light", color = [4.0, 4.0, 4.0] }}
+
+        [[scene.objects]]
+        type = "quad"
+        corner = [-3.0, 0.0, -3.0]
+        u = [6.0, 0.0, 0.0]
+        v = [0.0, 0.0, 6.0]
+        material = {{ type = "lambertian", color = [0.5, 0.5, 0.5] }}
+
+        {objects}
+        "#
+    );
+    let config: Config = toml::from_str(&toml_string).expect("invalid scene file");
+    let (mut camera, world, lights) = config.to_scene().expect("invalid scene");
+    let world = BvhNode::from_list(world);
+    let lights: HittableList = lights;
+    camera.render_image(&world, Arc::new(lights))
+}
+
+#[test]
+fn cylinders_light_scenes_with_less_noise_from_the_lights_list() {
+    let brightness = |image: &[Color]| -> Vec<f32> {
+        image.iter().map(|c| (c.x() + c.y() + c.z()) / 3.).collect()
+    };
+    let sampled = [1, 2].map(|seed| brightness(&render_cylinder_light(true, seed)));
+    let unsampled = [1, 2].map(|seed| brightness(&render_cylinder_light(false, seed)));
+
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+    let (sampled_mean, unsampled_mean) = (mean(&sampled[0]), mean(&unsampled[0]));
+    assert!(
+        (sampled_mean - unsampled_mean).abs() < 0.05 * sampled_mean,
+        "{sampled_mean} with light sampling, {unsampled_mean} without"
+    );
+
+    // Renders with different seeds differ by their noise
+    let noise = |renders: &[Vec<f32>; 2]| {
+        let differences: Vec<f32> = renders[0]
+            .iter()
+            .zip(&renders[1])
+            .map(|(a, b)| (a - b).powi(2))
+            .collect();
+        mean(&differences).sqrt()
+    };
+    let (sampled_noise, unsampled_noise) = (noise(&sampled), noise(&unsampled));
+    assert!(
+        sampled_noise < 0.5 * unsampled_noise,
+        "noise of {sampled_noise} with light sampling, {unsampled_noise} without"
+    );
+}
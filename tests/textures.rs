@@ -0,0 +1,398 @@
+//! Checks that image textures decode their files and look up pixels the way they're configured.
+
+mod common;
+
+use common::{assert_close_within, temp_path};
+use ray_tracing::{
+    srgb_to_linear, Checkerboard, Color, HitRecord, ImageFilter, ImageTexture, Point3, SolidColor,
+    Texture, TextureConfig, Vec3, WrapMode,
+};
+
+use std::sync::Arc;
+
+/// Colors go through 8 bit files and filters, so they only match to about a thousandth
+fn assert_color(actual: Color, expected: Color, what: &str) {
+    assert_close_within(actual, expected, 1e-3, what);
+}
+
+/// Writes an 8 bit RGB PNG, row by row from the top
+fn write_png(path: &str, width: u32, height: u32, rgb: &[u8]) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(rgb)
+        .unwrap();
+}
+
+/// A 2 by 2 image with red, green, blue and white pixels, from the top left
+fn quadrants() -> ImageTexture {
+    let path = temp_path("quadrants.png");
+    write_png(
+        &path,
+        2,
+        2,
+        &[255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255],
+    );
+    ImageTexture::load(&path, false).expect("couldn't load the png")
+}
+
+const RED: Color = Vec3([1., 0., 0.]);
+const GREEN: Color = Vec3([0., 1., 0.]);
+const BLUE: Color = Vec3([0., 0., 1.]);
+const WHITE: Color = Vec3([1., 1., 1.]);
+
+#[test]
+fn image_textures_put_the_first_row_at_the_top() {
+    let texture = quadrants().with_filter(ImageFilter::Nearest);
+    let p = Point3::default();
+    assert_color(texture.value(0.25, 0.75, p), RED, "top left");
+    assert_color(texture.value(0.75, 0.75, p), GREEN, "top right");
+    assert_color(texture.value(0.25, 0.25, p), BLUE, "bottom left");
+    assert_color(texture.value(0.75, 0.25, p), WHITE, "bottom right");
+}
+
+#[test]
+fn bilinear_lookups_blend_neighbouring_pixels() {
+    let p = Point3::default();
+    let clamped = quadrants().with_wrap(WrapMode::Clamp);
+    assert_color(clamped.value(0.25, 0.75, p), RED, "pixel centre");
+    assert_color(
+        clamped.value(0.5, 0.75, p),
+        (RED + GREEN) / 2.,
+        "between the top pixels",
+    );
+    assert_color(
+        clamped.value(0.5, 0.5, p),
+        (RED + GREEN + BLUE + WHITE) / 4.,
+        "centre",
+    );
+    // Clamping keeps the edge pixels, where repeating blends in the other side
+    assert_color(clamped.value(0., 0.75, p), RED, "clamped edge");
+    let repeated = quadrants();
+    assert_color(
+        repeated.value(0., 0.75, p),
+        (RED + GREEN) / 2.,
+        "repeated edge",
+    );
+}
+
+#[test]
+fn wrap_modes_tile_the_image() {
+    let p = Point3::default();
+    let nearest = quadrants().with_filter(ImageFilter::Nearest);
+
+    let repeat = nearest.clone().with_wrap(WrapMode::Repeat);
+    assert_color(repeat.value(1.25, 0.75, p), RED, "repeat");
+    assert_color(repeat.value(-0.25, 0.75, p), GREEN, "repeat, negative");
+
+    let clamp = nearest.clone().with_wrap(WrapMode::Clamp);
+    assert_color(clamp.value(1.25, 0.75, p), GREEN, "clamp");
+    assert_color(clamp.value(-3., -3., p), BLUE, "clamp, negative");
+
+    let mirror = nearest.with_wrap(WrapMode::Mirror);
+    assert_color(mirror.value(1.25, 0.75, p), GREEN, "mirror");
+    assert_color(mirror.value(1.75, 0.75, p), RED, "mirror, far side");
+    assert_color(mirror.value(2.25, 0.75, p), RED, "mirror, second copy");
+}
+
+#[test]
+fn srgb_images_are_decoded_to_linear_colors() {
+    let path = temp_path("grey.png");
+    write_png(&path, 1, 1, &[128, 64, 255]);
+    let p = Point3::default();
+
+    let decoded = ImageTexture::load(&path, true).unwrap();
+    let expected = Color::new(srgb_to_linear(128. / 255.), srgb_to_linear(64. / 255.), 1.);
+    assert_color(decoded.value(0.5, 0.5, p), expected, "srgb");
+    assert!((srgb_to_linear(128. / 255.) - 0.2158).abs() < 1e-3);
+
+    let raw = ImageTexture::load(&path, false).unwrap();
+    assert_color(
+        raw.value(0.5, 0.5, p),
+        Color::new(128. / 255., 64. / 255., 1.),
+        "linear",
+    );
+}
+
+#[test]
+fn sixteen_bit_grey_pngs_load() {
+    let path = temp_path("grey16.png");
+    let file = std::fs::File::create(&path).unwrap();
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), 2, 1);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    let samples: Vec<u8> = [0u16, 32768].iter().flat_map(|s| s.to_be_bytes()).collect();
+    encoder
+        .write_header()
+        .unwrap()
+        .write_image_data(&samples)
+        .unwrap();
+
+    let texture = ImageTexture::load(&path, false)
+        .unwrap()
+        .with_filter(ImageFilter::Nearest);
+    let p = Point3::default();
+    assert_color(texture.value(0.25, 0.5, p), Color::new(0., 0., 0.), "black");
+    let half = 32768. / 65535.;
+    assert_color(
+        texture.value(0.75, 0.5, p),
+        Color::new(half, half, half),
+        "grey",
+    );
+}
+
+#[test]
+fn exr_images_stay_linear() {
+    use exr::prelude::*;
+
+    let path = temp_path("bright.exr");
+    let pixels = [(4., 0.5, 0.25), (0., 2., 8.)];
+    Image::from_channels((2, 1), SpecificChannels::rgb(|Vec2(x, _)| pixels[x]))
+        .write()
+        .to_file(&path)
+        .unwrap();
+
+    let texture = ImageTexture::load(&path, true)
+        .unwrap()
+        .with_filter(ImageFilter::Nearest);
+    let p = Point3::default();
+    assert_color(
+        texture.value(0.25, 0.5, p),
+        Color::new(4., 0.5, 0.25),
+        "left",
+    );
+    assert_color(texture.value(0.75, 0.5, p), Color::new(0., 2., 8.), "right");
+}
+
+/// A baseline JPEG of two 8 by 8 grey blocks, 200 on the left and 64 on the right, with only
+/// the DC coefficients that set each block's level
+fn tiny_jpeg() -> Vec<u8> {
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let length = (payload.len() + 2) as u16;
+        [&[0xff, marker][..], &length.to_be_bytes(), payload].concat()
+    }
+    let mut jpeg = vec![0xff, 0xd8];
+    // Quantization table 0, all ones
+    jpeg.extend(segment(0xdb, &[&[0][..], &[1; 64]].concat()));
+    // 8 bit samples, 8 rows of 16 columns, one component using table 0
+    jpeg.extend(segment(0xc0, &[8, 0, 8, 0, 16, 1, 1, 0x11, 0]));
+    // DC table: "0" for a difference of 10 bits and "10" for one of 11 bits
+    jpeg.extend(segment(
+        0xc4,
+        &[&[0x00, 1, 1][..], &[0; 14], &[10, 11]].concat(),
+    ));
+    // AC table: "0" ends the block
+    jpeg.extend(segment(0xc4, &[&[0x10, 1][..], &[0; 15], &[0]].concat()));
+    jpeg.extend(segment(0xda, &[1, 1, 0x00, 0, 63, 0]));
+    // The DC differences 8 (200 - 128) = 576 and 8 (64 - 128) - 576 = -1088, then padding
+    jpeg.extend([0x48, 0x09, 0xdf, 0xbf]);
+    jpeg.extend([0xff, 0xd9]);
+    jpeg
+}
+
+#[test]
+fn jpegs_load() {
+    let path = temp_path("blocks.jpg");
+    std::fs::write(&path, tiny_jpeg()).unwrap();
+
+    let texture = ImageTexture::load(&path, false)
+        .unwrap()
+        .with_filter(ImageFilter::Nearest);
+    assert_eq!((texture.width(), texture.height()), (16, 8));
+    let p = Point3::default();
+    let (light, dark) = (200. / 255., 64. / 255.);
+    assert_color(
+        texture.value(0.25, 0.5, p),
+        Color::new(light, light, light),
+        "left block",
+    );
+    assert_color(
+        texture.value(0.75, 0.5, p),
+        Color::new(dark, dark, dark),
+        "right block",
+    );
+}
+
+#[test]
+fn image_texture_configs_build() {
+    let path = temp_path("config.png");
+    write_png(&path, 1, 1, &[255, 255, 255]);
+
+    let config: TextureConfig = toml::from_str(&format!(
+        r#"
+        type = "image"
+        path = '{path}'
+        filter = "ewa"
+        wrap = "mirror"
+        srgb = false
+        "#
+    ))
+    .expect("invalid texture");
+    let texture = config
+        .build(&mut |name| Err(format!("unexpected reference to {name}")))
+        .expect("couldn't build the texture");
+    assert_color(texture.value(0.3, 0.6, Point3::default()), WHITE, "config");
+
+    let missing: TextureConfig =
+        toml::from_str("type = \"image\"\npath = \"missing.png\"").unwrap();
+    assert!(missing.build(&mut |_| unreachable!()).is_err());
+    let unknown: TextureConfig = toml::from_str("type = \"image\"\npath = \"image.bmp\"").unwrap();
+    assert!(unknown.build(&mut |_| unreachable!()).is_err());
+}
+
+/// A `size` by `size` image of alternating black and white pixels
+fn fine_checks(size: usize) -> ImageTexture {
+    let pixels = (0..size * size)
+        .map(|i| {
+            if (i % size + i / size).is_multiple_of(2) {
+                WHITE
+            } else {
+                Color::default()
+            }
+        })
+        .collect();
+    ImageTexture::new(size, size, pixels).unwrap()
+}
+
+/// A hit at `(u, v)` whose pixel footprint spans `dudx` along `u` and `dvdy` along `v`
+fn footprint(u: f32, v: f32, dudx: f32, dvdy: f32) -> HitRecord {
+    HitRecord {
+        u,
+        v,
+        dudx,
+        dvdy,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn mip_pyramids_halve_down_to_a_pixel() {
+    let texture = ImageTexture::new(5, 3, vec![WHITE; 15]).unwrap();
+    // 5 by 3, 3 by 2, 2 by 1 and 1 by 1
+    assert_eq!(texture.mip_levels(), 4);
+    assert_eq!(fine_checks(16).mip_levels(), 5);
+}
+
+#[test]
+fn footprint_filters_average_detail_finer_than_a_pixel() {
+    let grey = WHITE / 2.;
+    for filter in [ImageFilter::Trilinear, ImageFilter::Ewa] {
+        let texture = fine_checks(64).with_filter(filter);
+        // Without a footprint, the lookup is bilinear in the full size image
+        let centre = footprint(0.5 / 64., 0.5 / 64., 0., 0.);
+        assert_color(
+            texture.filtered_value(&centre),
+            texture.value(centre.u, centre.v, centre.p),
+            &format!("{filter:?} without a footprint"),
+        );
+        // A footprint of several pixels sees grey
+        for (dudx, dvdy) in [(0.1, 0.1), (0.02, 0.2), (0.3, 0.05)] {
+            let rec = footprint(0.37, 0.61, dudx, dvdy);
+            assert_color(
+                texture.filtered_value(&rec),
+                grey,
+                &format!("{filter:?} over {dudx} by {dvdy}"),
+            );
+        }
+    }
+
+    // Plain bilinear lookups ignore the footprint
+    let bilinear = fine_checks(64);
+    let rec = footprint(0.5 / 64., 0.5 / 64., 0.1, 0.1);
+    assert_color(
+        bilinear.filtered_value(&rec),
+        bilinear.value(rec.u, rec.v, rec.p),
+        "bilinear",
+    );
+}
+
+#[test]
+fn checkerboards_fade_to_the_average_far_away() {
+    let checkerboard = Checkerboard::new(
+        Arc::new(SolidColor::new(WHITE)),
+        Arc::new(SolidColor::new(Color::default())),
+        0.5,
+    );
+    let p = Point3::new(0.1, 0.2, 0.3);
+    let sharp = HitRecord {
+        p,
+        ..Default::default()
+    };
+    assert_color(
+        checkerboard.filtered_value(&sharp),
+        checkerboard.value(0., 0., p),
+        "point",
+    );
+
+    // A footprint covering many checks along one axis averages them
+    let wide = HitRecord {
+        p,
+        dpdx: Vec3::new(20., 0., 0.),
+        dpdy: Vec3::new(0., 0., 0.01),
+        ..Default::default()
+    };
+    assert_color(checkerboard.filtered_value(&wide), WHITE / 2., "wide");
+
+    // Half a check wide and centred on an edge, the footprint sees both colors equally
+    let edge = HitRecord {
+        p: Point3::new(0.5, 0.2, 0.3),
+        dpdx: Vec3::new(0.25, 0., 0.),
+        ..Default::default()
+    };
+    assert_color(checkerboard.filtered_value(&edge), WHITE / 2., "edge");
+}
+
+/// A Radiance HDR file of `width` by `height` pixels, followed by the encoded rows
+fn hdr_file(width: usize, height: usize, rows: &[u8]) -> Vec<u8> {
+    let header = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n");
+    [header.as_bytes(), rows].concat()
+}
+
+#[test]
+fn radiance_hdr_images_load() {
+    let p = Point3::default();
+    // Two run-length encoded rows of 8: the first one colour, the second a ramp of red
+    let mut rows = vec![2, 2, 0, 8];
+    for value in [128, 64, 32, 129] {
+        rows.extend([128 + 8, value]);
+    }
+    rows.extend([2, 2, 0, 8]);
+    rows.extend([8, 0, 16, 32, 48, 64, 80, 96, 112]);
+    rows.extend([128 + 8, 0, 128 + 8, 0, 128 + 8, 136]);
+    let path = temp_path("encoded.hdr");
+    std::fs::write(&path, hdr_file(8, 2, &rows)).unwrap();
+
+    let texture = ImageTexture::load(&path, true)
+        .unwrap()
+        .with_filter(ImageFilter::Nearest);
+    assert_eq!((texture.width(), texture.height()), (8, 2));
+    // Each channel is (value + 0.5) 2^(exponent - 136), and sRGB doesn't apply
+    assert_color(
+        texture.value(0.3, 0.75, p),
+        Color::new(128.5, 64.5, 32.5) / 128.,
+        "first row",
+    );
+    assert_color(
+        texture.value(7.5 / 8., 0.25, p),
+        Color::new(112.5, 0.5, 0.5),
+        "second row",
+    );
+
+    // Rows narrower than 8 pixels are stored flat
+    let path = temp_path("flat.hdr");
+    std::fs::write(&path, hdr_file(1, 1, &[128, 128, 128, 128])).unwrap();
+    let grey = 128.5 / 256.;
+    assert_color(
+        ImageTexture::load(&path, false).unwrap().value(0.5, 0.5, p),
+        Color::new(grey, grey, grey),
+        "flat",
+    );
+
+    let path = temp_path("truncated.hdr");
+    std::fs::write(&path, hdr_file(8, 2, &rows[..20])).unwrap();
+    assert!(ImageTexture::load(&path, false).is_err());
+}
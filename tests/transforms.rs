@@ -0,0 +1,297 @@
+//! Checks matrices, transforms and the instances they place in the world, and where the
+//! transforms of scene files put points.
+
+mod common;
+
+use common::{assert_close, material};
+use ray_tracing::{
+    vec3, HitRecord, Hittable, Instance, Interval, Matrix4, Point3, Ray, Sphere, Transform,
+    TransformConfig, Vec3,
+};
+
+use std::sync::Arc;
+
+fn assert_identity(m: Matrix4) {
+    let identity = Matrix4::identity();
+    for (row, expected) in m.m.iter().zip(identity.m) {
+        for (value, expected) in row.iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-5, "{m:?} isn't the identity");
+        }
+    }
+}
+
+/// Translation, rotation and non-uniform scale all at once
+fn affine() -> Matrix4 {
+    Matrix4::translate(Vec3::new(1., -2., 3.))
+        * Matrix4::rotate_z(-20.)
+        * Matrix4::rotate_x(37.)
+        * Matrix4::scale(Vec3::new(2., 0.5, 3.))
+}
+
+#[test]
+fn matrices_invert() {
+    let m = affine();
+    let inverse = m.inverse().unwrap();
+    assert_identity(m * inverse);
+    assert_identity(inverse * m);
+
+    let p = Point3::new(0.3, -1.2, 4.);
+    assert_close(
+        inverse.transform_point(m.transform_point(p)),
+        p,
+        "round trip",
+    );
+
+    // A matrix that flattens space has no inverse
+    assert!(Matrix4::scale(Vec3::new(1., 0., 1.)).inverse().is_none());
+    assert!(Transform::new(Matrix4::scale(Vec3::new(1., 0., 1.))).is_none());
+}
+
+#[test]
+fn matrices_compose_right_to_left() {
+    let scale = Matrix4::scale(Vec3::new(2., 2., 2.));
+    let translate = Matrix4::translate(Vec3::new(1., 0., 0.));
+    let p = Point3::new(1., 1., 1.);
+
+    // The right-hand matrix applies first
+    assert_close(
+        (translate * scale).transform_point(p),
+        Point3::new(3., 2., 2.),
+        "scale then translate",
+    );
+    assert_close(
+        (scale * translate).transform_point(p),
+        Point3::new(4., 2., 2.),
+        "translate then scale",
+    );
+
+    // Vectors aren't translated
+    assert_close(
+        (translate * scale).transform_vector(p),
+        Vec3::new(2., 2., 2.),
+        "vector",
+    );
+
+    // Rotations are counter-clockwise looking down their axis
+    assert_close(
+        Matrix4::rotate_z(90.).transform_point(Point3::new(1., 0., 0.)),
+        Point3::new(0., 1., 0.),
+        "rotate_z",
+    );
+
+    // `then` applies the receiver first, and keeps the inverse in step
+    let first = Transform::new(scale).unwrap();
+    let second = Transform::new(translate).unwrap();
+    let both = first.then(&second);
+    assert_close(both.point(p), Point3::new(3., 2., 2.), "then");
+    assert_close(both.inverse().point(both.point(p)), p, "inverse of then");
+    assert_identity(*both.matrix() * *both.inverse_matrix());
+}
+
+#[test]
+fn normals_stay_perpendicular_under_non_uniform_scale() {
+    let transform = Transform::new(affine()).unwrap();
+
+    // Two tangents of a surface and its normal
+    let (tangent1, tangent2) = (Vec3::new(1., -1., 0.), Vec3::new(0., 1., 2.));
+    let normal = vec3::cross(tangent1, tangent2);
+
+    let normal = transform.normal(normal);
+    for tangent in [tangent1, tangent2] {
+        let tangent = transform.vector(tangent);
+        assert!(
+            vec3::dot(normal, tangent).abs() < 1e-4 * normal.length() * tangent.length(),
+            "{normal:?} isn't perpendicular to {tangent:?}"
+        );
+    }
+}
+
+#[test]
+fn instances_hit_and_bound_the_transformed_object() {
+    let sphere: Arc<dyn Hittable> =
+        Arc::new(Sphere::stationary(Point3::new(0., 0., 0.), 1., material()));
+
+    // Stretched to an ellipsoid twice as long along x, turned 45 degrees and moved
+    let m = Matrix4::translate(Vec3::new(5., 1., 0.))
+        * Matrix4::rotate_y(45.)
+        * Matrix4::scale(Vec3::new(2., 1., 1.));
+    let instance = Instance::new(sphere.clone(), Transform::new(m).unwrap());
+
+    // The corners of the box around the scaled sphere, (+-2, +-1, +-1), turned 45 degrees
+    let extent = 3. / 2f32.sqrt();
+    let bbox = instance.bounding_box();
+    for (interval, min, max) in [
+        (&bbox.x, 5. - extent, 5. + extent),
+        (&bbox.y, 0., 2.),
+        (&bbox.z, -extent, extent),
+    ] {
+        assert!(
+            (interval.min - min).abs() < 1e-4 && (interval.max - max).abs() < 1e-4,
+            "{interval:?} isn't [{min}, {max}]"
+        );
+    }
+
+    // Without the rotation, the normal of the ellipsoid x^2/4 + y^2 + z^2 = 1 at (x, y, z) is
+    // along (x/4, y, z)
+    let stretched = Instance::new(
+        sphere,
+        Transform::new(Matrix4::scale(Vec3::new(2., 1., 1.))).unwrap(),
+    );
+    let mut rec = HitRecord::default();
+    assert!(stretched.hit(
+        &Ray::new(Point3::new(1., 0., 5.), Vec3::new(0., 0., -1.), 0.),
+        &mut Interval::new(0.001, f32::INFINITY),
+        &mut rec,
+    ));
+    let z = 0.75f32.sqrt();
+    assert_close(rec.p, Point3::new(1., 0., z), "hit point");
+    assert_close(
+        rec.normal,
+        vec3::unit_vector(Vec3::new(0.25, 0., z)),
+        "normal",
+    );
+}
+
+/// Where the transform given as TOML moves each of `points`
+fn place(toml: &str, points: &[Point3]) -> Vec<Point3> {
+    let config: TransformConfig = toml::from_str(toml).expect("invalid transform");
+    let transform = config.to_transform().expect("transform failed to build");
+    points.iter().map(|&p| transform.point(p)).collect()
+}
+
+fn assert_places(toml: &str, points: &[(Point3, Point3)]) {
+    let (from, to): (Vec<_>, Vec<_>) = points.iter().copied().unzip();
+    for ((p, actual), expected) in from.iter().zip(place(toml, &from)).zip(to) {
+        assert_close(actual, expected, &format!("{toml} on {p:?}"));
+    }
+}
+
+#[test]
+fn axis_angles_and_quaternions_rotate_counter_clockwise() {
+    let x = Point3::new(1., 0., 0.);
+    assert_places(
+        "rotate = { axis = [0, 0, 3], degrees = 90 }",
+        &[(x, Point3::new(0., 1., 0.))],
+    );
+    assert_places(
+        "rotate = { axis = [1, 1, 1], degrees = 120 }",
+        &[(x, Point3::new(0., 1., 0.))],
+    );
+
+    // A quarter turn about y, which needn't be normalized
+    let half = 0.5f32.sqrt();
+    for toml in [
+        format!("quaternion = {{ w = {half}, x = 0, y = {half}, z = 0 }}"),
+        "quaternion = { w = 2, x = 0, y = 2, z = 0 }".to_string(),
+    ] {
+        assert_places(&toml, &[(x, Point3::new(0., 0., -1.))]);
+    }
+
+    for toml in [
+        "rotate = { axis = [0, 0, 0], degrees = 90 }",
+        "quaternion = { w = 0, x = 0, y = 0, z = 0 }",
+    ] {
+        let config: TransformConfig = toml::from_str(toml).unwrap();
+        assert!(config.to_transform().is_err(), "{toml} built");
+    }
+}
+
+#[test]
+fn look_at_turns_the_local_z_axis_towards_the_target() {
+    // Placed at z = 5, looking back at the origin with y still up
+    assert_places(
+        "translate = [0, 0, 5]\nlook_at = { target = [0, 0, 0] }",
+        &[
+            (Point3::new(0., 0., 0.), Point3::new(0., 0., 5.)),
+            (Point3::new(0., 0., 1.), Point3::new(0., 0., 4.)),
+            (Point3::new(0., 1., 0.), Point3::new(0., 1., 5.)),
+            (Point3::new(1., 0., 0.), Point3::new(-1., 0., 5.)),
+        ],
+    );
+
+    // Looking along +x with z up
+    assert_places(
+        "look_at = { target = [3, 0, 0], up = [0, 0, 1] }",
+        &[
+            (Point3::new(0., 0., 1.), Point3::new(1., 0., 0.)),
+            (Point3::new(0., 1., 0.), Point3::new(0., 0., 1.)),
+        ],
+    );
+
+    // In a list, about wherever the earlier steps have moved the origin to
+    assert_places(
+        "transforms = [{ translate = [2, 0, 0] }, { look_at = { target = [2, 0, -4] } }]",
+        &[(Point3::new(0., 0., 1.), Point3::new(2., 0., -1.))],
+    );
+
+    for toml in [
+        "look_at = { target = [0, 0, 0] }",
+        "look_at = { target = [0, 4, 0] }",
+    ] {
+        let config: TransformConfig = toml::from_str(toml).unwrap();
+        assert!(config.to_transform().is_err(), "{toml} built");
+    }
+}
+
+#[test]
+fn transform_fields_apply_in_the_documented_order() {
+    let x = Point3::new(1., 0., 0.);
+
+    // Scale, then rotate, then translate, whatever order the fields are written in
+    for toml in [
+        "scale = [2, 2, 2]\nrotate_z = 90\ntranslate = [1, 0, 0]",
+        "translate = [1, 0, 0]\nrotate_z = 90\nscale = [2, 2, 2]",
+    ] {
+        assert_places(toml, &[(x, Point3::new(1., 2., 0.))]);
+    }
+
+    // rotate_x before rotate_y before rotate_z before rotate before quaternion
+    let y = Point3::new(0., 1., 0.);
+    assert_places("rotate_x = 90\nrotate_y = 90", &[(y, x)]);
+    assert_places(
+        "rotate_y = 90\nrotate_z = 90",
+        &[(x, Point3::new(0., 0., -1.))],
+    );
+    assert_places(
+        "rotate_z = 90\nrotate = { axis = [1, 0, 0], degrees = 90 }",
+        &[(x, Point3::new(0., 0., 1.))],
+    );
+    assert_places(
+        "rotate = { axis = [0, 0, 1], degrees = 90 }\nquaternion = { w = 1, x = 1, y = 0, z = 0 }",
+        &[(x, Point3::new(0., 0., 1.))],
+    );
+
+    // The fields all come before the list
+    assert_places(
+        "translate = [1, 0, 0]\ntransforms = [{ scale = [2, 2, 2] }]",
+        &[(Point3::new(0., 0., 0.), Point3::new(2., 0., 0.))],
+    );
+}
+
+#[test]
+fn transform_lists_apply_in_the_order_listed() {
+    let origin = Point3::new(0., 0., 0.);
+    assert_places(
+        "transforms = [{ translate = [1, 0, 0] }, { rotate_z = 90 }]",
+        &[(origin, Point3::new(0., 1., 0.))],
+    );
+    assert_places(
+        "transforms = [{ rotate_z = 90 }, { translate = [1, 0, 0] }]",
+        &[(origin, Point3::new(1., 0., 0.))],
+    );
+    assert_places(
+        "transforms = [
+            { scale = [1, 2, 1] },
+            { rotate = { axis = [0, 1, 0], degrees = 90 } },
+            { matrix = [[1, 0, 0, 0], [0, 1, 0, 5], [0, 0, 1, 0], [0, 0, 0, 1]] },
+        ]",
+        &[(Point3::new(1., 1., 0.), Point3::new(0., 7., -1.))],
+    );
+
+    // Projective matrices would need a divide by w that instances never do
+    let toml =
+        "transforms = [{ matrix = [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 1, 1]] }]";
+    let config: TransformConfig = toml::from_str(toml).unwrap();
+    let error = config.to_transform().err().unwrap();
+    assert!(error.contains("last row"), "{toml} failed with '{error}'");
+}